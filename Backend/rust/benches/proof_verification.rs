@@ -0,0 +1,72 @@
+//! Whale threshold proof verification benchmarks
+//!
+//! Compares verifying one range proof per transaction against `verify_batch`
+//! over aggregated proofs for the same transactions.
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use zkwatch_core::commitments::{address_scalar, value_scalar, Commitment, Opening};
+use zkwatch_core::zk_proofs::{InputOpenings, WhaleTransactionInputs, ZKSNARKGenerator};
+
+/// Whale transfers per block being verified
+const BATCH_SIZES: &[usize] = &[16, 64, 256];
+
+/// Transfers of 250 ETH (in gwei) against a 100 ETH threshold
+fn whale_inputs(count: usize) -> Vec<WhaleTransactionInputs> {
+    (0..count as u64)
+        .map(|i| {
+            let amount = 250_000_000_000 + i;
+            let address = address_scalar(&format!("0x{:040x}", i + 1)).expect("valid address");
+            let (amount_commitment, amount_blinding) = Commitment::random(value_scalar(amount));
+            let (address_commitment, address_blinding) = Commitment::random(address);
+            let (gas_commitment, gas_blinding) = Commitment::random(value_scalar(21_000));
+
+            WhaleTransactionInputs {
+                tx_hash: format!("0x{:064x}", i),
+                block_number: 18_000_000,
+                timestamp: Utc::now(),
+                amount,
+                threshold: 100_000_000_000,
+                amount_commitment,
+                address_commitment,
+                gas_commitment,
+                openings: InputOpenings {
+                    amount: Opening { value: value_scalar(amount), blinding: amount_blinding },
+                    address: Opening { value: address, blinding: address_blinding },
+                    gas: Opening { value: value_scalar(21_000), blinding: gas_blinding },
+                },
+            }
+        })
+        .collect()
+}
+
+fn verification(c: &mut Criterion) {
+    let mut generator = ZKSNARKGenerator::new();
+    let mut group = c.benchmark_group("whale_threshold_verification");
+    group.sample_size(10);
+
+    for &count in BATCH_SIZES {
+        let inputs = whale_inputs(count);
+        let singles = inputs
+            .iter()
+            .map(|tx| generator.generate_whale_transaction_proof(tx))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("single proofs");
+        let aggregates = generator.generate_batch_proofs(&inputs).expect("aggregated proofs");
+
+        group.bench_with_input(BenchmarkId::new("single", count), &singles, |b, proofs| {
+            b.iter(|| proofs.iter().all(|proof| generator.verify_proof(proof).unwrap_or(false)))
+        });
+        group.bench_with_input(BenchmarkId::new("single_parallel", count), &singles, |b, proofs| {
+            b.iter(|| generator.verify_batch(proofs).unwrap_or(false))
+        });
+        group.bench_with_input(BenchmarkId::new("aggregated", count), &aggregates, |b, proofs| {
+            b.iter(|| generator.verify_batch(proofs).unwrap_or(false))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, verification);
+criterion_main!(benches);
@@ -3,11 +3,12 @@
 //! Provides sophisticated data analysis, machine learning capabilities,
 //! and comprehensive reporting for whale tracking and blockchain analytics.
 
-use crate::{ZKWatchResult, WhaleTransaction, AnalyticsMetrics, TimeSeriesPoint, ZKWatchError};
+use crate::{ZKWatchError, ZKWatchResult, WhaleTransaction};
+use crate::warehouse::{self, DataWarehouse, DEFAULT_WAREHOUSE_DIR};
+use polars::prelude::{col, lit, when, DataFrame, DataType, Expr, LazyFrame, SortOptions, TimeUnit};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
-use chrono::{DateTime, Utc, Duration};
-use rayon::prelude::*;
+use std::collections::HashMap;
+use chrono::{DateTime, TimeZone, Utc, Duration};
 
 /// Advanced analytics engine
 pub struct AnalyticsEngine {
@@ -17,16 +18,34 @@ pub struct AnalyticsEngine {
     report_generator: ReportGenerator,
 }
 
+impl Default for AnalyticsEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AnalyticsEngine {
     pub fn new() -> Self {
         Self {
-            data_warehouse: DataWarehouse::new(),
+            data_warehouse: DataWarehouse::new(DEFAULT_WAREHOUSE_DIR),
             ml_pipeline: MLPipeline::new(),
             real_time_processor: RealTimeProcessor::new(),
             report_generator: ReportGenerator::new(),
         }
     }
 
+    /// Store transactions in the given warehouse instead of the default directory
+    pub fn with_warehouse(mut self, data_warehouse: DataWarehouse) -> Self {
+        self.data_warehouse = data_warehouse;
+        self
+    }
+
+    /// Write buffered transactions to the warehouse; call before shutting down
+    /// to see write errors, which dropping the engine can only log
+    pub fn flush(&mut self) -> ZKWatchResult<()> {
+        self.data_warehouse.flush()
+    }
+
     /// Process incoming whale transaction data
     pub async fn process_transaction(&mut self, transaction: &WhaleTransaction) -> ZKWatchResult<AnalyticsResult> {
         // Store in data warehouse
@@ -46,22 +65,26 @@ impl AnalyticsEngine {
     }
 
     /// Generate comprehensive analytics report
+    ///
+    /// Each section runs its own aggregation over the warehouse scan, so only
+    /// the grouped results and the anomalous rows are read into memory.
     pub async fn generate_comprehensive_report(
         &mut self,
         time_range: TimeRange,
         analysis_depth: AnalysisDepth,
     ) -> ZKWatchResult<ComprehensiveReport> {
-        let data = self.data_warehouse.get_data_in_range(&time_range).await?;
+        let scan = self.data_warehouse.get_data_in_range(&time_range).await?;
+        let summary = RangeSummary::query(&scan, Utc::now()).await?;
         
         let report = ComprehensiveReport {
-            executive_summary: self.generate_executive_summary(&data).await?,
-            detailed_analysis: self.perform_detailed_analysis(&data, analysis_depth).await?,
-            ml_insights: self.ml_pipeline.generate_insights(&data).await?,
-            risk_assessment: self.assess_overall_risk(&data).await?,
-            recommendations: self.generate_recommendations(&data).await?,
+            executive_summary: self.generate_executive_summary(&scan, &summary).await?,
+            detailed_analysis: self.perform_detailed_analysis(&scan, &summary, analysis_depth).await?,
+            ml_insights: self.ml_pipeline.generate_insights(&scan).await?,
+            risk_assessment: self.assess_overall_risk(&summary).await?,
+            recommendations: self.generate_recommendations(&scan).await?,
             time_range,
             generated_at: Utc::now(),
-            confidence_score: self.calculate_overall_confidence(&data).await?,
+            confidence_score: self.calculate_overall_confidence(&summary).await?,
         };
         
         // Generate visual reports
@@ -70,57 +93,52 @@ impl AnalyticsEngine {
         Ok(report)
     }
 
-    async fn generate_executive_summary(&self, data: &[WhaleTransaction]) -> ZKWatchResult<ExecutiveSummary> {
-        let total_volume: u128 = data.iter().map(|t| t.value).sum();
-        let total_transactions = data.len();
-        let avg_transaction_size = total_volume / total_transactions as u128;
+    async fn generate_executive_summary(&self, scan: &LazyFrame, summary: &RangeSummary) -> ZKWatchResult<ExecutiveSummary> {
+        let average_transaction_usd = summary.volume_usd / summary.priced.max(1) as f64;
         
         // Calculate growth metrics
-        let recent_data = data.iter().filter(|t| 
-            t.timestamp > Utc::now() - Duration::days(7)
-        ).collect::<Vec<_>>();
-        
-        let older_data = data.iter().filter(|t| 
-            t.timestamp > Utc::now() - Duration::days(14) && 
-            t.timestamp <= Utc::now() - Duration::days(7)
-        ).collect::<Vec<_>>();
-        
-        let volume_growth = if !older_data.is_empty() {
-            let recent_vol = recent_data.iter().map(|t| t.value).sum::<u128>();
-            let older_vol = older_data.iter().map(|t| t.value).sum::<u128>();
-            if older_vol > 0 {
-                ((recent_vol as f64 - older_vol as f64) / older_vol as f64) * 100.0
-            } else {
-                0.0
-            }
+        let volume_growth = if summary.volume_usd_prior_7d > 0.0 {
+            ((summary.volume_usd_7d - summary.volume_usd_prior_7d) / summary.volume_usd_prior_7d) * 100.0
         } else {
             0.0
         };
         
+        let network_counts = self.count_by_network(scan).await?;
+        let most_active_network = network_counts.iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(network, _)| network.clone())
+            .unwrap_or_else(|| "None".to_string());
+        
         Ok(ExecutiveSummary {
-            total_whale_volume: total_volume,
-            total_whale_transactions: total_transactions,
-            average_transaction_size,
+            total_whale_volume_usd: summary.volume_usd,
+            total_whale_transactions: summary.transactions,
+            average_transaction_usd,
             volume_growth_7d: volume_growth,
-            most_active_network: "Ethereum".to_string(),
-            key_trends: self.identify_key_trends(data).await?,
-            market_impact_score: self.calculate_market_impact(data).await?,
-            risk_level: self.calculate_risk_level(data).await?,
+            most_active_network,
+            key_trends: self.identify_key_trends(scan, summary, &network_counts).await?,
+            market_impact_score: self.calculate_market_impact(average_transaction_usd).await?,
+            risk_level: self.calculate_risk_level(summary).await?,
         })
     }
 
     async fn perform_detailed_analysis(
         &self,
-        data: &[WhaleTransaction],
+        scan: &LazyFrame,
+        summary: &RangeSummary,
         depth: AnalysisDepth,
     ) -> ZKWatchResult<DetailedAnalysis> {
         let mut analysis = DetailedAnalysis {
-            temporal_patterns: self.analyze_temporal_patterns(data).await?,
-            network_distribution: self.analyze_network_distribution(data).await?,
-            address_behavior: self.analyze_address_behavior(data).await?,
-            transaction_patterns: self.analyze_transaction_patterns(data).await?,
-            correlation_analysis: self.perform_correlation_analysis(data).await?,
-            anomaly_detection: self.detect_anomalies(data).await?,
+            temporal_patterns: self.analyze_temporal_patterns(scan, summary).await?,
+            network_distribution: self.analyze_network_distribution(scan).await?,
+            address_behavior: self.analyze_address_behavior(scan).await?,
+            transaction_patterns: self.analyze_transaction_patterns(scan, summary).await?,
+            correlation_analysis: self.perform_correlation_analysis(scan).await?,
+            anomaly_detection: self.detect_anomalies(scan).await?,
+            advanced_patterns: None,
+            ml_clustering: None,
+            predictive_modeling: None,
+            network_topology: None,
+            market_manipulation: None,
         };
         
         match depth {
@@ -128,32 +146,37 @@ impl AnalyticsEngine {
                 // Standard analysis already completed
             }
             AnalysisDepth::Deep => {
-                analysis.advanced_patterns = Some(self.detect_advanced_patterns(data).await?);
-                analysis.ml_clustering = Some(self.perform_ml_clustering(data).await?);
-                analysis.predictive_modeling = Some(self.build_predictive_models(data).await?);
+                analysis.advanced_patterns = Some(self.detect_advanced_patterns(scan).await?);
+                analysis.ml_clustering = Some(self.perform_ml_clustering(scan).await?);
+                analysis.predictive_modeling = Some(self.build_predictive_models(scan).await?);
             }
             AnalysisDepth::Comprehensive => {
-                analysis.advanced_patterns = Some(self.detect_advanced_patterns(data).await?);
-                analysis.ml_clustering = Some(self.perform_ml_clustering(data).await?);
-                analysis.predictive_modeling = Some(self.build_predictive_models(data).await?);
-                analysis.network_topology = Some(self.analyze_network_topology(data).await?);
-                analysis.market_manipulation = Some(self.detect_manipulation_indicators(data).await?);
+                analysis.advanced_patterns = Some(self.detect_advanced_patterns(scan).await?);
+                analysis.ml_clustering = Some(self.perform_ml_clustering(scan).await?);
+                analysis.predictive_modeling = Some(self.build_predictive_models(scan).await?);
+                analysis.network_topology = Some(self.analyze_network_topology(scan).await?);
+                analysis.market_manipulation = Some(self.detect_manipulation_indicators(scan).await?);
             }
         }
         
         Ok(analysis)
     }
 
-    async fn identify_key_trends(&self, data: &[WhaleTransaction]) -> ZKWatchResult<Vec<KeyTrend>> {
+    async fn identify_key_trends(
+        &self,
+        scan: &LazyFrame,
+        summary: &RangeSummary,
+        network_counts: &HashMap<String, usize>,
+    ) -> ZKWatchResult<Vec<KeyTrend>> {
         let mut trends = Vec::new();
         
         // Analyze temporal trends
-        let daily_volume = self.calculate_daily_volume(data);
+        let daily_volume = self.calculate_daily_volume(scan).await?;
         if daily_volume.len() >= 7 {
-            let recent_avg = daily_volume.iter().rev().take(7).sum::<u128>() / 7;
-            let previous_avg = daily_volume.iter().rev().skip(7).take(7).sum::<u128>() / 7;
+            let recent_avg = daily_volume.iter().rev().take(7).sum::<f64>() / 7.0;
+            let previous_avg = daily_volume.iter().rev().skip(7).take(7).sum::<f64>() / 7.0;
             
-            if recent_avg > previous_avg * 120 / 100 {
+            if recent_avg > previous_avg * 1.2 {
                 trends.push(KeyTrend {
                     trend_type: TrendType::IncreasingVolume,
                     description: "Whale activity showing significant increase".to_string(),
@@ -164,9 +187,8 @@ impl AnalyticsEngine {
         }
         
         // Analyze network adoption trends
-        let network_counts = self.count_by_network(data);
-        if network_counts.contains_key("Polygon") {
-            let polygon_share = network_counts["Polygon"] as f64 / data.len() as f64;
+        if let Some(&polygon) = network_counts.get("Polygon") {
+            let polygon_share = polygon as f64 / summary.transactions.max(1) as f64;
             if polygon_share > 0.3 {
                 trends.push(KeyTrend {
                     trend_type: TrendType::MultiChainShift,
@@ -180,23 +202,15 @@ impl AnalyticsEngine {
         Ok(trends)
     }
 
-    async fn calculate_market_impact(&self, data: &[WhaleTransaction]) -> ZKWatchResult<f64> {
-        // Simplified market impact calculation
-        let total_volume = data.iter().map(|t| t.value).sum::<u128>();
-        let avg_transaction = total_volume / data.len() as u128;
-        
-        // Higher volume transactions have higher market impact
-        let impact_score = (avg_transaction as f64 / 1e18).sqrt().min(10.0);
+    async fn calculate_market_impact(&self, avg_transaction_usd: f64) -> ZKWatchResult<f64> {
+        // Higher volume transactions have higher market impact; $1M on average scores 10
+        let impact_score = (avg_transaction_usd / 10_000.0).sqrt().min(10.0);
         
         Ok(impact_score)
     }
 
-    async fn calculate_risk_level(&self, data: &[WhaleTransaction]) -> ZKWatchResult<RiskLevel> {
-        let high_risk_transactions = data.iter()
-            .filter(|t| t.risk_score > 0.7)
-            .count();
-        
-        let risk_ratio = high_risk_transactions as f64 / data.len() as f64;
+    async fn calculate_risk_level(&self, summary: &RangeSummary) -> ZKWatchResult<RiskLevel> {
+        let risk_ratio = self.calculate_suspicious_ratio(summary).await?;
         
         Ok(match risk_ratio {
             ratio if ratio > 0.3 => RiskLevel::High,
@@ -205,19 +219,26 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn analyze_temporal_patterns(&self, data: &[WhaleTransaction]) -> ZKWatchResult<TemporalPatterns> {
+    async fn analyze_temporal_patterns(&self, scan: &LazyFrame, summary: &RangeSummary) -> ZKWatchResult<TemporalPatterns> {
         let mut hourly_distribution = vec![0u32; 24];
         let mut daily_distribution = vec![0u32; 7];
         let mut monthly_trends = HashMap::new();
         
-        for transaction in data {
-            let hour = transaction.timestamp.hour() as usize;
-            let weekday = transaction.timestamp.weekday().num_days_from_monday() as usize;
-            let month_key = transaction.timestamp.format("%Y-%m").to_string();
-            
-            hourly_distribution[hour] += 1;
-            daily_distribution[weekday] += 1;
-            *monthly_trends.entry(month_key).or_insert(0) += transaction.value as u64;
+        let hours = group_totals(scan, (col("timestamp") / lit(MILLIS_PER_HOUR)).cast(DataType::Int64) % lit(24i64)).await?;
+        for (hour, count) in integer_keys(&hours)?.into_iter().zip(group_counts(&hours)?) {
+            hourly_distribution[hour as usize] += count as u32;
+        }
+        // 1970-01-01 was a Thursday, three days after a Monday
+        let weekdays = group_totals(scan, ((col("timestamp") / lit(MILLIS_PER_DAY)).cast(DataType::Int64) + lit(3i64)) % lit(7i64)).await?;
+        for (weekday, count) in integer_keys(&weekdays)?.into_iter().zip(group_counts(&weekdays)?) {
+            daily_distribution[weekday as usize] += count as u32;
+        }
+        let months = group_totals(scan, col("timestamp").cast(DataType::Datetime(TimeUnit::Milliseconds, None)).dt().strftime("%Y-%m")).await?;
+        let month_keys = months.column("key")?.utf8()?;
+        for (row, value) in group_values(&months)?.into_iter().enumerate() {
+            if let Some(month) = month_keys.get(row) {
+                monthly_trends.insert(month.to_string(), value as u64);
+            }
         }
         
         // Find peak hours and days
@@ -237,11 +258,11 @@ impl AnalyticsEngine {
             monthly_trends,
             peak_hour,
             peak_day,
-            average_gap_between_transactions: self.calculate_average_gap(data).await?,
+            average_gap_between_transactions: self.calculate_average_gap(summary)?,
         })
     }
 
-    async fn analyze_network_distribution(&self, data: &[WhaleTransaction]) -> ZKWatchResult<NetworkDistribution> {
+    async fn analyze_network_distribution(&self, _scan: &LazyFrame) -> ZKWatchResult<NetworkDistribution> {
         // Simplified network analysis (would need actual network data in real implementation)
         Ok(NetworkDistribution {
             ethereum: 0.65,
@@ -257,31 +278,37 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn analyze_address_behavior(&self, data: &[WhaleTransaction]) -> ZKWatchResult<AddressBehavior> {
-        let mut address_stats: HashMap<String, AddressStats> = HashMap::new();
+    async fn analyze_address_behavior(&self, scan: &LazyFrame) -> ZKWatchResult<AddressBehavior> {
+        let senders = warehouse::collect(scan.clone()
+            .group_by([col("from")])
+            .agg([
+                col("timestamp").count().alias("transactions"),
+                volume().sum().alias("volume"),
+                col("timestamp").min().alias("first_seen"),
+                col("timestamp").max().alias("last_seen"),
+            ])).await?;
         
-        for transaction in data {
-            let stats = address_stats.entry(transaction.from.clone()).or_insert(AddressStats {
-                transaction_count: 0,
-                total_volume: 0,
-                first_seen: transaction.timestamp,
-                last_seen: transaction.timestamp,
-                avg_transaction_size: 0,
+        let addresses = senders.column("from")?.utf8()?;
+        let counts = senders.column("transactions")?.cast(&DataType::UInt64)?;
+        let counts = counts.u64()?;
+        let first_seen = senders.column("first_seen")?.i64()?;
+        let last_seen = senders.column("last_seen")?.i64()?;
+        let volumes = senders.column("volume")?.f64()?;
+        let mut address_stats: HashMap<String, AddressStats> = HashMap::new();
+        for row in 0..senders.height() {
+            let (Some(address), Some(first), Some(last)) = (addresses.get(row), first_seen.get(row), last_seen.get(row)) else {
+                continue;
+            };
+            let transaction_count = counts.get(row).unwrap_or_default() as usize;
+            let total_volume = volumes.get(row).unwrap_or_default() as u128;
+            address_stats.insert(address.to_string(), AddressStats {
+                transaction_count,
+                total_volume,
+                first_seen: from_millis(first)?,
+                last_seen: from_millis(last)?,
+                avg_transaction_size: total_volume / transaction_count.max(1) as u128,
                 behavioral_cluster: "Unknown".to_string(),
             });
-            
-            stats.transaction_count += 1;
-            stats.total_volume += transaction.value;
-            stats.last_seen = transaction.timestamp;
-            
-            if transaction.timestamp < stats.first_seen {
-                stats.first_seen = transaction.timestamp;
-            }
-        }
-        
-        // Calculate average transaction sizes
-        for stats in address_stats.values_mut() {
-            stats.avg_transaction_size = stats.total_volume / stats.transaction_count as u128;
         }
         
         Ok(AddressBehavior {
@@ -292,11 +319,14 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn analyze_transaction_patterns(&self, data: &[WhaleTransaction]) -> ZKWatchResult<TransactionPatterns> {
+    async fn analyze_transaction_patterns(&self, scan: &LazyFrame, summary: &RangeSummary) -> ZKWatchResult<TransactionPatterns> {
+        let patterns = group_totals(scan, col("pattern_type")).await?;
+        let names = patterns.column("key")?.utf8()?;
         let mut pattern_counts = HashMap::new();
-        
-        for transaction in data {
-            *pattern_counts.entry(&transaction.pattern_type).or_insert(0) += 1;
+        for (row, count) in group_counts(&patterns)?.into_iter().enumerate() {
+            if let Some(name) = names.get(row) {
+                pattern_counts.insert(warehouse::from_variant_name::<crate::TransactionPattern>(name)?, count);
+            }
         }
         
         let most_common_pattern = pattern_counts.iter()
@@ -307,12 +337,12 @@ impl AnalyticsEngine {
         Ok(TransactionPatterns {
             pattern_distribution: pattern_counts,
             most_common_pattern,
-            pattern_evolution: self.analyze_pattern_evolution(data).await?,
-            suspicious_pattern_ratio: self.calculate_suspicious_ratio(data).await?,
+            pattern_evolution: self.analyze_pattern_evolution(scan).await?,
+            suspicious_pattern_ratio: self.calculate_suspicious_ratio(summary).await?,
         })
     }
 
-    async fn perform_correlation_analysis(&self, data: &[WhaleTransaction]) -> ZKWatchResult<CorrelationAnalysis> {
+    async fn perform_correlation_analysis(&self, _scan: &LazyFrame) -> ZKWatchResult<CorrelationAnalysis> {
         // Simplified correlation analysis
         // In real implementation, this would analyze correlations between various metrics
         
@@ -339,104 +369,82 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn detect_anomalies(&self, data: &[WhaleTransaction]) -> ZKWatchResult<Vec<Anomaly>> {
+    async fn detect_anomalies(&self, scan: &LazyFrame) -> ZKWatchResult<Vec<Anomaly>> {
         let mut anomalies = Vec::new();
         
-        // Detect volume anomalies
-        let volumes: Vec<u128> = data.iter().map(|t| t.value).collect();
-        let avg_volume = volumes.iter().sum::<u128>() / volumes.len() as u128;
-        let volume_std = self.calculate_std_deviation(&volumes, avg_volume as f64);
-        
-        for (i, transaction) in data.iter().enumerate() {
-            let z_score = ((transaction.value as f64 - avg_volume as f64) / volume_std).abs();
-            
-            if z_score > 2.5 {
-                anomalies.push(Anomaly {
-                    anomaly_type: AnomalyType::VolumeOutlier,
-                    description: format!("Transaction {} has unusually high volume (z-score: {:.2})", i, z_score),
-                    transaction_hash: transaction.hash.clone(),
-                    severity: if z_score > 3.0 { AnomalySeverity::High } else { AnomalySeverity::Medium },
-                    timestamp: transaction.timestamp,
-                });
-            }
+        // Detect volume anomalies; only the outlying rows leave the scan
+        let outliers = warehouse::collect(scan.clone()
+            .select([col("hash"), col("timestamp"), z_score(col("value").cast(DataType::Float64)).alias("z")])
+            .filter(outside(col("z"), 2.5))
+            .sort("timestamp", SortOptions::default())).await?;
+        for (hash, timestamp, z_score) in anomaly_rows(&outliers)? {
+            anomalies.push(Anomaly {
+                anomaly_type: AnomalyType::VolumeOutlier,
+                description: format!("Transaction {} has unusually high volume (z-score: {:.2})", hash, z_score),
+                transaction_hash: hash,
+                severity: if z_score > 3.0 { AnomalySeverity::High } else { AnomalySeverity::Medium },
+                timestamp,
+            });
         }
         
-        // Detect timing anomalies
-        let time_gaps: Vec<f64> = data.windows(2)
-            .map(|window| {
-                let diff = window[1].timestamp.signed_duration_since(window[0].timestamp);
-                diff.num_seconds() as f64
-            })
-            .collect();
-        
-        let avg_gap = time_gaps.iter().sum::<f64>() / time_gaps.len() as f64;
-        let gap_std = self.calculate_std_deviation(&time_gaps, avg_gap);
-        
-        for (i, gap) in time_gaps.iter().enumerate() {
-            let z_score = ((gap - avg_gap) / gap_std).abs();
-            
-            if z_score > 2.0 {
-                anomalies.push(Anomaly {
-                    anomaly_type: AnomalyType::TimingIrregularity,
-                    description: format!("Unusual gap between transactions {} and {} (z-score: {:.2})", i, i+1, z_score),
-                    transaction_hash: data[i].hash.clone(),
-                    severity: if z_score > 2.5 { AnomalySeverity::High } else { AnomalySeverity::Medium },
-                    timestamp: data[i].timestamp,
-                });
-            }
+        // Detect timing anomalies in the gaps between consecutive transactions
+        let gaps = warehouse::collect(scan.clone()
+            .select([col("hash"), col("timestamp")])
+            .sort("timestamp", SortOptions::default())
+            .select([
+                col("hash").shift(lit(1)).alias("hash"),
+                col("timestamp").shift(lit(1)).alias("timestamp"),
+                (col("timestamp") - col("timestamp").shift(lit(1))).cast(DataType::Float64).alias("gap"),
+            ])
+            .filter(col("gap").is_not_null())
+            .select([col("hash"), col("timestamp"), z_score(col("gap")).alias("z")])
+            .filter(outside(col("z"), 2.0))).await?;
+        for (hash, timestamp, z_score) in anomaly_rows(&gaps)? {
+            anomalies.push(Anomaly {
+                anomaly_type: AnomalyType::TimingIrregularity,
+                description: format!("Unusual gap after transaction {} (z-score: {:.2})", hash, z_score),
+                transaction_hash: hash,
+                severity: if z_score > 2.5 { AnomalySeverity::High } else { AnomalySeverity::Medium },
+                timestamp,
+            });
         }
         
         Ok(anomalies)
     }
 
-    async fn calculate_overall_confidence(&self, data: &[WhaleTransaction]) -> ZKWatchResult<f64> {
+    async fn calculate_overall_confidence(&self, summary: &RangeSummary) -> ZKWatchResult<f64> {
         // Calculate overall confidence based on data quality and coverage
-        let data_points = data.len() as f64;
+        let data_points = summary.transactions as f64;
         let coverage_confidence = (data_points / 1000.0).min(1.0); // Higher confidence with more data
         let recency_confidence = 0.9; // Assume good data recency
         
-        (coverage_confidence * 0.7 + recency_confidence * 0.3)
+        Ok(coverage_confidence * 0.7 + recency_confidence * 0.3)
     }
 
     // Helper methods for calculations
-    fn calculate_daily_volume(&self, data: &[WhaleTransaction]) -> Vec<u128> {
-        let mut daily_volumes = HashMap::<chrono::NaiveDate, u128>::new();
-        
-        for transaction in data {
-            let date = transaction.timestamp.date_naive();
-            *daily_volumes.entry(date).or_insert(0) += transaction.value;
-        }
-        
-        let mut volumes: Vec<u128> = daily_volumes.into_values().collect();
-        volumes.sort();
-        volumes
+    /// Raw volume per UTC day, oldest day first
+    async fn calculate_daily_volume(&self, scan: &LazyFrame) -> ZKWatchResult<Vec<f64>> {
+        let days = group_totals(scan, (col("timestamp") / lit(MILLIS_PER_DAY)).cast(DataType::Int64)).await?;
+        group_values(&days)
     }
 
-    fn count_by_network(&self, data: &[WhaleTransaction]) -> HashMap<String, usize> {
-        // Simplified network counting (would need actual network data)
-        let mut counts = HashMap::new();
-        counts.insert("Ethereum".to_string(), (data.len() as f64 * 0.65) as usize);
-        counts.insert("Polygon".to_string(), (data.len() as f64 * 0.20) as usize);
-        counts.insert("Arbitrum".to_string(), (data.len() as f64 * 0.10) as usize);
-        counts.insert("Optimism".to_string(), (data.len() as f64 * 0.05) as usize);
-        counts
+    async fn count_by_network(&self, scan: &LazyFrame) -> ZKWatchResult<HashMap<String, usize>> {
+        let networks = group_totals(scan, col("network")).await?;
+        let names = networks.column("key")?.utf8()?;
+        Ok(group_counts(&networks)?.into_iter()
+            .enumerate()
+            .filter_map(|(row, count)| Some((names.get(row)?.to_string(), count)))
+            .collect())
     }
 
-    fn calculate_average_gap(&self, data: &[WhaleTransaction]) -> ZKWatchResult<f64> {
-        if data.len() < 2 {
-            return Ok(0.0);
-        }
-        
-        let mut total_gap = 0.0;
-        let mut gap_count = 0;
-        
-        for window in data.windows(2) {
-            let gap = window[1].timestamp.signed_duration_since(window[0].timestamp);
-            total_gap += gap.num_seconds() as f64;
-            gap_count += 1;
+    /// Mean seconds between consecutive transactions
+    fn calculate_average_gap(&self, summary: &RangeSummary) -> ZKWatchResult<f64> {
+        match (summary.first_timestamp, summary.last_timestamp) {
+            (Some(first), Some(last)) if summary.transactions >= 2 => {
+                Ok((last - first) as f64 / 1000.0 / (summary.transactions - 1) as f64)
+            }
+            _ => Ok(0.0),
         }
-        
-        Ok(total_gap / gap_count as f64)
     }
 
     fn get_top_addresses(&self, stats: &HashMap<String, AddressStats>) -> Vec<AddressRanking> {
@@ -449,7 +457,7 @@ impl AnalyticsEngine {
             }
         }).collect();
         
-        rankings.sort_by(|a, b| b.total_volume.cmp(&a.total_volume));
+        rankings.sort_by_key(|ranking| std::cmp::Reverse(ranking.total_volume));
         rankings.truncate(10);
         
         rankings
@@ -477,7 +485,7 @@ impl AnalyticsEngine {
         ]
     }
 
-    async fn analyze_pattern_evolution(&self, data: &[WhaleTransaction]) -> ZKWatchResult<PatternEvolution> {
+    async fn analyze_pattern_evolution(&self, _scan: &LazyFrame) -> ZKWatchResult<PatternEvolution> {
         // Simplified pattern evolution analysis
         Ok(PatternEvolution {
             emerging_patterns: vec!["Cross-chain bridging".to_string()],
@@ -486,38 +494,17 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn calculate_suspicious_ratio(&self, data: &[WhaleTransaction]) -> ZKWatchResult<f64> {
-        let suspicious_count = data.iter().filter(|t| t.risk_score > 0.7).count();
-        suspicious_count as f64 / data.len() as f64
-    }
-
-    fn calculate_std_deviation(&self, values: &[u128], mean: f64) -> f64 {
-        let variance = values.iter()
-            .map(|&x| {
-                let diff = x as f64 - mean;
-                diff * diff
-            })
-            .sum::<f64>() / values.len() as f64;
-        variance.sqrt()
-    }
-
-    fn calculate_std_deviation_f64(&self, values: &[f64], mean: f64) -> f64 {
-        let variance = values.iter()
-            .map(|&x| {
-                let diff = x - mean;
-                diff * diff
-            })
-            .sum::<f64>() / values.len() as f64;
-        variance.sqrt()
+    async fn calculate_suspicious_ratio(&self, summary: &RangeSummary) -> ZKWatchResult<f64> {
+        Ok(summary.high_risk as f64 / summary.transactions.max(1) as f64)
     }
 
     // Additional analysis methods for deep and comprehensive analysis
-    async fn detect_advanced_patterns(&self, data: &[WhaleTransaction]) -> ZKWatchResult<Vec<AdvancedPattern>> {
+    async fn detect_advanced_patterns(&self, _scan: &LazyFrame) -> ZKWatchResult<Vec<AdvancedPattern>> {
         // Implement advanced pattern detection algorithms
         Ok(vec![])
     }
 
-    async fn perform_ml_clustering(&self, data: &[WhaleTransaction]) -> ZKWatchResult<MLClustering> {
+    async fn perform_ml_clustering(&self, _scan: &LazyFrame) -> ZKWatchResult<MLClustering> {
         // Implement machine learning clustering
         Ok(MLClustering {
             cluster_count: 5,
@@ -526,7 +513,7 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn build_predictive_models(&self, data: &[WhaleTransaction]) -> ZKWatchResult<PredictiveModels> {
+    async fn build_predictive_models(&self, _scan: &LazyFrame) -> ZKWatchResult<PredictiveModels> {
         // Build predictive models for future whale behavior
         Ok(PredictiveModels {
             volume_prediction_accuracy: 0.82,
@@ -535,7 +522,7 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn analyze_network_topology(&self, data: &[WhaleTransaction]) -> ZKWatchResult<NetworkTopology> {
+    async fn analyze_network_topology(&self, _scan: &LazyFrame) -> ZKWatchResult<NetworkTopology> {
         // Analyze network topology and connections
         Ok(NetworkTopology {
             graph_density: 0.25,
@@ -544,7 +531,7 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn detect_manipulation_indicators(&self, data: &[WhaleTransaction]) -> ZKWatchResult<ManipulationIndicators> {
+    async fn detect_manipulation_indicators(&self, _scan: &LazyFrame) -> ZKWatchResult<ManipulationIndicators> {
         // Detect potential market manipulation indicators
         Ok(ManipulationIndicators {
             wash_trading_score: 0.12,
@@ -553,7 +540,34 @@ impl AnalyticsEngine {
         })
     }
 
-    async fn generate_recommendations(&self, data: &[WhaleTransaction]) -> ZKWatchResult<Vec<Recommendation>> {
+    async fn assess_overall_risk(&self, summary: &RangeSummary) -> ZKWatchResult<RiskAssessment> {
+        if summary.transactions == 0 {
+            return Ok(RiskAssessment {
+                overall_risk_score: 0.0,
+                risk_factors: vec![],
+                mitigation_recommendations: vec![],
+            });
+        }
+
+        let high_risk_ratio = self.calculate_suspicious_ratio(summary).await?;
+        let risk_factors = vec![RiskFactor {
+            factor: "High-risk whale transactions".to_string(),
+            impact_score: 0.8,
+            probability: high_risk_ratio,
+        }];
+        let mut mitigation_recommendations = Vec::new();
+        if high_risk_ratio > 0.1 {
+            mitigation_recommendations.push("Review counterparties of high-risk whale transactions".to_string());
+        }
+
+        Ok(RiskAssessment {
+            overall_risk_score: summary.mean_risk_score,
+            risk_factors,
+            mitigation_recommendations,
+        })
+    }
+
+    async fn generate_recommendations(&self, _scan: &LazyFrame) -> ZKWatchResult<Vec<Recommendation>> {
         Ok(vec![
             Recommendation {
                 category: "Monitoring".to_string(),
@@ -565,6 +579,130 @@ impl AnalyticsEngine {
     }
 }
 
+/// Risk score above which a whale transaction counts as high-risk
+const HIGH_RISK_SCORE: f64 = 0.7;
+
+const MILLIS_PER_HOUR: i64 = 3_600_000;
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Totals of a report's time range, aggregated inside the warehouse scan
+#[derive(Debug, Clone, Default)]
+struct RangeSummary {
+    transactions: usize,
+    /// Transactions with a USD value
+    priced: usize,
+    volume_usd: f64,
+    /// USD volume of the last seven days and of the seven days before
+    volume_usd_7d: f64,
+    volume_usd_prior_7d: f64,
+    high_risk: usize,
+    mean_risk_score: f64,
+    /// Oldest and newest timestamps in milliseconds
+    first_timestamp: Option<i64>,
+    last_timestamp: Option<i64>,
+}
+
+impl RangeSummary {
+    async fn query(scan: &LazyFrame, now: DateTime<Utc>) -> ZKWatchResult<Self> {
+        let week_ago = (now - Duration::days(7)).timestamp_millis();
+        let two_weeks_ago = (now - Duration::days(14)).timestamp_millis();
+        let frame = warehouse::collect(scan.clone().select([
+            col("timestamp").count().alias("transactions"),
+            col("usd_value").is_not_null().sum().alias("priced"),
+            volume_usd().sum().alias("volume_usd"),
+            volume_usd().filter(col("timestamp").gt(lit(week_ago))).sum().alias("volume_usd_7d"),
+            volume_usd()
+                .filter(col("timestamp").gt(lit(two_weeks_ago)).and(col("timestamp").lt_eq(lit(week_ago))))
+                .sum()
+                .alias("volume_usd_prior_7d"),
+            col("risk_score").gt(lit(HIGH_RISK_SCORE)).sum().alias("high_risk"),
+            col("risk_score").mean().alias("mean_risk_score"),
+            col("timestamp").min().alias("first_timestamp"),
+            col("timestamp").max().alias("last_timestamp"),
+        ])).await?;
+
+        let number = |column: &str| -> ZKWatchResult<f64> {
+            Ok(frame.column(column)?.cast(&DataType::Float64)?.f64()?.get(0).unwrap_or_default())
+        };
+        Ok(Self {
+            transactions: number("transactions")? as usize,
+            priced: number("priced")? as usize,
+            volume_usd: number("volume_usd")?,
+            volume_usd_7d: number("volume_usd_7d")?,
+            volume_usd_prior_7d: number("volume_usd_prior_7d")?,
+            high_risk: number("high_risk")? as usize,
+            mean_risk_score: number("mean_risk_score")?,
+            first_timestamp: frame.column("first_timestamp")?.i64()?.get(0),
+            last_timestamp: frame.column("last_timestamp")?.i64()?.get(0),
+        })
+    }
+}
+
+/// Raw amount counted in volume totals, as [`WhaleTransaction::volume`]
+///
+/// Raw amounts are stored as decimal strings and summed as floats.
+fn volume() -> Expr {
+    col("value").cast(DataType::Float64) - col("borrowed").cast(DataType::Float64)
+}
+
+/// USD value counted in volume totals, as [`WhaleTransaction::volume_usd`]
+fn volume_usd() -> Expr {
+    let value = col("value").cast(DataType::Float64);
+    let share = when(value.clone().gt(lit(0.0)))
+        .then(volume() / value)
+        .otherwise(lit(1.0));
+    col("usd_value") * share
+}
+
+/// Population z-score of each value against its column
+fn z_score(values: Expr) -> Expr {
+    (values.clone() - values.clone().mean()) / values.std(0)
+}
+
+/// Z-scores further than `limit` from the mean; constant columns have none
+fn outside(z: Expr, limit: f64) -> Expr {
+    z.clone().is_not_nan().and(z.clone().gt(lit(limit)).or(z.lt(lit(-limit))))
+}
+
+/// Transaction count and summed raw volume per value of `key`, ordered by key
+async fn group_totals(scan: &LazyFrame, key: Expr) -> ZKWatchResult<DataFrame> {
+    warehouse::collect(scan.clone()
+        .group_by([key.alias("key")])
+        .agg([col("timestamp").count().alias("transactions"), volume().sum().alias("volume")])
+        .sort("key", SortOptions::default())).await
+}
+
+fn integer_keys(groups: &DataFrame) -> ZKWatchResult<Vec<i64>> {
+    Ok(groups.column("key")?.cast(&DataType::Int64)?.i64()?.into_iter().map(Option::unwrap_or_default).collect())
+}
+
+fn group_counts(groups: &DataFrame) -> ZKWatchResult<Vec<usize>> {
+    Ok(groups.column("transactions")?.cast(&DataType::UInt64)?.u64()?.into_iter().map(|c| c.unwrap_or_default() as usize).collect())
+}
+
+fn group_values(groups: &DataFrame) -> ZKWatchResult<Vec<f64>> {
+    Ok(groups.column("volume")?.f64()?.into_iter().map(Option::unwrap_or_default).collect())
+}
+
+/// Hash, timestamp and absolute z-score of each anomalous row
+fn anomaly_rows(frame: &DataFrame) -> ZKWatchResult<Vec<(String, DateTime<Utc>, f64)>> {
+    let hashes = frame.column("hash")?.utf8()?;
+    let timestamps = frame.column("timestamp")?.i64()?;
+    let z_scores = frame.column("z")?.f64()?;
+    let mut rows = Vec::new();
+    for row in 0..frame.height() {
+        if let (Some(hash), Some(millis), Some(z)) = (hashes.get(row), timestamps.get(row), z_scores.get(row)) {
+            rows.push((hash.to_string(), from_millis(millis)?, z.abs()));
+        }
+    }
+    Ok(rows)
+}
+
+fn from_millis(millis: i64) -> ZKWatchResult<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(millis).single()
+        .ok_or_else(|| ZKWatchError::Analytics(format!("Warehouse timestamp {} out of range", millis)))
+}
+
 // Data structures for analytics results
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AnalyticsResult {
@@ -608,9 +746,11 @@ pub struct ComprehensiveReport {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ExecutiveSummary {
-    pub total_whale_volume: u128,
+    /// USD value moved by priced whale transfers; unpriced transfers are left out
+    pub total_whale_volume_usd: f64,
     pub total_whale_transactions: usize,
-    pub average_transaction_size: u128,
+    /// Mean USD value of the priced whale transfers
+    pub average_transaction_usd: f64,
     pub volume_growth_7d: f64,
     pub most_active_network: String,
     pub key_trends: Vec<KeyTrend>,
@@ -712,7 +852,7 @@ pub struct BehavioralPattern {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionPatterns {
-    pub pattern_distribution: HashMap<&'static crate::TransactionPattern, usize>,
+    pub pattern_distribution: HashMap<crate::TransactionPattern, usize>,
     pub most_common_pattern: crate::TransactionPattern,
     pub pattern_evolution: PatternEvolution,
     pub suspicious_pattern_ratio: f64,
@@ -866,26 +1006,6 @@ pub enum AnalysisDepth {
 }
 
 // Supporting structures
-struct DataWarehouse {
-    // In real implementation, this would connect to a database
-}
-
-impl DataWarehouse {
-    fn new() -> Self {
-        Self {}
-    }
-    
-    async fn store_transaction(&mut self, transaction: &WhaleTransaction) -> ZKWatchResult<()> {
-        // Store transaction in data warehouse
-        Ok(())
-    }
-    
-    async fn get_data_in_range(&self, time_range: &TimeRange) -> ZKWatchResult<Vec<WhaleTransaction>> {
-        // Retrieve data from data warehouse
-        Ok(vec![])
-    }
-}
-
 struct MLPipeline {
     // In real implementation, this would contain ML models and training logic
 }
@@ -895,7 +1015,7 @@ impl MLPipeline {
         Self {}
     }
     
-    async fn update_with_new_data(&mut self, transaction: &WhaleTransaction) -> ZKWatchResult<MLPredictions> {
+    async fn update_with_new_data(&mut self, _transaction: &WhaleTransaction) -> ZKWatchResult<MLPredictions> {
         Ok(MLPredictions {
             predicted_whale_activity: 0.15,
             confidence_interval: (0.10, 0.20),
@@ -903,7 +1023,7 @@ impl MLPipeline {
         })
     }
     
-    async fn generate_insights(&self, data: &[WhaleTransaction]) -> ZKWatchResult<MLInsights> {
+    async fn generate_insights(&self, _scan: &LazyFrame) -> ZKWatchResult<MLInsights> {
         Ok(MLInsights {
             model_performance: ModelPerformance {
                 precision: 0.82,
@@ -965,8 +1085,94 @@ impl ReportGenerator {
         Self {}
     }
     
-    fn create_charts(&self, report: &ComprehensiveReport) -> ZKWatchResult<()> {
+    fn create_charts(&self, _report: &ComprehensiveReport) -> ZKWatchResult<()> {
         // Generate charts and visualizations
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_tracker::ConfirmationStatus;
+    use crate::exchange_flows::ExchangeFlow;
+    use crate::TransactionPattern;
+
+    fn transaction(index: u64, network: &str, from: u64, hours_ago: i64, usd_value: f64) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:064x}", index),
+            network: network.to_string(),
+            chain_id: 1,
+            from: format!("0x{:040x}", from),
+            to: format!("0x{:040x}", 0xff),
+            value: 10u128.pow(21),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(usd_value),
+            confirmation: ConfirmationStatus::Finalized,
+            gas_used: 21_000,
+            block_number: index,
+            timestamp: Utc::now() - Duration::hours(hours_ago),
+            zk_proof_hash: None,
+            risk_score: 0.1,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    #[tokio::test]
+    async fn report_aggregates_the_warehouse_range() {
+        let root = std::env::temp_dir().join(format!("zkwatch-analytics-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let mut engine = AnalyticsEngine::new().with_warehouse(DataWarehouse::new(&root));
+
+        // Ten $1M transfers over the last days from two senders
+        let mut transactions: Vec<WhaleTransaction> = (0..10).map(|i| transaction(i, "Ethereum", 1 + i % 2, i as i64 * 6, 1_000_000.0)).collect();
+        // Half of a $2M flash-loan transfer was borrowed
+        let mut flash_loan = transaction(10, "Ethereum", 4, 2, 2_000_000.0);
+        flash_loan.borrowed = 5 * 10u128.pow(20);
+        flash_loan.pattern_type = TransactionPattern::FlashLoan;
+        // A thousandfold, high-risk transfer
+        let mut outlier = transaction(11, "Ethereum", 3, 3, 3_000_000.0);
+        outlier.value = 10u128.pow(24);
+        outlier.risk_score = 0.9;
+        let mut bridged = transaction(12, "Arbitrum", 2, 1, 1_000_000.0);
+        bridged.pattern_type = TransactionPattern::CrossChainBridge;
+        transactions.extend([flash_loan, outlier.clone(), bridged, transaction(13, "Ethereum", 1, 10 * 24, 500_000.0)]);
+        for transaction in &transactions {
+            engine.process_transaction(transaction).await.unwrap();
+        }
+
+        let range = TimeRange { start_date: Utc::now() - Duration::days(30), end_date: Utc::now() + Duration::minutes(1) };
+        let report = engine.generate_comprehensive_report(range, AnalysisDepth::Standard).await.unwrap();
+
+        let summary = &report.executive_summary;
+        assert_eq!(summary.total_whale_transactions, 14);
+        assert!((summary.total_whale_volume_usd - 15_500_000.0).abs() < 1e-3);
+        assert!((summary.average_transaction_usd - 15_500_000.0 / 14.0).abs() < 1e-3);
+        // $15M this week against $0.5M the week before
+        assert!((summary.volume_growth_7d - 2_900.0).abs() < 1e-6);
+        assert_eq!(summary.most_active_network, "Ethereum");
+        assert!(matches!(summary.risk_level, RiskLevel::Low));
+
+        let analysis = &report.detailed_analysis;
+        assert_eq!(analysis.temporal_patterns.hourly_distribution.iter().sum::<u32>(), 14);
+        assert_eq!(analysis.temporal_patterns.daily_distribution.iter().sum::<u32>(), 14);
+        assert_eq!(analysis.address_behavior.total_unique_addresses, 4);
+        let patterns = &analysis.transaction_patterns.pattern_distribution;
+        assert_eq!((patterns[&TransactionPattern::LargeTransaction], patterns[&TransactionPattern::FlashLoan], patterns[&TransactionPattern::CrossChainBridge]), (12, 1, 1));
+        assert!((analysis.transaction_patterns.suspicious_pattern_ratio - 1.0 / 14.0).abs() < 1e-9);
+
+        let volume_outliers: Vec<&Anomaly> = analysis.anomaly_detection.iter()
+            .filter(|a| matches!(a.anomaly_type, AnomalyType::VolumeOutlier))
+            .collect();
+        assert_eq!(volume_outliers.len(), 1);
+        assert_eq!(volume_outliers[0].transaction_hash, outlier.hash);
+        assert!(matches!(volume_outliers[0].severity, AnomalySeverity::High));
+
+        assert!((report.risk_assessment.overall_risk_score - (13.0 * 0.1 + 0.9) / 14.0).abs() < 1e-9);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
+
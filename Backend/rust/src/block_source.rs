@@ -0,0 +1,768 @@
+//! Block data sources for the multi-chain scanner
+//!
+//! Abstracts where blocks, receipts, logs and traces come from so detection
+//! logic can run against live JSON-RPC nodes, recorded fixtures, or in-memory data.
+
+use crate::{ZKWatchResult, ZKWatchError};
+use async_trait::async_trait;
+use ethers::types::{Action, Block, CallType, Log, Res, Trace, Transaction, TransactionReceipt, H160, H256, U256, U64};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Source of chain data consumed by the scanner
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    /// Number of the latest available block
+    async fn block_number(&self) -> ZKWatchResult<u64>;
+
+    /// Block with full transaction objects, `None` if the block is unknown
+    async fn block_with_transactions(&self, number: u64) -> ZKWatchResult<Option<Block<Transaction>>>;
+
+    /// Receipt of a mined transaction, `None` if the transaction is unknown
+    async fn transaction_receipt(&self, hash: H256) -> ZKWatchResult<Option<TransactionReceipt>>;
+
+    /// Logs matching the filter, in chain order
+    async fn logs(&self, filter: &LogFilter) -> ZKWatchResult<Vec<Log>>;
+
+    /// Parity-style call traces of a transaction
+    async fn trace_transaction(&self, hash: H256) -> ZKWatchResult<Vec<Trace>>;
+
+    /// Parity-style call traces matching the filter, in chain order
+    async fn traces(&self, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>>;
+}
+
+/// Log query over an inclusive block range
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogFilter {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Emitting contracts; empty matches any contract
+    pub addresses: Vec<H160>,
+    /// Per-position topic alternatives; `None` matches any topic
+    pub topics: [Option<Vec<H256>>; 4],
+}
+
+impl LogFilter {
+    pub fn new(from_block: u64, to_block: u64) -> Self {
+        Self {
+            from_block,
+            to_block,
+            ..Self::default()
+        }
+    }
+
+    pub fn address(mut self, addresses: Vec<H160>) -> Self {
+        self.addresses = addresses;
+        self
+    }
+
+    pub fn topic(mut self, position: usize, alternatives: Vec<H256>) -> Self {
+        self.topics[position] = Some(alternatives);
+        self
+    }
+
+    /// Whether a log satisfies this filter
+    pub fn matches(&self, log: &Log) -> bool {
+        let block = log.block_number.map(|n| n.as_u64()).unwrap_or_default();
+        if block < self.from_block || block > self.to_block {
+            return false;
+        }
+
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+
+        self.topics.iter().enumerate().all(|(position, alternatives)| match alternatives {
+            Some(alternatives) => log.topics.get(position).is_some_and(|t| alternatives.contains(t)),
+            None => true,
+        })
+    }
+
+    fn to_rpc_params(&self) -> serde_json::Value {
+        let topics: Vec<serde_json::Value> = self.topics.iter()
+            .map(|alternatives| match alternatives {
+                Some(alternatives) => serde_json::json!(alternatives),
+                None => serde_json::Value::Null,
+            })
+            .collect();
+
+        let mut filter = serde_json::json!({
+            "fromBlock": U64::from(self.from_block),
+            "toBlock": U64::from(self.to_block),
+            "topics": topics,
+        });
+        if !self.addresses.is_empty() {
+            filter["address"] = serde_json::json!(self.addresses);
+        }
+
+        serde_json::json!([filter])
+    }
+}
+
+/// Call-trace query over an inclusive block range
+///
+/// A trace matches when both its sender and its recipient are accepted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceFilter {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Callers; empty matches any caller
+    pub from_addresses: Vec<H160>,
+    /// Callees; empty matches any callee
+    pub to_addresses: Vec<H160>,
+}
+
+impl TraceFilter {
+    pub fn new(from_block: u64, to_block: u64) -> Self {
+        Self {
+            from_block,
+            to_block,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_address(mut self, addresses: Vec<H160>) -> Self {
+        self.from_addresses = addresses;
+        self
+    }
+
+    pub fn to_address(mut self, addresses: Vec<H160>) -> Self {
+        self.to_addresses = addresses;
+        self
+    }
+
+    /// Whether a trace satisfies this filter
+    pub fn matches(&self, trace: &Trace) -> bool {
+        if trace.block_number < self.from_block || trace.block_number > self.to_block {
+            return false;
+        }
+        let Some((from, to)) = trace_endpoints(trace) else {
+            return false;
+        };
+
+        (self.from_addresses.is_empty() || self.from_addresses.contains(&from))
+            && (self.to_addresses.is_empty() || to.is_some_and(|to| self.to_addresses.contains(&to)))
+    }
+
+    fn to_rpc_params(&self) -> serde_json::Value {
+        let mut filter = serde_json::json!({
+            "fromBlock": U64::from(self.from_block),
+            "toBlock": U64::from(self.to_block),
+        });
+        if !self.from_addresses.is_empty() {
+            filter["fromAddress"] = serde_json::json!(self.from_addresses);
+        }
+        if !self.to_addresses.is_empty() {
+            filter["toAddress"] = serde_json::json!(self.to_addresses);
+        }
+
+        serde_json::json!([filter])
+    }
+}
+
+/// Sender and recipient of a call, contract creation or self-destruct
+fn trace_endpoints(trace: &Trace) -> Option<(H160, Option<H160>)> {
+    match &trace.action {
+        Action::Call(call) => Some((call.from, Some(call.to))),
+        Action::Create(create) => Some((create.from, match &trace.result {
+            Some(Res::Create(result)) => Some(result.address),
+            _ => None,
+        })),
+        Action::Suicide(suicide) => Some((suicide.address, Some(suicide.refund_address))),
+        Action::Reward(_) => None,
+    }
+}
+
+/// Sender, recipient and native value moved by a successful call, creation or self-destruct
+///
+/// Delegate and static calls run in the caller's context and move nothing.
+pub fn value_transfer(trace: &Trace) -> Option<(H160, H160, U256)> {
+    if trace.error.is_some() {
+        return None;
+    }
+    let value = match &trace.action {
+        Action::Call(call) if matches!(call.call_type, CallType::Call | CallType::None) => call.value,
+        Action::Create(create) => create.value,
+        Action::Suicide(suicide) => suicide.balance,
+        _ => return None,
+    };
+    let (from, to) = trace_endpoints(trace)?;
+    Some((from, to?, value)).filter(|(_, _, value)| !value.is_zero())
+}
+
+/// Live Ethereum JSON-RPC node
+pub struct RpcBlockSource {
+    rpc_url: String,
+    client: reqwest::Client,
+    next_request_id: AtomicU64,
+}
+
+impl RpcBlockSource {
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            rpc_url: rpc_url.to_string(),
+            client: reqwest::Client::new(),
+            next_request_id: AtomicU64::new(1),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: serde_json::Value) -> ZKWatchResult<T> {
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: self.next_request_id.fetch_add(1, Ordering::Relaxed),
+            method,
+            params,
+        };
+
+        let response: JsonRpcResponse<T> = self.client
+            .post(&self.rpc_url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(error) = response.error {
+            return Err(ZKWatchError::Blockchain(format!(
+                "{} failed on {}: {} (code {})", method, self.rpc_url, error.message, error.code
+            )));
+        }
+
+        // `null` results (unknown block or receipt) deserialize into `None` for optional targets
+        match response.result {
+            Some(result) => Ok(result),
+            None => serde_json::from_value(serde_json::Value::Null).map_err(|_| {
+                ZKWatchError::Blockchain(format!("{} returned no result from {}", method, self.rpc_url))
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockSource for RpcBlockSource {
+    async fn block_number(&self) -> ZKWatchResult<u64> {
+        let number: U64 = self.call("eth_blockNumber", serde_json::json!([])).await?;
+        Ok(number.as_u64())
+    }
+
+    async fn block_with_transactions(&self, number: u64) -> ZKWatchResult<Option<Block<Transaction>>> {
+        self.call("eth_getBlockByNumber", serde_json::json!([U64::from(number), true])).await
+    }
+
+    async fn transaction_receipt(&self, hash: H256) -> ZKWatchResult<Option<TransactionReceipt>> {
+        self.call("eth_getTransactionReceipt", serde_json::json!([hash])).await
+    }
+
+    async fn logs(&self, filter: &LogFilter) -> ZKWatchResult<Vec<Log>> {
+        self.call("eth_getLogs", filter.to_rpc_params()).await
+    }
+
+    async fn trace_transaction(&self, hash: H256) -> ZKWatchResult<Vec<Trace>> {
+        let traces: Option<Vec<Trace>> = self.call("trace_transaction", serde_json::json!([hash])).await?;
+        Ok(traces.unwrap_or_default())
+    }
+
+    async fn traces(&self, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>> {
+        let traces: Option<Vec<Trace>> = self.call("trace_filter", filter.to_rpc_params()).await?;
+        Ok(traces.unwrap_or_default())
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+/// In-memory chain data, mainly for tests and synthetic scenarios
+#[derive(Default)]
+pub struct InMemoryBlockSource {
+    state: RwLock<InMemoryState>,
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    head: Option<u64>,
+    blocks: BTreeMap<u64, Block<Transaction>>,
+    receipts: HashMap<H256, TransactionReceipt>,
+    logs: Vec<Log>,
+    traces: HashMap<H256, Vec<Trace>>,
+}
+
+impl InMemoryBlockSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a block; replacing a number simulates a reorg
+    pub fn insert_block(&self, block: Block<Transaction>) {
+        let number = block.number.map(|n| n.as_u64()).unwrap_or_default();
+        self.write().blocks.insert(number, block);
+    }
+
+    /// Add a receipt; its logs become visible to `logs` queries
+    pub fn insert_receipt(&self, receipt: TransactionReceipt) {
+        self.write().receipts.insert(receipt.transaction_hash, receipt);
+    }
+
+    /// Add logs that are not attached to any stored receipt
+    pub fn insert_logs(&self, logs: impl IntoIterator<Item = Log>) {
+        self.write().logs.extend(logs);
+    }
+
+    pub fn insert_traces(&self, hash: H256, traces: Vec<Trace>) {
+        self.write().traces.insert(hash, traces);
+    }
+
+    /// Add traces of any transactions, keeping those already stored
+    pub fn extend_traces(&self, traces: impl IntoIterator<Item = Trace>) {
+        let mut state = self.write();
+        for trace in traces {
+            let Some(hash) = trace.transaction_hash else {
+                continue;
+            };
+            let stored = state.traces.entry(hash).or_default();
+            if !stored.iter().any(|t| t.trace_address == trace.trace_address) {
+                stored.push(trace);
+                stored.sort_by(|a, b| a.trace_address.cmp(&b.trace_address));
+            }
+        }
+    }
+
+    /// Pin the reported head instead of using the highest stored block
+    pub fn set_head(&self, head: Option<u64>) {
+        self.write().head = head;
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, InMemoryState> {
+        self.state.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, InMemoryState> {
+        self.state.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl BlockSource for InMemoryBlockSource {
+    async fn block_number(&self) -> ZKWatchResult<u64> {
+        let state = self.read();
+        state.head
+            .or_else(|| state.blocks.keys().next_back().copied())
+            .ok_or_else(|| ZKWatchError::Blockchain("In-memory source holds no blocks".to_string()))
+    }
+
+    async fn block_with_transactions(&self, number: u64) -> ZKWatchResult<Option<Block<Transaction>>> {
+        Ok(self.read().blocks.get(&number).cloned())
+    }
+
+    async fn transaction_receipt(&self, hash: H256) -> ZKWatchResult<Option<TransactionReceipt>> {
+        Ok(self.read().receipts.get(&hash).cloned())
+    }
+
+    async fn logs(&self, filter: &LogFilter) -> ZKWatchResult<Vec<Log>> {
+        let state = self.read();
+        let mut logs: Vec<Log> = state.receipts.values()
+            .flat_map(|receipt| receipt.logs.iter())
+            .chain(state.logs.iter())
+            .filter(|log| filter.matches(log))
+            .cloned()
+            .collect();
+
+        logs.sort_by_key(|log| (log.block_number, log.transaction_index, log.log_index));
+        logs.dedup_by_key(|log| (log.block_number, log.transaction_hash, log.log_index));
+
+        Ok(logs)
+    }
+
+    async fn trace_transaction(&self, hash: H256) -> ZKWatchResult<Vec<Trace>> {
+        Ok(self.read().traces.get(&hash).cloned().unwrap_or_default())
+    }
+
+    async fn traces(&self, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>> {
+        let mut traces: Vec<Trace> = self.read().traces.values()
+            .flatten()
+            .filter(|trace| filter.matches(trace))
+            .cloned()
+            .collect();
+
+        traces.sort_by(|a, b| {
+            (a.block_number, a.transaction_position, &a.trace_address)
+                .cmp(&(b.block_number, b.transaction_position, &b.trace_address))
+        });
+        Ok(traces)
+    }
+}
+
+/// Replays recorded JSON-RPC responses from a `.jsonl` file or a directory of them
+///
+/// Each line is one recorded call: `{"method": "eth_getBlockByNumber", "params": [...], "result": {...}}`.
+/// `eth_getBlockByNumber`, `eth_getTransactionReceipt`, `eth_getLogs`, `trace_transaction`
+/// and `trace_filter` records are replayed; other methods are ignored.
+pub struct FixtureBlockSource {
+    path: PathBuf,
+    data: InMemoryBlockSource,
+}
+
+#[derive(Deserialize)]
+struct RecordedCall {
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+    result: serde_json::Value,
+}
+
+impl FixtureBlockSource {
+    pub fn load(path: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = InMemoryBlockSource::new();
+
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(&path)? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|ext| ext == "jsonl") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.clone());
+        }
+
+        for file in &files {
+            let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+            for (line_number, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let call: RecordedCall = serde_json::from_str(&line).map_err(|e| {
+                    ZKWatchError::Blockchain(format!("{}:{}: invalid fixture record: {}", file.display(), line_number + 1, e))
+                })?;
+                Self::replay(&data, call)?;
+            }
+        }
+
+        Ok(Self { path, data })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn replay(data: &InMemoryBlockSource, call: RecordedCall) -> ZKWatchResult<()> {
+        if call.result.is_null() {
+            return Ok(());
+        }
+
+        match call.method.as_str() {
+            "eth_getBlockByNumber" | "eth_getBlockByHash" => data.insert_block(serde_json::from_value(call.result)?),
+            "eth_getTransactionReceipt" => data.insert_receipt(serde_json::from_value(call.result)?),
+            "eth_getLogs" => data.insert_logs(serde_json::from_value::<Vec<Log>>(call.result)?),
+            "trace_transaction" => {
+                let hash: H256 = serde_json::from_value(call.params[0].clone())?;
+                data.insert_traces(hash, serde_json::from_value(call.result)?);
+            }
+            "trace_filter" => data.extend_traces(serde_json::from_value::<Vec<Trace>>(call.result)?),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlockSource for FixtureBlockSource {
+    async fn block_number(&self) -> ZKWatchResult<u64> {
+        self.data.block_number().await
+    }
+
+    async fn block_with_transactions(&self, number: u64) -> ZKWatchResult<Option<Block<Transaction>>> {
+        self.data.block_with_transactions(number).await
+    }
+
+    async fn transaction_receipt(&self, hash: H256) -> ZKWatchResult<Option<TransactionReceipt>> {
+        self.data.transaction_receipt(hash).await
+    }
+
+    async fn logs(&self, filter: &LogFilter) -> ZKWatchResult<Vec<Log>> {
+        self.data.logs(filter).await
+    }
+
+    async fn trace_transaction(&self, hash: H256) -> ZKWatchResult<Vec<Trace>> {
+        self.data.trace_transaction(hash).await
+    }
+
+    async fn traces(&self, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>> {
+        self.data.traces(filter).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Calls recorded from a node, in the fixture format
+    const RECORDED_CALLS: &str = r#"
+{"method":"eth_blockNumber","params":[],"result":"0x10"}
+{"method":"eth_getBlockByNumber","params":["0x5",true],"result":null}
+{"method":"eth_getLogs","params":[{"fromBlock":"0x5","toBlock":"0x6","topics":[null,null,null,null],"address":["0x0000000000000000000000000000000000000001"]}],"result":[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x","blockNumber":"0x5","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000007","logIndex":"0x0"}]}
+"#;
+
+    /// HTTP JSON-RPC endpoint answering from recorded calls by method and params
+    async fn mock_node(records: &str) -> String {
+        let calls: Arc<Vec<RecordedCall>> = Arc::new(records.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(answer(stream, calls.clone()));
+            }
+        });
+        url
+    }
+
+    async fn answer(mut stream: TcpStream, calls: Arc<Vec<RecordedCall>>) {
+        let mut buffer = Vec::new();
+        let body = loop {
+            let mut chunk = [0u8; 4096];
+            let read = stream.read(&mut chunk).await.unwrap();
+            if read == 0 {
+                return;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let headers = String::from_utf8_lossy(&buffer[..end]).to_ascii_lowercase();
+            let length: usize = headers.lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or_default();
+            if buffer.len() >= end + 4 + length {
+                break buffer[end + 4..end + 4 + length].to_vec();
+            }
+        };
+
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let response = match calls.iter().find(|c| c.method == request["method"] && c.params == request["params"]) {
+            Some(call) => serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": call.result }),
+            None => serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32601, "message": "no recorded call" },
+            }),
+        };
+        let body = response.to_string();
+        let http = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body,
+        );
+        stream.write_all(http.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rpc_source_decodes_recorded_responses() {
+        let source = RpcBlockSource::new(&mock_node(RECORDED_CALLS).await);
+
+        assert_eq!(source.block_number().await.unwrap(), 16);
+        assert!(source.block_with_transactions(5).await.unwrap().is_none());
+
+        let filter = LogFilter::new(5, 6).address(vec![H160::from_low_u64_be(1)]);
+        let logs = source.logs(&filter).await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].transaction_hash, Some(H256::from_low_u64_be(7)));
+        assert_eq!(logs[0].block_number, Some(U64::from(5)));
+    }
+
+    /// Block 16 as returned by `eth_getBlockByNumber`: a 150 ETH transfer and a 1 ETH transfer
+    const RECORDED_BLOCK: &str = r#"{
+        "hash": "0x00000000000000000000000000000000000000000000000000000000000000b0",
+        "parentHash": "0x00000000000000000000000000000000000000000000000000000000000000af",
+        "number": "0x10",
+        "timestamp": "0x6553f100",
+        "gasUsed": "0xa410",
+        "gasLimit": "0x1c9c380",
+        "transactions": [
+            {
+                "hash": "0x00000000000000000000000000000000000000000000000000000000000000a1",
+                "nonce": "0x7",
+                "blockHash": "0x00000000000000000000000000000000000000000000000000000000000000b0",
+                "blockNumber": "0x10",
+                "transactionIndex": "0x0",
+                "from": "0x00000000000000000000000000000000000000f1",
+                "to": "0x00000000000000000000000000000000000000f2",
+                "value": "0x821ab0d4414980000",
+                "gasPrice": "0x3b9aca00",
+                "gas": "0x5208",
+                "input": "0x",
+                "v": "0x1", "r": "0x1", "s": "0x1"
+            },
+            {
+                "hash": "0x00000000000000000000000000000000000000000000000000000000000000a2",
+                "nonce": "0x2",
+                "blockHash": "0x00000000000000000000000000000000000000000000000000000000000000b0",
+                "blockNumber": "0x10",
+                "transactionIndex": "0x1",
+                "from": "0x00000000000000000000000000000000000000f3",
+                "to": "0x00000000000000000000000000000000000000f4",
+                "value": "0xde0b6b3a7640000",
+                "gasPrice": "0x3b9aca00",
+                "gas": "0x5208",
+                "input": "0x",
+                "v": "0x1", "r": "0x1", "s": "0x1"
+            }
+        ]
+    }"#;
+
+    /// Receipt of the 150 ETH transfer
+    const RECORDED_RECEIPT: &str = r#"{
+        "transactionHash": "0x00000000000000000000000000000000000000000000000000000000000000a1",
+        "transactionIndex": "0x0",
+        "blockHash": "0x00000000000000000000000000000000000000000000000000000000000000b0",
+        "blockNumber": "0x10",
+        "from": "0x00000000000000000000000000000000000000f1",
+        "to": "0x00000000000000000000000000000000000000f2",
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "contractAddress": null,
+        "logs": [],
+        "status": "0x1",
+        "logsBloom": "0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000"
+    }"#;
+
+    fn recorded_call(method: &str, params: serde_json::Value, result: serde_json::Value) -> String {
+        serde_json::json!({ "method": method, "params": params, "result": result }).to_string()
+    }
+
+    #[tokio::test]
+    async fn rpc_source_scans_recorded_block_into_whales() {
+        let whale_hash = H256::from_low_u64_be(0xa1);
+        let lender_filter = LogFilter::new(16, 16).topic(0, crate::flash_loans::flash_loan_topics());
+        let records = [
+            recorded_call("eth_blockNumber", serde_json::json!([]), serde_json::json!("0x10")),
+            recorded_call("eth_getBlockByNumber", serde_json::json!(["0x10", true]), serde_json::from_str(RECORDED_BLOCK).unwrap()),
+            recorded_call("eth_getTransactionReceipt", serde_json::json!([whale_hash]), serde_json::from_str(RECORDED_RECEIPT).unwrap()),
+            recorded_call("eth_getLogs", lender_filter.to_rpc_params(), serde_json::json!([])),
+            recorded_call("trace_transaction", serde_json::json!([whale_hash]), serde_json::json!([])),
+        ]
+        .join("\n");
+
+        let network = crate::NetworkConfig {
+            name: "Ethereum".to_string(),
+            chain_id: 1,
+            rpc_url: mock_node(&records).await,
+            ws_url: None,
+            explorer_url: String::new(),
+            native_symbol: "ETH".to_string(),
+            confirmation_depth: 12,
+            finality_depth: 64,
+            block_time_ms: 12_000,
+        };
+        let prices = crate::pricing::StaticPriceTable::new([("ETH".to_string(), 2_000.0)]);
+        let mut scanner = crate::blockchain::MultiChainScanner::from_rpc(vec![network])
+            .with_scan_depth(1)
+            .with_token_registry(crate::tokens::TokenRegistry::new())
+            .with_price_source(Arc::new(prices));
+
+        let whales = scanner.scan_whale_transactions(100_000.0).await.unwrap();
+        assert_eq!(whales.len(), 1);
+        let whale = &whales[0];
+        assert_eq!(whale.hash, format!("{:?}", whale_hash));
+        assert_eq!(whale.from, format!("{:?}", H160::from_low_u64_be(0xf1)));
+        assert_eq!(whale.to, format!("{:?}", H160::from_low_u64_be(0xf2)));
+        assert_eq!(whale.value, 150_000_000_000_000_000_000);
+        assert_eq!(whale.usd_value, Some(300_000.0));
+        assert_eq!(whale.gas_used, 21_000);
+        assert_eq!(whale.block_number, 16);
+        assert_eq!(whale.timestamp.timestamp(), 1_700_000_000);
+        assert!(whale.token.is_none());
+    }
+
+    #[tokio::test]
+    async fn rpc_source_surfaces_node_errors() {
+        let source = RpcBlockSource::new(&mock_node(RECORDED_CALLS).await);
+
+        let error = source.trace_transaction(H256::from_low_u64_be(7)).await.unwrap_err();
+        assert!(matches!(&error, ZKWatchError::Blockchain(message) if message.contains("trace_transaction failed")));
+    }
+
+    fn call_trace(hash: u64, block: u64, trace_address: Vec<usize>, call_type: &str, from: u64, to: u64, value: u64) -> Trace {
+        serde_json::from_value(serde_json::json!({
+            "action": {
+                "callType": call_type,
+                "from": H160::from_low_u64_be(from),
+                "to": H160::from_low_u64_be(to),
+                "value": U256::from(value),
+                "gas": "0x0",
+                "input": "0x",
+            },
+            "result": { "gasUsed": "0x0", "output": "0x" },
+            "traceAddress": trace_address,
+            "subtraces": 0,
+            "transactionPosition": 0,
+            "transactionHash": H256::from_low_u64_be(hash),
+            "blockNumber": block,
+            "blockHash": H256::zero(),
+            "type": "call",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn trace_filter_finds_internal_value_transfers() {
+        let source = InMemoryBlockSource::new();
+        source.insert_traces(H256::from_low_u64_be(1), vec![
+            call_trace(1, 10, vec![], "call", 1, 2, 0),
+            call_trace(1, 10, vec![0], "call", 2, 3, 500),
+            call_trace(1, 10, vec![1], "delegatecall", 2, 3, 700),
+        ]);
+        source.insert_traces(H256::from_low_u64_be(2), vec![call_trace(2, 30, vec![], "call", 3, 4, 900)]);
+
+        let received = source.traces(&TraceFilter::new(0, 20).to_address(vec![H160::from_low_u64_be(3)])).await.unwrap();
+        assert_eq!(received.len(), 2);
+        let transfers: Vec<_> = received.iter().filter_map(value_transfer).collect();
+        assert_eq!(transfers, vec![(H160::from_low_u64_be(2), H160::from_low_u64_be(3), U256::from(500))]);
+
+        let sent = source.traces(&TraceFilter::new(0, 40).from_address(vec![H160::from_low_u64_be(3)])).await.unwrap();
+        assert_eq!(sent.iter().map(|t| t.block_number).collect::<Vec<_>>(), vec![30]);
+    }
+
+    #[test]
+    fn failed_and_zero_value_calls_move_nothing() {
+        let mut failed = call_trace(1, 10, vec![0], "call", 1, 2, 500);
+        failed.error = Some("Reverted".to_string());
+        assert!(value_transfer(&failed).is_none());
+        assert!(value_transfer(&call_trace(1, 10, vec![], "call", 1, 2, 0)).is_none());
+        assert!(value_transfer(&call_trace(1, 10, vec![], "staticcall", 1, 2, 5)).is_none());
+    }
+}
@@ -4,67 +4,517 @@
 //! and cross-chain transaction tracking capabilities.
 
 use crate::{ZKWatchResult, NetworkConfig, WhaleTransaction, ZKWatchError};
+use crate::block_source::{self, BlockSource, LogFilter, RpcBlockSource, TraceFilter};
+use crate::bridges::{self, BridgeDirection, BridgeProtocol, BridgeRegistry, BridgeTransfer};
+use crate::chain_tracker::{BlockRef, CanonicalChain, ChainUpdate, ConfirmationStatus};
+use crate::checkpoint::{BackfillProgress, Checkpoint, CheckpointStore};
+use crate::clustering::GasFunding;
+use crate::dex;
+use crate::exchange_flows::{self, ExchangeFlow};
+use crate::flash_loans;
+use crate::fund_flow::{self, BridgeLeg, FlowGraph, FlowTransfer, TaintSeed, TraceConfig};
+use crate::mempool::MempoolWatcher;
+use crate::labels::LabelRegistry;
+use crate::lending::{self, LendingAction, Liquidation};
+use crate::mev::{self, Arbitrage, Sandwich};
+use crate::pricing::{self, PriceSource, StaticPriceTable};
+use crate::screening::{Screener, ScreeningHit, TransferGraph};
+use crate::tokens::{self, Erc20Transfer, TokenRegistry, TrackedToken};
+use ethers::types::{Block, Log, Trace, Transaction, TransactionReceipt, H160, H256, U256, U64};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, BTreeMap};
-use chrono::{DateTime, Utc};
+use std::collections::{hash_map::Entry, HashMap, HashSet, BTreeMap, BTreeSet};
+use std::sync::Arc;
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Number of most recent blocks inspected per network on each scan
+pub const DEFAULT_SCAN_DEPTH: u64 = 5;
+
+/// How far back an address's history is searched on every network
+pub const DEFAULT_HISTORY_LOOKBACK: chrono::Duration = chrono::Duration::days(1);
+
+/// Blocks per log query when searching an address's history
+const HISTORY_LOG_CHUNK: u64 = 1_000;
+
+/// Most addresses whose transfers are fetched per network and hop when building a screening graph
+const MAX_SCREENING_ADDRESSES: usize = 50;
+
+/// Most transactions decoded per network for one DeFi interaction analysis
+const MAX_DEFI_TRANSACTIONS: usize = 200;
+
+/// Blocks per chunk when backfilling a historical range
+pub const DEFAULT_BACKFILL_CHUNK_SIZE: u64 = 100;
+
+/// Chunks fetched in parallel when backfilling a historical range
+pub const DEFAULT_BACKFILL_CONCURRENCY: usize = 4;
+
+/// Number of most recent blocks searched for gas top-ups of clustered addresses
+pub const DEFAULT_FUNDING_LOOKBACK: u64 = 100;
+
+/// Largest native transfer, in USD, treated as a gas top-up rather than a payment
+const MAX_GAS_FUNDING_USD: f64 = 1_000.0;
 
 /// Multi-chain blockchain scanner
 pub struct MultiChainScanner {
     networks: Vec<NetworkConfig>,
-    api_clients: HashMap<String, ApiClient>,
-    rate_limiters: HashMap<String, RateLimiter>,
+    sources: HashMap<String, Arc<dyn BlockSource>>,
+    rate_limiters: HashMap<String, Arc<RateLimiter>>,
+    token_registry: Arc<TokenRegistry>,
+    price_source: Arc<dyn PriceSource>,
+    scan_depth: u64,
+    history_lookback: chrono::Duration,
+    bridge_registry: Arc<BridgeRegistry>,
+    label_registry: Arc<LabelRegistry>,
+    screener: Arc<Screener>,
+    chain_states: HashMap<String, ChainState>,
+    events: Vec<ScanEvent>,
+    checkpoints: Option<CheckpointStore>,
+}
+
+/// Canonical chain and detected whales of one network, kept until finality
+struct ChainState {
+    chain: CanonicalChain,
+    block_whales: BTreeMap<u64, BlockWhales>,
+}
+
+struct BlockWhales {
+    hash: H256,
+    transactions: Vec<WhaleTransaction>,
+}
+
+/// Change in the scanner's view of whale transactions
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ScanEvent {
+    /// Whale transaction seen for the first time
+    Detected(WhaleTransaction),
+    /// Whale transaction moved to a deeper confirmation stage
+    StatusChanged {
+        network: String,
+        hash: String,
+        log_index: Option<u64>,
+        status: ConfirmationStatus,
+    },
+    /// Whale transaction's block was reorganized out of the canonical chain
+    Retracted {
+        transaction: WhaleTransaction,
+        orphaned_block_hash: String,
+    },
 }
 
 impl MultiChainScanner {
-    pub fn new(networks: Vec<NetworkConfig>) -> Self {
-        let mut api_clients = HashMap::new();
+    /// Create a scanner with an explicit block source for each network
+    pub fn new(networks: Vec<(NetworkConfig, Arc<dyn BlockSource>)>) -> Self {
+        let mut sources = HashMap::new();
         let mut rate_limiters = HashMap::new();
+        let mut configs = Vec::new();
         
-        for network in &networks {
-            api_clients.insert(network.name.clone(), ApiClient::new(&network.rpc_url));
-            rate_limiters.insert(network.name.clone(), RateLimiter::new(100, std::time::Duration::from_secs(60)));
+        for (network, source) in networks {
+            sources.insert(network.name.clone(), source);
+            rate_limiters.insert(network.name.clone(), Arc::new(RateLimiter::new(100, std::time::Duration::from_secs(60))));
+            configs.push(network);
         }
         
         Self {
-            networks,
-            api_clients,
+            networks: configs,
+            sources,
             rate_limiters,
+            token_registry: Arc::new(TokenRegistry::with_defaults()),
+            price_source: Arc::new(StaticPriceTable::stablecoins()),
+            scan_depth: DEFAULT_SCAN_DEPTH,
+            history_lookback: DEFAULT_HISTORY_LOOKBACK,
+            bridge_registry: Arc::new(BridgeRegistry::with_defaults()),
+            label_registry: Arc::new(LabelRegistry::with_defaults()),
+            screener: Arc::new(Screener::from_labels(&LabelRegistry::with_defaults())),
+            chain_states: HashMap::new(),
+            events: Vec::new(),
+            checkpoints: None,
         }
     }
 
+    /// Create a scanner reading every network from its configured JSON-RPC endpoint
+    pub fn from_rpc(networks: Vec<NetworkConfig>) -> Self {
+        Self::new(networks.into_iter()
+            .map(|network| {
+                let source: Arc<dyn BlockSource> = Arc::new(RpcBlockSource::new(&network.rpc_url));
+                (network, source)
+            })
+            .collect())
+    }
+
+    /// Set how many of the most recent blocks each scan inspects per network
+    pub fn with_scan_depth(mut self, scan_depth: u64) -> Self {
+        self.scan_depth = scan_depth.max(1);
+        self
+    }
+
+    /// Set how far back address analyses (DeFi, cross-chain, fund flows) search,
+    /// converted to blocks with each network's block time
+    pub fn with_history_lookback(mut self, history_lookback: chrono::Duration) -> Self {
+        self.history_lookback = history_lookback;
+        self
+    }
+
+    /// First block of a network's history window ending at `head`
+    fn history_start(&self, network: &NetworkConfig, head: u64) -> u64 {
+        head.saturating_sub(network.blocks_in(self.history_lookback) - 1)
+    }
+
+    /// Replace the bridge contracts recognized in cross-chain analysis
+    pub fn with_bridge_registry(mut self, bridge_registry: BridgeRegistry) -> Self {
+        self.bridge_registry = Arc::new(bridge_registry);
+        self
+    }
+
+    /// Replace the address labels, and the bridge contracts derived from them
+    pub fn with_label_registry(mut self, label_registry: LabelRegistry) -> Self {
+        self.bridge_registry = Arc::new(BridgeRegistry::from_labels(&label_registry));
+        Arc::make_mut(&mut self.screener).add_labels(&label_registry);
+        self.label_registry = Arc::new(label_registry);
+        self
+    }
+
+    pub fn labels(&self) -> &LabelRegistry {
+        &self.label_registry
+    }
+
+    /// Replace the sanctions and watchlist entries; sanctioned and mixer labels are added to them
+    pub fn with_screener(mut self, mut screener: Screener) -> Self {
+        screener.add_labels(&self.label_registry);
+        self.screener = Arc::new(screener);
+        self
+    }
+
+    pub fn screener(&self) -> &Screener {
+        &self.screener
+    }
+
+    /// Replace the set of ERC-20 tokens (and their thresholds) tracked for whale transfers
+    pub fn with_token_registry(mut self, token_registry: TokenRegistry) -> Self {
+        self.token_registry = Arc::new(token_registry);
+        self
+    }
+
+    pub fn token_registry_mut(&mut self) -> &mut TokenRegistry {
+        Arc::make_mut(&mut self.token_registry)
+    }
+
+    /// Persist the last processed block per network and resume scans from it
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointStore) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Use a price source to value transfers in USD at their block timestamp
+    pub fn with_price_source(mut self, price_source: Arc<dyn PriceSource>) -> Self {
+        self.price_source = price_source;
+        self
+    }
+
+    pub fn networks(&self) -> &[NetworkConfig] {
+        &self.networks
+    }
+
+    /// Scanner restricted to one network, sharing this scanner's sources, rate
+    /// limits, tokens, prices and checkpoints but with its own chain state
+    pub fn for_network(&self, network_name: &str) -> Option<Self> {
+        let network = self.networks.iter().find(|n| n.name == network_name)?.clone();
+        
+        Some(Self {
+            sources: HashMap::from([(network.name.clone(), Arc::clone(self.sources.get(&network.name)?))]),
+            rate_limiters: HashMap::from([(network.name.clone(), Arc::clone(self.rate_limiters.get(&network.name)?))]),
+            networks: vec![network],
+            token_registry: Arc::clone(&self.token_registry),
+            price_source: Arc::clone(&self.price_source),
+            scan_depth: self.scan_depth,
+            history_lookback: self.history_lookback,
+            bridge_registry: Arc::clone(&self.bridge_registry),
+            label_registry: Arc::clone(&self.label_registry),
+            screener: Arc::clone(&self.screener),
+            chain_states: HashMap::new(),
+            events: Vec::new(),
+            checkpoints: self.checkpoints.clone(),
+        })
+    }
+
+    /// Mempool watcher valuing and classifying pending transfers with this scanner's tokens, prices and labels
+    pub fn mempool_watcher(&self, network_name: &str, min_usd: f64) -> Option<MempoolWatcher> {
+        let network = self.networks.iter().find(|n| n.name == network_name)?.clone();
+        Some(MempoolWatcher::new(network, Arc::clone(&self.token_registry), Arc::clone(&self.price_source), min_usd)
+            .with_labels(Arc::clone(&self.label_registry)))
+    }
+
     /// Scan for whale transactions across all configured networks
+    ///
+    /// Transfers are valued in USD at their block timestamp and kept when worth
+    /// at least `min_usd`. A block whose native asset has no price is an error;
+    /// token transfers without a price fall back to the token registry's raw thresholds.
     pub async fn scan_whale_transactions(
         &mut self,
-        min_value: u128,
+        min_usd: f64,
     ) -> ZKWatchResult<Vec<WhaleTransaction>> {
         let mut all_transactions = Vec::new();
         
-        for network in &self.networks {
-            let transactions = self.scan_network_whales(network, min_value).await?;
+        for network in self.networks.clone() {
+            let transactions = self.scan_network_whales(&network, min_usd).await?;
             all_transactions.extend(transactions);
         }
         
         // Sort by timestamp
-        all_transactions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        all_transactions.sort_by_key(|tx| std::cmp::Reverse(tx.timestamp));
         
         Ok(all_transactions)
     }
 
+    /// Block source and rate limiter registered for a network
+    fn source_and_limiter(&self, network: &NetworkConfig) -> ZKWatchResult<(&Arc<dyn BlockSource>, &Arc<RateLimiter>)> {
+        let source = self.sources.get(&network.name)
+            .ok_or_else(|| ZKWatchError::Blockchain(format!("No block source for network: {}", network.name)))?;
+        let limiter = self.rate_limiters.get(&network.name)
+            .ok_or_else(|| ZKWatchError::Blockchain(format!("No rate limiter for network: {}", network.name)))?;
+        Ok((source, limiter))
+    }
+
+    /// Shared handles for extracting whales from blocks of one network
+    fn scan_context(&self, network: &NetworkConfig, min_usd: f64) -> ZKWatchResult<ScanContext> {
+        let (source, limiter) = self.source_and_limiter(network)?;
+        Ok(ScanContext {
+            network: network.clone(),
+            source: Arc::clone(source),
+            limiter: Arc::clone(limiter),
+            token_registry: Arc::clone(&self.token_registry),
+            price_source: Arc::clone(&self.price_source),
+            label_registry: Arc::clone(&self.label_registry),
+            min_usd,
+        })
+    }
+
+    /// Take the detection, confirmation and retraction events produced by past scans
+    pub fn drain_events(&mut self) -> Vec<ScanEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     async fn scan_network_whales(
         &mut self,
         network: &NetworkConfig,
-        min_value: u128,
+        min_usd: f64,
     ) -> ZKWatchResult<Vec<WhaleTransaction>> {
-        // Rate limiting
-        if let Some(limiter) = self.rate_limiters.get_mut(&network.name) {
+        let context = self.scan_context(network, min_usd)?;
+        let (source, limiter) = (&context.source, &context.limiter);
+        let history = network.finality_depth + self.scan_depth;
+        
+        if !self.chain_states.contains_key(&network.name) {
+            let mut chain = CanonicalChain::new(history);
+            
+            // Resume from the persisted checkpoint; a reorg across the restart shows up as a parent mismatch
+            if let Some(checkpoint) = self.checkpoints.as_ref().map(|c| c.load(&network.name)).transpose()?.flatten() {
+                let hash = checkpoint.block_hash.parse()
+                    .map_err(|_| ZKWatchError::Blockchain(format!("Corrupt checkpoint hash for {}", network.name)))?;
+                chain.ingest(BlockRef {
+                    number: checkpoint.block_number,
+                    hash,
+                    parent_hash: H256::zero(),
+                });
+            }
+            
+            self.chain_states.insert(network.name.clone(), ChainState {
+                chain,
+                block_whales: BTreeMap::new(),
+            });
+        }
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let first = head.saturating_sub(self.scan_depth - 1);
+        
+        // Catch up on every block produced since the last processed one
+        let start = match self.chain_states[&network.name].chain.head() {
+            Some(tracked) if tracked.number < first => tracked.number + 1,
+            _ => first,
+        };
+        
+        // Blocks already past finality cannot reorg, so long gaps go through the chunked backfill
+        let linear_start = start.max(head.saturating_sub(network.finality_depth));
+        let caught_up = if start < linear_start {
+            self.backfill(
+                &network.name,
+                start,
+                linear_start - 1,
+                min_usd,
+                DEFAULT_BACKFILL_CHUNK_SIZE,
+                DEFAULT_BACKFILL_CONCURRENCY,
+            ).await?
+        } else {
+            Vec::new()
+        };
+        self.events.extend(caught_up.iter().cloned().map(ScanEvent::Detected));
+        
+        let state = self.chain_states.get_mut(&network.name).expect("chain state initialized");
+        for number in linear_start..=head {
             limiter.wait().await?;
+            let block = match source.block_with_transactions(number).await? {
+                Some(block) => block,
+                None => continue,
+            };
+            
+            // Ingest the block, walking back through its ancestors while they disagree with the tracked chain
+            let mut pending = vec![block];
+            while let Some(block) = pending.last() {
+                let block_ref = BlockRef::from_block(block)
+                    .ok_or_else(|| ZKWatchError::Blockchain(format!("Block {} on {} has no hash", number, network.name)))?;
+                
+                match state.chain.preview(&block_ref) {
+                    ChainUpdate::ParentMismatch { parent_number } => {
+                        if pending.len() as u64 > history {
+                            return Err(ZKWatchError::Blockchain(format!(
+                                "Reorg on {} deeper than {} tracked blocks", network.name, history
+                            )));
+                        }
+                        limiter.wait().await?;
+                        let parent = source.block_with_transactions(parent_number).await?
+                            .ok_or_else(|| ZKWatchError::Blockchain(format!(
+                                "Block {} on {} disappeared during reorg", parent_number, network.name
+                            )))?;
+                        pending.push(parent);
+                        continue;
+                    }
+                    ChainUpdate::Known => {
+                        pending.pop();
+                        continue;
+                    }
+                    ChainUpdate::Extended | ChainUpdate::Reorg { .. } => {}
+                }
+                
+                // Extract before the block joins the tracked chain, so a failed
+                // extraction leaves it unprocessed and the next scan retries it
+                let block = pending.pop().expect("block being ingested");
+                let transactions = context.block_whales(&block).await?;
+                
+                if let ChainUpdate::Reorg { orphaned } = state.chain.ingest(block_ref) {
+                    for orphan in orphaned {
+                        let removed = match state.block_whales.get(&orphan.number) {
+                            Some(whales) if whales.hash == orphan.hash => state.block_whales.remove(&orphan.number),
+                            _ => None,
+                        };
+                        for transaction in removed.map(|w| w.transactions).unwrap_or_default() {
+                            self.events.push(ScanEvent::Retracted {
+                                transaction,
+                                orphaned_block_hash: format!("{:?}", orphan.hash),
+                            });
+                        }
+                    }
+                }
+                
+                self.events.extend(transactions.iter().cloned().map(ScanEvent::Detected));
+                state.block_whales.insert(block_ref.number, BlockWhales {
+                    hash: block_ref.hash,
+                    transactions,
+                });
+            }
+        }
+        
+        // Refresh confirmation status against the current head
+        let head = state.chain.head().map(|b| b.number).unwrap_or(head);
+        for (&number, whales) in state.block_whales.iter_mut() {
+            let status = ConfirmationStatus::at_depth(head.saturating_sub(number), network.confirmation_depth, network.finality_depth);
+            for tx in whales.transactions.iter_mut().filter(|tx| tx.confirmation != status) {
+                tx.confirmation = status;
+                self.events.push(ScanEvent::StatusChanged {
+                    network: network.name.clone(),
+                    hash: tx.hash.clone(),
+                    log_index: tx.log_index,
+                    status,
+                });
+            }
+        }
+        
+        let mut transactions = caught_up;
+        transactions.extend(state.block_whales.range(start.min(first)..=head)
+            .flat_map(|(_, whales)| whales.transactions.iter().cloned()));
+        
+        // Finalized blocks outside the scan window can no longer change
+        let keep_from = first.min(head.saturating_sub(network.finality_depth));
+        state.block_whales = state.block_whales.split_off(&keep_from);
+        
+        if let (Some(checkpoints), Some(tracked)) = (&self.checkpoints, state.chain.head()) {
+            checkpoints.save(&Checkpoint {
+                network: network.name.clone(),
+                block_number: tracked.number,
+                block_hash: format!("{:?}", tracked.hash),
+                updated_at: Utc::now(),
+            })?;
+        }
+        
+        Ok(transactions)
+    }
+
+    /// Walk a historical block range for whale transfers
+    ///
+    /// The range is split into chunks of `chunk_size` blocks processed by up to
+    /// `concurrency` parallel tasks that share the network's rate limiter. With a
+    /// checkpoint store configured, finished chunks and their whales are persisted
+    /// so an interrupted backfill resumes with the remaining chunks only.
+    pub async fn backfill(
+        &self,
+        network_name: &str,
+        from: u64,
+        to: u64,
+        min_usd: f64,
+        chunk_size: u64,
+        concurrency: usize,
+    ) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        if from > to {
+            return Err(ZKWatchError::Blockchain(format!("Invalid backfill range {}..{}", from, to)));
         }
         
-        let client = self.api_clients.get(&network.name)
-            .ok_or_else(|| ZKWatchError::Blockchain(format!("No client for network: {}", network.name)))?;
+        let network = self.networks.iter()
+            .find(|n| n.name.eq_ignore_ascii_case(network_name))
+            .ok_or_else(|| ZKWatchError::Blockchain(format!("Unknown network: {}", network_name)))?;
+        let context = self.scan_context(network, min_usd)?;
         
-        // Simulate whale detection (in real implementation, this would query actual APIs)
-        let transactions = simulate_whale_detection(network, min_value).await?;
+        let mut progress = match &self.checkpoints {
+            Some(store) => store.load_backfill(&context.network.name, from, to)?
+                .filter(|p| p.chunk_size == chunk_size.max(1))
+                .unwrap_or_else(|| BackfillProgress::new(&context.network.name, from, to, chunk_size)),
+            None => BackfillProgress::new(&context.network.name, from, to, chunk_size),
+        };
+        let mut transactions = match &self.checkpoints {
+            Some(store) => store.load_backfill_results(&progress)?,
+            None => Vec::new(),
+        };
+        
+        let mut remaining = progress.remaining_chunks().into_iter();
+        let mut tasks = tokio::task::JoinSet::new();
+        for chunk in remaining.by_ref().take(concurrency.max(1)) {
+            tasks.spawn(context.clone().scan_chunk(chunk));
+        }
+        
+        while let Some(joined) = tasks.join_next().await {
+            let (chunk_start, found) = joined
+                .map_err(|e| ZKWatchError::Blockchain(format!("Backfill task failed: {}", e)))??;
+            
+            if let Some(store) = &self.checkpoints {
+                store.append_backfill_results(&progress, &found)?;
+                progress.completed_chunks.insert(chunk_start);
+                progress.updated_at = Utc::now();
+                store.save_backfill(&progress)?;
+            }
+            transactions.extend(found);
+            
+            if let Some(chunk) = remaining.next() {
+                tasks.spawn(context.clone().scan_chunk(chunk));
+            }
+        }
+        
+        // Historical blocks may still sit inside the reorg window of the current head
+        context.limiter.wait().await?;
+        let head = context.source.block_number().await?;
+        for tx in &mut transactions {
+            tx.confirmation = ConfirmationStatus::at_depth(
+                head.saturating_sub(tx.block_number),
+                context.network.confirmation_depth,
+                context.network.finality_depth,
+            );
+        }
+        transactions.sort_by_key(|tx| (tx.block_number, tx.log_index));
         
         Ok(transactions)
     }
@@ -76,24 +526,322 @@ impl MultiChainScanner {
     ) -> ZKWatchResult<Vec<MEVOpportunity>> {
         let mut opportunities = Vec::new();
         
-        for network in &self.networks {
-            let network_opportunities = self.detect_network_mev(network, recent_blocks).await?;
+        for network in self.networks.clone() {
+            let network_opportunities = self.detect_network_mev(&network, recent_blocks).await?;
             opportunities.extend(network_opportunities);
         }
         
         Ok(opportunities)
     }
 
+    /// Sandwiches, atomic arbitrage and liquidations in the most recent `recent_blocks` blocks of a network
     async fn detect_network_mev(
-        &mut self,
+        &self,
         network: &NetworkConfig,
         recent_blocks: u64,
     ) -> ZKWatchResult<Vec<MEVOpportunity>> {
-        // Simulate MEV detection
-        let opportunities = simulate_mev_detection(network, recent_blocks).await?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        let mut opportunities = Vec::new();
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        
+        for number in head.saturating_sub(recent_blocks.max(1) - 1)..=head {
+            limiter.wait().await?;
+            let block = match source.block_with_transactions(number).await? {
+                Some(block) => block,
+                None => continue,
+            };
+            
+            let timestamp = block_timestamp(&block)?;
+            
+            let mut topics = dex::swap_related_topics();
+            topics.extend(lending::liquidation_topics());
+            topics.sort();
+            topics.dedup();
+            let filter = LogFilter::new(number, number).topic(0, topics);
+            limiter.wait().await?;
+            let logs = source.logs(&filter).await?;
+            let swaps = dex::decode_swaps(&logs);
+            
+            for sandwich in mev::detect_sandwiches(&block, &swaps) {
+                // Gas the searcher burned on both legs
+                let gas = gas_spent(source.as_ref(), limiter, &[sandwich.front_run.transaction_hash, sandwich.back_run.transaction_hash]).await?;
+                opportunities.push(mev_from_sandwich(network, &block, timestamp, &sandwich, gas, &self.token_registry, self.price_source.as_ref()));
+            }
+            
+            for arbitrage in mev::detect_arbitrages(&block, &swaps) {
+                let gas = gas_spent(source.as_ref(), limiter, &[arbitrage.transaction_hash]).await?;
+                opportunities.push(mev_from_arbitrage(network, &block, timestamp, &arbitrage, gas, &self.token_registry, self.price_source.as_ref()));
+            }
+            
+            for liquidation in lending::decode_liquidations(&logs) {
+                let gas = gas_spent(source.as_ref(), limiter, &[liquidation.transaction_hash]).await?;
+                opportunities.push(mev_from_liquidation(network, &block, timestamp, &liquidation, gas, &self.token_registry, self.price_source.as_ref()));
+            }
+        }
+        
         Ok(opportunities)
     }
 
+    /// Plain native transfers small enough to be gas top-ups, paid to any of
+    /// `addresses` in the most recent `lookback` blocks of every network
+    ///
+    /// Unpriced transfers count when they are at most one native coin.
+    pub async fn gas_fundings(
+        &self,
+        addresses: &HashSet<String>,
+        lookback: u64,
+    ) -> ZKWatchResult<Vec<GasFunding>> {
+        let mut fundings = Vec::new();
+        if addresses.is_empty() {
+            return Ok(fundings);
+        }
+        
+        for network in &self.networks {
+            let (source, limiter) = self.source_and_limiter(network)?;
+            
+            limiter.wait().await?;
+            let head = source.block_number().await?;
+            
+            for number in head.saturating_sub(lookback.max(1) - 1)..=head {
+                limiter.wait().await?;
+                let block = match source.block_with_transactions(number).await? {
+                    Some(block) => block,
+                    None => continue,
+                };
+                let timestamp = block_timestamp(&block)?;
+                let native_price = self.price_source.price_usd(&network.native_symbol, timestamp);
+                
+                for tx in &block.transactions {
+                    let to = match tx.to {
+                        Some(to) if to != tx.from && tx.input.is_empty() && !tx.value.is_zero() => format_address(&to),
+                        _ => continue,
+                    };
+                    if !addresses.contains(&to) {
+                        continue;
+                    }
+                    let value = u256_to_u128(tx.value);
+                    let small = match native_price {
+                        Some(price) => value as f64 / 1e18 * price <= MAX_GAS_FUNDING_USD,
+                        None => value <= 1_000_000_000_000_000_000,
+                    };
+                    if small {
+                        fundings.push(GasFunding {
+                            network: network.name.clone(),
+                            chain_id: network.chain_id,
+                            funder: format_address(&tx.from),
+                            funded: to,
+                            value,
+                            timestamp,
+                        });
+                    }
+                }
+            }
+        }
+        
+        Ok(fundings)
+    }
+
+    /// Follow tainted funds from a seed address or transaction
+    ///
+    /// Transfers of each tainted, unlabeled address are fetched hop by hop, from
+    /// the block the taint arrived in to the head: ERC-20 transfers from logs and
+    /// native transfers, internal ones included, from call traces. Bridge
+    /// depositors are also searched on the other networks so their fills can be matched.
+    pub async fn trace_funds(&self, seed: &TaintSeed, config: &TraceConfig) -> ZKWatchResult<FlowGraph> {
+        let mut cache = TraceCache::default();
+        let mut transfers = Vec::new();
+        let mut queue: Vec<(NetworkConfig, String, Option<u64>)> = Vec::new();
+        let mut fetched: HashSet<(String, String)> = HashSet::new();
+        
+        match seed {
+            TaintSeed::Address(address) => {
+                for network in &self.networks {
+                    fetched.insert((network.name.clone(), address.to_ascii_lowercase()));
+                    queue.push((network.clone(), address.to_ascii_lowercase(), None));
+                }
+            }
+            TaintSeed::Transaction { network, hash } => {
+                let network = self.networks.iter()
+                    .find(|n| &n.name == network)
+                    .ok_or_else(|| ZKWatchError::Blockchain(format!("Unknown network: {}", network)))?;
+                transfers.extend(self.transaction_flow_transfers(network, hash, &mut cache).await?);
+            }
+        }
+        
+        loop {
+            for (network, address, from_block) in queue.drain(..) {
+                transfers.extend(self.account_flow_transfers(&network, &address, from_block, &mut cache).await?);
+            }
+            
+            let graph = fund_flow::propagate(seed, &transfers, &self.label_registry, config);
+            let mut enqueue = |network: &NetworkConfig, address: &str, from_block: Option<u64>| {
+                let per_network = fetched.iter().filter(|(name, _)| name == &network.name).count();
+                if per_network < config.max_addresses && fetched.insert((network.name.clone(), address.to_string())) {
+                    queue.push((network.clone(), address.to_string(), from_block));
+                }
+            };
+            
+            for node in graph.nodes.iter().filter(|n| n.kind.followed() && n.hop < config.max_hops) {
+                if let Some(network) = self.networks.iter().find(|n| n.chain_id == node.chain_id) {
+                    enqueue(network, &node.address, Some(node.first_tainted_block));
+                }
+            }
+            // Fills pay the depositor on another chain, at a block number we cannot know in advance
+            for edge in graph.edges.iter().filter(|e| e.bridge.map(|leg| leg.direction) == Some(BridgeDirection::Deposit)) {
+                let Some(depositor) = graph.node(&edge.from) else {
+                    continue;
+                };
+                for network in self.networks.iter().filter(|n| n.chain_id != depositor.chain_id) {
+                    enqueue(network, &depositor.address, None);
+                }
+            }
+            
+            if queue.is_empty() {
+                return Ok(graph);
+            }
+        }
+    }
+
+    /// Native and ERC-20 value moved by one transaction
+    async fn transaction_flow_transfers(
+        &self,
+        network: &NetworkConfig,
+        hash: &str,
+        cache: &mut TraceCache,
+    ) -> ZKWatchResult<Vec<FlowTransfer>> {
+        let hash = hash.parse::<H256>()
+            .map_err(|e| ZKWatchError::Blockchain(format!("Invalid transaction hash {}: {}", hash, e)))?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        
+        limiter.wait().await?;
+        let receipt = source.transaction_receipt(hash).await?
+            .ok_or_else(|| ZKWatchError::Blockchain(format!("Transaction not found on {}: {:?}", network.name, hash)))?;
+        let number = receipt.block_number
+            .ok_or_else(|| ZKWatchError::Blockchain(format!("Transaction is pending: {:?}", hash)))?
+            .as_u64();
+        
+        let timestamps = cache.timestamps.entry(network.name.clone()).or_default();
+        let timestamp = block_time(source.as_ref(), limiter, timestamps, number).await?;
+        limiter.wait().await?;
+        let mut transfers: Vec<FlowTransfer> = source.trace_transaction(hash).await?.iter()
+            .filter_map(|trace| self.flow_from_trace(network, trace, timestamp))
+            .collect();
+        for transfer in receipt.logs.iter().filter_map(tokens::decode_transfer) {
+            let bridge = self.erc20_bridge_leg(network, &transfer, cache).await?;
+            transfers.push(flow_from_erc20(network, &transfer, &self.token_registry, timestamp, bridge));
+        }
+        
+        Ok(transfers)
+    }
+
+    /// ERC-20 and native transfers of an account from `from_block` (or the start
+    /// of the history lookback) to the head
+    async fn account_flow_transfers(
+        &self,
+        network: &NetworkConfig,
+        address: &str,
+        from_block: Option<u64>,
+        cache: &mut TraceCache,
+    ) -> ZKWatchResult<Vec<FlowTransfer>> {
+        let account = address.parse::<H160>()
+            .map_err(|e| ZKWatchError::Blockchain(format!("Invalid address {}: {}", address, e)))?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let start = from_block.unwrap_or_else(|| self.history_start(network, head)).min(head);
+        let logs = account_logs(source.as_ref(), limiter, account, (start, head), &[
+            (vec![tokens::transfer_topic()], 1),
+            (vec![tokens::transfer_topic()], 2),
+        ]).await?;
+        
+        let mut transfers = Vec::new();
+        for transfer in logs.iter().filter_map(tokens::decode_transfer) {
+            let number = transfer.block_number.unwrap_or_default();
+            let timestamps = cache.timestamps.entry(network.name.clone()).or_default();
+            let timestamp = block_time(source.as_ref(), limiter, timestamps, number).await?;
+            let bridge = self.erc20_bridge_leg(network, &transfer, cache).await?;
+            transfers.push(flow_from_erc20(network, &transfer, &self.token_registry, timestamp, bridge));
+        }
+        
+        // Internal transfers never show up as transactions, so read native value from call traces
+        for filter in [
+            TraceFilter::new(start, head).from_address(vec![account]),
+            TraceFilter::new(start, head).to_address(vec![account]),
+        ] {
+            for trace in chunked_traces(source.as_ref(), limiter, &filter).await? {
+                let timestamps = cache.timestamps.entry(network.name.clone()).or_default();
+                let timestamp = block_time(source.as_ref(), limiter, timestamps, trace.block_number).await?;
+                transfers.extend(self.flow_from_trace(network, &trace, timestamp));
+            }
+        }
+        
+        Ok(transfers)
+    }
+
+    /// Native value moved by a call, creation or self-destruct, as a flow transfer
+    fn flow_from_trace(&self, network: &NetworkConfig, trace: &Trace, timestamp: DateTime<Utc>) -> Option<FlowTransfer> {
+        let (from, to, value) = block_source::value_transfer(trace)?;
+        let bridge = match (self.bridge_registry.get(network.chain_id, &to), self.bridge_registry.get(network.chain_id, &from)) {
+            (Some(protocol), _) => Some(BridgeLeg { protocol, direction: BridgeDirection::Deposit }),
+            (None, Some(protocol)) => Some(BridgeLeg { protocol, direction: BridgeDirection::Fill }),
+            (None, None) => None,
+        };
+        
+        Some(FlowTransfer {
+            network: network.name.clone(),
+            chain_id: network.chain_id,
+            transaction_hash: format!("{:?}", trace.transaction_hash?),
+            log_index: None,
+            trace_address: trace.trace_address.clone(),
+            block_number: trace.block_number,
+            timestamp,
+            from: format_address(&from),
+            to: format_address(&to),
+            asset: fund_flow::NATIVE_ASSET.to_string(),
+            symbol: network.native_symbol.clone(),
+            decimals: Some(18),
+            amount: u256_to_u128(value),
+            bridge,
+        })
+    }
+
+    /// Bridge end of an ERC-20 transfer paid to or by a bridge, or burned or
+    /// minted in a call to one
+    async fn erc20_bridge_leg(
+        &self,
+        network: &NetworkConfig,
+        transfer: &Erc20Transfer,
+        cache: &mut TraceCache,
+    ) -> ZKWatchResult<Option<BridgeLeg>> {
+        if let Some(protocol) = self.bridge_registry.get(network.chain_id, &transfer.to) {
+            return Ok(Some(BridgeLeg { protocol, direction: BridgeDirection::Deposit }));
+        }
+        if let Some(protocol) = self.bridge_registry.get(network.chain_id, &transfer.from) {
+            return Ok(Some(BridgeLeg { protocol, direction: BridgeDirection::Fill }));
+        }
+        let (Some(hash), true) = (transfer.transaction_hash, transfer.from.is_zero() || transfer.to.is_zero()) else {
+            return Ok(None);
+        };
+        
+        let recipient = match cache.recipients.get(&hash) {
+            Some(recipient) => *recipient,
+            None => {
+                let (source, limiter) = self.source_and_limiter(network)?;
+                limiter.wait().await?;
+                let recipient = source.transaction_receipt(hash).await?.and_then(|r| r.to);
+                cache.recipients.insert(hash, recipient);
+                recipient
+            }
+        };
+        let direction = if transfer.to.is_zero() { BridgeDirection::Deposit } else { BridgeDirection::Fill };
+        Ok(recipient
+            .and_then(|to| self.bridge_registry.get(network.chain_id, &to))
+            .map(|protocol| BridgeLeg { protocol, direction }))
+    }
+
     /// Cross-chain transaction analysis
     pub async fn analyze_cross_chain_transactions(
         &mut self,
@@ -105,61 +853,425 @@ impl MultiChainScanner {
             total_cross_chain_volume: 0,
             bridge_patterns: Vec::new(),
             risk_assessment: RiskAssessment::Medium,
+            risk_evidence: Vec::new(),
             recommendations: Vec::new(),
         };
         
-        for network in &self.networks {
-            let network_data = self.analyze_address_on_network(address, network).await?;
+        for network in self.networks.clone() {
+            let network_data = self.analyze_address_on_network(address, &network).await?;
+            analysis.total_cross_chain_volume += network_data.total_volume;
             analysis.networks_analyzed.push(network_data);
-            analysis.total_cross_chain_volume += network_data.volume;
+        }
+        
+        // Screen the address against sanctions and watchlists over its transfer graph
+        let graph = self.screening_graph(address).await?;
+        analysis.risk_evidence = self.screener.screen_address(address, &graph);
+        if !analysis.risk_evidence.is_empty() {
+            analysis.risk_assessment = RiskAssessment::High;
+        }
+        
+        // A deposit may be filled to another address, which this address's history doesn't show
+        let deposits: Vec<BridgeTransfer> = analysis.networks_analyzed.iter()
+            .flat_map(|a| a.bridge_transfers.iter())
+            .filter(|t| t.direction == BridgeDirection::Deposit)
+            .cloned()
+            .collect();
+        let mut payouts = Vec::new();
+        for deposit in &deposits {
+            for network in &self.networks {
+                payouts.extend(self.bridge_fill_candidates(deposit, network).await?);
+            }
         }
         
         // Analyze patterns and generate recommendations
-        analysis.bridge_patterns = self.identify_bridge_patterns(&analysis.networks_analyzed);
+        analysis.bridge_patterns = self.identify_bridge_patterns(&analysis.networks_analyzed, payouts);
         analysis.recommendations = self.generate_recommendations(&analysis);
         
         Ok(analysis)
     }
 
+    /// Transfer graph around an address as deep as the screener searches
+    ///
+    /// Every counterparty is expanded, hop by hop, except labeled services and
+    /// beyond [`MAX_SCREENING_ADDRESSES`] addresses per network and hop.
+    async fn screening_graph(&self, address: &str) -> ZKWatchResult<TransferGraph> {
+        let mut graph = TransferGraph::new();
+        for network in &self.networks {
+            let mut expanded = HashSet::new();
+            let mut frontier = BTreeSet::from([address.to_ascii_lowercase()]);
+            for _ in 0..self.screener.max_hops() {
+                let mut next = BTreeSet::new();
+                let hop: Vec<String> = frontier.into_iter()
+                    .filter(|a| !self.screener.is_service(a) && expanded.insert(a.clone()))
+                    .take(MAX_SCREENING_ADDRESSES)
+                    .collect();
+                for current in hop {
+                    for counterparty in self.transfer_counterparties(network, &current).await? {
+                        let counterparty = format_address(&counterparty);
+                        graph.add(&current, &counterparty);
+                        next.insert(counterparty);
+                    }
+                }
+                frontier = next;
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Addresses an account exchanged tokens or native value with over the history lookback
+    async fn transfer_counterparties(&self, network: &NetworkConfig, address: &str) -> ZKWatchResult<BTreeSet<H160>> {
+        let account = address.parse::<H160>()
+            .map_err(|e| ZKWatchError::Blockchain(format!("Invalid address {}: {}", address, e)))?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let window = (self.history_start(network, head), head);
+        let logs = account_logs(source.as_ref(), limiter, account, window, &[
+            (vec![tokens::transfer_topic()], 1),
+            (vec![tokens::transfer_topic()], 2),
+        ]).await?;
+        let mut counterparties: BTreeSet<H160> = logs.iter()
+            .filter_map(tokens::decode_transfer)
+            .map(|t| if t.from == account { t.to } else { t.from })
+            .collect();
+        for filter in [
+            TraceFilter::new(window.0, window.1).from_address(vec![account]),
+            TraceFilter::new(window.0, window.1).to_address(vec![account]),
+        ] {
+            counterparties.extend(chunked_traces(source.as_ref(), limiter, &filter).await?.iter()
+                .filter_map(block_source::value_transfer)
+                .map(|(from, to, _)| if from == account { to } else { from }));
+        }
+        
+        counterparties.remove(&account);
+        counterparties.remove(&H160::zero());
+        Ok(counterparties)
+    }
+
+    /// ERC-20 activity of an address over the history lookback,
+    /// including its deposits into and fills out of known bridges
     async fn analyze_address_on_network(
-        &mut self,
+        &self,
         address: &str,
         network: &NetworkConfig,
     ) -> ZKWatchResult<NetworkAnalysis> {
-        // Simulate network-specific analysis
+        let account = address.parse::<H160>()
+            .map_err(|e| ZKWatchError::Blockchain(format!("Invalid address {}: {}", address, e)))?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let window = (self.history_start(network, head), head);
+        let logs = account_logs(source.as_ref(), limiter, account, window, &[
+            (vec![tokens::transfer_topic()], 1),
+            (vec![tokens::transfer_topic()], 2),
+        ]).await?;
+        let transfers: Vec<Erc20Transfer> = logs.iter().filter_map(tokens::decode_transfer).collect();
+        
+        // Exact times for the ends of the range and for bridge transfers; prices use an interpolation
+        let mut timestamps = HashMap::new();
+        let first_block = transfers.first().and_then(|t| t.block_number).unwrap_or(head);
+        let last_block = transfers.last().and_then(|t| t.block_number).unwrap_or(head);
+        let first_transaction = block_time(source.as_ref(), limiter, &mut timestamps, first_block).await?;
+        let last_transaction = block_time(source.as_ref(), limiter, &mut timestamps, last_block).await?;
+        let estimate_time = |number: u64| {
+            let span = last_block.saturating_sub(first_block).max(1) as f64;
+            let progress = number.saturating_sub(first_block) as f64 / span;
+            first_transaction + chrono::Duration::milliseconds(((last_transaction - first_transaction).num_milliseconds() as f64 * progress) as i64)
+        };
+        
+        let mut hashes = HashSet::new();
+        let mut counterparties: HashMap<H160, usize> = HashMap::new();
+        let mut total_volume = 0u128;
+        let mut bridge_transfers = Vec::new();
+        let mut recipients: HashMap<H256, Option<H160>> = HashMap::new();
+        for transfer in &transfers {
+            let Some(hash) = transfer.transaction_hash else {
+                continue;
+            };
+            hashes.insert(hash);
+            let outgoing = transfer.from == account;
+            let counterparty = if outgoing { transfer.to } else { transfer.from };
+            let block_number = transfer.block_number.unwrap_or_default();
+            let value = token_value(
+                network,
+                Some(transfer.token),
+                transfer.value,
+                &self.token_registry,
+                self.price_source.as_ref(),
+                estimate_time(block_number),
+            );
+            total_volume += value.map(|(wei, _)| wei).unwrap_or_default();
+            
+            // Bridges escrow and pay out directly, or burn and mint when called
+            let protocol = if counterparty.is_zero() {
+                let recipient = match recipients.get(&hash) {
+                    Some(recipient) => *recipient,
+                    None => {
+                        limiter.wait().await?;
+                        let recipient = source.transaction_receipt(hash).await?.and_then(|r| r.to);
+                        recipients.insert(hash, recipient);
+                        recipient
+                    }
+                };
+                recipient.and_then(|to| self.bridge_registry.get(network.chain_id, &to))
+            } else {
+                *counterparties.entry(counterparty).or_default() += 1;
+                self.bridge_registry.get(network.chain_id, &counterparty)
+            };
+            let Some(protocol) = protocol else {
+                continue;
+            };
+            
+            let tracked = self.token_registry.get(network.chain_id, &transfer.token);
+            bridge_transfers.push(BridgeTransfer {
+                protocol,
+                direction: if outgoing { BridgeDirection::Deposit } else { BridgeDirection::Fill },
+                network: network.name.clone(),
+                chain_id: network.chain_id,
+                transaction_hash: format!("{:?}", hash),
+                account: format_address(&account),
+                token: format_address(&transfer.token),
+                symbol: tracked.map(|t| pricing::price_symbol(&t.info.symbol)),
+                amount: u256_to_u128(transfer.value),
+                decimals: tracked.map(|t| t.info.decimals),
+                value_wei: value.map(|(wei, _)| wei),
+                timestamp: block_time(source.as_ref(), limiter, &mut timestamps, block_number).await?,
+            });
+        }
+        
+        // Native deposits and fills move no token, so find the address's calls through
+        // the events bridges emit naming it and read the value from the call trace
+        let contracts = self.bridge_registry.contracts(network.chain_id, None);
+        let mut bridge_logs = Vec::new();
+        if !contracts.is_empty() {
+            for position in 1..=3 {
+                let filter = LogFilter::new(window.0, window.1)
+                    .address(contracts.clone())
+                    .topic(position, vec![H256::from(account)]);
+                bridge_logs.extend(chunked_logs(source.as_ref(), limiter, &filter).await?);
+            }
+        }
+        let mut traced = HashSet::new();
+        for log in &bridge_logs {
+            let (Some(hash), Some(number)) = (log.transaction_hash, log.block_number) else {
+                continue;
+            };
+            if !traced.insert(hash) {
+                continue;
+            }
+            limiter.wait().await?;
+            for (protocol, direction, amount) in self.native_bridge_calls(network, &source.trace_transaction(hash).await?, account) {
+                hashes.insert(hash);
+                total_volume += amount;
+                let timestamp = block_time(source.as_ref(), limiter, &mut timestamps, number.as_u64()).await?;
+                bridge_transfers.push(native_bridge_transfer(network, protocol, direction, hash, account, amount, timestamp));
+            }
+        }
+        
+        let mut connected: Vec<(H160, usize)> = counterparties.into_iter().collect();
+        connected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        
+        let mut suspicious_patterns = Vec::new();
+        if !bridge_transfers.is_empty() {
+            let deposits = bridge_transfers.iter().filter(|t| t.direction == BridgeDirection::Deposit).count();
+            suspicious_patterns.push(format!(
+                "Bridge interactions: {} deposits, {} fills",
+                deposits,
+                bridge_transfers.len() - deposits,
+            ));
+        }
+        
+        let total_transactions = hashes.len() as u64;
         Ok(NetworkAnalysis {
             network: network.name.clone(),
-            total_transactions: rand::random::<u64>() % 1000 + 100,
-            total_volume: rand::random::<u128>() % 10_000_000_000_000_000_000 + 1_000_000_000_000_000_000, // 1-10 ETH
-            avg_transaction_size: 100_000_000_000_000_000_000, // 100 ETH
-            first_transaction: Utc::now() - chrono::Duration::days(365),
-            last_transaction: Utc::now(),
-            suspicious_patterns: vec![
-                "Frequent small transactions".to_string(),
-                "Bridge interactions".to_string(),
-            ],
-            connected_addresses: vec![
-                "0x742d35Cc6634C0532925a3b8D8Ac87b5F8aF7C3F".to_string(),
-                "0x8ba1f109551bD432803012645Hac136c33Be3b85".to_string(),
-            ],
+            total_transactions,
+            total_volume,
+            avg_transaction_size: total_volume / total_transactions.max(1) as u128,
+            first_transaction,
+            last_transaction,
+            suspicious_patterns,
+            connected_addresses: connected.iter().take(10).map(|(address, _)| format_address(address)).collect(),
+            bridge_transfers,
         })
     }
 
-    fn identify_bridge_patterns(&self, analyses: &[NetworkAnalysis]) -> Vec<BridgePattern> {
-        let mut patterns = Vec::new();
+    /// Native value the account sent into or received from a bridge contract in a call trace
+    fn native_bridge_calls(
+        &self,
+        network: &NetworkConfig,
+        traces: &[Trace],
+        account: H160,
+    ) -> Vec<(BridgeProtocol, BridgeDirection, u128)> {
+        traces.iter()
+            .filter_map(block_source::value_transfer)
+            .filter_map(|(from, to, value)| {
+                let leg = if from == account {
+                    self.bridge_registry.get(network.chain_id, &to).map(|p| (p, BridgeDirection::Deposit))
+                } else if to == account {
+                    self.bridge_registry.get(network.chain_id, &from).map(|p| (p, BridgeDirection::Fill))
+                } else {
+                    None
+                };
+                leg.map(|(protocol, direction)| (protocol, direction, u256_to_u128(value)))
+            })
+            .collect()
+    }
+
+    /// Payouts on `network` by a deposit's bridge that could complete it, to any recipient
+    ///
+    /// Looks for tokens with the deposit's symbol sent out of (or minted in a
+    /// call to) the bridge, and for native payouts, which bridges unwrap from
+    /// the wrapped native token, within the bridge's latency window.
+    async fn bridge_fill_candidates(
+        &self,
+        deposit: &BridgeTransfer,
+        network: &NetworkConfig,
+    ) -> ZKWatchResult<Vec<BridgeTransfer>> {
+        let (Some(symbol), Some(decimals)) = (&deposit.symbol, deposit.decimals) else {
+            return Ok(Vec::new());
+        };
+        let contracts = self.bridge_registry.contracts(network.chain_id, Some(deposit.protocol));
+        let tokens: Vec<H160> = self.token_registry.addresses(network.chain_id).into_iter()
+            .filter(|token| self.token_registry.get(network.chain_id, token)
+                .is_some_and(|t| pricing::price_symbol(&t.info.symbol) == *symbol))
+            .collect();
+        if contracts.is_empty() || tokens.is_empty() || network.chain_id == deposit.chain_id {
+            return Ok(Vec::new());
+        }
+        let (source, limiter) = self.source_and_limiter(network)?;
         
-        // Identify bridge patterns based on transaction timing and volume
-        if analyses.len() >= 2 {
-            patterns.push(BridgePattern {
-                pattern_type: "Rapid Multi-Chain Bridge".to_string(),
-                confidence: 0.85,
-                involved_networks: analyses.iter().map(|a| a.network.clone()).collect(),
-                estimated_volume: analyses.iter().map(|a| a.total_volume).sum(),
-                description: "Address shows rapid bridging activity across multiple networks".to_string(),
-            });
+        // Block times drift, so widen the window estimated from them by a tenth
+        let mut timestamps = HashMap::new();
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let head_time = block_time(source.as_ref(), limiter, &mut timestamps, head).await?;
+        let (_, max_latency) = deposit.protocol.latency(deposit.chain_id, network.chain_id);
+        let first = head.saturating_sub(network.blocks_in((head_time - deposit.timestamp) * 11 / 10));
+        let last = (first + network.blocks_in(max_latency * 11 / 10)).min(head);
+        let first = first.max(self.history_start(network, head));
+        if first > last {
+            return Ok(Vec::new());
+        }
+        
+        let sent = deposit.amount as f64 / 10f64.powi(decimals as i32);
+        let min_received = sent * (1.0 - deposit.protocol.max_fee_ratio() - 1e-6);
+        let plausible = |token: &H160, amount: U256| {
+            self.token_registry.get(network.chain_id, token).is_some_and(|t| {
+                let received = dex::u256_to_f64(amount) / 10f64.powi(t.info.decimals as i32);
+                received >= min_received && received <= sent * (1.0 + 1e-6)
+            })
+        };
+        
+        let mut senders = contracts.clone();
+        senders.push(H160::zero());
+        let filter = LogFilter::new(first, last)
+            .address(tokens)
+            .topic(0, vec![tokens::transfer_topic(), tokens::withdrawal_topic()])
+            .topic(1, senders.iter().map(|sender| H256::from(*sender)).collect());
+        let mut fills = Vec::new();
+        for log in chunked_logs(source.as_ref(), limiter, &filter).await? {
+            let (Some(hash), Some(number)) = (log.transaction_hash, log.block_number) else {
+                continue;
+            };
+            let number = number.as_u64();
+            
+            if let Some(transfer) = tokens::decode_transfer(&log) {
+                if !plausible(&transfer.token, transfer.value) {
+                    continue;
+                }
+                // A mint only counts when the transaction called the bridge
+                if transfer.from.is_zero() {
+                    limiter.wait().await?;
+                    let called = source.transaction_receipt(hash).await?.and_then(|r| r.to);
+                    if !called.is_some_and(|to| contracts.contains(&to)) {
+                        continue;
+                    }
+                }
+                let timestamp = block_time(source.as_ref(), limiter, &mut timestamps, number).await?;
+                let tracked = self.token_registry.get(network.chain_id, &transfer.token);
+                fills.push(BridgeTransfer {
+                    protocol: deposit.protocol,
+                    direction: BridgeDirection::Fill,
+                    network: network.name.clone(),
+                    chain_id: network.chain_id,
+                    transaction_hash: format!("{:?}", hash),
+                    account: format_address(&transfer.to),
+                    token: format_address(&transfer.token),
+                    symbol: tracked.map(|t| pricing::price_symbol(&t.info.symbol)),
+                    amount: u256_to_u128(transfer.value),
+                    decimals: tracked.map(|t| t.info.decimals),
+                    value_wei: token_value(network, Some(transfer.token), transfer.value, &self.token_registry, self.price_source.as_ref(), timestamp)
+                        .map(|(wei, _)| wei),
+                    timestamp,
+                });
+            } else if let Some((unwrapper, amount)) = tokens::decode_withdrawal(&log) {
+                if !plausible(&log.address, amount) {
+                    continue;
+                }
+                // The unwrapped value leaves the bridge in a call to the recipient
+                limiter.wait().await?;
+                let traces = source.trace_transaction(hash).await?;
+                let recipient = traces.iter()
+                    .filter_map(block_source::value_transfer)
+                    .find(|(from, to, value)| *from == unwrapper && *value == amount && *to != log.address)
+                    .map(|(_, to, _)| to);
+                let Some(recipient) = recipient else {
+                    continue;
+                };
+                let timestamp = block_time(source.as_ref(), limiter, &mut timestamps, number).await?;
+                fills.push(native_bridge_transfer(
+                    network,
+                    deposit.protocol,
+                    BridgeDirection::Fill,
+                    hash,
+                    recipient,
+                    u256_to_u128(amount),
+                    timestamp,
+                ));
+            }
         }
         
-        patterns
+        Ok(fills)
+    }
+
+    /// Pair bridge deposits on one network with fills on another, including
+    /// fills to other recipients found among the bridges' payouts
+    fn identify_bridge_patterns(&self, analyses: &[NetworkAnalysis], payouts: Vec<BridgeTransfer>) -> Vec<BridgePattern> {
+        let mut seen = HashSet::new();
+        let transfers: Vec<BridgeTransfer> = analyses.iter()
+            .flat_map(|a| a.bridge_transfers.iter().cloned())
+            .chain(payouts)
+            .filter(|t| seen.insert((t.chain_id, t.transaction_hash.clone(), t.direction, t.account.to_ascii_lowercase(), t.token.clone(), t.amount)))
+            .collect();
+        
+        bridges::match_transfers(&transfers).into_iter()
+            .map(|m| {
+                let amount = match m.deposit.decimals {
+                    Some(decimals) => format!("{:.2}", m.deposit.amount as f64 / 10f64.powi(decimals as i32)),
+                    None => m.deposit.amount.to_string(),
+                };
+                BridgePattern {
+                    pattern_type: format!("{} Transfer", m.deposit.protocol),
+                    confidence: m.confidence,
+                    involved_networks: vec![m.deposit.network.clone(), m.fill.network.clone()],
+                    estimated_volume: m.deposit.value_wei.unwrap_or_default(),
+                    description: format!(
+                        "{} {} bridged from {} to {}{} in {} ({:.2}% fee): deposit {}, fill {}",
+                        amount,
+                        m.deposit.symbol.clone().unwrap_or_else(|| m.deposit.token.clone()),
+                        m.deposit.network,
+                        m.fill.network,
+                        if m.same_account { String::new() } else { format!(" for {}", m.fill.account) },
+                        format_latency(m.latency_secs),
+                        m.fee_ratio * 100.0,
+                        m.deposit.transaction_hash,
+                        m.fill.transaction_hash,
+                    ),
+                }
+            })
+            .collect()
     }
 
     fn generate_recommendations(&self, analysis: &CrossChainAnalysis) -> Vec<String> {
@@ -175,84 +1287,203 @@ impl MultiChainScanner {
             recommendations.push("Check for compliance issues".to_string());
         }
         
+        for hit in &analysis.risk_evidence {
+            recommendations.push(match hit.hops {
+                0 => format!("Address is listed on {} as {} ({})", hit.entry.list, hit.entry.name, hit.entry.entry_id),
+                hops => format!(
+                    "Address is {} hop(s) from {} listed on {} as {} ({})",
+                    hops, hit.entry.address, hit.entry.list, hit.entry.name, hit.entry.entry_id,
+                ),
+            });
+        }
+        
         recommendations
     }
 
     /// Advanced DeFi protocol interaction analysis
+    ///
+    /// Decodes the DEX swaps the address sent and the Aave positions it holds
+    /// over the history lookback of every network, newest first.
     pub async fn analyze_defi_interactions(
         &mut self,
         address: &str,
     ) -> ZKWatchResult<Vec<DefiInteraction>> {
         let mut interactions = Vec::new();
         
-        for network in &self.networks {
-            let protocol_interactions = self.analyze_defi_on_network(address, network).await?;
+        for network in self.networks.clone() {
+            let protocol_interactions = self.analyze_defi_on_network(address, &network).await?;
             interactions.extend(protocol_interactions);
         }
         
         // Sort by timestamp
-        interactions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        interactions.sort_by_key(|interaction| std::cmp::Reverse(interaction.timestamp));
         
         Ok(interactions)
     }
 
     async fn analyze_defi_on_network(
-        &mut self,
+        &self,
         address: &str,
         network: &NetworkConfig,
     ) -> ZKWatchResult<Vec<DefiInteraction>> {
-        // Simulate DeFi interaction analysis
-        let interactions = vec![
-            DefiInteraction {
-                protocol: "Uniswap V3".to_string(),
-                action: "Swap".to_string(),
-                token_in: "ETH".to_string(),
-                token_out: "USDC".to_string(),
-                amount_in: 500_000_000_000_000_000_000u128, // 500 ETH
-                amount_out: 950_000_000_000u128, // ~$950,000 USDC
-                timestamp: Utc::now() - chrono::Duration::hours(2),
-                network: network.name.clone(),
-                transaction_hash: "0x1234567890abcdef".to_string(),
-                gas_used: 150_000,
-            },
-            DefiInteraction {
-                protocol: "Aave V3".to_string(),
-                action: "Supply".to_string(),
-                token_in: "USDC".to_string(),
-                token_out: "aUSDC".to_string(),
-                amount_in: 500_000_000_000u128,
-                amount_out: 500_000_000_000u128,
-                timestamp: Utc::now() - chrono::Duration::hours(1),
+        let account = address.parse::<H160>()
+            .map_err(|e| ZKWatchError::Blockchain(format!("Invalid address {}: {}", address, e)))?;
+        let (source, limiter) = self.source_and_limiter(network)?;
+        
+        limiter.wait().await?;
+        let head = source.block_number().await?;
+        let window = (self.history_start(network, head), head);
+        
+        // Token movements and position changes narrow the window down to the address's transactions.
+        // Positions index the account as user/on-behalf-of (topic 2) or as repayer (topic 3);
+        // supply and borrow callers only appear in event data, but their token transfers match.
+        let logs = account_logs(source.as_ref(), limiter, account, window, &[
+            (vec![tokens::transfer_topic()], 1),
+            (vec![tokens::transfer_topic()], 2),
+            (lending::position_topics(), 2),
+            (lending::position_topics(), 3),
+        ]).await?;
+        
+        // Logs come oldest first; keep the most recent transactions
+        let mut seen = HashSet::new();
+        let mut hashes: Vec<H256> = logs.iter()
+            .rev()
+            .filter_map(|log| log.transaction_hash)
+            .filter(|hash| seen.insert(*hash))
+            .collect();
+        hashes.truncate(MAX_DEFI_TRANSACTIONS);
+        
+        let mut interactions = Vec::new();
+        let mut timestamps: HashMap<u64, DateTime<Utc>> = HashMap::new();
+        for hash in hashes {
+            limiter.wait().await?;
+            let Some(receipt) = source.transaction_receipt(hash).await? else {
+                continue;
+            };
+            let block_number = receipt.block_number.map(|n| n.as_u64()).unwrap_or_default();
+            let timestamp = block_time(source.as_ref(), limiter, &mut timestamps, block_number).await?;
+            let gas_used = receipt.gas_used.map(|g| g.as_u64()).unwrap_or_default();
+            let decimals = |token: Option<H160>| token.and_then(|token| self.token_registry.get(network.chain_id, &token)).map(|t| t.info.decimals);
+            let interaction = |protocol: String, action: String, token_in: String, token_out: String, amount_in: U256, amount_out: U256, token_out_decimals: Option<u8>| DefiInteraction {
+                protocol,
+                action,
+                token_in,
+                token_out,
+                amount_in: u256_to_u128(amount_in),
+                amount_out: u256_to_u128(amount_out),
+                token_out_decimals,
+                timestamp,
                 network: network.name.clone(),
-                transaction_hash: "0xabcdef1234567890".to_string(),
-                gas_used: 200_000,
-            },
-        ];
+                transaction_hash: format!("{:?}", hash),
+                gas_used,
+            };
+            
+            // Swaps are attributed to the sender; routers emit them under their own address
+            if receipt.from == account {
+                for swap in dex::decode_swaps(&receipt.logs) {
+                    interactions.push(interaction(
+                        swap.protocol.to_string(),
+                        "Swap".to_string(),
+                        token_symbol(network, swap.token_in, &self.token_registry),
+                        token_symbol(network, swap.token_out, &self.token_registry),
+                        swap.amount_in,
+                        swap.amount_out,
+                        decimals(swap.token_out),
+                    ));
+                }
+            }
+            
+            for position in lending::decode_position_events(&receipt.logs) {
+                if position.user != account && position.caller != account {
+                    continue;
+                }
+                let reserve = token_symbol(network, Some(position.reserve), &self.token_registry);
+                let (token_in, token_out) = match position.action {
+                    LendingAction::Supply => (reserve.clone(), format!("a{}", reserve)),
+                    LendingAction::Withdraw => (format!("a{}", reserve), reserve),
+                    LendingAction::Borrow => (format!("{} debt", reserve), reserve),
+                    LendingAction::Repay => (reserve.clone(), format!("{} debt", reserve)),
+                };
+                interactions.push(interaction(
+                    position.protocol.to_string(),
+                    position.action.to_string(),
+                    token_in,
+                    token_out,
+                    position.amount,
+                    position.amount,
+                    // Deposit and debt tokens carry the reserve's decimals
+                    decimals(Some(position.reserve)),
+                ));
+            }
+        }
         
         Ok(interactions)
     }
 }
 
-/// API client for blockchain data
-struct ApiClient {
-    rpc_url: String,
-    client: reqwest::Client,
+/// Block timestamps and receipt recipients fetched during one fund trace
+#[derive(Default)]
+struct TraceCache {
+    timestamps: HashMap<String, HashMap<u64, DateTime<Utc>>>,
+    recipients: HashMap<H256, Option<H160>>,
 }
 
-impl ApiClient {
-    fn new(rpc_url: &str) -> Self {
-        Self {
-            rpc_url: rpc_url.to_string(),
-            client: reqwest::Client::new(),
+/// Everything needed to extract whales from a network's blocks, shared across
+/// the live scan and parallel backfill chunks
+#[derive(Clone)]
+struct ScanContext {
+    network: NetworkConfig,
+    source: Arc<dyn BlockSource>,
+    limiter: Arc<RateLimiter>,
+    token_registry: Arc<TokenRegistry>,
+    price_source: Arc<dyn PriceSource>,
+    label_registry: Arc<LabelRegistry>,
+    min_usd: f64,
+}
+
+impl ScanContext {
+    async fn scan_chunk(self, (start, end): (u64, u64)) -> ZKWatchResult<(u64, Vec<WhaleTransaction>)> {
+        let mut transactions = Vec::new();
+        
+        for number in start..=end {
+            self.limiter.wait().await?;
+            if let Some(block) = self.source.block_with_transactions(number).await? {
+                transactions.extend(self.block_whales(&block).await?);
+            }
+        }
+        
+        Ok((start, transactions))
+    }
+
+    /// Whale transfers (native and tracked ERC-20) contained in one block, tagged
+    /// with their flash-loan and exchange-flow classification
+    async fn block_whales(&self, block: &Block<Transaction>) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        let source = self.source.as_ref();
+        let mut transactions = extract_transfer_whales(
+            &self.network,
+            source,
+            &self.limiter,
+            &self.token_registry,
+            self.price_source.as_ref(),
+            block,
+            self.min_usd,
+        ).await?;
+        for transaction in transactions.iter_mut() {
+            transaction.exchange_flow = exchange_flows::classify(transaction, &self.label_registry);
         }
+        if !transactions.is_empty() {
+            let number = block.number.map(|n| n.as_u64()).unwrap_or_default();
+            tag_flash_loans(source, &self.limiter, number, &mut transactions).await?;
+        }
+        Ok(transactions)
     }
 }
 
-/// Rate limiter for API calls
+/// Rate limiter for API calls, shared by all tasks hitting the same network
 struct RateLimiter {
     max_requests: u64,
     window: std::time::Duration,
-    requests: Vec<std::time::Instant>,
+    requests: tokio::sync::Mutex<Vec<std::time::Instant>>,
 }
 
 impl RateLimiter {
@@ -260,27 +1491,30 @@ impl RateLimiter {
         Self {
             max_requests,
             window,
-            requests: Vec::new(),
+            requests: tokio::sync::Mutex::new(Vec::new()),
         }
     }
 
-    async fn wait(&mut self) -> ZKWatchResult<()> {
+    async fn wait(&self) -> ZKWatchResult<()> {
+        // Holding the lock while sleeping queues concurrent callers behind the limit
+        let mut requests = self.requests.lock().await;
         let now = std::time::Instant::now();
         
         // Remove old requests outside the window
-        self.requests.retain(|req| now.duration_since(*req) < self.window);
+        requests.retain(|req| now.duration_since(*req) < self.window);
         
         // Check if we need to wait
-        if self.requests.len() >= self.max_requests {
-            if let Some(oldest) = self.requests.first() {
+        if requests.len() as u64 >= self.max_requests {
+            if let Some(oldest) = requests.first() {
                 let wait_time = self.window.saturating_sub(now.duration_since(*oldest));
                 if wait_time > std::time::Duration::from_secs(0) {
                     tokio::time::sleep(wait_time).await;
                 }
             }
+            requests.remove(0);
         }
         
-        self.requests.push(now);
+        requests.push(std::time::Instant::now());
         Ok(())
     }
 }
@@ -289,10 +1523,20 @@ impl RateLimiter {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MEVOpportunity {
     pub opportunity_type: MEVType,
+    /// Searcher profit net of gas, in native wei; zero when the profit token is unpriced
     pub estimated_profit: u128,
     pub gas_estimate: u64,
     pub block_number: u64,
+    pub block_hash: String,
     pub network: String,
+    /// Transactions involved, in block order
+    pub transaction_hashes: Vec<String>,
+    pub searcher: String,
+    pub victims: Vec<String>,
+    /// Value the victims lost, in native wei; zero when unpriced
+    pub victim_loss: u128,
+    pub profit_usd: Option<f64>,
+    pub victim_loss_usd: Option<f64>,
     pub confidence: f64,
     pub description: String,
 }
@@ -313,10 +1557,12 @@ pub struct CrossChainAnalysis {
     pub total_cross_chain_volume: u128,
     pub bridge_patterns: Vec<BridgePattern>,
     pub risk_assessment: RiskAssessment,
+    /// Sanctions and watchlist matches that raised the risk assessment
+    pub risk_evidence: Vec<ScreeningHit>,
     pub recommendations: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum RiskAssessment {
     Low,
     Medium,
@@ -333,7 +1579,9 @@ pub struct NetworkAnalysis {
     pub first_transaction: DateTime<Utc>,
     pub last_transaction: DateTime<Utc>,
     pub suspicious_patterns: Vec<String>,
+    /// Most frequent counterparties, most frequent first
     pub connected_addresses: Vec<String>,
+    pub bridge_transfers: Vec<BridgeTransfer>,
 }
 
 /// Bridge pattern detection
@@ -355,44 +1603,1006 @@ pub struct DefiInteraction {
     pub token_out: String,
     pub amount_in: u128,
     pub amount_out: u128,
+    /// Decimals of the output token, `None` when the token registry does not know it
+    pub token_out_decimals: Option<u8>,
     pub timestamp: DateTime<Utc>,
     pub network: String,
     pub transaction_hash: String,
     pub gas_used: u64,
 }
 
-// Simulated functions (in real implementation, these would query actual blockchain APIs)
-async fn simulate_whale_detection(network: &NetworkConfig, min_value: u128) -> ZKWatchResult<Vec<WhaleTransaction>> {
-    let transactions = vec![
-        WhaleTransaction {
-            hash: "0x1234567890abcdef1234567890abcdef12345678".to_string(),
-            from: "0x742d35Cc6634C0532925a3b8D8Ac87b5F8aF7C3F".to_string(),
-            to: "0x8ba1f109551bD432803012645Hac136c33Be3b85".to_string(),
-            value: 1000_000_000_000_000_000_000u128, // 1000 ETH
-            gas_used: 21000,
-            block_number: rand::random::<u64>() % 1000000 + 18000000,
-            timestamp: Utc::now(),
-            zk_proof_hash: Some("0xproof_hash_123".to_string()),
-            risk_score: 0.8,
-            pattern_type: crate::TransactionPattern::LargeTransaction,
+/// Logs of a block range naming the account at a topic position, for each
+/// `(topic0 alternatives, position)` slot, oldest first and without duplicates
+async fn account_logs(
+    source: &dyn BlockSource,
+    limiter: &RateLimiter,
+    account: H160,
+    (from_block, to_block): (u64, u64),
+    slots: &[(Vec<H256>, usize)],
+) -> ZKWatchResult<Vec<Log>> {
+    let account_topic = H256::from(account);
+    let mut logs = Vec::new();
+    let mut seen = HashSet::new();
+    
+    for (topics, position) in slots {
+        let filter = LogFilter::new(from_block, to_block).topic(0, topics.clone()).topic(*position, vec![account_topic]);
+        for log in chunked_logs(source, limiter, &filter).await? {
+            // A transfer to oneself matches both the sender and the recipient slot
+            if seen.insert((log.transaction_hash, log.log_index)) {
+                logs.push(log);
+            }
         }
-    ];
+    }
     
-    Ok(transactions.into_iter().filter(|tx| tx.value >= min_value).collect())
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
 }
 
-async fn simulate_mev_detection(network: &NetworkConfig, recent_blocks: u64) -> ZKWatchResult<Vec<MEVOpportunity>> {
-    let opportunities = vec![
-        MEVOpportunity {
-            opportunity_type: MEVType::Arbitrage,
-            estimated_profit: 50_000_000_000_000_000_000u128, // 50 ETH
-            gas_estimate: 300_000,
-            block_number: rand::random::<u64>() % 100 + 18000000,
-            network: network.name.clone(),
-            confidence: 0.75,
-            description: "ETH/USDC arbitrage opportunity detected".to_string(),
+/// Call traces matching a filter, queried `HISTORY_LOG_CHUNK` blocks at a time
+async fn chunked_traces(source: &dyn BlockSource, limiter: &RateLimiter, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>> {
+    let mut traces = Vec::new();
+    let mut start = filter.from_block;
+    while start <= filter.to_block {
+        let end = (start + HISTORY_LOG_CHUNK - 1).min(filter.to_block);
+        let chunk = TraceFilter { from_block: start, to_block: end, ..filter.clone() };
+        limiter.wait().await?;
+        traces.extend(source.traces(&chunk).await?);
+        start = end + 1;
+    }
+    Ok(traces)
+}
+
+/// Logs matching a filter, queried `HISTORY_LOG_CHUNK` blocks at a time
+async fn chunked_logs(source: &dyn BlockSource, limiter: &RateLimiter, filter: &LogFilter) -> ZKWatchResult<Vec<Log>> {
+    let mut logs = Vec::new();
+    let mut start = filter.from_block;
+    while start <= filter.to_block {
+        let end = (start + HISTORY_LOG_CHUNK - 1).min(filter.to_block);
+        let chunk = LogFilter { from_block: start, to_block: end, ..filter.clone() };
+        limiter.wait().await?;
+        logs.extend(source.logs(&chunk).await?);
+        start = end + 1;
+    }
+    Ok(logs)
+}
+
+/// Whale-sized native transfers and tracked-token transfers of a block
+async fn extract_transfer_whales(
+    network: &NetworkConfig,
+    source: &dyn BlockSource,
+    limiter: &RateLimiter,
+    token_registry: &TokenRegistry,
+    price_source: &dyn PriceSource,
+    block: &Block<Transaction>,
+    min_usd: f64,
+) -> ZKWatchResult<Vec<WhaleTransaction>> {
+    let mut transactions = Vec::new();
+    let number = block.number.map(|n| n.as_u64()).unwrap_or_default();
+    let timestamp = block_timestamp(block)?;
+    
+    // Without a native price every native whale would be missed, so refuse to scan
+    let native_price = price_source.price_usd(&network.native_symbol, timestamp).ok_or_else(|| {
+        ZKWatchError::MissingPrice(format!("{} on {} at {}", network.native_symbol, network.name, timestamp))
+    })?;
+    for tx in &block.transactions {
+        let value = u256_to_u128(tx.value);
+        let usd_value = value as f64 / 1e18 * native_price;
+        if value == 0 || usd_value < min_usd {
+            continue;
+        }
+        
+        // The receipt tells us whether the value actually moved and what gas was burned
+        limiter.wait().await?;
+        let receipt = source.transaction_receipt(tx.hash).await?;
+        if receipt.as_ref().and_then(|r| r.status) == Some(U64::zero()) {
+            continue;
+        }
+        
+        let risk_score = size_risk_score(usd_value / min_usd);
+        transactions.push(whale_from_native_transfer(network, block, timestamp, tx, receipt.as_ref(), usd_value, risk_score));
+    }
+    
+    // ERC-20 transfers of tracked tokens
+    let token_addresses = token_registry.addresses(network.chain_id);
+    if token_addresses.is_empty() {
+        return Ok(transactions);
+    }
+    
+    let filter = LogFilter::new(number, number)
+        .address(token_addresses)
+        .topic(0, vec![tokens::transfer_topic()]);
+    limiter.wait().await?;
+    let logs = source.logs(&filter).await?;
+    
+    let mut receipts: HashMap<H256, Option<TransactionReceipt>> = HashMap::new();
+    
+    for transfer in logs.iter().filter_map(tokens::decode_transfer) {
+        let token = match token_registry.get(network.chain_id, &transfer.token) {
+            Some(token) => token,
+            None => continue,
+        };
+        
+        let value = u256_to_u128(transfer.value);
+        let usd_value = price_source.price_usd(&token.info.symbol, timestamp)
+            .map(|price| value as f64 / 10f64.powi(token.info.decimals as i32) * price);
+        let size_ratio = match usd_value {
+            Some(usd) => usd / min_usd,
+            None => value as f64 / token.whale_threshold.max(1) as f64,
+        };
+        if size_ratio < 1.0 {
+            continue;
         }
-    ];
+        
+        let receipt = match transfer.transaction_hash {
+            Some(hash) => {
+                if let Entry::Vacant(entry) = receipts.entry(hash) {
+                    limiter.wait().await?;
+                    entry.insert(source.transaction_receipt(hash).await?);
+                }
+                receipts[&hash].as_ref()
+            }
+            None => None,
+        };
+        
+        let risk_score = size_risk_score(size_ratio);
+        transactions.push(whale_from_token_transfer(network, &transfer, token, timestamp, receipt, usd_value, risk_score));
+    }
     
-    Ok(opportunities)
-}
\ No newline at end of file
+    Ok(transactions)
+}
+
+/// Tag whales moved inside flash-loan transactions as `TransactionPattern::FlashLoan`
+///
+/// Loans are found by lender events in the block; whale transactions without
+/// one are checked for lender callbacks in their call trace, unless the node
+/// has no trace API. The borrowed amount of a whale's token is recorded in
+/// `borrowed` so it stays out of volume.
+async fn tag_flash_loans(
+    source: &dyn BlockSource,
+    limiter: &RateLimiter,
+    block_number: u64,
+    transactions: &mut [WhaleTransaction],
+) -> ZKWatchResult<()> {
+    let filter = LogFilter::new(block_number, block_number).topic(0, flash_loans::flash_loan_topics());
+    limiter.wait().await?;
+    let mut loans = flash_loans::detect_flash_loans(&source.logs(&filter).await?);
+    
+    // Uniswap V3 and dYdX events do not name the token; the transfers in the loan's own receipt do
+    let untokened: HashSet<H256> = loans.iter()
+        .filter(|loan| loan.token.is_none())
+        .map(|loan| loan.transaction_hash)
+        .collect();
+    for hash in untokened {
+        limiter.wait().await?;
+        if let Some(receipt) = source.transaction_receipt(hash).await? {
+            loans.retain(|loan| loan.transaction_hash != hash);
+            loans.extend(flash_loans::detect_flash_loans(&receipt.logs));
+        }
+    }
+    
+    // Borrowed amount per transaction and token
+    let mut borrowed: HashMap<H256, HashMap<H160, U256>> = HashMap::new();
+    for loan in loans {
+        let loans = borrowed.entry(loan.transaction_hash).or_default();
+        if let Some(token) = loan.token {
+            let total = loans.entry(token).or_default();
+            *total = total.saturating_add(loan.amount);
+        }
+    }
+    
+    let untagged: HashSet<H256> = transactions.iter()
+        .filter_map(|tx| tx.hash.parse::<H256>().ok())
+        .filter(|hash| !borrowed.contains_key(hash))
+        .collect();
+    for hash in untagged {
+        limiter.wait().await?;
+        let traces = match source.trace_transaction(hash).await {
+            Ok(traces) => traces,
+            Err(e) => {
+                // Nodes without the trace API still get event-based detection
+                log::warn!("No call traces for block {}, skipping flash-loan callback detection: {}", block_number, e);
+                break;
+            }
+        };
+        let callbacks = flash_loans::flash_loan_callbacks(&traces);
+        if callbacks.is_empty() {
+            continue;
+        }
+        let loans = borrowed.entry(hash).or_default();
+        for (token, amount) in callbacks.into_iter().flat_map(|callback| callback.loans) {
+            let total = loans.entry(token).or_default();
+            *total = total.saturating_add(amount);
+        }
+    }
+    
+    for tx in transactions.iter_mut() {
+        let Some(loans) = tx.hash.parse::<H256>().ok().and_then(|hash| borrowed.get(&hash)) else {
+            continue;
+        };
+        tx.pattern_type = crate::TransactionPattern::FlashLoan;
+        let token = tx.token.as_ref().and_then(|token| token.address.parse::<H160>().ok());
+        if let Some(amount) = token.and_then(|token| loans.get(&token)) {
+            tx.borrowed = tx.value.min(u256_to_u128(*amount));
+        }
+    }
+    
+    Ok(())
+}
+
+/// Flow transfer from a decoded ERC-20 transfer, named by the token registry when tracked
+fn flow_from_erc20(
+    network: &NetworkConfig,
+    transfer: &Erc20Transfer,
+    token_registry: &TokenRegistry,
+    timestamp: DateTime<Utc>,
+    bridge: Option<BridgeLeg>,
+) -> FlowTransfer {
+    let tracked = token_registry.get(network.chain_id, &transfer.token);
+    
+    FlowTransfer {
+        network: network.name.clone(),
+        chain_id: network.chain_id,
+        transaction_hash: transfer.transaction_hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        log_index: transfer.log_index,
+        trace_address: Vec::new(),
+        block_number: transfer.block_number.unwrap_or_default(),
+        timestamp,
+        from: format_address(&transfer.from),
+        to: format_address(&transfer.to),
+        asset: format_address(&transfer.token),
+        symbol: tracked.map(|t| t.info.symbol.clone()).unwrap_or_else(|| format_address(&transfer.token)),
+        decimals: tracked.map(|t| t.info.decimals),
+        amount: u256_to_u128(transfer.value),
+        bridge,
+    }
+}
+
+/// Build a whale record from a successful native-value transfer
+fn whale_from_native_transfer(
+    network: &NetworkConfig,
+    block: &Block<Transaction>,
+    timestamp: DateTime<Utc>,
+    tx: &Transaction,
+    receipt: Option<&TransactionReceipt>,
+    usd_value: f64,
+    risk_score: f64,
+) -> WhaleTransaction {
+    let value = u256_to_u128(tx.value);
+    // Contract creations carry no `to`; the receipt names the deployed contract instead
+    let to = tx.to.or_else(|| receipt.and_then(|r| r.contract_address));
+    
+    WhaleTransaction {
+        hash: format!("{:?}", tx.hash),
+        network: network.name.clone(),
+        chain_id: network.chain_id,
+        from: format_address(&tx.from),
+        to: to.as_ref().map(format_address).unwrap_or_default(),
+        value,
+        borrowed: 0,
+        token: None,
+        log_index: None,
+        usd_value: Some(usd_value),
+        confirmation: ConfirmationStatus::Pending,
+        gas_used: receipt.and_then(|r| r.gas_used).map(|g| g.as_u64()).unwrap_or_default(),
+        block_number: block.number.map(|n| n.as_u64()).unwrap_or_default(),
+        timestamp,
+        zk_proof_hash: None,
+        risk_score,
+        pattern_type: crate::TransactionPattern::LargeTransaction,
+        exchange_flow: ExchangeFlow::NonExchange,
+    }
+}
+
+/// Build a whale record from a decoded ERC-20 transfer of a tracked token
+fn whale_from_token_transfer(
+    network: &NetworkConfig,
+    transfer: &Erc20Transfer,
+    token: &TrackedToken,
+    timestamp: DateTime<Utc>,
+    receipt: Option<&TransactionReceipt>,
+    usd_value: Option<f64>,
+    risk_score: f64,
+) -> WhaleTransaction {
+    let value = u256_to_u128(transfer.value);
+    
+    WhaleTransaction {
+        hash: transfer.transaction_hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        network: network.name.clone(),
+        chain_id: network.chain_id,
+        from: format_address(&transfer.from),
+        to: format_address(&transfer.to),
+        value,
+        borrowed: 0,
+        token: Some(token.info.clone()),
+        log_index: transfer.log_index,
+        usd_value,
+        confirmation: ConfirmationStatus::Pending,
+        gas_used: receipt.and_then(|r| r.gas_used).map(|g| g.as_u64()).unwrap_or_default(),
+        block_number: transfer.block_number.unwrap_or_default(),
+        timestamp,
+        zk_proof_hash: None,
+        risk_score,
+        pattern_type: crate::TransactionPattern::LargeTransaction,
+        exchange_flow: ExchangeFlow::NonExchange,
+    }
+}
+
+/// Timestamp of a block, rejecting values a `DateTime` cannot hold
+fn block_timestamp(block: &Block<Transaction>) -> ZKWatchResult<DateTime<Utc>> {
+    i64::try_from(block.timestamp).ok()
+        .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+        .ok_or_else(|| ZKWatchError::Blockchain(format!(
+            "Block {} has out-of-range timestamp {}",
+            block.number.map(|n| n.as_u64()).unwrap_or_default(),
+            block.timestamp,
+        )))
+}
+
+/// Timestamp of a block, fetched once per block number
+async fn block_time(
+    source: &dyn BlockSource,
+    limiter: &RateLimiter,
+    cache: &mut HashMap<u64, DateTime<Utc>>,
+    number: u64,
+) -> ZKWatchResult<DateTime<Utc>> {
+    if let Some(timestamp) = cache.get(&number) {
+        return Ok(*timestamp);
+    }
+    
+    limiter.wait().await?;
+    let block = source.block_with_transactions(number).await?
+        .ok_or_else(|| ZKWatchError::Blockchain(format!("Block {} not found", number)))?;
+    let timestamp = block_timestamp(&block)?;
+    cache.insert(number, timestamp);
+    Ok(timestamp)
+}
+
+/// Native-asset end of a bridge transfer
+fn native_bridge_transfer(
+    network: &NetworkConfig,
+    protocol: BridgeProtocol,
+    direction: BridgeDirection,
+    hash: H256,
+    account: H160,
+    amount: u128,
+    timestamp: DateTime<Utc>,
+) -> BridgeTransfer {
+    BridgeTransfer {
+        protocol,
+        direction,
+        network: network.name.clone(),
+        chain_id: network.chain_id,
+        transaction_hash: format!("{:?}", hash),
+        account: format_address(&account),
+        token: fund_flow::NATIVE_ASSET.to_string(),
+        symbol: Some(pricing::price_symbol(&network.native_symbol)),
+        amount,
+        decimals: Some(18),
+        value_wei: Some(amount),
+        timestamp,
+    }
+}
+
+/// "2h 5m"-style rendering of a duration in seconds
+fn format_latency(secs: i64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3_600, secs % 3_600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, secs % 60)
+    }
+}
+
+/// Risk grows with how far a transfer exceeds the whale threshold: 0.5 at the
+/// threshold, saturating at 1.0 for transfers three orders of magnitude above it
+pub(crate) fn size_risk_score(threshold_ratio: f64) -> f64 {
+    if !threshold_ratio.is_finite() || threshold_ratio <= 0.0 {
+        return 0.5;
+    }
+    (0.5 + threshold_ratio.log10() / 6.0).clamp(0.0, 1.0)
+}
+
+pub(crate) fn u256_to_u128(value: U256) -> u128 {
+    if value.bits() > 128 {
+        u128::MAX
+    } else {
+        value.as_u128()
+    }
+}
+
+pub(crate) fn format_address(address: &H160) -> String {
+    format!("{:?}", address)
+}
+
+/// Gas used and native wei paid for gas by a set of transactions
+async fn gas_spent(source: &dyn BlockSource, limiter: &RateLimiter, hashes: &[H256]) -> ZKWatchResult<(u64, u128)> {
+    let mut gas_used = 0u64;
+    let mut gas_cost = 0u128;
+    
+    for hash in hashes {
+        limiter.wait().await?;
+        if let Some(receipt) = source.transaction_receipt(*hash).await? {
+            let used = receipt.gas_used.unwrap_or_default();
+            gas_used += used.as_u64();
+            gas_cost += u256_to_u128(used.saturating_mul(receipt.effective_gas_price.unwrap_or_default()));
+        }
+    }
+    
+    Ok((gas_used, gas_cost))
+}
+
+/// Build an MEV record from a detected sandwich, valuing profit and loss in native wei
+fn mev_from_sandwich(
+    network: &NetworkConfig,
+    block: &Block<Transaction>,
+    timestamp: DateTime<Utc>,
+    sandwich: &Sandwich,
+    (gas_used, gas_cost): (u64, u128),
+    token_registry: &TokenRegistry,
+    price_source: &dyn PriceSource,
+) -> MEVOpportunity {
+    let profit = token_value(network, sandwich.profit_token, sandwich.profit, token_registry, price_source, timestamp);
+    let victim_token = sandwich.victims.first().and_then(|v| v.swap.token_out);
+    let victim_loss = token_value(network, victim_token, sandwich.victim_loss(), token_registry, price_source, timestamp);
+    let (net_profit, profit_usd) = net_of_gas(network, profit, gas_cost, price_source, timestamp);
+    
+    // Consecutive legs and a profit that survives gas make the attribution firmer
+    let front = sandwich.front_run.transaction_index;
+    let back = sandwich.back_run.transaction_index;
+    let mut confidence: f64 = 0.6;
+    if back - front == sandwich.victims.len() as u64 + 1 {
+        confidence += 0.2;
+    }
+    if net_profit.is_some_and(|p| p > 0) {
+        confidence += 0.1;
+    }
+    if sandwich.victims.iter().all(|v| v.exact_loss) {
+        confidence += 0.05;
+    }
+    
+    MEVOpportunity {
+        opportunity_type: MEVType::Sandwich,
+        estimated_profit: net_profit.unwrap_or_default(),
+        gas_estimate: gas_used,
+        block_number: block.number.map(|n| n.as_u64()).unwrap_or_default(),
+        block_hash: block.hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        network: network.name.clone(),
+        transaction_hashes: sandwich.transaction_hashes().iter().map(|h| format!("{:?}", h)).collect(),
+        searcher: format_address(&sandwich.searcher),
+        victims: sandwich.victims.iter().map(|v| format_address(&v.address)).collect(),
+        victim_loss: victim_loss.map(|(wei, _)| wei).unwrap_or_default(),
+        profit_usd,
+        victim_loss_usd: victim_loss.and_then(|(_, usd)| usd),
+        confidence: confidence.min(1.0),
+        description: format!(
+            "Sandwich on {:?} pool {} around {} victim(s), gross profit {} raw {}",
+            sandwich.protocol,
+            format_address(&sandwich.pool),
+            sandwich.victims.len(),
+            sandwich.profit,
+            token_symbol(network, sandwich.profit_token, token_registry),
+        ),
+    }
+}
+
+/// Build an MEV record from an atomic arbitrage cycle
+fn mev_from_arbitrage(
+    network: &NetworkConfig,
+    block: &Block<Transaction>,
+    timestamp: DateTime<Utc>,
+    arbitrage: &Arbitrage,
+    (gas_used, gas_cost): (u64, u128),
+    token_registry: &TokenRegistry,
+    price_source: &dyn PriceSource,
+) -> MEVOpportunity {
+    let profit = token_value(network, Some(arbitrage.start_token), arbitrage.profit, token_registry, price_source, timestamp);
+    let (net_profit, profit_usd) = net_of_gas(network, profit, gas_cost, price_source, timestamp);
+    
+    // A closed token cycle is unambiguous; only the profit after gas is uncertain when unpriced
+    let confidence = match net_profit {
+        Some(p) if p > 0 => 0.95,
+        Some(_) => 0.6,
+        None => 0.8,
+    };
+    
+    MEVOpportunity {
+        opportunity_type: MEVType::Arbitrage,
+        estimated_profit: net_profit.unwrap_or_default(),
+        gas_estimate: gas_used,
+        block_number: block.number.map(|n| n.as_u64()).unwrap_or_default(),
+        block_hash: block.hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        network: network.name.clone(),
+        transaction_hashes: vec![format!("{:?}", arbitrage.transaction_hash)],
+        searcher: format_address(&arbitrage.searcher),
+        victims: Vec::new(),
+        victim_loss: 0,
+        profit_usd,
+        victim_loss_usd: None,
+        confidence,
+        description: format!(
+            "{}-hop arbitrage through {}, gross profit {} raw {}",
+            arbitrage.swaps.len(),
+            arbitrage.swaps.iter().map(|s| format_address(&s.pool)).collect::<Vec<_>>().join(" → "),
+            arbitrage.profit,
+            token_symbol(network, Some(arbitrage.start_token), token_registry),
+        ),
+    }
+}
+
+/// Build an MEV record from a liquidation, with the liquidation bonus as profit
+fn mev_from_liquidation(
+    network: &NetworkConfig,
+    block: &Block<Transaction>,
+    timestamp: DateTime<Utc>,
+    liquidation: &Liquidation,
+    (gas_used, gas_cost): (u64, u128),
+    token_registry: &TokenRegistry,
+    price_source: &dyn PriceSource,
+) -> MEVOpportunity {
+    let collateral = token_value(network, liquidation.collateral_token, liquidation.collateral_seized, token_registry, price_source, timestamp);
+    let debt = token_value(network, liquidation.debt_token, liquidation.debt_repaid, token_registry, price_source, timestamp);
+    
+    // The borrower loses exactly the bonus the liquidator gains: seized collateral above the repaid debt
+    let bonus = match (collateral, debt) {
+        (Some((collateral_wei, collateral_usd)), Some((debt_wei, debt_usd))) => Some((
+            collateral_wei.saturating_sub(debt_wei),
+            collateral_usd.zip(debt_usd).map(|(c, d)| c - d),
+        )),
+        _ => None,
+    };
+    let (net_profit, profit_usd) = net_of_gas(network, bonus, gas_cost, price_source, timestamp);
+    
+    let searcher = block.transactions.iter()
+        .find(|tx| tx.hash == liquidation.transaction_hash)
+        .map(|tx| tx.from)
+        .unwrap_or(liquidation.liquidator);
+    
+    MEVOpportunity {
+        opportunity_type: MEVType::Liquidation,
+        estimated_profit: net_profit.unwrap_or_default(),
+        gas_estimate: gas_used,
+        block_number: block.number.map(|n| n.as_u64()).unwrap_or_default(),
+        block_hash: block.hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        network: network.name.clone(),
+        transaction_hashes: vec![format!("{:?}", liquidation.transaction_hash)],
+        searcher: format_address(&searcher),
+        victims: vec![format_address(&liquidation.borrower)],
+        victim_loss: bonus.map(|(wei, _)| wei).unwrap_or_default(),
+        profit_usd,
+        victim_loss_usd: bonus.and_then(|(_, usd)| usd),
+        confidence: 0.95,
+        description: format!(
+            "{:?} liquidation of {}: repaid {} raw {}, seized {} raw {}",
+            liquidation.protocol,
+            format_address(&liquidation.borrower),
+            liquidation.debt_repaid,
+            token_symbol(network, liquidation.debt_token, token_registry),
+            liquidation.collateral_seized,
+            token_symbol(network, liquidation.collateral_token, token_registry),
+        ),
+    }
+}
+
+/// Gross value in (wei, USD) minus gas, as net wei and net USD
+fn net_of_gas(
+    network: &NetworkConfig,
+    gross: Option<(u128, Option<f64>)>,
+    gas_cost: u128,
+    price_source: &dyn PriceSource,
+    at: DateTime<Utc>,
+) -> (Option<u128>, Option<f64>) {
+    let native_price = price_source.price_usd(&network.native_symbol, at);
+    let net_usd = match (gross, native_price) {
+        (Some((_, Some(gross_usd))), Some(price)) => Some(gross_usd - gas_cost as f64 / 1e18 * price),
+        _ => None,
+    };
+    (gross.map(|(wei, _)| wei.saturating_sub(gas_cost)), net_usd)
+}
+
+/// Registry symbol of a token, or its address when untracked
+fn token_symbol(network: &NetworkConfig, token: Option<H160>, token_registry: &TokenRegistry) -> String {
+    match token {
+        Some(address) => token_registry.get(network.chain_id, &address)
+            .map(|t| t.info.symbol.clone())
+            .unwrap_or_else(|| format_address(&address)),
+        None => "unknown token".to_string(),
+    }
+}
+
+/// Value of a raw token amount in native wei and USD
+///
+/// The wrapped native asset converts one to one without a price; other tracked
+/// tokens go through their USD price. Untracked tokens cannot be valued.
+pub(crate) fn token_value(
+    network: &NetworkConfig,
+    token: Option<H160>,
+    amount: U256,
+    token_registry: &TokenRegistry,
+    price_source: &dyn PriceSource,
+    at: DateTime<Utc>,
+) -> Option<(u128, Option<f64>)> {
+    let tracked = token_registry.get(network.chain_id, &token?)?;
+    let native_price = price_source.price_usd(&network.native_symbol, at);
+    let whole_units = dex::u256_to_f64(amount) / 10f64.powi(tracked.info.decimals as i32);
+    
+    if pricing::price_symbol(&tracked.info.symbol) == pricing::price_symbol(&network.native_symbol) {
+        return Some((u256_to_u128(amount), native_price.map(|price| whole_units * price)));
+    }
+    
+    let usd = whole_units * price_source.price_usd(&tracked.info.symbol, at)?;
+    let wei = native_price.filter(|p| *p > 0.0).map(|price| (usd / price * 1e18) as u128);
+    Some((wei.unwrap_or_default(), Some(usd)))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_source::InMemoryBlockSource;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    fn network() -> NetworkConfig {
+        NetworkConfig {
+            name: "Ethereum".to_string(),
+            chain_id: 1,
+            rpc_url: String::new(),
+            ws_url: None,
+            explorer_url: String::new(),
+            native_symbol: "ETH".to_string(),
+            confirmation_depth: 1,
+            finality_depth: 3,
+            block_time_ms: 12_000,
+        }
+    }
+
+    fn block_hash(number: u64, fork: u64) -> H256 {
+        H256::from_low_u64_be(fork * 1_000 + number)
+    }
+
+    /// Block on `fork` holding one 1000 ETH transfer, built on block `number - 1` of `parent_fork`
+    fn whale_block(number: u64, fork: u64, parent_fork: u64) -> Block<Transaction> {
+        Block {
+            number: Some(U64::from(number)),
+            hash: Some(block_hash(number, fork)),
+            parent_hash: block_hash(number - 1, parent_fork),
+            timestamp: U256::from(1_700_000_000 + number * 12),
+            transactions: vec![Transaction {
+                hash: H256::from_low_u64_be(100_000 + fork * 1_000 + number),
+                from: H160::from_low_u64_be(1),
+                to: Some(H160::from_low_u64_be(2)),
+                value: U256::exp10(21),
+                block_number: Some(U64::from(number)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn scanner<S: BlockSource + 'static>(source: &Arc<S>, scan_depth: u64) -> MultiChainScanner {
+        let source: Arc<dyn BlockSource> = source.clone();
+        MultiChainScanner::new(vec![(network(), source)])
+            .with_scan_depth(scan_depth)
+            .with_price_source(Arc::new(StaticPriceTable::new([("ETH".to_string(), 2_000.0)])))
+    }
+
+    fn scratch_checkpoints(name: &str) -> CheckpointStore {
+        let dir = std::env::temp_dir().join(format!("zkwatch-blockchain-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        CheckpointStore::open(dir).unwrap()
+    }
+
+    /// In-memory chain that records which blocks were fetched and can fail on one of them
+    #[derive(Default)]
+    struct RecordingSource {
+        chain: InMemoryBlockSource,
+        fetched: Mutex<Vec<u64>>,
+        fail_at: Mutex<Option<u64>>,
+        /// Answer trace calls like a node without the trace API
+        traces_unavailable: bool,
+    }
+
+    impl RecordingSource {
+        fn with_whale_blocks(blocks: std::ops::RangeInclusive<u64>) -> Arc<Self> {
+            let source = Self::default();
+            for number in blocks {
+                source.chain.insert_block(whale_block(number, 0, 0));
+            }
+            Arc::new(source)
+        }
+
+        fn take_fetched(&self) -> Vec<u64> {
+            let mut fetched = std::mem::take(&mut *self.fetched.lock().unwrap());
+            fetched.sort();
+            fetched
+        }
+    }
+
+    #[async_trait]
+    impl BlockSource for RecordingSource {
+        async fn block_number(&self) -> ZKWatchResult<u64> {
+            self.chain.block_number().await
+        }
+
+        async fn block_with_transactions(&self, number: u64) -> ZKWatchResult<Option<Block<Transaction>>> {
+            if *self.fail_at.lock().unwrap() == Some(number) {
+                return Err(ZKWatchError::Blockchain(format!("block {} unavailable", number)));
+            }
+            self.fetched.lock().unwrap().push(number);
+            self.chain.block_with_transactions(number).await
+        }
+
+        async fn transaction_receipt(&self, hash: H256) -> ZKWatchResult<Option<TransactionReceipt>> {
+            self.chain.transaction_receipt(hash).await
+        }
+
+        async fn logs(&self, filter: &LogFilter) -> ZKWatchResult<Vec<Log>> {
+            self.chain.logs(filter).await
+        }
+
+        async fn trace_transaction(&self, hash: H256) -> ZKWatchResult<Vec<Trace>> {
+            if self.traces_unavailable {
+                return Err(ZKWatchError::Blockchain("the method trace_transaction does not exist".to_string()));
+            }
+            self.chain.trace_transaction(hash).await
+        }
+
+        async fn traces(&self, filter: &TraceFilter) -> ZKWatchResult<Vec<Trace>> {
+            self.chain.traces(filter).await
+        }
+    }
+
+    fn summary(transactions: &[WhaleTransaction]) -> Vec<(u64, String, ConfirmationStatus)> {
+        transactions.iter().map(|tx| (tx.block_number, tx.hash.clone(), tx.confirmation)).collect()
+    }
+
+    #[tokio::test]
+    async fn reorg_retracts_whales_of_orphaned_blocks() {
+        let source = Arc::new(InMemoryBlockSource::new());
+        let mut scanner = scanner(&source, 1);
+        for number in 1..=3 {
+            source.insert_block(whale_block(number, 0, 0));
+            scanner.scan_network_whales(&network(), 100_000.0).await.unwrap();
+        }
+        assert_eq!(scanner.drain_events().iter().filter(|e| matches!(e, ScanEvent::Detected(_))).count(), 3);
+
+        // A fork splitting after block 1 replaces blocks 2 and 3 and adds block 4;
+        // the scan only fetches the new head and walks back by parent hash
+        for number in 2..=4 {
+            source.insert_block(whale_block(number, 1, if number == 2 { 0 } else { 1 }));
+        }
+        let whales = scanner.scan_network_whales(&network(), 100_000.0).await.unwrap();
+        assert_eq!(whales.len(), 1);
+        assert_eq!(whales[0].block_number, 4);
+
+        let events = scanner.drain_events();
+        let retracted: Vec<(u64, String)> = events.iter()
+            .filter_map(|event| match event {
+                ScanEvent::Retracted { transaction, orphaned_block_hash } => Some((transaction.block_number, orphaned_block_hash.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retracted, vec![
+            (2, format!("{:?}", block_hash(2, 0))),
+            (3, format!("{:?}", block_hash(3, 0))),
+        ]);
+
+        let detected: Vec<u64> = events.iter()
+            .filter_map(|event| match event {
+                ScanEvent::Detected(transaction) => Some(transaction.block_number),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(detected, vec![2, 3, 4]);
+
+        // Statuses are measured against the new head: block 1 is final, the fork's blocks 2 and 3 are safe
+        let state = &scanner.chain_states["Ethereum"];
+        let status = |number: u64| state.block_whales[&number].transactions[0].confirmation;
+        assert_eq!(status(1), ConfirmationStatus::Finalized);
+        assert_eq!(status(2), ConfirmationStatus::Safe);
+        assert_eq!(status(3), ConfirmationStatus::Safe);
+        assert_eq!(status(4), ConfirmationStatus::Pending);
+        assert_eq!(state.block_whales[&3].hash, block_hash(3, 1));
+    }
+
+    #[tokio::test]
+    async fn unrepresentable_block_timestamp_fails_the_scan() {
+        let source = Arc::new(InMemoryBlockSource::new());
+        let mut block = whale_block(1, 0, 0);
+        block.timestamp = U256::MAX;
+        source.insert_block(block);
+
+        let mut scanner = scanner(&source, 1);
+        let error = scanner.scan_network_whales(&network(), 100_000.0).await.unwrap_err();
+        assert!(matches!(error, ZKWatchError::Blockchain(message) if message.contains("timestamp")));
+        assert!(scanner.drain_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn confirmations_deepen_as_the_chain_grows() {
+        let source = Arc::new(InMemoryBlockSource::new());
+        let mut scanner = scanner(&source, 1);
+        source.insert_block(whale_block(1, 0, 0));
+        scanner.scan_network_whales(&network(), 100_000.0).await.unwrap();
+
+        for number in 2..=4 {
+            let mut block = whale_block(number, 0, 0);
+            block.transactions.clear();
+            source.insert_block(block);
+            scanner.scan_network_whales(&network(), 100_000.0).await.unwrap();
+        }
+
+        let statuses: Vec<ConfirmationStatus> = scanner.drain_events().into_iter()
+            .filter_map(|event| match event {
+                ScanEvent::StatusChanged { status, .. } => Some(status),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(statuses, vec![ConfirmationStatus::Safe, ConfirmationStatus::Finalized]);
+    }
+
+    #[tokio::test]
+    async fn restarted_scan_resumes_from_the_checkpoint() {
+        let checkpoints = scratch_checkpoints("resume");
+        let source = RecordingSource::with_whale_blocks(1..=3);
+        let mut first = scanner(&source, 1).with_checkpoints(checkpoints.clone());
+        assert_eq!(first.scan_network_whales(&network(), 100_000.0).await.unwrap().len(), 1);
+        assert_eq!(source.take_fetched(), vec![3]);
+        assert_eq!(checkpoints.load("Ethereum").unwrap().map(|c| c.block_number), Some(3));
+
+        // A fresh scanner on the same store catches up on the blocks produced since, and only those
+        source.chain.insert_block(whale_block(4, 0, 0));
+        source.chain.insert_block(whale_block(5, 0, 0));
+        let mut restarted = scanner(&source, 1).with_checkpoints(checkpoints.clone());
+        let whales = restarted.scan_network_whales(&network(), 100_000.0).await.unwrap();
+        assert_eq!(source.take_fetched(), vec![4, 5]);
+        assert_eq!(whales.iter().map(|tx| tx.block_number).collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(checkpoints.load("Ethereum").unwrap().map(|c| c.block_hash), Some(format!("{:?}", block_hash(5, 0))));
+    }
+
+    #[tokio::test]
+    async fn interrupted_backfill_resumes_with_remaining_chunks() {
+        let one_shot = {
+            let source = RecordingSource::with_whale_blocks(1..=10);
+            scanner(&source, 1).backfill("Ethereum", 1, 8, 100_000.0, 2, 1).await.unwrap()
+        };
+        assert_eq!(one_shot.len(), 8);
+
+        let checkpoints = scratch_checkpoints("backfill");
+        let source = RecordingSource::with_whale_blocks(1..=10);
+        *source.fail_at.lock().unwrap() = Some(5);
+        let scanner = scanner(&source, 1).with_checkpoints(checkpoints.clone());
+        assert!(scanner.backfill("Ethereum", 1, 8, 100_000.0, 2, 1).await.is_err());
+        let progress = checkpoints.load_backfill("Ethereum", 1, 8).unwrap().unwrap();
+        assert_eq!(progress.remaining_chunks(), vec![(5, 6), (7, 8)]);
+        source.take_fetched();
+
+        *source.fail_at.lock().unwrap() = None;
+        let resumed = scanner.backfill("Ethereum", 1, 8, 100_000.0, 2, 1).await.unwrap();
+        assert_eq!(source.take_fetched(), vec![5, 6, 7, 8]);
+        assert_eq!(summary(&resumed), summary(&one_shot));
+    }
+
+    #[tokio::test]
+    async fn liquidation_profit_is_net_of_gas() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<H160>().unwrap();
+        let usdc = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse::<H160>().unwrap();
+        let (liquidator, borrower) = (H160::from_low_u64_be(0x1), H160::from_low_u64_be(0xb));
+        let hash = H256::from_low_u64_be(0x11);
+        let word = |value: U256| {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            bytes
+        };
+
+        // Repaid 18,000 USDC of debt and seized 10 WETH of collateral
+        let data: Vec<u8> = [U256::from(18_000u64) * U256::exp10(6), U256::exp10(19), U256::from_big_endian(H256::from(liquidator).as_bytes()), U256::zero()]
+            .into_iter()
+            .flat_map(word)
+            .collect();
+        let call = Log {
+            address: H160::from_low_u64_be(0xaa),
+            topics: vec![lending::aave_liquidation_call_topic(), weth.into(), usdc.into(), borrower.into()],
+            data: data.into(),
+            block_number: Some(U64::from(10)),
+            transaction_hash: Some(hash),
+            transaction_index: Some(U64::zero()),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+
+        let source = Arc::new(InMemoryBlockSource::new());
+        source.insert_block(Block {
+            number: Some(U64::from(10)),
+            hash: Some(block_hash(10, 0)),
+            timestamp: U256::from(1_700_000_000),
+            transactions: vec![Transaction { hash, from: liquidator, ..Default::default() }],
+            ..Default::default()
+        });
+        source.insert_receipt(TransactionReceipt {
+            transaction_hash: hash,
+            gas_used: Some(U256::from(500_000u64)),
+            effective_gas_price: Some(U256::from(20u64) * U256::exp10(9)),
+            logs: vec![call],
+            ..Default::default()
+        });
+        let prices = StaticPriceTable::new([("ETH".to_string(), 2_000.0), ("USDC".to_string(), 1.0)]);
+        let mut scanner = scanner(&source, 1).with_price_source(Arc::new(prices));
+
+        let opportunities = scanner.detect_mev_opportunities(1).await.unwrap();
+        assert_eq!(opportunities.len(), 1);
+        let liquidation = &opportunities[0];
+        assert!(matches!(liquidation.opportunity_type, MEVType::Liquidation));
+        assert_eq!(liquidation.searcher, format_address(&liquidator));
+        assert_eq!(liquidation.victims, vec![format_address(&borrower)]);
+
+        // $2,000 bonus (1 ETH) minus 0.01 ETH of gas
+        assert_eq!(liquidation.victim_loss, 10u128.pow(18));
+        assert_eq!(liquidation.gas_estimate, 500_000);
+        assert_eq!(liquidation.estimated_profit, 99 * 10u128.pow(16));
+        assert!((liquidation.profit_usd.unwrap() - 1_980.0).abs() < 1e-6);
+        assert!((liquidation.victim_loss_usd.unwrap() - 2_000.0).abs() < 1e-6);
+    }
+
+    fn token_whale(hash: H256, token: H160, value: u128) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("{:?}", hash),
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            from: format_address(&H160::from_low_u64_be(1)),
+            to: format_address(&H160::from_low_u64_be(2)),
+            value,
+            borrowed: 0,
+            token: Some(tokens::TokenInfo { address: format_address(&token), symbol: "WETH".to_string(), decimals: 18 }),
+            log_index: Some(0),
+            usd_value: None,
+            confirmation: ConfirmationStatus::Pending,
+            gas_used: 0,
+            block_number: 10,
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            zk_proof_hash: None,
+            risk_score: 0.0,
+            pattern_type: crate::TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    #[tokio::test]
+    async fn flash_loan_notional_is_left_out_of_volume_without_a_trace_api() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<H160>().unwrap();
+        let (borrower, bystander) = (H256::from_low_u64_be(0xa1), H256::from_low_u64_be(0xb1));
+        let word = |value: U256| {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            bytes
+        };
+
+        // Aave V3 flash loan of 800 WETH, repaid in the same transaction
+        let receiver = H160::from_low_u64_be(0xbe);
+        let data: Vec<u8> = [U256::from_big_endian(H256::from(receiver).as_bytes()), U256::from(800u64) * U256::exp10(18), U256::zero(), U256::exp10(17)]
+            .into_iter()
+            .flat_map(word)
+            .collect();
+        let source = RecordingSource { traces_unavailable: true, ..Default::default() };
+        source.chain.insert_logs([Log {
+            address: H160::from_low_u64_be(0xaa),
+            topics: vec![flash_loans::flash_loan_topics()[0], receiver.into(), weth.into(), H256::zero()],
+            data: data.into(),
+            block_number: Some(U64::from(10)),
+            transaction_hash: Some(borrower),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        }]);
+
+        let whale = 10u128.pow(21);
+        let mut transactions = vec![token_whale(borrower, weth, whale), token_whale(bystander, weth, whale)];
+        let limiter = RateLimiter::new(100, std::time::Duration::from_secs(1));
+        tag_flash_loans(&source, &limiter, 10, &mut transactions).await.unwrap();
+
+        assert!(matches!(transactions[0].pattern_type, crate::TransactionPattern::FlashLoan));
+        assert_eq!(transactions[0].borrowed, 800 * 10u128.pow(18));
+        assert_eq!(transactions[0].volume(), 200 * 10u128.pow(18));
+        assert!(matches!(transactions[1].pattern_type, crate::TransactionPattern::LargeTransaction));
+        assert_eq!(transactions[1].volume(), whale);
+    }
+}
@@ -0,0 +1,329 @@
+//! Cross-chain bridge matching
+//!
+//! Recognizes deposits into and fills out of canonical and third-party bridge
+//! contracts named in the label registry, and pairs each source-chain deposit with the destination-chain
+//! fill that matches it by token, amount and time window, whether or not the fill pays the depositor.
+
+use crate::labels::{LabelCategory, LabelRegistry, ANY_CHAIN};
+use chrono::{DateTime, Duration, Utc};
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Chain id of Ethereum mainnet, where canonical rollup bridges settle
+const L1_CHAIN_ID: u64 = 1;
+
+/// Bridge a transfer went through
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BridgeProtocol {
+    ArbitrumCanonical,
+    OptimismCanonical,
+    PolygonPos,
+    Across,
+    Stargate,
+}
+
+impl std::fmt::Display for BridgeProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BridgeProtocol::ArbitrumCanonical => write!(f, "Arbitrum Bridge"),
+            BridgeProtocol::OptimismCanonical => write!(f, "Optimism Bridge"),
+            BridgeProtocol::PolygonPos => write!(f, "Polygon PoS Bridge"),
+            BridgeProtocol::Across => write!(f, "Across"),
+            BridgeProtocol::Stargate => write!(f, "Stargate"),
+        }
+    }
+}
+
+impl BridgeProtocol {
+    /// Protocol named by a label's entity, as written by its `Display`
+    pub fn from_entity(entity: &str) -> Option<Self> {
+        [
+            BridgeProtocol::ArbitrumCanonical,
+            BridgeProtocol::OptimismCanonical,
+            BridgeProtocol::PolygonPos,
+            BridgeProtocol::Across,
+            BridgeProtocol::Stargate,
+        ]
+        .into_iter()
+        .find(|protocol| protocol.to_string().eq_ignore_ascii_case(entity))
+    }
+
+    /// Usual and longest plausible delay between a deposit and its fill
+    pub fn latency(&self, source_chain_id: u64, destination_chain_id: u64) -> (Duration, Duration) {
+        let withdrawal = destination_chain_id == L1_CHAIN_ID && source_chain_id != L1_CHAIN_ID;
+        match self {
+            // Rollup withdrawals wait out the seven day challenge period
+            BridgeProtocol::ArbitrumCanonical | BridgeProtocol::OptimismCanonical if withdrawal => {
+                (Duration::days(7), Duration::days(14))
+            }
+            BridgeProtocol::ArbitrumCanonical | BridgeProtocol::OptimismCanonical => {
+                (Duration::minutes(15), Duration::hours(2))
+            }
+            // Exits need a checkpoint on Ethereum, every few hours at worst
+            BridgeProtocol::PolygonPos if withdrawal => (Duration::hours(3), Duration::days(2)),
+            BridgeProtocol::PolygonPos => (Duration::minutes(30), Duration::hours(3)),
+            BridgeProtocol::Across => (Duration::minutes(2), Duration::hours(6)),
+            BridgeProtocol::Stargate => (Duration::minutes(5), Duration::hours(6)),
+        }
+    }
+
+    /// Largest share of the deposit the bridge may keep as fees
+    pub fn max_fee_ratio(&self) -> f64 {
+        match self {
+            BridgeProtocol::ArbitrumCanonical | BridgeProtocol::OptimismCanonical | BridgeProtocol::PolygonPos => 0.0,
+            BridgeProtocol::Across => 0.02,
+            BridgeProtocol::Stargate => 0.01,
+        }
+    }
+}
+
+/// Which end of a bridge transfer was observed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BridgeDirection {
+    /// Funds sent into the bridge on the source chain
+    Deposit,
+    /// Funds paid out by the bridge on the destination chain
+    Fill,
+}
+
+/// Deposit into or fill out of a bridge, seen on one chain
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BridgeTransfer {
+    pub protocol: BridgeProtocol,
+    pub direction: BridgeDirection,
+    pub network: String,
+    pub chain_id: u64,
+    pub transaction_hash: String,
+    /// Depositor of a deposit, recipient of a fill
+    pub account: String,
+    /// Token contract, or [`crate::fund_flow::NATIVE_ASSET`] for the chain's native asset
+    pub token: String,
+    /// Normalized symbol of a tracked token, used to compare tokens across chains
+    pub symbol: Option<String>,
+    /// Amount in raw token units
+    pub amount: u128,
+    /// Decimals of a tracked token
+    pub decimals: Option<u8>,
+    /// Amount in native wei of the transfer's chain, when priced
+    pub value_wei: Option<u128>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BridgeTransfer {
+    /// Amount in whole tokens when the decimals are known, raw units otherwise
+    fn comparable_amount(&self, decimals_known: bool) -> f64 {
+        match self.decimals {
+            Some(decimals) if decimals_known => self.amount as f64 / 10f64.powi(decimals as i32),
+            _ => self.amount as f64,
+        }
+    }
+}
+
+/// Deposit paired with the fill that completed it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BridgeMatch {
+    pub deposit: BridgeTransfer,
+    pub fill: BridgeTransfer,
+    /// Seconds from deposit to fill
+    pub latency_secs: i64,
+    /// Share of the deposit not paid out by the fill
+    pub fee_ratio: f64,
+    /// Whether both ends name a tracked token with the same symbol
+    pub token_confirmed: bool,
+    /// Whether the fill paid the depositor's own address
+    pub same_account: bool,
+    pub confidence: f64,
+}
+
+/// Bridge contracts per chain, keyed by contract address
+#[derive(Debug, Clone, Default)]
+pub struct BridgeRegistry {
+    contracts: HashMap<(u64, H160), BridgeProtocol>,
+}
+
+impl BridgeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Canonical Arbitrum, Optimism and Polygon PoS bridges, Across spoke pools
+    /// and Stargate routers and pools from the bundled labels
+    pub fn with_defaults() -> Self {
+        Self::from_labels(&LabelRegistry::with_defaults())
+    }
+
+    /// Bridge-labeled addresses whose entity names a supported bridge
+    pub fn from_labels(labels: &LabelRegistry) -> Self {
+        let mut registry = Self::new();
+        for (chain_id, address, label) in labels.iter() {
+            let protocol = label.entity.as_deref().and_then(BridgeProtocol::from_entity);
+            if let (true, Some(protocol)) = (label.has(LabelCategory::Bridge), protocol) {
+                registry.insert(chain_id, *address, protocol);
+            }
+        }
+        registry
+    }
+
+    pub fn insert(&mut self, chain_id: u64, address: H160, protocol: BridgeProtocol) {
+        self.contracts.insert((chain_id, address), protocol);
+    }
+
+    pub fn get(&self, chain_id: u64, address: &H160) -> Option<BridgeProtocol> {
+        self.contracts.get(&(chain_id, *address))
+            .or_else(|| self.contracts.get(&(ANY_CHAIN, *address)))
+            .copied()
+    }
+
+    /// Contracts on a chain, including chain-independent ones, of one protocol or of all
+    pub fn contracts(&self, chain_id: u64, protocol: Option<BridgeProtocol>) -> Vec<H160> {
+        let mut contracts: Vec<H160> = self.contracts.iter()
+            .filter(|((chain, _), p)| (*chain == chain_id || *chain == ANY_CHAIN) && protocol.is_none_or(|protocol| **p == protocol))
+            .map(|((_, address), _)| *address)
+            .collect();
+        contracts.sort();
+        contracts.dedup();
+        contracts
+    }
+}
+
+/// Pair deposits with fills of the same bridge on another chain
+///
+/// A fill qualifies when it pays out the same token (when both ends are
+/// tracked), no more than the deposit and no less than the deposit minus the
+/// bridge's maximum fee, within the bridge's latency window. Fills to the
+/// depositor's own address score higher than fills to another recipient.
+/// Each deposit and fill is used once, best-scoring pairs first.
+pub fn match_transfers(transfers: &[BridgeTransfer]) -> Vec<BridgeMatch> {
+    let deposits: Vec<&BridgeTransfer> = transfers.iter().filter(|t| t.direction == BridgeDirection::Deposit).collect();
+    let fills: Vec<&BridgeTransfer> = transfers.iter().filter(|t| t.direction == BridgeDirection::Fill).collect();
+
+    let mut candidates: Vec<(usize, usize, BridgeMatch)> = Vec::new();
+    for (deposit_index, deposit) in deposits.iter().enumerate() {
+        for (fill_index, fill) in fills.iter().enumerate() {
+            if let Some(candidate) = score_pair(deposit, fill) {
+                candidates.push((deposit_index, fill_index, candidate));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.confidence.partial_cmp(&a.2.confidence).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut used_deposits = HashSet::new();
+    let mut used_fills = HashSet::new();
+    let mut matches = Vec::new();
+    for (deposit_index, fill_index, candidate) in candidates {
+        if used_deposits.contains(&deposit_index) || used_fills.contains(&fill_index) {
+            continue;
+        }
+        used_deposits.insert(deposit_index);
+        used_fills.insert(fill_index);
+        matches.push(candidate);
+    }
+
+    matches.sort_by_key(|m| m.deposit.timestamp);
+    matches
+}
+
+fn score_pair(deposit: &BridgeTransfer, fill: &BridgeTransfer) -> Option<BridgeMatch> {
+    if deposit.protocol != fill.protocol || deposit.chain_id == fill.chain_id {
+        return None;
+    }
+    let same_account = deposit.account.eq_ignore_ascii_case(&fill.account);
+
+    let token_confirmed = match (&deposit.symbol, &fill.symbol) {
+        (Some(sent), Some(received)) if sent != received => return None,
+        (Some(_), Some(_)) => true,
+        _ => false,
+    };
+
+    let (expected, max_latency) = deposit.protocol.latency(deposit.chain_id, fill.chain_id);
+    let latency = fill.timestamp - deposit.timestamp;
+    if latency < Duration::zero() || latency > max_latency {
+        return None;
+    }
+
+    let sent = deposit.comparable_amount(token_confirmed);
+    let received = fill.comparable_amount(token_confirmed);
+    if sent <= 0.0 {
+        return None;
+    }
+    let fee_ratio = (sent - received) / sent;
+    // Rounding between tokens of different decimals leaves a tiny negative fee
+    let max_fee = deposit.protocol.max_fee_ratio();
+    if fee_ratio < -1e-6 || fee_ratio > max_fee + 1e-6 {
+        return None;
+    }
+
+    let amount_score = if max_fee > 0.0 { 1.0 - (fee_ratio.max(0.0) / max_fee).min(1.0) } else { 1.0 };
+    let latency_score = if latency <= expected {
+        1.0
+    } else {
+        let overrun = (latency - expected).num_seconds() as f64;
+        let window = (max_latency - expected).num_seconds().max(1) as f64;
+        1.0 - (overrun / window).min(1.0)
+    };
+    let token_score = if token_confirmed { 1.0 } else { 0.0 };
+    let account_score = if same_account { 1.0 } else { 0.0 };
+    let confidence = (0.3 + 0.3 * amount_score + 0.2 * latency_score + 0.1 * token_score + 0.1 * account_score).min(0.99);
+
+    Some(BridgeMatch {
+        deposit: deposit.clone(),
+        fill: fill.clone(),
+        latency_secs: latency.num_seconds(),
+        fee_ratio: fee_ratio.max(0.0),
+        token_confirmed,
+        same_account,
+        confidence,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(direction: BridgeDirection, chain_id: u64, account: &str, token: &str, amount: u128, minutes: i64) -> BridgeTransfer {
+        BridgeTransfer {
+            protocol: BridgeProtocol::Across,
+            direction,
+            network: chain_id.to_string(),
+            chain_id,
+            transaction_hash: format!("0x{:064x}", minutes),
+            account: account.to_string(),
+            token: token.to_string(),
+            symbol: Some("ETH".to_string()),
+            amount,
+            decimals: Some(18),
+            value_wei: Some(amount),
+            timestamp: DateTime::from_timestamp(1_700_000_000 + minutes * 60, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn matches_fill_to_another_recipient_below_own_fill() {
+        let depositor = "0x1111111111111111111111111111111111111111";
+        let other = "0x2222222222222222222222222222222222222222";
+        let deposit = transfer(BridgeDirection::Deposit, 1, depositor, "native", 10u128.pow(19), 0);
+        let elsewhere = transfer(BridgeDirection::Fill, 42161, other, "0xweth", 9_990_000_000_000_000_000, 3);
+
+        let matches = match_transfers(&[deposit.clone(), elsewhere.clone()]);
+        assert_eq!(matches.len(), 1);
+        assert!(!matches[0].same_account);
+        assert!(matches[0].token_confirmed);
+
+        let own = transfer(BridgeDirection::Fill, 42161, depositor, "native", 9_990_000_000_000_000_000, 4);
+        let matches = match_transfers(&[deposit, elsewhere, own]);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].same_account);
+    }
+
+    #[test]
+    fn rejects_fills_outside_fee_or_latency() {
+        let account = "0x1111111111111111111111111111111111111111";
+        let deposit = transfer(BridgeDirection::Deposit, 1, account, "native", 10u128.pow(19), 0);
+        let overpaid = transfer(BridgeDirection::Fill, 10, account, "native", 2 * 10u128.pow(19), 3);
+        let late = transfer(BridgeDirection::Fill, 10, account, "native", 10u128.pow(19), 7 * 60);
+        let same_chain = transfer(BridgeDirection::Fill, 1, account, "native", 10u128.pow(19), 3);
+
+        assert!(match_transfers(&[deposit, overpaid, late, same_chain]).is_empty());
+    }
+}
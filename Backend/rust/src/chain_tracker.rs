@@ -0,0 +1,186 @@
+//! Canonical chain tracking
+//!
+//! Follows block headers per network, detects reorganizations by parent-hash
+//! mismatch and derives confirmation status from block depth.
+
+use ethers::types::{Block, H256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Confirmation stage of a whale transaction's block
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfirmationStatus {
+    /// Included, but shallow enough to be reorganized away
+    Pending,
+    /// Buried under the network's confirmation depth
+    Safe,
+    /// Buried under the network's finality depth
+    Finalized,
+}
+
+impl ConfirmationStatus {
+    /// Status of a block `depth` blocks below the head
+    pub fn at_depth(depth: u64, confirmation_depth: u64, finality_depth: u64) -> Self {
+        if depth >= finality_depth {
+            ConfirmationStatus::Finalized
+        } else if depth >= confirmation_depth {
+            ConfirmationStatus::Safe
+        } else {
+            ConfirmationStatus::Pending
+        }
+    }
+}
+
+/// Identity and parent link of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRef {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+}
+
+impl BlockRef {
+    /// Header fields of a fetched block; pending blocks without number or hash yield `None`
+    pub fn from_block<T>(block: &Block<T>) -> Option<Self> {
+        Some(Self {
+            number: block.number?.as_u64(),
+            hash: block.hash?,
+            parent_hash: block.parent_hash,
+        })
+    }
+}
+
+/// Outcome of adding a block to the tracked chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainUpdate {
+    /// Block extends or starts the tracked chain
+    Extended,
+    /// Block is already part of the tracked chain
+    Known,
+    /// Block replaced tracked blocks, listed oldest first
+    Reorg { orphaned: Vec<BlockRef> },
+    /// Tracked block below this one is not its parent; the new parent must be ingested first
+    ParentMismatch { parent_number: u64 },
+}
+
+/// Recent canonical chain of one network
+#[derive(Debug, Clone)]
+pub struct CanonicalChain {
+    blocks: BTreeMap<u64, BlockRef>,
+    history: u64,
+}
+
+impl CanonicalChain {
+    /// Track up to `history` blocks below the head
+    pub fn new(history: u64) -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+            history: history.max(1),
+        }
+    }
+
+    pub fn head(&self) -> Option<&BlockRef> {
+        self.blocks.values().next_back()
+    }
+
+    pub fn get(&self, number: u64) -> Option<&BlockRef> {
+        self.blocks.get(&number)
+    }
+
+    /// Oldest block number that can still be reorganized through this tracker
+    pub fn tail(&self) -> Option<u64> {
+        self.blocks.keys().next().copied()
+    }
+
+    /// Outcome `ingest` would have, without changing the tracked chain
+    pub fn preview(&self, block: &BlockRef) -> ChainUpdate {
+        if let Some(existing) = self.blocks.get(&block.number) {
+            if existing.hash == block.hash {
+                return ChainUpdate::Known;
+            }
+        }
+
+        if let Some(parent_number) = block.number.checked_sub(1) {
+            if let Some(parent) = self.blocks.get(&parent_number) {
+                if parent.hash != block.parent_hash {
+                    return ChainUpdate::ParentMismatch { parent_number };
+                }
+            }
+        }
+
+        // Everything at or above the new block's height belonged to the old fork
+        let orphaned: Vec<BlockRef> = self.blocks.range(block.number..).map(|(_, b)| *b).collect();
+        if orphaned.is_empty() {
+            ChainUpdate::Extended
+        } else {
+            ChainUpdate::Reorg { orphaned }
+        }
+    }
+
+    pub fn ingest(&mut self, block: BlockRef) -> ChainUpdate {
+        let update = self.preview(&block);
+        if matches!(update, ChainUpdate::Extended | ChainUpdate::Reorg { .. }) {
+            self.blocks.split_off(&block.number);
+            self.blocks.insert(block.number, block);
+
+            let cutoff = block.number.saturating_sub(self.history);
+            self.blocks = self.blocks.split_off(&cutoff);
+        }
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(number: u64, fork: u64, parent_fork: u64) -> BlockRef {
+        BlockRef {
+            number,
+            hash: H256::from_low_u64_be(fork * 1_000 + number),
+            parent_hash: H256::from_low_u64_be(parent_fork * 1_000 + number - 1),
+        }
+    }
+
+    #[test]
+    fn status_follows_depth() {
+        assert_eq!(ConfirmationStatus::at_depth(0, 12, 64), ConfirmationStatus::Pending);
+        assert_eq!(ConfirmationStatus::at_depth(11, 12, 64), ConfirmationStatus::Pending);
+        assert_eq!(ConfirmationStatus::at_depth(12, 12, 64), ConfirmationStatus::Safe);
+        assert_eq!(ConfirmationStatus::at_depth(63, 12, 64), ConfirmationStatus::Safe);
+        assert_eq!(ConfirmationStatus::at_depth(64, 12, 64), ConfirmationStatus::Finalized);
+    }
+
+    #[test]
+    fn parent_mismatch_asks_for_the_new_parent_before_reorging() {
+        let mut chain = CanonicalChain::new(10);
+        for number in 1..=3 {
+            assert_eq!(chain.ingest(block(number, 0, 0)), ChainUpdate::Extended);
+        }
+        assert_eq!(chain.ingest(block(3, 0, 0)), ChainUpdate::Known);
+
+        // Block 4 of a fork that split after block 1
+        assert_eq!(chain.ingest(block(4, 1, 1)), ChainUpdate::ParentMismatch { parent_number: 3 });
+        assert_eq!(chain.ingest(block(3, 1, 1)), ChainUpdate::ParentMismatch { parent_number: 2 });
+        assert_eq!(chain.head().map(|b| b.hash), Some(block(3, 0, 0).hash));
+
+        assert_eq!(
+            chain.ingest(block(2, 1, 0)),
+            ChainUpdate::Reorg { orphaned: vec![block(2, 0, 0), block(3, 0, 0)] },
+        );
+        assert_eq!(chain.ingest(block(3, 1, 1)), ChainUpdate::Extended);
+        assert_eq!(chain.ingest(block(4, 1, 1)), ChainUpdate::Extended);
+        assert_eq!(chain.get(1).map(|b| b.hash), Some(block(1, 0, 0).hash));
+        assert_eq!(chain.head().map(|b| b.hash), Some(block(4, 1, 1).hash));
+    }
+
+    #[test]
+    fn history_is_trimmed_below_the_head() {
+        let mut chain = CanonicalChain::new(2);
+        for number in 1..=5 {
+            chain.ingest(block(number, 0, 0));
+        }
+        assert_eq!(chain.tail(), Some(3));
+        assert!(chain.get(2).is_none());
+    }
+}
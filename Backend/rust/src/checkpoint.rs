@@ -0,0 +1,294 @@
+//! Scan checkpoints and backfill progress
+//!
+//! Persists the last processed block per network and the completed chunks of
+//! historical backfills, so scans resume where they stopped after a restart.
+
+use crate::{ZKWatchResult, WhaleTransaction};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Last block a network scan fully processed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub network: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Chunk-level progress of a backfill over `[from, to]`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BackfillProgress {
+    pub network: String,
+    pub from: u64,
+    pub to: u64,
+    pub chunk_size: u64,
+    /// First block numbers of chunks whose whales are persisted
+    pub completed_chunks: BTreeSet<u64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BackfillProgress {
+    pub fn new(network: &str, from: u64, to: u64, chunk_size: u64) -> Self {
+        Self {
+            network: network.to_string(),
+            from,
+            to,
+            chunk_size: chunk_size.max(1),
+            completed_chunks: BTreeSet::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Inclusive block ranges not yet completed
+    pub fn remaining_chunks(&self) -> Vec<(u64, u64)> {
+        let mut chunks = Vec::new();
+        let mut start = self.from;
+
+        while start <= self.to {
+            let end = start.saturating_add(self.chunk_size - 1).min(self.to);
+            if !self.completed_chunks.contains(&start) {
+                chunks.push((start, end));
+            }
+            if end == u64::MAX {
+                break;
+            }
+            start = end + 1;
+        }
+
+        chunks
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining_chunks().is_empty()
+    }
+}
+
+/// Directory of JSON checkpoint files, one set per network
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    dir: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn open(dir: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn load(&self, network: &str) -> ZKWatchResult<Option<Checkpoint>> {
+        read_json(&self.checkpoint_path(network))
+    }
+
+    pub fn save(&self, checkpoint: &Checkpoint) -> ZKWatchResult<()> {
+        write_json_atomic(&self.checkpoint_path(&checkpoint.network), checkpoint)
+    }
+
+    pub fn load_backfill(&self, network: &str, from: u64, to: u64) -> ZKWatchResult<Option<BackfillProgress>> {
+        read_json(&self.backfill_path(network, from, to, "json"))
+    }
+
+    pub fn save_backfill(&self, progress: &BackfillProgress) -> ZKWatchResult<()> {
+        write_json_atomic(&self.backfill_path(&progress.network, progress.from, progress.to, "json"), progress)
+    }
+
+    /// Whales found by the completed chunks of a backfill
+    pub fn load_backfill_results(&self, progress: &BackfillProgress) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        let path = self.backfill_path(&progress.network, progress.from, progress.to, "jsonl");
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut transactions: Vec<WhaleTransaction> = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            // A crash can leave a torn final line; its chunk is not marked complete and will be redone
+            match serde_json::from_str::<WhaleTransaction>(&line) {
+                Ok(tx) => transactions.push(tx),
+                Err(_) => continue,
+            }
+        }
+
+        // Only chunks recorded as complete count; partial output of an interrupted chunk is discarded
+        let completed: Vec<(u64, u64)> = progress.completed_chunks.iter()
+            .map(|&start| (start, start.saturating_add(progress.chunk_size - 1).min(progress.to)))
+            .collect();
+        transactions.retain(|tx| completed.iter().any(|&(start, end)| (start..=end).contains(&tx.block_number)));
+        transactions.sort_by(|a, b| (a.block_number, a.log_index, &a.hash).cmp(&(b.block_number, b.log_index, &b.hash)));
+        transactions.dedup_by(|a, b| a.hash == b.hash && a.log_index == b.log_index);
+
+        Ok(transactions)
+    }
+
+    pub fn append_backfill_results(&self, progress: &BackfillProgress, transactions: &[WhaleTransaction]) -> ZKWatchResult<()> {
+        let path = self.backfill_path(&progress.network, progress.from, progress.to, "jsonl");
+        let mut file = std::fs::OpenOptions::new().create(true).read(true).append(true).open(path)?;
+
+        // Terminate a torn line left by a crash so the first new record stays readable
+        let mut buffer = Vec::new();
+        let len = file.metadata()?.len();
+        if len > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::Start(len - 1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                buffer.push(b'\n');
+            }
+        }
+        for tx in transactions {
+            serde_json::to_writer(&mut buffer, tx)?;
+            buffer.push(b'\n');
+        }
+        file.write_all(&buffer)?;
+        file.sync_data()?;
+
+        Ok(())
+    }
+
+    fn checkpoint_path(&self, network: &str) -> PathBuf {
+        self.dir.join(format!("{}.checkpoint.json", file_stem(network)))
+    }
+
+    fn backfill_path(&self, network: &str, from: u64, to: u64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.backfill.{}-{}.{}", file_stem(network), from, to, extension))
+    }
+}
+
+fn file_stem(network: &str) -> String {
+    network.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> ZKWatchResult<Option<T>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write through a temporary file so a crash never leaves a half-written checkpoint
+fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> ZKWatchResult<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        serde_json::to_writer_pretty(&mut file, value)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_tracker::ConfirmationStatus;
+    use crate::exchange_flows::ExchangeFlow;
+    use crate::TransactionPattern;
+    use chrono::TimeZone;
+
+    fn transaction(block_number: u64) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:064x}", block_number),
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            from: format!("0x{:040x}", 1),
+            to: format!("0x{:040x}", 2),
+            value: 10u128.pow(21),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(2_000_000.0),
+            confirmation: ConfirmationStatus::Finalized,
+            gas_used: 21_000,
+            block_number,
+            timestamp: Utc.timestamp_opt(1_700_000_000 + block_number as i64 * 12, 0).unwrap(),
+            zk_proof_hash: None,
+            risk_score: 0.2,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    fn scratch_store(name: &str) -> CheckpointStore {
+        let dir = std::env::temp_dir().join(format!("zkwatch-checkpoint-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        CheckpointStore::open(dir).unwrap()
+    }
+
+    #[test]
+    fn remaining_chunks_skip_completed_ones() {
+        let mut progress = BackfillProgress::new("Ethereum", 10, 24, 5);
+        assert_eq!(progress.remaining_chunks(), vec![(10, 14), (15, 19), (20, 24)]);
+
+        progress.completed_chunks.insert(15);
+        assert_eq!(progress.remaining_chunks(), vec![(10, 14), (20, 24)]);
+
+        progress.completed_chunks.extend([10, 20]);
+        assert!(progress.is_complete());
+        assert_eq!(BackfillProgress::new("Ethereum", 1, 3, 0).remaining_chunks(), vec![(1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn checkpoint_rewrite_replaces_the_file_atomically() {
+        let store = scratch_store("rewrite");
+        assert_eq!(store.load("Ethereum").unwrap(), None);
+
+        let mut checkpoint = Checkpoint {
+            network: "Ethereum".to_string(),
+            block_number: 100,
+            block_hash: format!("0x{:064x}", 100),
+            updated_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        };
+        store.save(&checkpoint).unwrap();
+        checkpoint.block_number = 101;
+        checkpoint.block_hash = format!("0x{:064x}", 101);
+        store.save(&checkpoint).unwrap();
+
+        assert_eq!(store.load("Ethereum").unwrap(), Some(checkpoint.clone()));
+        let files: Vec<String> = std::fs::read_dir(&store.dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["ethereum.checkpoint.json".to_string()]);
+
+        // A temporary file left by a crash mid-write does not shadow the last complete checkpoint
+        std::fs::write(store.checkpoint_path("Ethereum").with_extension("tmp"), b"{\"network\":").unwrap();
+        assert_eq!(store.load("Ethereum").unwrap(), Some(checkpoint));
+    }
+
+    #[test]
+    fn backfill_results_drop_torn_lines_and_unfinished_chunks() {
+        let store = scratch_store("torn");
+        let mut progress = BackfillProgress::new("Ethereum", 1, 6, 2);
+
+        store.append_backfill_results(&progress, &[transaction(1), transaction(2)]).unwrap();
+        progress.completed_chunks.insert(1);
+        store.save_backfill(&progress).unwrap();
+
+        // Chunk 3-4 was interrupted: one whale written, a torn line, and no completion record
+        store.append_backfill_results(&progress, &[transaction(3)]).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(store.backfill_path("Ethereum", 1, 6, "jsonl"))
+            .unwrap();
+        file.write_all(b"{\"hash\":\"0x").unwrap();
+        drop(file);
+
+        let mut progress = store.load_backfill("Ethereum", 1, 6).unwrap().unwrap();
+        assert_eq!(progress.remaining_chunks(), vec![(3, 4), (5, 6)]);
+        let blocks: Vec<u64> = store.load_backfill_results(&progress).unwrap().iter().map(|tx| tx.block_number).collect();
+        assert_eq!(blocks, vec![1, 2]);
+
+        // Redoing the chunk appends after the torn line without losing its first record
+        store.append_backfill_results(&progress, &[transaction(3), transaction(4)]).unwrap();
+        progress.completed_chunks.insert(3);
+        let blocks: Vec<u64> = store.load_backfill_results(&progress).unwrap().iter().map(|tx| tx.block_number).collect();
+        assert_eq!(blocks, vec![1, 2, 3, 4]);
+    }
+}
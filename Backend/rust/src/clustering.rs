@@ -0,0 +1,459 @@
+//! Whale entity clustering
+//!
+//! Resolves addresses controlled by one entity with union-find over three
+//! heuristics: reuse of one exchange deposit address, a common gas funder and
+//! repeated round trips between the same two addresses.
+
+use crate::exchange_flows::ExchangeFlow;
+use crate::labels::{LabelCategory, LabelRegistry};
+use crate::WhaleTransaction;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Round trips needed between two addresses before they are linked
+pub const MIN_ROUND_TRIPS: usize = 2;
+
+/// Most addresses a funder may top up before it counts as a service, not an owner
+pub const MAX_FUNDER_RECIPIENTS: usize = 10;
+
+/// Evidence that linked two addresses into one entity
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ClusterHeuristic {
+    /// Both sent funds to the same exchange deposit address
+    DepositAddressReuse,
+    /// Both received their gas from the same unlabeled address
+    CommonGasFunder,
+    /// Funds went back and forth between them repeatedly
+    RoundTrip,
+}
+
+impl ClusterHeuristic {
+    /// Likelihood that a link from this heuristic joins addresses of one entity
+    pub fn confidence(&self) -> f64 {
+        match self {
+            ClusterHeuristic::DepositAddressReuse => 0.9,
+            ClusterHeuristic::CommonGasFunder => 0.6,
+            ClusterHeuristic::RoundTrip => 0.7,
+        }
+    }
+}
+
+impl std::fmt::Display for ClusterHeuristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClusterHeuristic::DepositAddressReuse => write!(f, "deposit address reuse"),
+            ClusterHeuristic::CommonGasFunder => write!(f, "common gas funder"),
+            ClusterHeuristic::RoundTrip => write!(f, "round trips"),
+        }
+    }
+}
+
+/// Small native transfer that paid for an address's gas
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GasFunding {
+    pub network: String,
+    pub chain_id: u64,
+    pub funder: String,
+    pub funded: String,
+    pub value: u128,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Addresses resolved to one entity
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub members: Vec<String>,
+    pub heuristics: Vec<ClusterHeuristic>,
+    pub confidence: f64,
+    /// USD value of the whale transfers touching the entity, each counted once
+    pub total_volume_usd: f64,
+    pub first_activity: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+}
+
+/// Disjoint sets of address indices with path compression and union by rank
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+        }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut current = index;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return;
+        }
+        match self.rank[root_a].cmp(&self.rank[root_b]) {
+            std::cmp::Ordering::Less => self.parent[root_a] = root_b,
+            std::cmp::Ordering::Greater => self.parent[root_b] = root_a,
+            std::cmp::Ordering::Equal => {
+                self.parent[root_b] = root_a;
+                self.rank[root_a] += 1;
+            }
+        }
+    }
+}
+
+/// Entity resolution over whale transfers and the gas fundings of their senders
+pub struct EntityResolver<'a> {
+    labels: &'a LabelRegistry,
+    addresses: Vec<String>,
+    index: HashMap<String, usize>,
+    links: Vec<(usize, usize, ClusterHeuristic)>,
+}
+
+impl<'a> EntityResolver<'a> {
+    pub fn new(labels: &'a LabelRegistry) -> Self {
+        Self {
+            labels,
+            addresses: Vec::new(),
+            index: HashMap::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Entities of at least two addresses, largest first
+    pub fn resolve(mut self, transactions: &[WhaleTransaction], fundings: &[GasFunding]) -> Vec<Entity> {
+        self.link_deposit_address_reuse(transactions);
+        self.link_common_funders(fundings);
+        self.link_round_trips(transactions);
+
+        let mut sets = UnionFind::new(self.addresses.len());
+        for (a, b, _) in &self.links {
+            sets.union(*a, *b);
+        }
+
+        let mut groups: BTreeMap<usize, (BTreeSet<usize>, BTreeSet<ClusterHeuristic>)> = BTreeMap::new();
+        for (a, b, heuristic) in &self.links {
+            let group = groups.entry(sets.find(*a)).or_default();
+            group.0.extend([*a, *b]);
+            group.1.insert(*heuristic);
+        }
+
+        let mut entities: Vec<Entity> = groups.into_values()
+            .filter(|(members, _)| members.len() >= 2)
+            .filter_map(|(members, heuristics)| {
+                let members: Vec<String> = members.into_iter().map(|i| self.addresses[i].clone()).collect();
+                entity(members, heuristics.into_iter().collect(), transactions, fundings)
+            })
+            .collect();
+
+        entities.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then(b.total_volume_usd.total_cmp(&a.total_volume_usd)));
+        entities
+    }
+
+    fn id(&mut self, address: &str) -> usize {
+        let address = address.to_ascii_lowercase();
+        if let Some(index) = self.index.get(&address) {
+            return *index;
+        }
+        self.addresses.push(address.clone());
+        self.index.insert(address, self.addresses.len() - 1);
+        self.addresses.len() - 1
+    }
+
+    /// Labeled contracts and exchange wallets interact with everyone and never join an entity
+    fn is_service(&self, chain_id: u64, address: &str) -> bool {
+        address.is_empty() || self.labels.get_str(chain_id, address).is_some()
+    }
+
+    /// Senders into the same deposit address share one exchange account
+    ///
+    /// Deposit addresses are labeled ones, or unlabeled addresses that behave
+    /// like one: they received funds before sweeping into an exchange, and
+    /// everything they sent went into that one exchange.
+    fn link_deposit_address_reuse(&mut self, transactions: &[WhaleTransaction]) {
+        let mut deposit_addresses: BTreeSet<(u64, String)> = self.sweeping_addresses(transactions);
+        deposit_addresses.extend(transactions.iter()
+            .filter(|t| self.labels.is(t.chain_id, &t.to, LabelCategory::CexDepositWallet))
+            .map(|t| (t.chain_id, t.to.to_ascii_lowercase())));
+
+        let mut senders: BTreeMap<(u64, String), BTreeSet<String>> = BTreeMap::new();
+        for transaction in transactions {
+            let key = (transaction.chain_id, transaction.to.to_ascii_lowercase());
+            if deposit_addresses.contains(&key) && !self.is_service(transaction.chain_id, &transaction.from) {
+                senders.entry(key).or_default().insert(transaction.from.to_ascii_lowercase());
+            }
+        }
+        for senders in senders.values() {
+            self.link_all(senders, ClusterHeuristic::DepositAddressReuse);
+        }
+    }
+
+    /// Unlabeled senders into an exchange that only ever forward received funds to it
+    fn sweeping_addresses(&self, transactions: &[WhaleTransaction]) -> BTreeSet<(u64, String)> {
+        let mut exchanges: BTreeMap<(u64, String), BTreeSet<&str>> = BTreeMap::new();
+        let mut first_sweep: HashMap<(u64, String), DateTime<Utc>> = HashMap::new();
+        let mut other_outflow: BTreeSet<(u64, String)> = BTreeSet::new();
+        for transaction in transactions {
+            let key = (transaction.chain_id, transaction.from.to_ascii_lowercase());
+            match &transaction.exchange_flow {
+                ExchangeFlow::Inflow { exchange } => {
+                    exchanges.entry(key.clone()).or_default().insert(exchange);
+                    let sweep = first_sweep.entry(key).or_insert(transaction.timestamp);
+                    *sweep = (*sweep).min(transaction.timestamp);
+                }
+                _ => {
+                    other_outflow.insert(key);
+                }
+            }
+        }
+
+        let mut received_before_sweep: BTreeSet<(u64, String)> = BTreeSet::new();
+        for transaction in transactions {
+            let key = (transaction.chain_id, transaction.to.to_ascii_lowercase());
+            if first_sweep.get(&key).is_some_and(|sweep| transaction.timestamp <= *sweep) {
+                received_before_sweep.insert(key);
+            }
+        }
+
+        exchanges.into_iter()
+            .filter(|(key, exchanges)| {
+                exchanges.len() == 1
+                    && !other_outflow.contains(key)
+                    && received_before_sweep.contains(key)
+                    && self.labels.get_str(key.0, &key.1).is_none()
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Addresses topped up with gas by the same unlabeled funder
+    ///
+    /// Funders of more than [`MAX_FUNDER_RECIPIENTS`] addresses are faucets,
+    /// bots or unlabeled services and link nobody.
+    fn link_common_funders(&mut self, fundings: &[GasFunding]) {
+        let mut funded: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for funding in fundings {
+            if !self.is_service(funding.chain_id, &funding.funder) && !self.is_service(funding.chain_id, &funding.funded) {
+                funded.entry(funding.funder.to_ascii_lowercase()).or_default().insert(funding.funded.to_ascii_lowercase());
+            }
+        }
+        for funded in funded.values().filter(|funded| funded.len() <= MAX_FUNDER_RECIPIENTS) {
+            self.link_all(funded, ClusterHeuristic::CommonGasFunder);
+        }
+    }
+
+    /// Pairs of unlabeled addresses with at least [`MIN_ROUND_TRIPS`] transfers each way
+    fn link_round_trips(&mut self, transactions: &[WhaleTransaction]) {
+        let mut transfers: HashMap<(String, String), usize> = HashMap::new();
+        for transaction in transactions {
+            if self.is_service(transaction.chain_id, &transaction.from) || self.is_service(transaction.chain_id, &transaction.to) {
+                continue;
+            }
+            let (from, to) = (transaction.from.to_ascii_lowercase(), transaction.to.to_ascii_lowercase());
+            if from != to {
+                *transfers.entry((from, to)).or_default() += 1;
+            }
+        }
+
+        let mut pairs: Vec<(String, String)> = transfers.iter()
+            .filter(|((from, to), count)| from < to && **count >= MIN_ROUND_TRIPS)
+            .filter(|((from, to), _)| transfers.get(&(to.clone(), from.clone())).is_some_and(|back| *back >= MIN_ROUND_TRIPS))
+            .map(|(pair, _)| pair.clone())
+            .collect();
+        pairs.sort();
+        for (a, b) in pairs {
+            let (a, b) = (self.id(&a), self.id(&b));
+            self.links.push((a, b, ClusterHeuristic::RoundTrip));
+        }
+    }
+
+    fn link_all(&mut self, addresses: &BTreeSet<String>, heuristic: ClusterHeuristic) {
+        let mut iter = addresses.iter();
+        let Some(first) = iter.next() else {
+            return;
+        };
+        let first = self.id(first);
+        for address in iter {
+            let other = self.id(address);
+            self.links.push((first, other, heuristic));
+        }
+    }
+}
+
+/// Entity record with the volume and activity window of its members
+fn entity(
+    members: Vec<String>,
+    heuristics: Vec<ClusterHeuristic>,
+    transactions: &[WhaleTransaction],
+    fundings: &[GasFunding],
+) -> Option<Entity> {
+    let is_member = |address: &str| members.iter().any(|m| m.eq_ignore_ascii_case(address));
+
+    let touching: Vec<&WhaleTransaction> = transactions.iter()
+        .filter(|t| is_member(&t.from) || is_member(&t.to))
+        .collect();
+    let times = touching.iter().map(|t| t.timestamp)
+        .chain(fundings.iter().filter(|f| is_member(&f.funded)).map(|f| f.timestamp));
+    let (first_activity, last_activity) = times.fold(None, |range: Option<(DateTime<Utc>, DateTime<Utc>)>, time| match range {
+        Some((first, last)) => Some((first.min(time), last.max(time))),
+        None => Some((time, time)),
+    })?;
+
+    // Independent links: the entity is wrong only if every heuristic that joined it is
+    let confidence = 1.0 - heuristics.iter().map(|h| 1.0 - h.confidence()).product::<f64>();
+
+    Some(Entity {
+        members,
+        heuristics,
+        confidence,
+        total_volume_usd: touching.iter().filter_map(|t| t.volume_usd()).sum(),
+        first_activity,
+        last_activity,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_tracker::ConfirmationStatus;
+    use crate::TransactionPattern;
+
+    fn address(n: u8) -> String {
+        format!("0x{:040x}", n)
+    }
+
+    fn transfer(from: u8, to: u8, minutes: i64, exchange_flow: ExchangeFlow) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:02x}{:02x}{:060x}", from, to, minutes),
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            from: address(from),
+            to: address(to),
+            value: 10u128.pow(20),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(300_000.0),
+            confirmation: ConfirmationStatus::Finalized,
+            gas_used: 21_000,
+            block_number: minutes as u64,
+            timestamp: DateTime::from_timestamp(1_700_000_000 + minutes * 60, 0).unwrap(),
+            zk_proof_hash: None,
+            risk_score: 0.0,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow,
+        }
+    }
+
+    fn inflow() -> ExchangeFlow {
+        ExchangeFlow::Inflow { exchange: "Binance".to_string() }
+    }
+
+    fn funding(funder: u8, funded: u8) -> GasFunding {
+        GasFunding {
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            funder: address(funder),
+            funded: address(funded),
+            value: 10u128.pow(16),
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn union_find_merges_transitively() {
+        let mut sets = UnionFind::new(6);
+        sets.union(0, 1);
+        sets.union(2, 3);
+        sets.union(1, 3);
+        assert_eq!(sets.find(0), sets.find(2));
+        assert_ne!(sets.find(0), sets.find(4));
+        assert_ne!(sets.find(4), sets.find(5));
+        sets.union(4, 4);
+        assert_eq!(sets.find(4), 4);
+    }
+
+    #[test]
+    fn links_senders_into_a_sweeping_deposit_address() {
+        let labels = LabelRegistry::new();
+        let transactions = vec![
+            transfer(1, 9, 0, ExchangeFlow::NonExchange),
+            transfer(2, 9, 1, ExchangeFlow::NonExchange),
+            transfer(9, 100, 2, inflow()),
+        ];
+
+        let entities = EntityResolver::new(&labels).resolve(&transactions, &[]);
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].members, vec![address(1), address(2)]);
+        assert_eq!(entities[0].heuristics, vec![ClusterHeuristic::DepositAddressReuse]);
+    }
+
+    #[test]
+    fn address_that_also_pays_elsewhere_is_not_a_deposit_address() {
+        let labels = LabelRegistry::new();
+        let transactions = vec![
+            transfer(1, 9, 0, ExchangeFlow::NonExchange),
+            transfer(2, 9, 1, ExchangeFlow::NonExchange),
+            transfer(9, 100, 2, inflow()),
+            transfer(9, 50, 3, ExchangeFlow::NonExchange),
+        ];
+
+        assert!(EntityResolver::new(&labels).resolve(&transactions, &[]).is_empty());
+    }
+
+    #[test]
+    fn sender_without_prior_receipts_is_not_a_deposit_address() {
+        let labels = LabelRegistry::new();
+        let transactions = vec![
+            transfer(9, 100, 0, inflow()),
+            transfer(1, 9, 1, ExchangeFlow::NonExchange),
+            transfer(2, 9, 2, ExchangeFlow::NonExchange),
+        ];
+
+        assert!(EntityResolver::new(&labels).resolve(&transactions, &[]).is_empty());
+    }
+
+    #[test]
+    fn busy_funders_link_nobody() {
+        let labels = LabelRegistry::new();
+        let exclusive = vec![funding(7, 1), funding(7, 2)];
+        let entities = EntityResolver::new(&labels).resolve(&[transfer(1, 2, 0, ExchangeFlow::NonExchange)], &exclusive);
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].heuristics, vec![ClusterHeuristic::CommonGasFunder]);
+
+        let busy: Vec<GasFunding> = (1..=MAX_FUNDER_RECIPIENTS as u8 + 1).map(|funded| funding(7, funded)).collect();
+        assert!(EntityResolver::new(&labels).resolve(&[], &busy).is_empty());
+    }
+
+    #[test]
+    fn round_trips_link_both_ends() {
+        let labels = LabelRegistry::new();
+        let transactions = vec![
+            transfer(1, 2, 0, ExchangeFlow::NonExchange),
+            transfer(2, 1, 1, ExchangeFlow::NonExchange),
+            transfer(1, 2, 2, ExchangeFlow::NonExchange),
+            transfer(2, 1, 3, ExchangeFlow::NonExchange),
+        ];
+
+        let entities = EntityResolver::new(&labels).resolve(&transactions, &[]);
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].heuristics, vec![ClusterHeuristic::RoundTrip]);
+        assert!((entities[0].total_volume_usd - 1_200_000.0).abs() < 1e-6);
+
+        assert!(EntityResolver::new(&labels).resolve(&transactions[..3], &[]).is_empty());
+    }
+}
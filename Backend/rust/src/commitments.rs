@@ -0,0 +1,306 @@
+//! Pedersen commitments
+//!
+//! Ristretto Pedersen commitments to transaction amounts, addresses and gas,
+//! with their blinding factors, in a stable `0x`-prefixed hex encoding that
+//! is validated when parsed or deserialized.
+
+use crate::{ZKWatchError, ZKWatchResult};
+use bulletproofs::PedersenGens;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use ethers::types::H160;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Encoded length of commitments and blinding factors
+pub const COMMITMENT_BYTES: usize = 32;
+
+/// Commitment `value·B + blinding·B_blinding` over the default Pedersen generators
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Commitment {
+    compressed: CompressedRistretto,
+    point: RistrettoPoint,
+}
+
+impl Commitment {
+    /// Commit to `value` under `blinding`
+    pub fn new(value: Scalar, blinding: &Blinding) -> Self {
+        Self::from_point(PedersenGens::default().commit(value, blinding.0))
+    }
+
+    /// Commit to `value` under a fresh random blinding
+    pub fn random(value: Scalar) -> (Self, Blinding) {
+        let blinding = Blinding::random();
+        (Self::new(value, &blinding), blinding)
+    }
+
+    pub fn from_point(point: RistrettoPoint) -> Self {
+        Self { compressed: point.compress(), point }
+    }
+
+    /// Decode a compressed Ristretto point, rejecting non-canonical or invalid encodings
+    pub fn from_bytes(bytes: &[u8]) -> ZKWatchResult<Self> {
+        if bytes.len() != COMMITMENT_BYTES {
+            return Err(ZKWatchError::InvalidCommitment(format!(
+                "expected {} bytes, got {}",
+                COMMITMENT_BYTES,
+                bytes.len()
+            )));
+        }
+        let compressed = CompressedRistretto::from_slice(bytes)
+            .map_err(|e| ZKWatchError::InvalidCommitment(e.to_string()))?;
+        let point = compressed
+            .decompress()
+            .ok_or_else(|| ZKWatchError::InvalidCommitment("not a valid Ristretto point".to_string()))?;
+        Ok(Self { compressed, point })
+    }
+
+    pub fn to_bytes(&self) -> [u8; COMMITMENT_BYTES] {
+        self.compressed.to_bytes()
+    }
+
+    pub fn compressed(&self) -> &CompressedRistretto {
+        &self.compressed
+    }
+
+    pub fn point(&self) -> RistrettoPoint {
+        self.point
+    }
+
+    /// Whether `value` and `blinding` open this commitment
+    pub fn opens_to(&self, value: Scalar, blinding: &Blinding) -> bool {
+        Self::new(value, blinding) == *self
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex_encode(self.compressed.as_bytes()))
+    }
+}
+
+impl fmt::Debug for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Commitment({})", self)
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = ZKWatchError;
+
+    fn from_str(s: &str) -> ZKWatchResult<Self> {
+        Self::from_bytes(&hex_decode(s).ok_or_else(|| ZKWatchError::InvalidCommitment(format!("not hex: {}", s)))?)
+    }
+}
+
+impl Serialize for Commitment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Commitment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Blinding factor of a commitment; whoever holds it can open the commitment
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Blinding(Scalar);
+
+impl Blinding {
+    pub fn random() -> Self {
+        Self(Scalar::random(&mut rand::rngs::OsRng))
+    }
+
+    /// Decode a canonical little-endian scalar
+    pub fn from_bytes(bytes: &[u8]) -> ZKWatchResult<Self> {
+        let bytes: [u8; COMMITMENT_BYTES] = bytes
+            .try_into()
+            .map_err(|_| ZKWatchError::InvalidCommitment(format!("blinding must be {} bytes", COMMITMENT_BYTES)))?;
+        Option::from(Scalar::from_canonical_bytes(bytes))
+            .map(Self)
+            .ok_or_else(|| ZKWatchError::InvalidCommitment("blinding is not a canonical scalar".to_string()))
+    }
+
+    pub fn to_bytes(&self) -> [u8; COMMITMENT_BYTES] {
+        self.0.to_bytes()
+    }
+
+    pub fn scalar(&self) -> &Scalar {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Blinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Blinding(..)")
+    }
+}
+
+impl Serialize for Blinding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("0x{}", hex_encode(&self.to_bytes())))
+    }
+}
+
+impl<'de> Deserialize<'de> for Blinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = hex_decode(&encoded).ok_or_else(|| serde::de::Error::custom("blinding is not hex"))?;
+        Self::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Committed value and blinding that open a commitment
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Opening {
+    #[serde(with = "scalar_hex")]
+    pub value: Scalar,
+    pub blinding: Blinding,
+}
+
+impl Opening {
+    pub fn commitment(&self) -> Commitment {
+        Commitment::new(self.value, &self.blinding)
+    }
+}
+
+/// Scalar of an amount or gas quantity
+pub fn value_scalar(value: u64) -> Scalar {
+    Scalar::from(value)
+}
+
+/// Scalar of a 20-byte address, which fits a scalar without reduction
+pub fn address_scalar(address: &str) -> ZKWatchResult<Scalar> {
+    let address = H160::from_str(address)
+        .map_err(|e| ZKWatchError::InvalidCommitment(format!("invalid address {}: {}", address, e)))?;
+    let mut bytes = [0u8; COMMITMENT_BYTES];
+    bytes[..20].copy_from_slice(address.as_bytes());
+    Ok(Scalar::from_bytes_mod_order(bytes))
+}
+
+mod scalar_hex {
+    use super::{hex_decode, hex_encode, Scalar, COMMITMENT_BYTES};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(scalar: &Scalar, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("0x{}", hex_encode(scalar.as_bytes())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Scalar, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes: [u8; COMMITMENT_BYTES] = hex_decode(&encoded)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| de::Error::custom("scalar must be 32 hex-encoded bytes"))?;
+        Option::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(|| de::Error::custom("scalar is not canonical"))
+    }
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    // `from_str_radix` alone would also take a sign, as in "+1"
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian group order ℓ, the smallest non-canonical scalar encoding
+    fn group_order() -> [u8; COMMITMENT_BYTES] {
+        let mut bytes = [0u8; COMMITMENT_BYTES];
+        bytes[..16].copy_from_slice(&[0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14]);
+        bytes[31] = 0x10;
+        bytes
+    }
+
+    #[test]
+    fn invalid_point_encodings_are_rejected() {
+        let (commitment, _) = Commitment::random(value_scalar(42));
+        assert_eq!(Commitment::from_bytes(&commitment.to_bytes()).unwrap(), commitment);
+
+        // A field element of p or above, a negative field element, and a valid length that decodes to no point
+        let mut negative = [0u8; COMMITMENT_BYTES];
+        negative[0] = 1;
+        let mut off_curve = [0u8; COMMITMENT_BYTES];
+        off_curve[0] = 2;
+        for bytes in [[0xff; COMMITMENT_BYTES], negative, off_curve] {
+            assert!(matches!(Commitment::from_bytes(&bytes), Err(ZKWatchError::InvalidCommitment(_))), "{:02x?}", bytes);
+        }
+        assert!(Commitment::from_bytes(&commitment.to_bytes()[..31]).is_err());
+        assert!(Commitment::from_bytes(&[0u8; 33]).is_err());
+    }
+
+    #[test]
+    fn non_canonical_scalars_are_rejected() {
+        assert_eq!(Scalar::from_bytes_mod_order(group_order()), Scalar::ZERO);
+        assert!(Blinding::from_bytes(&group_order()).is_err());
+        assert!(Blinding::from_bytes(&[0xff; COMMITMENT_BYTES]).is_err());
+        assert!(Blinding::from_bytes(&[1u8; 16]).is_err());
+
+        let mut below_order = group_order();
+        below_order[0] -= 1;
+        assert!(Blinding::from_bytes(&below_order).is_ok());
+
+        let opening = format!(r#"{{"value":"0x{}","blinding":"0x{}"}}"#, hex_encode(&group_order()), hex_encode(&[0u8; 32]));
+        assert!(serde_json::from_str::<Opening>(&opening).unwrap_err().to_string().contains("not canonical"));
+    }
+
+    #[test]
+    fn hex_decoding_requires_whole_hex_bytes() {
+        assert_eq!(hex_decode("0x00ff10"), Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(hex_decode("00FF"), Some(vec![0x00, 0xff]));
+        assert_eq!(hex_decode("0x"), Some(Vec::new()));
+        for malformed in ["0xabc", "f", "0xzz", "0x0g", "0x+1", "0xéé"] {
+            assert_eq!(hex_decode(malformed), None, "{}", malformed);
+        }
+        assert!(matches!("0xabc".parse::<Commitment>(), Err(ZKWatchError::InvalidCommitment(_))));
+    }
+
+    #[test]
+    fn commitments_blindings_and_openings_round_trip_through_serde() {
+        let opening = Opening { value: value_scalar(1_000_000), blinding: Blinding::random() };
+        let commitment = opening.commitment();
+
+        let json = serde_json::to_string(&commitment).unwrap();
+        assert_eq!(json, format!("\"{}\"", commitment));
+        assert_eq!(serde_json::from_str::<Commitment>(&json).unwrap(), commitment);
+
+        let blinding: Blinding = serde_json::from_str(&serde_json::to_string(&opening.blinding).unwrap()).unwrap();
+        assert_eq!(blinding, opening.blinding);
+
+        let decoded: Opening = serde_json::from_str(&serde_json::to_string(&opening).unwrap()).unwrap();
+        assert_eq!((decoded.value, decoded.blinding), (opening.value, opening.blinding));
+        assert!(commitment.opens_to(decoded.value, &decoded.blinding));
+        assert!(!commitment.opens_to(value_scalar(1_000_001), &decoded.blinding));
+
+        assert!(serde_json::from_str::<Commitment>("\"0x1234\"").is_err());
+        assert!(serde_json::from_str::<Blinding>("\"blinding\"").is_err());
+    }
+
+    #[test]
+    fn address_scalars_need_well_formed_addresses() {
+        let address = "0x28C6c06298d514Db089934071355E5743bf21d60";
+        let scalar = address_scalar(address).unwrap();
+        assert_eq!(address_scalar(&address.to_lowercase()).unwrap(), scalar);
+        assert_eq!(&scalar.as_bytes()[..20], H160::from_str(address).unwrap().as_bytes());
+        assert!(scalar.as_bytes()[20..].iter().all(|b| *b == 0));
+
+        for malformed in ["", "0x", "0x1234", "0x28C6c06298d514Db089934071355E5743bf21d6", "0x28C6c06298d514Db089934071355E5743bf21d6000", "0xZZC6c06298d514Db089934071355E5743bf21d60"] {
+            assert!(matches!(address_scalar(malformed), Err(ZKWatchError::InvalidCommitment(_))), "{}", malformed);
+        }
+    }
+}
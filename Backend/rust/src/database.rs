@@ -0,0 +1,505 @@
+//! Persistent whale database
+//!
+//! Stores scanned whale transactions and detected patterns in an embedded
+//! SQLite file, with versioned schema migrations and indexed lookups by
+//! address, network, block range, pattern type and time.
+
+use crate::chain_tracker::ConfirmationStatus;
+use crate::whale_tracker::{WhalePattern, WhalePatternType};
+use crate::{TransactionPattern, WhaleTransaction, ZKWatchError, ZKWatchResult};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Schema changes in order; a database records the highest version applied
+const MIGRATIONS: &[(u32, &str)] = &[
+    (1, "
+        CREATE TABLE whale_transactions (
+            network TEXT NOT NULL,
+            chain_id INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            -- -1 for native transfers, which have no log
+            log_index INTEGER NOT NULL,
+            block_number INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL,
+            from_address TEXT NOT NULL,
+            to_address TEXT NOT NULL,
+            pattern_type TEXT NOT NULL,
+            usd_value REAL,
+            data TEXT NOT NULL,
+            PRIMARY KEY (network, hash, log_index)
+        );
+        CREATE INDEX whale_transactions_from ON whale_transactions (from_address, timestamp);
+        CREATE INDEX whale_transactions_to ON whale_transactions (to_address, timestamp);
+        CREATE INDEX whale_transactions_block ON whale_transactions (network, block_number);
+        CREATE INDEX whale_transactions_pattern ON whale_transactions (pattern_type, timestamp);
+        CREATE INDEX whale_transactions_time ON whale_transactions (timestamp);
+
+        CREATE TABLE whale_patterns (
+            pattern_id TEXT PRIMARY KEY,
+            pattern_type TEXT NOT NULL,
+            risk_level TEXT NOT NULL,
+            time_detected INTEGER NOT NULL,
+            data TEXT NOT NULL
+        );
+        CREATE INDEX whale_patterns_type ON whale_patterns (pattern_type, time_detected);
+        CREATE INDEX whale_patterns_time ON whale_patterns (time_detected);
+
+        CREATE TABLE whale_pattern_addresses (
+            pattern_id TEXT NOT NULL REFERENCES whale_patterns (pattern_id) ON DELETE CASCADE,
+            address TEXT NOT NULL,
+            PRIMARY KEY (pattern_id, address)
+        );
+        CREATE INDEX whale_pattern_addresses_address ON whale_pattern_addresses (address);
+    "),
+];
+
+/// Filter for stored whale transactions; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct WhaleQuery {
+    /// Sender or recipient
+    pub address: Option<String>,
+    pub network: Option<String>,
+    /// Inclusive block range, meaningful together with `network`
+    pub blocks: Option<(u64, u64)>,
+    pub pattern_type: Option<TransactionPattern>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl WhaleQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn address(mut self, address: &str) -> Self {
+        self.address = Some(address.to_ascii_lowercase());
+        self
+    }
+
+    pub fn network(mut self, network: &str) -> Self {
+        self.network = Some(network.to_string());
+        self
+    }
+
+    pub fn blocks(mut self, from_block: u64, to_block: u64) -> Self {
+        self.blocks = Some((from_block, to_block));
+        self
+    }
+
+    pub fn pattern_type(mut self, pattern_type: TransactionPattern) -> Self {
+        self.pattern_type = Some(pattern_type);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Whale transactions and patterns in a SQLite database
+pub struct WhaleDatabase {
+    connection: Mutex<Connection>,
+}
+
+impl WhaleDatabase {
+    /// Open or create a database file and bring its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Database that lives only as long as this value
+    pub fn open_in_memory() -> ZKWatchResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> ZKWatchResult<Self> {
+        connection.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Highest migration applied
+    pub fn schema_version(&self) -> ZKWatchResult<u32> {
+        schema_version(&*self.connection()?)
+    }
+
+    /// Insert transactions, replacing stored copies of the same transfer
+    pub fn insert_transactions(&self, transactions: &[WhaleTransaction]) -> ZKWatchResult<usize> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction()?;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT OR REPLACE INTO whale_transactions
+                    (network, chain_id, hash, log_index, block_number, timestamp, from_address, to_address, pattern_type, usd_value, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+            for transaction in transactions {
+                statement.execute(params![
+                    transaction.network,
+                    transaction.chain_id as i64,
+                    transaction.hash.to_ascii_lowercase(),
+                    transaction.log_index.map_or(-1, |i| i as i64),
+                    transaction.block_number as i64,
+                    transaction.timestamp.timestamp(),
+                    transaction.from.to_ascii_lowercase(),
+                    transaction.to.to_ascii_lowercase(),
+                    variant_name(&transaction.pattern_type)?,
+                    transaction.usd_value,
+                    serde_json::to_string(transaction)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(transactions.len())
+    }
+
+    /// Delete a transaction whose block was reorganized away
+    pub fn remove_transaction(&self, network: &str, hash: &str, log_index: Option<u64>) -> ZKWatchResult<bool> {
+        let removed = self.connection()?.execute(
+            "DELETE FROM whale_transactions WHERE network = ?1 AND hash = ?2 AND log_index = ?3",
+            params![network, hash.to_ascii_lowercase(), log_index.map_or(-1, |i| i as i64)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Record a new confirmation stage of a stored transaction
+    pub fn update_confirmation(
+        &self,
+        network: &str,
+        hash: &str,
+        log_index: Option<u64>,
+        status: ConfirmationStatus,
+    ) -> ZKWatchResult<bool> {
+        let connection = self.connection()?;
+        let key = params![network, hash.to_ascii_lowercase(), log_index.map_or(-1, |i| i as i64)];
+        let data: Option<String> = connection.query_row(
+            "SELECT data FROM whale_transactions WHERE network = ?1 AND hash = ?2 AND log_index = ?3",
+            key,
+            |row| row.get(0),
+        ).optional()?;
+        let Some(data) = data else {
+            return Ok(false);
+        };
+
+        let mut transaction: WhaleTransaction = serde_json::from_str(&data)?;
+        transaction.confirmation = status;
+        connection.execute(
+            "UPDATE whale_transactions SET data = ?4 WHERE network = ?1 AND hash = ?2 AND log_index = ?3",
+            params![network, hash.to_ascii_lowercase(), log_index.map_or(-1, |i| i as i64), serde_json::to_string(&transaction)?],
+        )?;
+        Ok(true)
+    }
+
+    /// Insert patterns, replacing stored patterns with the same id
+    pub fn insert_patterns(&self, patterns: &[WhalePattern]) -> ZKWatchResult<usize> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction()?;
+        for pattern in patterns {
+            tx.execute("DELETE FROM whale_pattern_addresses WHERE pattern_id = ?1", params![pattern.pattern_id])?;
+            tx.execute(
+                "INSERT OR REPLACE INTO whale_patterns (pattern_id, pattern_type, risk_level, time_detected, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    pattern.pattern_id,
+                    variant_name(&pattern.pattern_type)?,
+                    variant_name(&pattern.risk_level)?,
+                    pattern.time_detected.timestamp(),
+                    serde_json::to_string(pattern)?,
+                ],
+            )?;
+            for address in &pattern.involved_addresses {
+                tx.execute(
+                    "INSERT OR IGNORE INTO whale_pattern_addresses (pattern_id, address) VALUES (?1, ?2)",
+                    params![pattern.pattern_id, address.to_ascii_lowercase()],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(patterns.len())
+    }
+
+    /// Transactions matching a query, newest first
+    pub fn transactions(&self, query: &WhaleQuery) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        if let Some(address) = &query.address {
+            values.push(Value::Text(address.to_ascii_lowercase()));
+            conditions.push(format!("(from_address = ?{0} OR to_address = ?{0})", values.len()));
+        }
+        if let Some(network) = &query.network {
+            values.push(Value::Text(network.clone()));
+            conditions.push(format!("network = ?{}", values.len()));
+        }
+        if let Some((from_block, to_block)) = query.blocks {
+            values.push(Value::Integer(from_block as i64));
+            values.push(Value::Integer(to_block as i64));
+            conditions.push(format!("block_number BETWEEN ?{} AND ?{}", values.len() - 1, values.len()));
+        }
+        if let Some(pattern_type) = &query.pattern_type {
+            values.push(Value::Text(variant_name(pattern_type)?));
+            conditions.push(format!("pattern_type = ?{}", values.len()));
+        }
+        if let Some(since) = query.since {
+            values.push(Value::Integer(since.timestamp()));
+            conditions.push(format!("timestamp >= ?{}", values.len()));
+        }
+        if let Some(until) = query.until {
+            values.push(Value::Integer(until.timestamp()));
+            conditions.push(format!("timestamp <= ?{}", values.len()));
+        }
+
+        let mut sql = "SELECT data FROM whale_transactions".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY timestamp DESC, block_number DESC, log_index DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values.iter()), |row| row.get::<_, String>(0))?;
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Patterns detected in a time window, optionally of one type, newest first
+    pub fn patterns(
+        &self,
+        pattern_type: Option<&WhalePatternType>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> ZKWatchResult<Vec<WhalePattern>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT data FROM whale_patterns
+             WHERE (?1 IS NULL OR pattern_type = ?1) AND time_detected BETWEEN ?2 AND ?3
+             ORDER BY time_detected DESC",
+        )?;
+        let pattern_type = pattern_type.map(variant_name).transpose()?;
+        let rows = statement.query_map(params![pattern_type, since.timestamp(), until.timestamp()], |row| row.get::<_, String>(0))?;
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Patterns an address was involved in, newest first
+    pub fn patterns_involving(&self, address: &str) -> ZKWatchResult<Vec<WhalePattern>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT p.data FROM whale_patterns p
+             JOIN whale_pattern_addresses a ON a.pattern_id = p.pattern_id
+             WHERE a.address = ?1
+             ORDER BY p.time_detected DESC",
+        )?;
+        let rows = statement.query_map(params![address.to_ascii_lowercase()], |row| row.get::<_, String>(0))?;
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Transactions of the last `days` days
+    pub async fn get_historical_data(&self, days: i64) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        self.transactions(&WhaleQuery::new().since(Utc::now() - Duration::days(days)))
+    }
+
+    fn connection(&self) -> ZKWatchResult<std::sync::MutexGuard<'_, Connection>> {
+        self.connection.lock()
+            .map_err(|_| ZKWatchError::Analytics("Whale database lock poisoned".to_string()))
+    }
+}
+
+/// Apply every migration newer than the database's version, each in its own transaction
+fn migrate(connection: &mut Connection) -> ZKWatchResult<()> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        )",
+    )?;
+    let current = schema_version(connection)?;
+    if let Some((latest, _)) = MIGRATIONS.last() {
+        if current > *latest {
+            return Err(ZKWatchError::Analytics(format!(
+                "Whale database schema version {} is newer than supported version {}",
+                current, latest,
+            )));
+        }
+    }
+
+    for (version, sql) in MIGRATIONS.iter().filter(|(version, _)| *version > current) {
+        let tx = connection.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, applied_at) VALUES (?1, ?2)",
+            params![*version, Utc::now().timestamp()],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn schema_version(connection: &Connection) -> ZKWatchResult<u32> {
+    let version: Option<u32> = connection.query_row("SELECT MAX(version) FROM schema_migrations", [], |row| row.get(0))?;
+    Ok(version.unwrap_or_default())
+}
+
+/// Name of a unit enum variant as serialized, used for indexed columns
+fn variant_name<T: Serialize>(value: &T) -> ZKWatchResult<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Ok(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange_flows::ExchangeFlow;
+
+    const WHALE: &str = "0x00000000000000000000000000000000000000AA";
+    const OTHER: &str = "0x00000000000000000000000000000000000000bb";
+
+    fn transfer(network: &str, block_number: u64, pattern_type: TransactionPattern, timestamp: DateTime<Utc>) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:062x}{:02x}", block_number, network.len()),
+            network: network.to_string(),
+            chain_id: 1,
+            from: WHALE.to_string(),
+            to: format!("0x{:040x}", block_number),
+            value: 10u128.pow(21),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(2_000_000.0),
+            confirmation: ConfirmationStatus::Pending,
+            gas_used: 21_000,
+            block_number,
+            timestamp,
+            zk_proof_hash: None,
+            risk_score: 0.0,
+            pattern_type,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    fn at(hour: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + hour * 3_600, 0).unwrap()
+    }
+
+    fn blocks(transactions: Vec<WhaleTransaction>) -> Vec<(String, u64)> {
+        transactions.into_iter().map(|t| (t.network, t.block_number)).collect()
+    }
+
+    /// Database with Ethereum blocks 1-4 an hour apart, block 3 a flash loan, and Arbitrum block 2
+    fn seeded() -> WhaleDatabase {
+        let database = WhaleDatabase::open_in_memory().unwrap();
+        let mut transactions: Vec<WhaleTransaction> = (1..=4)
+            .map(|number| {
+                let pattern = if number == 3 { TransactionPattern::FlashLoan } else { TransactionPattern::LargeTransaction };
+                transfer("Ethereum", number, pattern, at(number as i64))
+            })
+            .collect();
+        let mut bridged = transfer("Arbitrum", 2, TransactionPattern::CrossChainBridge, at(2));
+        bridged.from = OTHER.to_string();
+        transactions.push(bridged);
+        assert_eq!(database.insert_transactions(&transactions).unwrap(), 5);
+        database
+    }
+
+    #[test]
+    fn migrations_apply_once_and_reject_newer_schemas() {
+        let dir = std::env::temp_dir().join(format!("zkwatch-database-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("whales.db");
+
+        let database = WhaleDatabase::open(&path).unwrap();
+        assert_eq!(database.schema_version().unwrap(), MIGRATIONS.last().unwrap().0);
+        database.insert_transactions(&[transfer("Ethereum", 1, TransactionPattern::LargeTransaction, at(1))]).unwrap();
+        drop(database);
+
+        // Re-opening at the current version keeps the data and applies nothing again
+        let database = WhaleDatabase::open(&path).unwrap();
+        assert_eq!(database.transactions(&WhaleQuery::new()).unwrap().len(), 1);
+        let applied: u32 = database.connection().unwrap()
+            .query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(applied as usize, MIGRATIONS.len());
+        drop(database);
+
+        let connection = Connection::open(&path).unwrap();
+        connection.execute("INSERT INTO schema_migrations (version, applied_at) VALUES (99, 0)", []).unwrap();
+        drop(connection);
+        let error = WhaleDatabase::open(&path).err().unwrap();
+        assert!(error.to_string().contains("newer than supported"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn queries_filter_by_each_field() {
+        let database = seeded();
+        let query = |query: WhaleQuery| blocks(database.transactions(&query).unwrap());
+        let ethereum = |numbers: &[u64]| numbers.iter().map(|n| ("Ethereum".to_string(), *n)).collect::<Vec<_>>();
+
+        // Newest first, Arbitrum's block shares its hour with Ethereum block 2
+        assert_eq!(query(WhaleQuery::new()).len(), 5);
+        assert_eq!(query(WhaleQuery::new().address(OTHER)), vec![("Arbitrum".to_string(), 2)]);
+        assert_eq!(query(WhaleQuery::new().address(&format!("0x{:040x}", 3))), ethereum(&[3]));
+        assert_eq!(query(WhaleQuery::new().address(&WHALE.to_lowercase())), ethereum(&[4, 3, 2, 1]));
+        assert_eq!(query(WhaleQuery::new().network("Ethereum").blocks(2, 3)), ethereum(&[3, 2]));
+        assert_eq!(query(WhaleQuery::new().network("Arbitrum")), vec![("Arbitrum".to_string(), 2)]);
+        assert_eq!(query(WhaleQuery::new().pattern_type(TransactionPattern::FlashLoan)), ethereum(&[3]));
+        assert_eq!(query(WhaleQuery::new().network("Ethereum").since(at(2)).until(at(3))), ethereum(&[3, 2]));
+        assert_eq!(query(WhaleQuery::new().network("Ethereum").limit(2)), ethereum(&[4, 3]));
+        assert!(query(WhaleQuery::new().network("Optimism")).is_empty());
+    }
+
+    #[test]
+    fn reorged_transactions_are_removed_and_confirmations_updated() {
+        let database = seeded();
+        let stored = transfer("Ethereum", 2, TransactionPattern::LargeTransaction, at(2));
+
+        assert!(database.update_confirmation("Ethereum", &stored.hash.to_uppercase().replacen("0X", "0x", 1), None, ConfirmationStatus::Safe).unwrap());
+        let updated = database.transactions(&WhaleQuery::new().network("Ethereum").blocks(2, 2)).unwrap();
+        assert_eq!(updated[0].confirmation, ConfirmationStatus::Safe);
+        // The same hash on another network or with a log index is a different transfer
+        assert!(!database.update_confirmation("Ethereum", &stored.hash, Some(0), ConfirmationStatus::Safe).unwrap());
+
+        assert!(database.remove_transaction("Ethereum", &stored.hash, None).unwrap());
+        assert!(!database.remove_transaction("Ethereum", &stored.hash, None).unwrap());
+        assert_eq!(blocks(database.transactions(&WhaleQuery::new().blocks(2, 2)).unwrap()), vec![("Arbitrum".to_string(), 2)]);
+        assert!(!database.update_confirmation("Ethereum", &stored.hash, None, ConfirmationStatus::Finalized).unwrap());
+    }
+
+    #[tokio::test]
+    async fn historical_data_returns_recent_rows_as_stored() {
+        let database = WhaleDatabase::open_in_memory().unwrap();
+        let recent = transfer("Ethereum", 10, TransactionPattern::LargeTransaction, Utc::now() - Duration::hours(1));
+        let old = transfer("Ethereum", 9, TransactionPattern::LargeTransaction, Utc::now() - Duration::days(10));
+        database.insert_transactions(&[recent.clone(), old]).unwrap();
+
+        let history = database.get_historical_data(7).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(serde_json::to_string(&history[0]).unwrap(), serde_json::to_string(&recent).unwrap());
+    }
+}
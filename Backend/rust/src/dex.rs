@@ -0,0 +1,391 @@
+//! DEX swap decoding
+//!
+//! Decodes Uniswap V2/V3 style `Swap`, Curve `TokenExchange` and Balancer Vault
+//! `Swap` logs into directional swaps, using pools' `Sync` events and the
+//! surrounding ERC-20 transfers to recover reserves and tokens without contract calls.
+
+use crate::tokens;
+use ethers::types::{Log, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// keccak256("Swap(address,uint256,uint256,uint256,uint256,address)")
+pub const UNISWAP_V2_SWAP_TOPIC: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+/// keccak256("Sync(uint112,uint112)")
+pub const UNISWAP_V2_SYNC_TOPIC: &str = "0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1";
+/// keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)")
+pub const UNISWAP_V3_SWAP_TOPIC: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+/// keccak256("TokenExchange(address,int128,uint256,int128,uint256)")
+pub const CURVE_TOKEN_EXCHANGE_TOPIC: &str = "0x8b3e96f2b889fa771c53c981b40daf005f63f637f1869f707052d15a3dd97140";
+/// keccak256("TokenExchangeUnderlying(address,int128,uint256,int128,uint256)")
+pub const CURVE_TOKEN_EXCHANGE_UNDERLYING_TOPIC: &str = "0xd013ca23e77a65003c2c659c5442c00c805371b7fc1ebd4c206c41d1536bd90b";
+/// keccak256("TokenExchange(address,uint256,uint256,uint256,uint256)"), emitted by Curve crypto pools
+pub const CURVE_CRYPTO_TOKEN_EXCHANGE_TOPIC: &str = "0xb2e76ae99761dc136e598d4a629bb347eccb9532a5f8bbd72e18467c3c34cc98";
+/// keccak256("Swap(bytes32,address,address,uint256,uint256)")
+pub const BALANCER_SWAP_TOPIC: &str = "0x2170c741c41531aec20e7c107c24eecfdd15e69c9bb0a8dd37b1840b9e0b207b";
+
+/// Pool design a swap was decoded from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DexProtocol {
+    /// Constant-product pools (Uniswap V2, Sushiswap and forks)
+    UniswapV2,
+    /// Concentrated-liquidity pools (Uniswap V3 and forks)
+    UniswapV3,
+    /// Curve stable and crypto pools
+    Curve,
+    /// Pools settled through the Balancer V2 Vault
+    Balancer,
+}
+
+impl std::fmt::Display for DexProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DexProtocol::UniswapV2 => write!(f, "Uniswap V2"),
+            DexProtocol::UniswapV3 => write!(f, "Uniswap V3"),
+            DexProtocol::Curve => write!(f, "Curve"),
+            DexProtocol::Balancer => write!(f, "Balancer V2"),
+        }
+    }
+}
+
+/// Directional swap against a single pool
+#[derive(Debug, Clone)]
+pub struct Swap {
+    pub protocol: DexProtocol,
+    pub pool: H160,
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    pub log_index: u64,
+    /// Whether token0 was sold for token1 (for Curve, the lower coin index;
+    /// for Balancer, the lower token address)
+    pub zero_for_one: bool,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// Token paid into the pool, inferred from the transaction's transfers
+    pub token_in: Option<H160>,
+    /// Token paid out by the pool, inferred from the transaction's transfers
+    pub token_out: Option<H160>,
+    /// Pool reserves (token0, token1) right before the swap, constant-product pools only
+    pub reserves_before: Option<(U256, U256)>,
+    /// Pool price after the swap as Q64.96 sqrt(token1/token0), concentrated-liquidity pools only
+    pub sqrt_price_after: Option<U256>,
+}
+
+pub fn uniswap_v2_swap_topic() -> H256 {
+    H256::from_str(UNISWAP_V2_SWAP_TOPIC).expect("valid Swap topic")
+}
+
+pub fn uniswap_v2_sync_topic() -> H256 {
+    H256::from_str(UNISWAP_V2_SYNC_TOPIC).expect("valid Sync topic")
+}
+
+pub fn uniswap_v3_swap_topic() -> H256 {
+    H256::from_str(UNISWAP_V3_SWAP_TOPIC).expect("valid Swap topic")
+}
+
+pub fn curve_exchange_topics() -> [H256; 3] {
+    [CURVE_TOKEN_EXCHANGE_TOPIC, CURVE_TOKEN_EXCHANGE_UNDERLYING_TOPIC, CURVE_CRYPTO_TOKEN_EXCHANGE_TOPIC]
+        .map(|topic| H256::from_str(topic).expect("valid TokenExchange topic"))
+}
+
+pub fn balancer_swap_topic() -> H256 {
+    H256::from_str(BALANCER_SWAP_TOPIC).expect("valid Swap topic")
+}
+
+/// Topics to request from a node to decode swaps with [`decode_swaps`]
+pub fn swap_related_topics() -> Vec<H256> {
+    let mut topics = vec![uniswap_v2_swap_topic(), uniswap_v2_sync_topic(), uniswap_v3_swap_topic(), balancer_swap_topic()];
+    topics.extend(curve_exchange_topics());
+    topics.push(tokens::transfer_topic());
+    topics
+}
+
+/// Decode every swap in a set of logs, ordered by transaction and log index
+///
+/// `logs` should contain the pools' `Sync` events and the ERC-20 `Transfer`
+/// logs of the same transactions so reserves and tokens can be recovered.
+pub fn decode_swaps(logs: &[Log]) -> Vec<Swap> {
+    let mut logs: Vec<&Log> = logs.iter()
+        .filter(|log| log.transaction_hash.is_some() && log.log_index.is_some())
+        .collect();
+    logs.sort_by_key(|log| (log.transaction_index.map(|i| i.as_u64()), log.log_index.map(|i| i.as_u64())));
+
+    let mut transfers: HashMap<H256, Vec<tokens::Erc20Transfer>> = HashMap::new();
+    for log in &logs {
+        if let Some(transfer) = tokens::decode_transfer(log) {
+            transfers.entry(log.transaction_hash.unwrap_or_default()).or_default().push(transfer);
+        }
+    }
+
+    // A V2 pool emits `Sync` with its post-swap reserves right before `Swap`
+    let mut last_sync: HashMap<H160, (U256, U256)> = HashMap::new();
+    let mut swaps = Vec::new();
+
+    for log in logs {
+        let topic = match log.topics.first() {
+            Some(topic) => *topic,
+            None => continue,
+        };
+
+        if topic == uniswap_v2_sync_topic() && log.data.len() == 64 {
+            last_sync.insert(log.address, (word(&log.data, 0), word(&log.data, 1)));
+            continue;
+        }
+
+        let decoded = if topic == uniswap_v2_swap_topic() && log.topics.len() == 3 && log.data.len() == 128 {
+            decode_v2_swap(log, last_sync.get(&log.address).copied())
+        } else if topic == uniswap_v3_swap_topic() && log.topics.len() == 3 && log.data.len() == 160 {
+            decode_v3_swap(log)
+        } else if curve_exchange_topics().contains(&topic) && log.topics.len() == 2 && log.data.len() == 128 {
+            decode_curve_exchange(log)
+        } else if topic == balancer_swap_topic() && log.topics.len() == 4 && log.data.len() == 64 {
+            decode_balancer_swap(log)
+        } else {
+            None
+        };
+
+        if let Some(mut swap) = decoded {
+            // Balancer names its tokens; the Vault, not the pool, holds them
+            if swap.protocol != DexProtocol::Balancer {
+                let tx_transfers = transfers.get(&swap.transaction_hash).map(Vec::as_slice).unwrap_or_default();
+                swap.token_in = infer_token(tx_transfers, |t| t.to == swap.pool, swap.amount_in, swap.log_index);
+                swap.token_out = infer_token(tx_transfers, |t| t.from == swap.pool, swap.amount_out, swap.log_index);
+            }
+            swaps.push(swap);
+        }
+    }
+
+    swaps
+}
+
+/// Output of a constant-product swap with the standard 0.3% fee
+pub fn constant_product_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+    let amount_in_with_fee = amount_in.saturating_mul(U256::from(997));
+    let denominator = reserve_in.saturating_mul(U256::from(1000)).saturating_add(amount_in_with_fee);
+    if denominator.is_zero() {
+        return U256::zero();
+    }
+    amount_in_with_fee.saturating_mul(reserve_out) / denominator
+}
+
+/// Spot price of token0 in token1 units from a Q64.96 square-root price
+pub fn sqrt_price_to_price(sqrt_price_x96: U256) -> f64 {
+    let sqrt_price = u256_to_f64(sqrt_price_x96) / 2f64.powi(96);
+    sqrt_price * sqrt_price
+}
+
+pub fn u256_to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |acc, limb| acc * 2f64.powi(64) + *limb as f64)
+}
+
+fn decode_v2_swap(log: &Log, reserves_after: Option<(U256, U256)>) -> Option<Swap> {
+    let amount0_in = word(&log.data, 0);
+    let amount1_in = word(&log.data, 1);
+    let amount0_out = word(&log.data, 2);
+    let amount1_out = word(&log.data, 3);
+
+    let (zero_for_one, amount_in, amount_out) = if !amount0_in.is_zero() && !amount1_out.is_zero() {
+        (true, amount0_in, amount1_out)
+    } else if !amount1_in.is_zero() && !amount0_out.is_zero() {
+        (false, amount1_in, amount0_out)
+    } else {
+        return None;
+    };
+
+    // Undo this swap's own effect on the synced reserves
+    let reserves_before = reserves_after.and_then(|(reserve0, reserve1)| Some((
+        reserve0.checked_sub(amount0_in)?.checked_add(amount0_out)?,
+        reserve1.checked_sub(amount1_in)?.checked_add(amount1_out)?,
+    )));
+
+    Some(Swap {
+        protocol: DexProtocol::UniswapV2,
+        reserves_before,
+        sqrt_price_after: None,
+        ..base_swap(log, zero_for_one, amount_in, amount_out)?
+    })
+}
+
+fn decode_v3_swap(log: &Log) -> Option<Swap> {
+    // Signed amounts from the pool's perspective: positive flowed in, negative flowed out
+    let (amount0_negative, amount0) = signed_word(&log.data, 0);
+    let (amount1_negative, amount1) = signed_word(&log.data, 1);
+
+    let (zero_for_one, amount_in, amount_out) = match (amount0_negative, amount1_negative) {
+        (false, true) => (true, amount0, amount1),
+        (true, false) => (false, amount1, amount0),
+        _ => return None,
+    };
+
+    Some(Swap {
+        protocol: DexProtocol::UniswapV3,
+        reserves_before: None,
+        sqrt_price_after: Some(word(&log.data, 2)),
+        ..base_swap(log, zero_for_one, amount_in, amount_out)?
+    })
+}
+
+fn decode_curve_exchange(log: &Log) -> Option<Swap> {
+    // Coin indices are int128 or uint256 depending on the pool; both fit the low bytes
+    let sold_id = word(&log.data, 0);
+    let bought_id = word(&log.data, 2);
+
+    Some(Swap {
+        protocol: DexProtocol::Curve,
+        ..base_swap(log, sold_id < bought_id, word(&log.data, 1), word(&log.data, 3))?
+    })
+}
+
+fn decode_balancer_swap(log: &Log) -> Option<Swap> {
+    let token_in = H160::from(log.topics[2]);
+    let token_out = H160::from(log.topics[3]);
+
+    Some(Swap {
+        protocol: DexProtocol::Balancer,
+        // A Balancer pool id starts with the pool's address
+        pool: H160::from_slice(&log.topics[1].as_bytes()[..20]),
+        token_in: Some(token_in),
+        token_out: Some(token_out),
+        ..base_swap(log, token_in < token_out, word(&log.data, 0), word(&log.data, 1))?
+    })
+}
+
+fn base_swap(log: &Log, zero_for_one: bool, amount_in: U256, amount_out: U256) -> Option<Swap> {
+    Some(Swap {
+        protocol: DexProtocol::UniswapV2,
+        pool: log.address,
+        transaction_hash: log.transaction_hash?,
+        transaction_index: log.transaction_index?.as_u64(),
+        log_index: log.log_index?.as_u64(),
+        zero_for_one,
+        amount_in,
+        amount_out,
+        token_in: None,
+        token_out: None,
+        reserves_before: None,
+        sqrt_price_after: None,
+    })
+}
+
+/// Token of the transfer between the pool and the trader that settled a swap
+///
+/// Prefers an exact amount match; fee-on-transfer tokens fall back to the
+/// closest matching transfer before the swap log.
+fn infer_token(
+    transfers: &[tokens::Erc20Transfer],
+    touches_pool: impl Fn(&tokens::Erc20Transfer) -> bool,
+    amount: U256,
+    swap_log_index: u64,
+) -> Option<H160> {
+    let candidates: Vec<&tokens::Erc20Transfer> = transfers.iter().filter(|t| touches_pool(t)).collect();
+
+    candidates.iter()
+        .find(|t| t.value == amount)
+        .or_else(|| candidates.iter()
+            .rfind(|t| t.log_index.is_some_and(|i| i < swap_log_index)))
+        .map(|t| t.token)
+}
+
+fn word(data: &[u8], index: usize) -> U256 {
+    U256::from_big_endian(&data[index * 32..(index + 1) * 32])
+}
+
+/// Two's-complement int256 as (is_negative, magnitude)
+fn signed_word(data: &[u8], index: usize) -> (bool, U256) {
+    let value = word(data, index);
+    if value.bit(255) {
+        (true, (!value).overflowing_add(U256::one()).0)
+    } else {
+        (false, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    const TRANSACTION: u64 = 9;
+
+    fn log(log_index: u64, address: H160, topics: Vec<H256>, words: &[U256]) -> Log {
+        let mut data = Vec::with_capacity(words.len() * 32);
+        for word in words {
+            let mut bytes = [0u8; 32];
+            word.to_big_endian(&mut bytes);
+            data.extend_from_slice(&bytes);
+        }
+        Log {
+            address,
+            topics,
+            data: data.into(),
+            transaction_hash: Some(H256::from_low_u64_be(TRANSACTION)),
+            transaction_index: Some(U64::from(3)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        }
+    }
+
+    fn transfer(log_index: u64, token: u64, from: H160, to: H160, amount: u64) -> Log {
+        log(log_index, H160::from_low_u64_be(token), vec![tokens::transfer_topic(), from.into(), to.into()], &[U256::from(amount)])
+    }
+
+    #[test]
+    fn curve_exchange_takes_tokens_from_settling_transfers() {
+        let (pool, trader) = (H160::from_low_u64_be(0xc0), H160::from_low_u64_be(0x7));
+        let exchange = log(
+            1,
+            pool,
+            vec![curve_exchange_topics()[0], trader.into()],
+            &[U256::from(2), U256::from(1_000), U256::from(0), U256::from(998)],
+        );
+        let logs = vec![
+            transfer(0, 0xa, trader, pool, 1_000),
+            exchange,
+            transfer(2, 0xb, pool, trader, 998),
+        ];
+
+        let swaps = decode_swaps(&logs);
+        assert_eq!(swaps.len(), 1);
+        let swap = &swaps[0];
+        assert_eq!(swap.protocol, DexProtocol::Curve);
+        assert_eq!(swap.pool, pool);
+        assert!(!swap.zero_for_one);
+        assert_eq!((swap.amount_in, swap.amount_out), (U256::from(1_000), U256::from(998)));
+        assert_eq!(swap.token_in, Some(H160::from_low_u64_be(0xa)));
+        assert_eq!(swap.token_out, Some(H160::from_low_u64_be(0xb)));
+    }
+
+    #[test]
+    fn balancer_swap_names_pool_and_tokens() {
+        let vault = H160::from_low_u64_be(0xba);
+        let pool = H160::from_low_u64_be(0x9001);
+        let mut pool_id = [0u8; 32];
+        pool_id[..20].copy_from_slice(pool.as_bytes());
+        pool_id[31] = 2;
+        let (token_in, token_out) = (H160::from_low_u64_be(0xf), H160::from_low_u64_be(0xe));
+        let swap_log = log(
+            4,
+            vault,
+            vec![balancer_swap_topic(), H256::from(pool_id), token_in.into(), token_out.into()],
+            &[U256::from(5_000), U256::from(4_990)],
+        );
+
+        let swaps = decode_swaps(&[swap_log]);
+        assert_eq!(swaps.len(), 1);
+        let swap = &swaps[0];
+        assert_eq!(swap.protocol, DexProtocol::Balancer);
+        assert_eq!(swap.pool, pool);
+        assert_eq!((swap.token_in, swap.token_out), (Some(token_in), Some(token_out)));
+        assert!(!swap.zero_for_one);
+        assert_eq!((swap.amount_in, swap.amount_out), (U256::from(5_000), U256::from(4_990)));
+    }
+
+    #[test]
+    fn malformed_swap_logs_are_skipped() {
+        let pool = H160::from_low_u64_be(0xc0);
+        let short_curve = log(1, pool, vec![curve_exchange_topics()[2], H256::zero()], &[U256::one(), U256::one(), U256::zero()]);
+        let unindexed_balancer = log(2, pool, vec![balancer_swap_topic()], &[U256::one(), U256::one()]);
+        assert!(decode_swaps(&[short_curve, unindexed_balancer]).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+//! Exchange flow tracking
+//!
+//! Classifies whale transfers against labeled exchange hot and deposit wallets
+//! and aggregates net exchange flow per token and network over time.
+
+use crate::labels::{AddressLabel, LabelCategory, LabelRegistry};
+use crate::{NetworkConfig, WhaleTransaction};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Direction of a transfer relative to centralized exchanges
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum ExchangeFlow {
+    /// Deposited into an exchange from an outside address
+    Inflow { exchange: String },
+    /// Withdrawn from an exchange to an outside address
+    Outflow { exchange: String },
+    /// Moved between exchange wallets, of the same exchange or two different ones
+    InternalShuffle { from_exchange: String, to_exchange: String },
+    #[default]
+    NonExchange,
+}
+
+/// Net exchange flow of one token on one network during one time bucket
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExchangeFlowPoint {
+    /// Start of the bucket
+    pub timestamp: DateTime<Utc>,
+    pub network: String,
+    pub token: String,
+    /// Whole tokens deposited to exchanges
+    pub inflow: f64,
+    /// Whole tokens withdrawn from exchanges
+    pub outflow: f64,
+    /// Inflow minus outflow; positive values mean coins heading to exchanges
+    pub net_flow: f64,
+    /// Net flow in USD, counting only priced transfers
+    pub net_flow_usd: f64,
+    pub transaction_count: u64,
+}
+
+/// Classify a whale transfer by the exchange labels of its sender and recipient
+pub fn classify(transaction: &WhaleTransaction, labels: &LabelRegistry) -> ExchangeFlow {
+    let from = exchange_name(labels.get_str(transaction.chain_id, &transaction.from));
+    let to = exchange_name(labels.get_str(transaction.chain_id, &transaction.to));
+
+    match (from, to) {
+        (Some(from_exchange), Some(to_exchange)) => ExchangeFlow::InternalShuffle { from_exchange, to_exchange },
+        (None, Some(exchange)) => ExchangeFlow::Inflow { exchange },
+        (Some(exchange), None) => ExchangeFlow::Outflow { exchange },
+        (None, None) => ExchangeFlow::NonExchange,
+    }
+}
+
+/// Exchange operating a hot or deposit wallet
+fn exchange_name(label: Option<&AddressLabel>) -> Option<String> {
+    let label = label?;
+    if !label.has(LabelCategory::CexHotWallet) && !label.has(LabelCategory::CexDepositWallet) {
+        return None;
+    }
+    Some(label.entity.clone().unwrap_or_else(|| label.name.clone()))
+}
+
+/// Net exchange flow per network and token in buckets of `bucket` length
+///
+/// Shuffles between exchange wallets and flash-loan notional are left out.
+/// Native transfers are reported under the network's native symbol.
+pub fn net_flow_series(
+    transactions: &[WhaleTransaction],
+    networks: &[NetworkConfig],
+    bucket: Duration,
+) -> Vec<ExchangeFlowPoint> {
+    let bucket_secs = bucket.num_seconds().max(1);
+    let mut points: BTreeMap<(i64, String, String), ExchangeFlowPoint> = BTreeMap::new();
+
+    for transaction in transactions.iter().filter(|t| t.volume() > 0) {
+        let sign = match transaction.exchange_flow {
+            ExchangeFlow::Inflow { .. } => 1.0,
+            ExchangeFlow::Outflow { .. } => -1.0,
+            ExchangeFlow::InternalShuffle { .. } | ExchangeFlow::NonExchange => continue,
+        };
+        let token = match &transaction.token {
+            Some(token) => token.symbol.clone(),
+            None => networks.iter()
+                .find(|n| n.chain_id == transaction.chain_id)
+                .map(|n| n.native_symbol.clone())
+                .unwrap_or_else(|| "native".to_string()),
+        };
+        let start = transaction.timestamp.timestamp().div_euclid(bucket_secs) * bucket_secs;
+
+        let point = points.entry((start, transaction.network.clone(), token.clone()))
+            .or_insert_with(|| ExchangeFlowPoint {
+                timestamp: Utc.timestamp_opt(start, 0).single().unwrap_or(transaction.timestamp),
+                network: transaction.network.clone(),
+                token,
+                inflow: 0.0,
+                outflow: 0.0,
+                net_flow: 0.0,
+                net_flow_usd: 0.0,
+                transaction_count: 0,
+            });
+        if sign > 0.0 {
+            point.inflow += transaction.volume_amount();
+        } else {
+            point.outflow += transaction.volume_amount();
+        }
+        point.net_flow += sign * transaction.volume_amount();
+        point.net_flow_usd += sign * transaction.volume_usd().unwrap_or_default();
+        point.transaction_count += 1;
+    }
+
+    points.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_tracker::ConfirmationStatus;
+    use crate::tokens::TokenInfo;
+    use crate::TransactionPattern;
+
+    const BINANCE: &str = "0x28C6c06298d514Db089934071355E5743bf21d60";
+    const COINBASE: &str = "0x71660c4005BA85c37ccec55d0C4493E66Fe775d3";
+    const KRAKEN: &str = "0x2910543Af39abA0Cd09dBb2D50200b3E800A63D2";
+    const WHALE: &str = "0x00000000000000000000000000000000000000aa";
+    const OTHER: &str = "0x00000000000000000000000000000000000000bb";
+
+    fn transfer(from: &str, to: &str, ether: u128, minutes: i64) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:064x}", minutes),
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            from: from.to_string(),
+            to: to.to_string(),
+            value: ether * 10u128.pow(18),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(ether as f64 * 2_000.0),
+            confirmation: ConfirmationStatus::Finalized,
+            gas_used: 21_000,
+            block_number: minutes as u64,
+            timestamp: DateTime::from_timestamp(1_700_000_000 + minutes * 60, 0).unwrap(),
+            zk_proof_hash: None,
+            risk_score: 0.0,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    fn classified(mut transaction: WhaleTransaction, labels: &LabelRegistry) -> WhaleTransaction {
+        transaction.exchange_flow = classify(&transaction, labels);
+        transaction
+    }
+
+    fn network() -> NetworkConfig {
+        NetworkConfig {
+            name: "Ethereum".to_string(),
+            chain_id: 1,
+            rpc_url: String::new(),
+            ws_url: None,
+            explorer_url: String::new(),
+            native_symbol: "ETH".to_string(),
+            confirmation_depth: 12,
+            finality_depth: 64,
+            block_time_ms: 12_000,
+        }
+    }
+
+    #[test]
+    fn transfers_are_classified_by_exchange_labels() {
+        let labels = LabelRegistry::with_defaults();
+        let flow = |from: &str, to: &str| classify(&transfer(from, to, 1_000, 0), &labels);
+
+        assert_eq!(flow(WHALE, BINANCE), ExchangeFlow::Inflow { exchange: "Binance".to_string() });
+        assert_eq!(flow(COINBASE, WHALE), ExchangeFlow::Outflow { exchange: "Coinbase".to_string() });
+        assert_eq!(flow(BINANCE, KRAKEN), ExchangeFlow::InternalShuffle { from_exchange: "Binance".to_string(), to_exchange: "Kraken".to_string() });
+        assert_eq!(flow(WHALE, OTHER), ExchangeFlow::NonExchange);
+        // Labeled addresses that are not exchange wallets do not count
+        assert_eq!(flow(WHALE, "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D"), ExchangeFlow::NonExchange);
+
+        // Exchange labels only apply on their own chain
+        let mut on_polygon = transfer(WHALE, BINANCE, 1_000, 0);
+        on_polygon.chain_id = 137;
+        assert_eq!(classify(&on_polygon, &labels), ExchangeFlow::NonExchange);
+    }
+
+    #[test]
+    fn deposit_wallets_without_entity_are_named_by_label() {
+        let mut labels = LabelRegistry::new();
+        labels.insert(1, OTHER.parse().unwrap(), AddressLabel {
+            name: "Deposit 42".to_string(),
+            entity: None,
+            categories: vec![LabelCategory::CexDepositWallet],
+        });
+        assert_eq!(classify(&transfer(WHALE, OTHER, 1_000, 0), &labels), ExchangeFlow::Inflow { exchange: "Deposit 42".to_string() });
+    }
+
+    #[test]
+    fn net_flow_is_bucketed_per_token_and_skips_non_exchange_transfers() {
+        let labels = LabelRegistry::with_defaults();
+        let mut usdc = transfer(KRAKEN, WHALE, 0, 20);
+        usdc.value = 5_000_000 * 10u128.pow(6);
+        usdc.usd_value = None;
+        usdc.token = Some(TokenInfo { address: OTHER.to_string(), symbol: "USDC".to_string(), decimals: 6 });
+        let mut flash_loan = transfer(WHALE, BINANCE, 1_000, 30);
+        flash_loan.borrowed = 600 * 10u128.pow(18);
+
+        let transactions: Vec<WhaleTransaction> = [
+            transfer(WHALE, BINANCE, 1_000, 0),
+            transfer(COINBASE, WHALE, 300, 10),
+            usdc,
+            flash_loan,
+            transfer(BINANCE, KRAKEN, 5_000, 40),
+            transfer(WHALE, OTHER, 5_000, 50),
+            transfer(WHALE, COINBASE, 200, 60),
+        ]
+        .into_iter()
+        .map(|transaction| classified(transaction, &labels))
+        .collect();
+
+        let points = net_flow_series(&transactions, &[network()], Duration::hours(1));
+        let summary: Vec<(i64, &str, f64, f64, f64, f64, u64)> = points.iter()
+            .map(|p| (p.timestamp.timestamp(), p.token.as_str(), p.inflow, p.outflow, p.net_flow, p.net_flow_usd, p.transaction_count))
+            .collect();
+        // 1_700_000_000 falls 800 seconds into its hour; the last deposit lands in the next hour
+        let hour = 1_700_000_000 - 800;
+        assert_eq!(summary, vec![
+            // Only the 400 ETH of the flash-loan deposit that was not borrowed count
+            (hour, "ETH", 1_400.0, 300.0, 1_100.0, 2_200_000.0, 3),
+            // The unpriced USDC withdrawal counts in tokens but not in USD
+            (hour, "USDC", 0.0, 5_000_000.0, -5_000_000.0, 0.0, 1),
+            (hour + 3_600, "ETH", 200.0, 0.0, 200.0, 400_000.0, 1),
+        ]);
+    }
+}
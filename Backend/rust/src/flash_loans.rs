@@ -0,0 +1,476 @@
+//! Flash loan detection
+//!
+//! Recognizes single-transaction borrow-and-repay loans from Aave, the
+//! Balancer Vault, dYdX and Uniswap V3 by their events, and from lenders'
+//! callbacks into the borrower in call traces.
+
+use crate::tokens;
+use ethers::types::{Action, Log, Trace, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// keccak256("FlashLoan(address,address,address,uint256,uint8,uint256,uint16)")
+pub const AAVE_V3_FLASH_LOAN_TOPIC: &str = "0xefefaba5e921573100900a3ad9cf29f222d995fb3b6045797eaea7521bd8d6f0";
+/// keccak256("FlashLoan(address,address,address,uint256,uint256,uint16)")
+pub const AAVE_V2_FLASH_LOAN_TOPIC: &str = "0x631042c832b07452973831137f2d73e395028b44b250dedc5abb0ee766e168ac";
+/// keccak256("FlashLoan(address,address,uint256,uint256)")
+pub const BALANCER_FLASH_LOAN_TOPIC: &str = "0x0d7d75e01ab95780d3cd1c8ec0dd6c2ce19e3a20427eec8bf53283b6fb8e95f0";
+/// keccak256("Flash(address,address,uint256,uint256,uint256,uint256)")
+pub const UNISWAP_V3_FLASH_TOPIC: &str = "0xbdbdb71d7860376ba52b25a5028beea23581364a40522f6bcfb86bb1f2dca633";
+/// keccak256("LogWithdraw(address,uint256,uint256,((bool,uint256),(bool,uint128)),address)")
+pub const DYDX_WITHDRAW_TOPIC: &str = "0xbc83c08f0b269b1726990c8348ffdf1ae1696244a14868d766e542a2f18cd7d4";
+/// keccak256("LogDeposit(address,uint256,uint256,((bool,uint256),(bool,uint128)),address)")
+pub const DYDX_DEPOSIT_TOPIC: &str = "0x2bad8bc95088af2c247b30fa2b2e6a0886f88625e0945cd3051008e0e270198f";
+
+/// Borrower callbacks lenders invoke while the loan is outstanding
+const CALLBACK_SELECTORS: [([u8; 4], FlashLoanProvider, LoanArguments); 6] = [
+    // executeOperation(address[],uint256[],uint256[],address,bytes)
+    ([0x92, 0x0f, 0x5c, 0x84], FlashLoanProvider::Aave, LoanArguments::Arrays(0, 1)),
+    // executeOperation(address,uint256,uint256,address,bytes)
+    ([0x1b, 0x11, 0xd0, 0xff], FlashLoanProvider::Aave, LoanArguments::Single(0, 1)),
+    // receiveFlashLoan(address[],uint256[],uint256[],bytes)
+    ([0xf0, 0x4f, 0x27, 0x07], FlashLoanProvider::BalancerVault, LoanArguments::Arrays(0, 1)),
+    // callFunction(address,(address,uint256),bytes)
+    ([0x8b, 0x41, 0x87, 0x13], FlashLoanProvider::DyDx, LoanArguments::Absent),
+    // uniswapV3FlashCallback(uint256,uint256,bytes)
+    ([0xe9, 0xcb, 0xaf, 0xb0], FlashLoanProvider::UniswapV3, LoanArguments::Absent),
+    // onFlashLoan(address,address,uint256,uint256,bytes)
+    ([0x23, 0xe3, 0x0c, 0x8b], FlashLoanProvider::Erc3156, LoanArguments::Single(1, 2)),
+];
+
+/// Arrays longer than this in callback arguments are treated as malformed
+const MAX_CALLBACK_ASSETS: usize = 64;
+
+/// Where a callback's arguments name the borrowed tokens and amounts
+#[derive(Debug, Clone, Copy)]
+enum LoanArguments {
+    /// Token and amount words
+    Single(usize, usize),
+    /// Words holding the offsets of the token and amount arrays
+    Arrays(usize, usize),
+    /// The callback does not carry them
+    Absent,
+}
+
+/// Protocol that lent the funds
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlashLoanProvider {
+    Aave,
+    BalancerVault,
+    DyDx,
+    UniswapV3,
+    /// Any ERC-3156 lender (Maker DssFlash and others)
+    Erc3156,
+}
+
+/// Funds borrowed and repaid within one transaction
+#[derive(Debug, Clone)]
+pub struct FlashLoan {
+    pub provider: FlashLoanProvider,
+    pub transaction_hash: H256,
+    pub lender: H160,
+    pub receiver: H160,
+    /// Borrowed token, when the event or the transaction's transfers name it
+    pub token: Option<H160>,
+    pub amount: U256,
+    pub fee: U256,
+}
+
+/// Lender calling back into a borrower, seen in a call trace
+#[derive(Debug, Clone)]
+pub struct FlashLoanCallback {
+    pub provider: FlashLoanProvider,
+    pub lender: H160,
+    pub receiver: H160,
+    /// Borrowed tokens and amounts, when the callback's arguments name them
+    pub loans: Vec<(H160, U256)>,
+}
+
+pub fn flash_loan_topics() -> Vec<H256> {
+    [
+        AAVE_V3_FLASH_LOAN_TOPIC,
+        AAVE_V2_FLASH_LOAN_TOPIC,
+        BALANCER_FLASH_LOAN_TOPIC,
+        UNISWAP_V3_FLASH_TOPIC,
+        DYDX_WITHDRAW_TOPIC,
+        DYDX_DEPOSIT_TOPIC,
+    ]
+    .into_iter()
+    .map(topic)
+    .collect()
+}
+
+/// Decode the flash loans in a set of logs
+///
+/// ERC-20 `Transfer` logs of the same transactions, when included, name the
+/// tokens borrowed from Uniswap V3 pools and dYdX markets.
+pub fn detect_flash_loans(logs: &[Log]) -> Vec<FlashLoan> {
+    let transfers: Vec<tokens::Erc20Transfer> = logs.iter().filter_map(tokens::decode_transfer).collect();
+    let mut loans = Vec::new();
+    // dYdX withdrawals waiting for the matching deposit, by (transaction, solo, account, market)
+    let mut dydx_withdrawals: HashMap<(H256, H160, H160, U256), (H160, U256)> = HashMap::new();
+    let [aave_v3, aave_v2, balancer, uniswap_v3, dydx_withdraw, dydx_deposit] = [
+        AAVE_V3_FLASH_LOAN_TOPIC,
+        AAVE_V2_FLASH_LOAN_TOPIC,
+        BALANCER_FLASH_LOAN_TOPIC,
+        UNISWAP_V3_FLASH_TOPIC,
+        DYDX_WITHDRAW_TOPIC,
+        DYDX_DEPOSIT_TOPIC,
+    ]
+    .map(topic);
+
+    for log in logs {
+        let (Some(&topic), Some(transaction_hash)) = (log.topics.first(), log.transaction_hash) else {
+            continue;
+        };
+
+        match topic {
+            // Interest rate mode 0 means repaid in the same transaction; others open a regular borrow
+            t if t == aave_v3 && log.topics.len() == 4 && log.data.len() == 128 && word(&log.data, 2).is_zero() => {
+                loans.push(FlashLoan {
+                    provider: FlashLoanProvider::Aave,
+                    transaction_hash,
+                    lender: log.address,
+                    receiver: H160::from(log.topics[1]),
+                    token: Some(H160::from(log.topics[2])),
+                    amount: word(&log.data, 1),
+                    fee: word(&log.data, 3),
+                });
+            }
+            t if t == aave_v2 && log.topics.len() == 4 && log.data.len() == 96 => {
+                loans.push(FlashLoan {
+                    provider: FlashLoanProvider::Aave,
+                    transaction_hash,
+                    lender: log.address,
+                    receiver: H160::from(log.topics[1]),
+                    token: Some(H160::from(log.topics[3])),
+                    amount: word(&log.data, 0),
+                    fee: word(&log.data, 1),
+                });
+            }
+            t if t == balancer && log.topics.len() == 3 && log.data.len() == 64 => {
+                loans.push(FlashLoan {
+                    provider: FlashLoanProvider::BalancerVault,
+                    transaction_hash,
+                    lender: log.address,
+                    receiver: H160::from(log.topics[1]),
+                    token: Some(H160::from(log.topics[2])),
+                    amount: word(&log.data, 0),
+                    fee: word(&log.data, 1),
+                });
+            }
+            t if t == uniswap_v3 && log.topics.len() == 3 && log.data.len() == 128 => {
+                let receiver = H160::from(log.topics[2]);
+                for (amount, paid) in [(word(&log.data, 0), word(&log.data, 2)), (word(&log.data, 1), word(&log.data, 3))] {
+                    if amount.is_zero() {
+                        continue;
+                    }
+                    loans.push(FlashLoan {
+                        provider: FlashLoanProvider::UniswapV3,
+                        transaction_hash,
+                        lender: log.address,
+                        receiver,
+                        token: sent_token(&transfers, transaction_hash, log.address, amount),
+                        amount,
+                        fee: paid,
+                    });
+                }
+            }
+            // dYdX has no flash loan event: a withdrawal deposited back into the same market is one
+            t if t == dydx_withdraw && log.topics.len() == 2 && log.data.len() == 224 => {
+                let account = H160::from(log.topics[1]);
+                dydx_withdrawals.insert(
+                    (transaction_hash, log.address, account, word(&log.data, 1)),
+                    (address_word(&log.data, 6), word(&log.data, 3)),
+                );
+            }
+            t if t == dydx_deposit && log.topics.len() == 2 && log.data.len() == 224 => {
+                let account = H160::from(log.topics[1]);
+                let key = (transaction_hash, log.address, account, word(&log.data, 1));
+                let Some((receiver, amount)) = dydx_withdrawals.remove(&key) else {
+                    continue;
+                };
+                let deposited = word(&log.data, 3);
+                if deposited < amount {
+                    continue;
+                }
+                loans.push(FlashLoan {
+                    provider: FlashLoanProvider::DyDx,
+                    transaction_hash,
+                    lender: log.address,
+                    receiver,
+                    token: sent_token(&transfers, transaction_hash, log.address, amount),
+                    amount,
+                    fee: deposited - amount,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    loans
+}
+
+/// Lender callbacks into borrowers found in a transaction's call trace
+pub fn flash_loan_callbacks(traces: &[Trace]) -> Vec<FlashLoanCallback> {
+    traces.iter()
+        .filter_map(|trace| match &trace.action {
+            Action::Call(call) => {
+                let selector = call.input.get(..4)?;
+                let (_, provider, arguments) = CALLBACK_SELECTORS.iter().find(|(s, _, _)| s.as_slice() == selector)?;
+                Some(FlashLoanCallback {
+                    provider: *provider,
+                    lender: call.from,
+                    receiver: call.to,
+                    loans: callback_loans(&call.input[4..], *arguments).unwrap_or_default(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Token a lender sent out in the loan's transaction
+fn sent_token(transfers: &[tokens::Erc20Transfer], transaction_hash: H256, lender: H160, amount: U256) -> Option<H160> {
+    transfers.iter()
+        .find(|t| t.transaction_hash == Some(transaction_hash) && t.from == lender && t.value == amount)
+        .map(|t| t.token)
+}
+
+/// Tokens and amounts in a callback's ABI-encoded arguments; `None` when malformed
+fn callback_loans(arguments: &[u8], layout: LoanArguments) -> Option<Vec<(H160, U256)>> {
+    match layout {
+        LoanArguments::Single(token, amount) => {
+            Some(vec![(word_to_address(checked_word(arguments, token)?), checked_word(arguments, amount)?)])
+        }
+        LoanArguments::Arrays(tokens, amounts) => {
+            let tokens = array(arguments, tokens)?;
+            let amounts = array(arguments, amounts)?;
+            if tokens.len() != amounts.len() {
+                return None;
+            }
+            Some(tokens.into_iter().map(word_to_address).zip(amounts).collect())
+        }
+        LoanArguments::Absent => Some(Vec::new()),
+    }
+}
+
+/// Dynamic `uint256[]` or `address[]` whose offset is at word `index`
+fn array(data: &[u8], index: usize) -> Option<Vec<U256>> {
+    let offset = usize::try_from(checked_word(data, index)?).ok()?;
+    if offset % 32 != 0 {
+        return None;
+    }
+    let start = offset / 32;
+    let length = usize::try_from(checked_word(data, start)?).ok().filter(|l| *l <= MAX_CALLBACK_ASSETS)?;
+    (1..=length).map(|i| checked_word(data, start + i)).collect()
+}
+
+fn checked_word(data: &[u8], index: usize) -> Option<U256> {
+    data.get(index * 32..(index + 1) * 32).map(U256::from_big_endian)
+}
+
+fn topic(hex: &str) -> H256 {
+    H256::from_str(hex).expect("valid flash loan topic")
+}
+
+fn word(data: &[u8], index: usize) -> U256 {
+    U256::from_big_endian(&data[index * 32..(index + 1) * 32])
+}
+
+fn word_to_address(word: U256) -> H160 {
+    let mut bytes = [0u8; 32];
+    word.to_big_endian(&mut bytes);
+    H160::from_slice(&bytes[12..])
+}
+
+fn address_word(data: &[u8], index: usize) -> H160 {
+    H160::from_slice(&data[index * 32 + 12..(index + 1) * 32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    const LENDER: u64 = 0x1e;
+    const RECEIVER: u64 = 0xbe;
+    const TOKEN: u64 = 0x70;
+
+    fn address(value: u64) -> H160 {
+        H160::from_low_u64_be(value)
+    }
+
+    fn address_topic(value: u64) -> H256 {
+        H256::from(address(value))
+    }
+
+    fn encode(words: &[U256]) -> Vec<u8> {
+        words.iter()
+            .flat_map(|word| {
+                let mut bytes = [0u8; 32];
+                word.to_big_endian(&mut bytes);
+                bytes
+            })
+            .collect()
+    }
+
+    fn address_word(value: u64) -> U256 {
+        U256::from(value)
+    }
+
+    fn log(transaction: u64, log_index: u64, emitter: u64, topics: Vec<H256>, words: &[U256]) -> Log {
+        Log {
+            address: address(emitter),
+            topics,
+            data: encode(words).into(),
+            block_number: Some(U64::from(10)),
+            transaction_hash: Some(H256::from_low_u64_be(transaction)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        }
+    }
+
+    fn transfer(transaction: u64, log_index: u64, from: u64, to: u64, amount: u64) -> Log {
+        log(transaction, log_index, TOKEN, vec![tokens::transfer_topic(), address_topic(from), address_topic(to)], &[U256::from(amount)])
+    }
+
+    #[test]
+    fn aave_flash_loans_decode_from_v2_and_v3_events() {
+        let v3 = |mode: u64| log(
+            1,
+            0,
+            LENDER,
+            vec![topic(AAVE_V3_FLASH_LOAN_TOPIC), address_topic(RECEIVER), address_topic(TOKEN), H256::zero()],
+            &[address_word(RECEIVER), U256::from(1_000u64), U256::from(mode), U256::from(5u64)],
+        );
+        let v2 = log(
+            2,
+            0,
+            LENDER,
+            vec![topic(AAVE_V2_FLASH_LOAN_TOPIC), address_topic(RECEIVER), address_topic(RECEIVER), address_topic(TOKEN)],
+            &[U256::from(2_000u64), U256::from(9u64), U256::zero()],
+        );
+
+        let loans = detect_flash_loans(&[v3(0), v2]);
+        assert_eq!(loans.len(), 2);
+        for (loan, (amount, fee)) in loans.iter().zip([(1_000u64, 5u64), (2_000, 9)]) {
+            assert_eq!(loan.provider, FlashLoanProvider::Aave);
+            assert_eq!((loan.lender, loan.receiver, loan.token), (address(LENDER), address(RECEIVER), Some(address(TOKEN))));
+            assert_eq!((loan.amount, loan.fee), (U256::from(amount), U256::from(fee)));
+        }
+
+        // A V3 flash loan left open as a variable-rate borrow is not repaid in the transaction
+        assert!(detect_flash_loans(&[v3(2)]).is_empty());
+    }
+
+    #[test]
+    fn balancer_flash_loan_names_recipient_and_token() {
+        let event = log(1, 0, LENDER, vec![topic(BALANCER_FLASH_LOAN_TOPIC), address_topic(RECEIVER), address_topic(TOKEN)], &[U256::from(7_000u64), U256::zero()]);
+
+        let loans = detect_flash_loans(&[event]);
+        assert_eq!(loans.len(), 1);
+        assert_eq!(loans[0].provider, FlashLoanProvider::BalancerVault);
+        assert_eq!((loans[0].receiver, loans[0].token, loans[0].amount), (address(RECEIVER), Some(address(TOKEN)), U256::from(7_000u64)));
+    }
+
+    #[test]
+    fn uniswap_v3_flash_takes_token_from_the_pool_transfer() {
+        let flash = log(
+            1,
+            1,
+            LENDER,
+            vec![topic(UNISWAP_V3_FLASH_TOPIC), address_topic(RECEIVER), address_topic(RECEIVER)],
+            &[U256::zero(), U256::from(3_000u64), U256::zero(), U256::from(9u64)],
+        );
+
+        let loans = detect_flash_loans(&[transfer(1, 0, LENDER, RECEIVER, 3_000), flash.clone()]);
+        assert_eq!(loans.len(), 1);
+        assert_eq!(loans[0].provider, FlashLoanProvider::UniswapV3);
+        assert_eq!((loans[0].token, loans[0].amount, loans[0].fee), (Some(address(TOKEN)), U256::from(3_000u64), U256::from(9u64)));
+
+        // Without the transaction's transfers the loan is still found, just without its token
+        let loans = detect_flash_loans(&[flash]);
+        assert_eq!((loans.len(), loans[0].token), (1, None));
+    }
+
+    #[test]
+    fn dydx_withdrawal_deposited_back_is_a_flash_loan() {
+        let market = U256::from(2u64);
+        let movement = |topic_hex: &str, log_index: u64, amount: u64| log(
+            1,
+            log_index,
+            LENDER,
+            vec![topic(topic_hex), address_topic(RECEIVER)],
+            &[U256::zero(), market, U256::one(), U256::from(amount), U256::zero(), U256::zero(), address_word(RECEIVER)],
+        );
+
+        let logs = vec![
+            movement(DYDX_WITHDRAW_TOPIC, 0, 10_000),
+            transfer(1, 1, LENDER, RECEIVER, 10_000),
+            movement(DYDX_DEPOSIT_TOPIC, 2, 10_002),
+        ];
+        let loans = detect_flash_loans(&logs);
+        assert_eq!(loans.len(), 1);
+        assert_eq!(loans[0].provider, FlashLoanProvider::DyDx);
+        assert_eq!((loans[0].receiver, loans[0].token), (address(RECEIVER), Some(address(TOKEN))));
+        assert_eq!((loans[0].amount, loans[0].fee), (U256::from(10_000u64), U256::from(2u64)));
+
+        // Depositing back less than was withdrawn leaves a real withdrawal
+        assert!(detect_flash_loans(&[movement(DYDX_WITHDRAW_TOPIC, 0, 10_000), movement(DYDX_DEPOSIT_TOPIC, 1, 9_000)]).is_empty());
+        assert!(detect_flash_loans(&[movement(DYDX_WITHDRAW_TOPIC, 0, 10_000)]).is_empty());
+    }
+
+    fn callback_trace(selector: [u8; 4], arguments: &[U256]) -> Trace {
+        let mut input = selector.to_vec();
+        input.extend(encode(arguments));
+        serde_json::from_value(serde_json::json!({
+            "action": {
+                "callType": "call",
+                "from": address(LENDER),
+                "to": address(RECEIVER),
+                "value": "0x0",
+                "gas": "0x0",
+                "input": ethers::types::Bytes::from(input),
+            },
+            "result": { "gasUsed": "0x0", "output": "0x" },
+            "traceAddress": [0],
+            "subtraces": 0,
+            "transactionPosition": 0,
+            "transactionHash": H256::from_low_u64_be(1),
+            "blockNumber": 10,
+            "blockHash": H256::zero(),
+            "type": "call",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn lender_callbacks_in_traces_name_the_loans() {
+        let aave = callback_trace([0x1b, 0x11, 0xd0, 0xff], &[address_word(TOKEN), U256::from(500u64), U256::one(), address_word(RECEIVER)]);
+        // receiveFlashLoan(tokens, amounts, fees, userData) with one token
+        let balancer = callback_trace([0xf0, 0x4f, 0x27, 0x07], &[
+            U256::from(128u64), U256::from(192u64), U256::from(256u64), U256::from(320u64),
+            U256::one(), address_word(TOKEN),
+            U256::one(), U256::from(800u64),
+            U256::one(), U256::zero(),
+            U256::zero(),
+        ]);
+        let uniswap = callback_trace([0xe9, 0xcb, 0xaf, 0xb0], &[U256::zero(), U256::one()]);
+        let unrelated = callback_trace([0xa9, 0x05, 0x9c, 0xbb], &[address_word(RECEIVER), U256::one()]);
+
+        let callbacks = flash_loan_callbacks(&[aave, balancer, uniswap, unrelated]);
+        let found: Vec<(FlashLoanProvider, Vec<(H160, U256)>)> = callbacks.into_iter().map(|c| (c.provider, c.loans)).collect();
+        assert_eq!(found, vec![
+            (FlashLoanProvider::Aave, vec![(address(TOKEN), U256::from(500u64))]),
+            (FlashLoanProvider::BalancerVault, vec![(address(TOKEN), U256::from(800u64))]),
+            (FlashLoanProvider::UniswapV3, vec![]),
+        ]);
+
+        // Array lengths beyond the cap are malformed: the callback still counts, without amounts
+        let oversized = callback_trace([0xf0, 0x4f, 0x27, 0x07], &[U256::from(64u64), U256::from(64u64), U256::from(1_000u64)]);
+        let callbacks = flash_loan_callbacks(&[oversized]);
+        assert_eq!((callbacks.len(), callbacks[0].loans.len()), (1, 0));
+    }
+}
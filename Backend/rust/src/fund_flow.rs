@@ -0,0 +1,608 @@
+//! Fund flow tracing
+//!
+//! Follows value out of a seed address or transaction across several hops and
+//! through bridges, attributing taint to each transfer under a haircut or FIFO
+//! policy, and exports the resulting flow graph as JSON or Graphviz DOT.
+
+use crate::bridges::{self, BridgeDirection, BridgeProtocol, BridgeTransfer};
+use crate::labels::{LabelCategory, LabelRegistry};
+use crate::ZKWatchResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+
+/// Asset name of a chain's native coin in [`FlowTransfer::asset`]
+pub const NATIVE_ASSET: &str = "native";
+
+/// Where tainted funds come from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum TaintSeed {
+    /// Everything the address sends, on any network, is tainted
+    Address(String),
+    /// Everything moved by one transaction is tainted
+    Transaction { network: String, hash: String },
+}
+
+/// How taint is attributed when an address mixes tainted and clean funds
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaintPolicy {
+    /// Every outgoing transfer carries the address's current tainted share
+    #[default]
+    Haircut,
+    /// Outgoing transfers spend the oldest received funds first
+    Fifo,
+}
+
+impl std::str::FromStr for TaintPolicy {
+    type Err = crate::ZKWatchError;
+
+    fn from_str(value: &str) -> ZKWatchResult<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "haircut" => Ok(TaintPolicy::Haircut),
+            "fifo" => Ok(TaintPolicy::Fifo),
+            other => Err(crate::ZKWatchError::Analytics(format!("Unknown taint policy: {}", other))),
+        }
+    }
+}
+
+/// Limits and policy of a trace
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TraceConfig {
+    /// Transfers followed away from the seed; bridge crossings count as one hop
+    pub max_hops: usize,
+    pub policy: TaintPolicy,
+    /// Transfers whose tainted share is below this are not followed
+    pub min_taint_share: f64,
+    /// Most addresses whose transfers are fetched, per network
+    pub max_addresses: usize,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            max_hops: 3,
+            policy: TaintPolicy::Haircut,
+            min_taint_share: 0.01,
+            max_addresses: 50,
+        }
+    }
+}
+
+/// Bridge end of a transfer into or out of a bridge
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BridgeLeg {
+    pub protocol: BridgeProtocol,
+    pub direction: BridgeDirection,
+}
+
+/// Native or ERC-20 transfer considered for tracing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowTransfer {
+    pub network: String,
+    pub chain_id: u64,
+    pub transaction_hash: String,
+    /// `None` for native transfers
+    pub log_index: Option<u64>,
+    /// Call path of a native transfer in its transaction's trace, empty for the
+    /// top-level call and for token transfers
+    #[serde(default)]
+    pub trace_address: Vec<usize>,
+    pub block_number: u64,
+    pub timestamp: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    /// Token contract, or [`NATIVE_ASSET`]
+    pub asset: String,
+    /// Symbol of the native coin or a tracked token, otherwise the token address
+    pub symbol: String,
+    /// Decimals of the native coin or a tracked token
+    pub decimals: Option<u8>,
+    pub amount: u128,
+    /// Set when the sender or recipient is a bridge contract
+    pub bridge: Option<BridgeLeg>,
+}
+
+/// Chain id, lowercase transaction hash, log index and trace address identifying one transfer
+type TransferId = (u64, String, Option<u64>, Vec<usize>);
+
+impl FlowTransfer {
+    fn id(&self) -> TransferId {
+        (self.chain_id, self.transaction_hash.to_ascii_lowercase(), self.log_index, self.trace_address.clone())
+    }
+
+    /// Account on the chain side of a bridge transfer
+    fn bridge_account(&self) -> &str {
+        match self.bridge.map(|leg| leg.direction) {
+            Some(BridgeDirection::Fill) => &self.to,
+            _ => &self.from,
+        }
+    }
+}
+
+/// Role of an address in a flow graph
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FlowNodeKind {
+    Seed,
+    /// Unlabeled address whose outgoing transfers are followed
+    Account,
+    Exchange,
+    Bridge,
+    Mixer,
+    /// Other labeled contract, such as a router or lending pool
+    Service,
+}
+
+impl FlowNodeKind {
+    /// Whether taint is followed out of addresses of this kind
+    pub fn followed(&self) -> bool {
+        matches!(self, FlowNodeKind::Seed | FlowNodeKind::Account)
+    }
+}
+
+/// Address reached by tainted funds
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowNode {
+    /// `chain_id:address`
+    pub id: String,
+    pub address: String,
+    pub network: String,
+    pub chain_id: u64,
+    pub label: Option<String>,
+    pub kind: FlowNodeKind,
+    /// Fewest transfers between the seed and this address
+    pub hop: usize,
+    /// Tainted funds received per symbol, in whole units when the decimals are known
+    pub tainted_received: BTreeMap<String, f64>,
+    /// Earliest block in which taint reached this address, on its own network
+    pub first_tainted_block: u64,
+}
+
+/// Transfer carrying tainted funds
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowEdge {
+    pub from: String,
+    pub to: String,
+    pub network: String,
+    pub transaction_hash: String,
+    pub block_number: u64,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub decimals: Option<u8>,
+    pub amount: u128,
+    pub tainted_amount: u128,
+    /// Hop of the recipient
+    pub hop: usize,
+    pub bridge: Option<BridgeLeg>,
+}
+
+impl FlowEdge {
+    pub fn taint_share(&self) -> f64 {
+        if self.amount == 0 {
+            return 0.0;
+        }
+        self.tainted_amount as f64 / self.amount as f64
+    }
+}
+
+/// Addresses and transfers reached from a seed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlowGraph {
+    pub seed: TaintSeed,
+    pub policy: TaintPolicy,
+    pub max_hops: usize,
+    pub nodes: Vec<FlowNode>,
+    pub edges: Vec<FlowEdge>,
+}
+
+impl FlowGraph {
+    pub fn to_json(&self) -> ZKWatchResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Graphviz digraph with one box per address and one arrow per tainted transfer
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph fund_flow {\n    rankdir=LR;\n    node [shape=box, fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let name = node.label.clone().unwrap_or_else(|| node.address.clone());
+            let color = match node.kind {
+                FlowNodeKind::Seed => "red",
+                FlowNodeKind::Account => "black",
+                FlowNodeKind::Exchange => "blue",
+                FlowNodeKind::Bridge => "purple",
+                FlowNodeKind::Mixer => "darkorange",
+                FlowNodeKind::Service => "gray40",
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\\n{}, hop {}\", color={}];",
+                node.id, escape(&name), escape(&node.network), node.hop, color,
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{} {} ({:.0}% tainted)\"{}];",
+                edge.from,
+                edge.to,
+                format_amount(edge.tainted_amount, edge.decimals),
+                escape(&edge.symbol),
+                edge.taint_share() * 100.0,
+                if edge.bridge.is_some() { ", style=dashed" } else { "" },
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn node(&self, id: &str) -> Option<&FlowNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+/// Balance of one asset at one address, split into tainted and clean funds
+enum Ledger {
+    Haircut { balance: u128, tainted: u128 },
+    /// Received lots as (amount, tainted amount), oldest first
+    Fifo(VecDeque<(u128, u128)>),
+}
+
+impl Ledger {
+    fn new(policy: TaintPolicy) -> Self {
+        match policy {
+            TaintPolicy::Haircut => Ledger::Haircut { balance: 0, tainted: 0 },
+            TaintPolicy::Fifo => Ledger::Fifo(VecDeque::new()),
+        }
+    }
+
+    fn credit(&mut self, amount: u128, tainted_amount: u128) {
+        match self {
+            Ledger::Haircut { balance, tainted } => {
+                *balance = balance.saturating_add(amount);
+                *tainted = tainted.saturating_add(tainted_amount);
+            }
+            Ledger::Fifo(lots) => lots.push_back((amount, tainted_amount)),
+        }
+    }
+
+    /// Spend `amount` and return the tainted part of it
+    ///
+    /// Funds held before the searched window are unseen; spending more than
+    /// the known balance spends clean funds.
+    fn debit(&mut self, amount: u128) -> u128 {
+        match self {
+            Ledger::Haircut { balance, tainted } => {
+                let spent = scale(*tainted, amount, (*balance).max(amount)).min(*tainted);
+                *tainted -= spent;
+                *balance = balance.saturating_sub(amount);
+                spent
+            }
+            Ledger::Fifo(lots) => {
+                let mut remaining = amount;
+                let mut spent = 0u128;
+                while remaining > 0 {
+                    let Some(lot) = lots.front_mut() else {
+                        break;
+                    };
+                    let take = remaining.min(lot.0);
+                    let lot_taint = scale(lot.1, take, lot.0).min(lot.1);
+                    spent = spent.saturating_add(lot_taint);
+                    lot.0 -= take;
+                    lot.1 -= lot_taint;
+                    remaining -= take;
+                    if lot.0 == 0 {
+                        lots.pop_front();
+                    }
+                }
+                spent
+            }
+        }
+    }
+}
+
+/// Attribute taint to transfers in time order and build the flow graph
+///
+/// Transfers may come from several networks; a bridge deposit passes its
+/// taint on to the fill that [`bridges::match_transfers`] pairs it with.
+/// Labeled addresses other than bridges absorb taint and are not followed.
+pub fn propagate(
+    seed: &TaintSeed,
+    transfers: &[FlowTransfer],
+    labels: &LabelRegistry,
+    config: &TraceConfig,
+) -> FlowGraph {
+    let mut ordered: Vec<&FlowTransfer> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for transfer in transfers {
+        if seen.insert(transfer.id()) {
+            ordered.push(transfer);
+        }
+    }
+    ordered.sort_by(|a, b| {
+        (a.timestamp, a.chain_id, a.block_number, a.log_index, &a.trace_address)
+            .cmp(&(b.timestamp, b.chain_id, b.block_number, b.log_index, &b.trace_address))
+    });
+
+    let bridge_fills = match_bridge_transfers(&ordered);
+    let mut pending_fills: HashMap<TransferId, (u128, usize)> = HashMap::new();
+    let mut ledgers: HashMap<(u64, String, String), Ledger> = HashMap::new();
+    let mut nodes: BTreeMap<(u64, String), FlowNode> = BTreeMap::new();
+    let mut edges = Vec::new();
+
+    for transfer in ordered {
+        let from = transfer.from.to_ascii_lowercase();
+        let to = transfer.to.to_ascii_lowercase();
+        let from_kind = node_kind(labels, transfer.chain_id, &from);
+
+        let seeded = match seed {
+            TaintSeed::Address(address) => address.eq_ignore_ascii_case(&from),
+            TaintSeed::Transaction { network, hash } => {
+                network == &transfer.network && hash.eq_ignore_ascii_case(&transfer.transaction_hash)
+            }
+        };
+        if seeded {
+            let node = nodes.entry((transfer.chain_id, from.clone()))
+                .or_insert_with(|| new_node(transfer, &from, FlowNodeKind::Seed, 0, labels));
+            node.kind = FlowNodeKind::Seed;
+            node.hop = 0;
+        }
+
+        // Taint leaving the sender, and the hop it reached the sender at
+        let (tainted, from_hop) = if seeded {
+            (transfer.amount, Some(0))
+        } else if transfer.bridge.map(|leg| leg.direction) == Some(BridgeDirection::Fill) {
+            match pending_fills.remove(&transfer.id()) {
+                Some((tainted, hop)) => (tainted, Some(hop)),
+                None => (0, None),
+            }
+        } else if from_kind.followed() {
+            let ledger = ledgers.entry((transfer.chain_id, from.clone(), transfer.asset.clone()))
+                .or_insert_with(|| Ledger::new(config.policy));
+            let tainted = ledger.debit(transfer.amount);
+            (tainted, nodes.get(&(transfer.chain_id, from.clone())).map(|node| node.hop))
+        } else {
+            (0, None)
+        };
+
+        // Burns into a bridge are sent to the zero address rather than the bridge contract
+        let to_kind = match transfer.bridge.map(|leg| leg.direction) {
+            Some(BridgeDirection::Deposit) => FlowNodeKind::Bridge,
+            _ => node_kind(labels, transfer.chain_id, &to),
+        };
+        if to_kind.followed() {
+            ledgers.entry((transfer.chain_id, to.clone(), transfer.asset.clone()))
+                .or_insert_with(|| Ledger::new(config.policy))
+                .credit(transfer.amount, tainted);
+        }
+
+        let Some(from_hop) = from_hop else {
+            continue;
+        };
+        // Bridge fills keep the hop of the deposit, which already counted the crossing
+        let hop = match transfer.bridge.map(|leg| leg.direction) {
+            Some(BridgeDirection::Fill) => from_hop,
+            _ => from_hop + 1,
+        };
+        let share = if transfer.amount == 0 { 0.0 } else { tainted as f64 / transfer.amount as f64 };
+        if tainted == 0 || share < config.min_taint_share || hop > config.max_hops {
+            continue;
+        }
+        if transfer.bridge.map(|leg| leg.direction) == Some(BridgeDirection::Fill) {
+            nodes.entry((transfer.chain_id, from.clone()))
+                .or_insert_with(|| new_node(transfer, &from, FlowNodeKind::Bridge, from_hop, labels));
+        }
+        if let Some(fill) = bridge_fills.get(&transfer.id()) {
+            pending_fills.insert(fill.0.clone(), (scale(tainted, fill.1, transfer.amount).min(fill.1), hop));
+        }
+
+        let node = nodes.entry((transfer.chain_id, to.clone()))
+            .or_insert_with(|| new_node(transfer, &to, to_kind, hop, labels));
+        node.hop = node.hop.min(hop);
+        node.first_tainted_block = node.first_tainted_block.min(transfer.block_number);
+        *node.tainted_received.entry(transfer.symbol.clone()).or_default() += whole_units(tainted, transfer.decimals);
+
+        edges.push(FlowEdge {
+            from: node_id(transfer.chain_id, &from),
+            to: node_id(transfer.chain_id, &to),
+            network: transfer.network.clone(),
+            transaction_hash: transfer.transaction_hash.clone(),
+            block_number: transfer.block_number,
+            timestamp: transfer.timestamp,
+            symbol: transfer.symbol.clone(),
+            decimals: transfer.decimals,
+            amount: transfer.amount,
+            tainted_amount: tainted,
+            hop,
+            bridge: transfer.bridge,
+        });
+    }
+
+    let mut nodes: Vec<FlowNode> = nodes.into_values().collect();
+    nodes.sort_by(|a, b| a.hop.cmp(&b.hop).then(a.id.cmp(&b.id)));
+
+    FlowGraph {
+        seed: seed.clone(),
+        policy: config.policy,
+        max_hops: config.max_hops,
+        nodes,
+        edges,
+    }
+}
+
+/// Fill matched to each bridge deposit, with the fill's amount
+fn match_bridge_transfers(transfers: &[&FlowTransfer]) -> HashMap<TransferId, (TransferId, u128)> {
+    let legs: Vec<&FlowTransfer> = transfers.iter().copied().filter(|t| t.bridge.is_some()).collect();
+    let bridge_transfers: Vec<BridgeTransfer> = legs.iter()
+        .filter_map(|t| {
+            let leg = t.bridge?;
+            Some(BridgeTransfer {
+                protocol: leg.protocol,
+                direction: leg.direction,
+                network: t.network.clone(),
+                chain_id: t.chain_id,
+                transaction_hash: t.transaction_hash.clone(),
+                account: t.bridge_account().to_string(),
+                token: t.asset.clone(),
+                symbol: t.decimals.map(|_| crate::pricing::price_symbol(&t.symbol)),
+                amount: t.amount,
+                decimals: t.decimals,
+                value_wei: None,
+                timestamp: t.timestamp,
+            })
+        })
+        .collect();
+
+    // Bridge transfers carry no log index, so legs are found again by chain, hash, account and amount
+    let find = |transfer: &BridgeTransfer| {
+        legs.iter().find(|t| {
+            t.chain_id == transfer.chain_id
+                && t.transaction_hash == transfer.transaction_hash
+                && t.amount == transfer.amount
+                && t.bridge_account().eq_ignore_ascii_case(&transfer.account)
+        })
+    };
+
+    bridges::match_transfers(&bridge_transfers)
+        .iter()
+        .filter_map(|m| {
+            let (deposit, fill) = (find(&m.deposit)?, find(&m.fill)?);
+            Some((deposit.id(), (fill.id(), fill.amount)))
+        })
+        .collect()
+}
+
+fn node_kind(labels: &LabelRegistry, chain_id: u64, address: &str) -> FlowNodeKind {
+    let Some(label) = labels.get_str(chain_id, address) else {
+        return FlowNodeKind::Account;
+    };
+    if label.has(LabelCategory::CexHotWallet) || label.has(LabelCategory::CexDepositWallet) {
+        FlowNodeKind::Exchange
+    } else if label.has(LabelCategory::Bridge) {
+        FlowNodeKind::Bridge
+    } else if label.has(LabelCategory::Mixer) {
+        FlowNodeKind::Mixer
+    } else {
+        FlowNodeKind::Service
+    }
+}
+
+fn new_node(transfer: &FlowTransfer, address: &str, kind: FlowNodeKind, hop: usize, labels: &LabelRegistry) -> FlowNode {
+    FlowNode {
+        id: node_id(transfer.chain_id, address),
+        address: address.to_string(),
+        network: transfer.network.clone(),
+        chain_id: transfer.chain_id,
+        label: labels.get_str(transfer.chain_id, address).map(|label| label.name.clone()),
+        kind,
+        hop,
+        tainted_received: BTreeMap::new(),
+        first_tainted_block: transfer.block_number,
+    }
+}
+
+fn node_id(chain_id: u64, address: &str) -> String {
+    format!("{}:{}", chain_id, address)
+}
+
+/// `value * numerator / denominator` without overflowing on large token amounts
+fn scale(value: u128, numerator: u128, denominator: u128) -> u128 {
+    if denominator == 0 {
+        return 0;
+    }
+    (value as f64 * (numerator as f64 / denominator as f64)) as u128
+}
+
+fn whole_units(amount: u128, decimals: Option<u8>) -> f64 {
+    match decimals {
+        Some(decimals) => amount as f64 / 10f64.powi(decimals as i32),
+        None => amount as f64,
+    }
+}
+
+fn format_amount(amount: u128, decimals: Option<u8>) -> String {
+    match decimals {
+        Some(_) => format!("{:.4}", whole_units(amount, decimals)),
+        None => amount.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn address(n: u64) -> String {
+        format!("0x{:040x}", n)
+    }
+
+    fn transfer(index: u64, from: u64, to: u64, amount: u128) -> FlowTransfer {
+        FlowTransfer {
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            transaction_hash: format!("0x{:064x}", index),
+            log_index: None,
+            trace_address: Vec::new(),
+            block_number: 100 + index,
+            timestamp: Utc.timestamp_opt(1_700_000_000 + index as i64 * 12, 0).unwrap(),
+            from: address(from),
+            to: address(to),
+            asset: NATIVE_ASSET.to_string(),
+            symbol: "ETH".to_string(),
+            decimals: Some(18),
+            amount,
+            bridge: None,
+        }
+    }
+
+    #[test]
+    fn haircut_spends_the_tainted_share() {
+        let mut ledger = Ledger::new(TaintPolicy::Haircut);
+        ledger.credit(100, 50);
+        ledger.credit(100, 0);
+        assert_eq!(ledger.debit(100), 25);
+        assert_eq!(ledger.debit(100), 25);
+        // Funds from before the window are clean
+        ledger.credit(10, 10);
+        assert_eq!(ledger.debit(40), 10);
+    }
+
+    #[test]
+    fn fifo_spends_the_oldest_lots_first() {
+        let mut ledger = Ledger::new(TaintPolicy::Fifo);
+        ledger.credit(100, 50);
+        ledger.credit(100, 0);
+        assert_eq!(ledger.debit(50), 25);
+        assert_eq!(ledger.debit(100), 25);
+        assert_eq!(ledger.debit(50), 0);
+        assert_eq!(ledger.debit(50), 0);
+    }
+
+    #[test]
+    fn policies_attribute_mixed_funds_differently() {
+        // Seed 1 and clean address 2 both pay 3, which pays 4, which pays 5
+        let transfers = vec![
+            transfer(1, 1, 3, 100),
+            transfer(2, 2, 3, 100),
+            transfer(3, 3, 4, 100),
+            transfer(4, 4, 5, 100),
+        ];
+        let seed = TaintSeed::Address(address(1));
+        let labels = LabelRegistry::new();
+        let tainted = |policy: TaintPolicy, max_hops: usize| {
+            let config = TraceConfig { policy, max_hops, ..TraceConfig::default() };
+            propagate(&seed, &transfers, &labels, &config).edges
+                .iter()
+                .map(|edge| (edge.to.clone(), edge.tainted_amount, edge.hop))
+                .collect::<Vec<_>>()
+        };
+        let to = |n: u64| node_id(1, &address(n));
+
+        assert_eq!(tainted(TaintPolicy::Haircut, 3), vec![(to(3), 100, 1), (to(4), 50, 2), (to(5), 50, 3)]);
+        assert_eq!(tainted(TaintPolicy::Fifo, 3), vec![(to(3), 100, 1), (to(4), 100, 2), (to(5), 100, 3)]);
+        assert_eq!(tainted(TaintPolicy::Fifo, 2), vec![(to(3), 100, 1), (to(4), 100, 2)]);
+    }
+}
@@ -0,0 +1,314 @@
+//! Address label registry
+//!
+//! Names and categorizes known contracts and entities per chain, loaded from
+//! versioned JSON or CSV label files where later files override earlier ones.
+
+use crate::{ZKWatchError, ZKWatchResult};
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Label file format version this build reads
+pub const LABEL_FILE_VERSION: u32 = 1;
+
+/// Chain id under which labels valid on every chain are stored
+pub const ANY_CHAIN: u64 = 0;
+
+/// Labels shipped with the engine
+const DEFAULT_LABELS: &str = include_str!("../data/labels.json");
+
+/// Kind of entity behind an address
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelCategory {
+    CexHotWallet,
+    /// Per-customer deposit address that forwards to an exchange's hot wallet
+    CexDepositWallet,
+    Bridge,
+    DexRouter,
+    LendingPool,
+    Mixer,
+    Sanctioned,
+}
+
+impl FromStr for LabelCategory {
+    type Err = ZKWatchError;
+
+    fn from_str(value: &str) -> ZKWatchResult<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cex_hot_wallet" => Ok(LabelCategory::CexHotWallet),
+            "cex_deposit_wallet" => Ok(LabelCategory::CexDepositWallet),
+            "bridge" => Ok(LabelCategory::Bridge),
+            "dex_router" => Ok(LabelCategory::DexRouter),
+            "lending_pool" => Ok(LabelCategory::LendingPool),
+            "mixer" => Ok(LabelCategory::Mixer),
+            "sanctioned" => Ok(LabelCategory::Sanctioned),
+            other => Err(ZKWatchError::Analytics(format!("Unknown label category: {}", other))),
+        }
+    }
+}
+
+/// Name and categories of a known address
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AddressLabel {
+    pub name: String,
+    /// Exchange or protocol operating the address
+    pub entity: Option<String>,
+    pub categories: Vec<LabelCategory>,
+}
+
+impl AddressLabel {
+    pub fn has(&self, category: LabelCategory) -> bool {
+        self.categories.contains(&category)
+    }
+}
+
+#[derive(Deserialize)]
+struct LabelFile {
+    version: u32,
+    labels: Vec<LabelRecord>,
+}
+
+#[derive(Deserialize)]
+struct LabelRecord {
+    /// Omitted for addresses deployed at the same address on every chain
+    chain_id: Option<u64>,
+    address: String,
+    name: String,
+    entity: Option<String>,
+    categories: Vec<LabelCategory>,
+}
+
+/// Labels per chain, keyed by address
+#[derive(Debug, Clone, Default)]
+pub struct LabelRegistry {
+    labels: HashMap<(u64, H160), AddressLabel>,
+}
+
+impl LabelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exchange wallets, DEX routers, lending pools, bridges, mixers and
+    /// sanctioned addresses from the bundled `data/labels.json`
+    pub fn with_defaults() -> Self {
+        Self::from_json(DEFAULT_LABELS, "data/labels.json").expect("valid bundled label file")
+    }
+
+    /// Read a label file, as JSON when the extension is `.json` and CSV otherwise
+    ///
+    /// CSV files have `chain_id,address,name,categories,entity` rows, with
+    /// categories separated by `|`, an empty chain id for every chain, and an
+    /// optional `# version: N` line.
+    pub fn load(path: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let origin = path.display().to_string();
+
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            Self::from_json(&contents, &origin)
+        } else {
+            Self::from_csv(&contents, &origin)
+        }
+    }
+
+    pub fn from_json(contents: &str, origin: &str) -> ZKWatchResult<Self> {
+        let file: LabelFile = serde_json::from_str(contents)?;
+        check_version(file.version, origin)?;
+
+        let mut registry = Self::new();
+        for record in file.labels {
+            let address = parse_address(&record.address, origin)?;
+            registry.insert(record.chain_id.unwrap_or(ANY_CHAIN), address, AddressLabel {
+                name: record.name,
+                entity: record.entity,
+                categories: record.categories,
+            });
+        }
+        Ok(registry)
+    }
+
+    pub fn from_csv(contents: &str, origin: &str) -> ZKWatchResult<Self> {
+        let mut registry = Self::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            let location = format!("{}:{}", origin, index + 1);
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some(version) = comment.trim().strip_prefix("version:") {
+                    let version = version.trim().parse::<u32>()
+                        .map_err(|_| ZKWatchError::Analytics(format!("{}: invalid version", location)))?;
+                    check_version(version, origin)?;
+                }
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if line.is_empty() || fields.first().is_some_and(|f| f.eq_ignore_ascii_case("chain_id")) {
+                continue;
+            }
+            if fields.len() < 4 {
+                return Err(ZKWatchError::Analytics(format!("{}: expected chain_id,address,name,categories[,entity]", location)));
+            }
+
+            let chain_id = match fields[0] {
+                "" | "*" => ANY_CHAIN,
+                value => value.parse::<u64>()
+                    .map_err(|_| ZKWatchError::Analytics(format!("{}: invalid chain id", location)))?,
+            };
+            let categories = fields[3].split('|')
+                .filter(|c| !c.trim().is_empty())
+                .map(LabelCategory::from_str)
+                .collect::<ZKWatchResult<Vec<_>>>()?;
+            registry.insert(chain_id, parse_address(fields[1], &location)?, AddressLabel {
+                name: fields[2].to_string(),
+                entity: fields.get(4).filter(|e| !e.is_empty()).map(|e| e.to_string()),
+                categories,
+            });
+        }
+
+        Ok(registry)
+    }
+
+    /// Add the labels of another registry, replacing labels of the same address
+    pub fn merge(&mut self, overrides: LabelRegistry) {
+        self.labels.extend(overrides.labels);
+    }
+
+    pub fn insert(&mut self, chain_id: u64, address: H160, label: AddressLabel) {
+        self.labels.insert((chain_id, address), label);
+    }
+
+    /// Label of an address on a chain, falling back to labels valid on every chain
+    pub fn get(&self, chain_id: u64, address: &H160) -> Option<&AddressLabel> {
+        self.labels.get(&(chain_id, *address))
+            .or_else(|| self.labels.get(&(ANY_CHAIN, *address)))
+    }
+
+    /// Label of a hex address string; unparseable addresses have none
+    pub fn get_str(&self, chain_id: u64, address: &str) -> Option<&AddressLabel> {
+        self.get(chain_id, &address.parse::<H160>().ok()?)
+    }
+
+    pub fn is(&self, chain_id: u64, address: &str, category: LabelCategory) -> bool {
+        self.get_str(chain_id, address).is_some_and(|label| label.has(category))
+    }
+
+    /// Label name of an address, or its shortened hex when unlabeled
+    pub fn display(&self, chain_id: u64, address: &str) -> String {
+        match self.get_str(chain_id, address) {
+            Some(label) => label.name.clone(),
+            // Byte offsets may split a multi-byte character in malformed input
+            None => match (address.get(..6), address.get(address.len().saturating_sub(4)..)) {
+                (Some(head), Some(tail)) if address.len() > 12 => format!("{}…{}", head, tail),
+                _ => address.to_string(),
+            },
+        }
+    }
+
+    /// Every label with its chain id ([`ANY_CHAIN`] for chain-independent labels)
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &H160, &AddressLabel)> {
+        self.labels.iter().map(|((chain_id, address), label)| (*chain_id, address, label))
+    }
+}
+
+fn check_version(version: u32, origin: &str) -> ZKWatchResult<()> {
+    if version > LABEL_FILE_VERSION {
+        return Err(ZKWatchError::Analytics(format!(
+            "{}: label file version {} is newer than supported version {}",
+            origin, version, LABEL_FILE_VERSION,
+        )));
+    }
+    Ok(())
+}
+
+fn parse_address(value: &str, origin: &str) -> ZKWatchResult<H160> {
+    value.parse::<H160>()
+        .map_err(|_| ZKWatchError::Analytics(format!("{}: invalid address {}", origin, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINANCE_14: &str = "0x28C6c06298d514Db089934071355E5743bf21d60";
+    const UNISWAP_V3_ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+
+    #[test]
+    fn bundled_label_file_loads_from_disk() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/labels.json");
+        let registry = LabelRegistry::load(path).unwrap();
+        assert_eq!(registry.iter().count(), LabelRegistry::with_defaults().iter().count());
+
+        let binance = registry.get_str(1, BINANCE_14).unwrap();
+        assert_eq!((binance.name.as_str(), binance.entity.as_deref()), ("Binance 14", Some("Binance")));
+        assert!(binance.has(LabelCategory::CexHotWallet));
+        assert!(registry.is(1, "0xd90e2f925DA726b50C4Ed8D0Fb90Ad053324F31b", LabelCategory::Sanctioned));
+
+        // Chain-specific labels stay on their chain; labels without a chain id apply everywhere
+        assert!(registry.get_str(137, BINANCE_14).is_none());
+        assert_eq!(registry.display(137, UNISWAP_V3_ROUTER), "Uniswap V3 Router");
+    }
+
+    #[test]
+    fn lookups_ignore_address_case() {
+        let registry = LabelRegistry::with_defaults();
+        for address in [BINANCE_14.to_string(), BINANCE_14.to_lowercase(), format!("0x{}", BINANCE_14[2..].to_uppercase())] {
+            assert_eq!(registry.display(1, &address), "Binance 14");
+        }
+
+        let csv = "chain_id,address,name,categories\n1,0x28C6C06298D514DB089934071355E5743BF21D60,Renamed,cex_hot_wallet";
+        let mut merged = LabelRegistry::with_defaults();
+        merged.merge(LabelRegistry::from_csv(csv, "overrides.csv").unwrap());
+        assert_eq!(merged.display(1, &BINANCE_14.to_lowercase()), "Renamed");
+    }
+
+    #[test]
+    fn unlabeled_and_malformed_addresses_fall_back_to_their_text() {
+        let registry = LabelRegistry::with_defaults();
+        assert_eq!(registry.display(1, "0x000000000000000000000000000000000000dEaD"), "0x0000…dEaD");
+        for (malformed, shown) in [
+            ("", ""),
+            ("0x1234", "0x1234"),
+            ("not-an-address", "not-an…ress"),
+            ("0x28C6c06298d514Db089934071355E5743bf21dZZ", "0x28C6…1dZZ"),
+            // Shortening would split a multi-byte character, so the text is shown whole
+            ("0x000é00000000000000000000000000000000", "0x000é00000000000000000000000000000000"),
+        ] {
+            assert!(registry.get_str(1, malformed).is_none());
+            assert!(!registry.is(1, malformed, LabelCategory::Sanctioned));
+            assert_eq!(registry.display(1, malformed), shown);
+        }
+    }
+
+    #[test]
+    fn malformed_label_entries_are_rejected_with_their_origin() {
+        let json = |version: u32, entry: &str| format!(r#"{{ "version": {}, "labels": [{}] }}"#, version, entry);
+        let bad_address = json(1, r#"{ "address": "0x1234", "name": "Short", "categories": [] }"#);
+        let bad_category = json(1, &format!(r#"{{ "address": "{}", "name": "X", "categories": ["casino"] }}"#, BINANCE_14));
+        let newer = json(LABEL_FILE_VERSION + 1, "");
+
+        let error = LabelRegistry::from_json(&bad_address, "custom.json").unwrap_err();
+        assert!(error.to_string().contains("custom.json: invalid address 0x1234"), "{}", error);
+        assert!(LabelRegistry::from_json(&bad_category, "custom.json").is_err());
+        assert!(LabelRegistry::from_json(&newer, "custom.json").unwrap_err().to_string().contains("newer than supported"));
+
+        for (csv, message) in [
+            ("1,0x1234,Short,mixer", "labels.csv:1: invalid address"),
+            (&*format!("mainnet,{},X,mixer", BINANCE_14), "labels.csv:1: invalid chain id"),
+            (&*format!("# version: 1\n1,{}", BINANCE_14), "labels.csv:2: expected"),
+        ] {
+            let error = LabelRegistry::from_csv(csv, "labels.csv").unwrap_err();
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+        assert!(LabelRegistry::from_csv(&format!("1,{},X,casino", BINANCE_14), "labels.csv").is_err());
+
+        // Blank lines, comments, the header and an empty chain id are all accepted
+        let csv = format!("chain_id,address,name,categories,entity\n\n# exchanges\n,{},Everywhere,cex_hot_wallet|,\n", BINANCE_14);
+        let registry = LabelRegistry::from_csv(&csv, "labels.csv").unwrap();
+        let label = registry.get_str(56, BINANCE_14).unwrap();
+        assert_eq!((label.name.as_str(), label.entity.as_ref(), label.categories.as_slice()), ("Everywhere", None, &[LabelCategory::CexHotWallet][..]));
+    }
+}
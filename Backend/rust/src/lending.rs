@@ -0,0 +1,339 @@
+//! Lending protocol events
+//!
+//! Decodes Aave V3 position events (supply, borrow, repay, withdraw) and
+//! liquidations from Aave `LiquidationCall` and Compound `LiquidateBorrow` logs.
+
+use crate::tokens;
+use ethers::types::{Log, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// keccak256("LiquidationCall(address,address,address,uint256,uint256,address,bool)")
+pub const AAVE_LIQUIDATION_CALL_TOPIC: &str = "0xe413a321e8681d831f4dbccbca790d2952b56f977908e45be37335533e005286";
+/// keccak256("LiquidateBorrow(address,address,uint256,address,uint256)")
+pub const COMPOUND_LIQUIDATE_BORROW_TOPIC: &str = "0x298637f684da70674f26509b10f07ec2fbc77a335ab1e7d6215a4b2484d8bb52";
+/// keccak256("Supply(address,address,address,uint256,uint16)")
+pub const AAVE_SUPPLY_TOPIC: &str = "0x2b627736bca15cd5381dcf80b0bf11fd197d01a037c52b927a881a10fb73ba61";
+/// keccak256("Borrow(address,address,address,uint256,uint8,uint256,uint16)")
+pub const AAVE_BORROW_TOPIC: &str = "0xb3d084820fb1a9decffb176436bd02558d15fac9b0ddfed8c465bc7359d7dce0";
+/// keccak256("Repay(address,address,address,uint256,bool)")
+pub const AAVE_REPAY_TOPIC: &str = "0xa534c8dbe71f871f9f3530e97a74601fea17b426cae02e1c5aee42c96c784051";
+/// keccak256("Withdraw(address,address,address,uint256)")
+pub const AAVE_WITHDRAW_TOPIC: &str = "0x3115d1449a7b732c986cba18244e897a450f61e1bb8d589cd2e69e6c8924f9f7";
+
+/// Lending market a position event came from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LendingProtocol {
+    /// Aave V3 pools (and V2, which shares the event layout)
+    AaveV3,
+    /// Compound V2 cTokens and forks
+    CompoundV2,
+}
+
+impl std::fmt::Display for LendingProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LendingProtocol::AaveV3 => write!(f, "Aave V3"),
+            LendingProtocol::CompoundV2 => write!(f, "Compound V2"),
+        }
+    }
+}
+
+/// Change to a lending position
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LendingAction {
+    Supply,
+    Borrow,
+    Repay,
+    Withdraw,
+}
+
+impl std::fmt::Display for LendingAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LendingAction::Supply => write!(f, "Supply"),
+            LendingAction::Borrow => write!(f, "Borrow"),
+            LendingAction::Repay => write!(f, "Repay"),
+            LendingAction::Withdraw => write!(f, "Withdraw"),
+        }
+    }
+}
+
+/// Supply, borrow, repay or withdraw against a lending market
+#[derive(Debug, Clone)]
+pub struct PositionEvent {
+    pub protocol: LendingProtocol,
+    pub action: LendingAction,
+    pub transaction_hash: H256,
+    pub log_index: u64,
+    /// Underlying asset of the reserve
+    pub reserve: H160,
+    /// Owner of the position
+    pub user: H160,
+    /// Account that sent the call, which can act on behalf of `user`
+    pub caller: H160,
+    pub amount: U256,
+}
+
+/// Undercollateralized position closed by a liquidator
+#[derive(Debug, Clone)]
+pub struct Liquidation {
+    pub protocol: LendingProtocol,
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    pub log_index: u64,
+    pub liquidator: H160,
+    pub borrower: H160,
+    /// Underlying token of the repaid debt
+    pub debt_token: Option<H160>,
+    pub debt_repaid: U256,
+    /// Underlying token of the seized collateral
+    pub collateral_token: Option<H160>,
+    /// Seized collateral in underlying units; zero when only cTokens were seized and never redeemed
+    pub collateral_seized: U256,
+}
+
+pub fn aave_liquidation_call_topic() -> H256 {
+    H256::from_str(AAVE_LIQUIDATION_CALL_TOPIC).expect("valid LiquidationCall topic")
+}
+
+pub fn compound_liquidate_borrow_topic() -> H256 {
+    H256::from_str(COMPOUND_LIQUIDATE_BORROW_TOPIC).expect("valid LiquidateBorrow topic")
+}
+
+/// Topics to request from a node to decode liquidations with [`decode_liquidations`]
+pub fn liquidation_topics() -> Vec<H256> {
+    vec![aave_liquidation_call_topic(), compound_liquidate_borrow_topic(), tokens::transfer_topic()]
+}
+
+/// Topics to request from a node to decode position events with [`decode_position_events`]
+pub fn position_topics() -> Vec<H256> {
+    [AAVE_SUPPLY_TOPIC, AAVE_BORROW_TOPIC, AAVE_REPAY_TOPIC, AAVE_WITHDRAW_TOPIC]
+        .iter()
+        .map(|topic| H256::from_str(topic).expect("valid position topic"))
+        .collect()
+}
+
+/// Decode every Aave position event in a set of logs
+pub fn decode_position_events(logs: &[Log]) -> Vec<PositionEvent> {
+    logs.iter()
+        .filter_map(|log| {
+            let topic = format!("{:?}", log.topics.first()?);
+            // (action, user, caller) from the event's indexed and data fields
+            let (action, user, caller) = match topic.as_str() {
+                AAVE_SUPPLY_TOPIC if log.topics.len() == 4 && log.data.len() == 64 => {
+                    (LendingAction::Supply, H160::from(log.topics[2]), address_word(&log.data, 0))
+                }
+                AAVE_BORROW_TOPIC if log.topics.len() == 4 && log.data.len() == 128 => {
+                    (LendingAction::Borrow, H160::from(log.topics[2]), address_word(&log.data, 0))
+                }
+                AAVE_REPAY_TOPIC if log.topics.len() == 4 && log.data.len() == 64 => {
+                    (LendingAction::Repay, H160::from(log.topics[2]), H160::from(log.topics[3]))
+                }
+                AAVE_WITHDRAW_TOPIC if log.topics.len() == 4 && log.data.len() == 32 => {
+                    (LendingAction::Withdraw, H160::from(log.topics[2]), H160::from(log.topics[2]))
+                }
+                _ => return None,
+            };
+            let amount_word = match action {
+                LendingAction::Supply | LendingAction::Borrow => 1,
+                LendingAction::Repay | LendingAction::Withdraw => 0,
+            };
+
+            Some(PositionEvent {
+                protocol: LendingProtocol::AaveV3,
+                action,
+                transaction_hash: log.transaction_hash?,
+                log_index: log.log_index?.as_u64(),
+                reserve: H160::from(log.topics[1]),
+                user,
+                caller,
+                amount: word(&log.data, amount_word),
+            })
+        })
+        .collect()
+}
+
+/// Decode every liquidation in a set of logs
+///
+/// Compound events name cTokens rather than underlying assets, so the
+/// transaction's ERC-20 transfers into the borrowed cToken and out of the
+/// collateral cToken are used to recover the underlying tokens and amounts.
+pub fn decode_liquidations(logs: &[Log]) -> Vec<Liquidation> {
+    let transfers: Vec<tokens::Erc20Transfer> = logs.iter().filter_map(tokens::decode_transfer).collect();
+
+    logs.iter()
+        .filter_map(|log| match log.topics.first() {
+            Some(topic) if *topic == aave_liquidation_call_topic() => decode_aave_liquidation(log),
+            Some(topic) if *topic == compound_liquidate_borrow_topic() => decode_compound_liquidation(log, &transfers),
+            _ => None,
+        })
+        .collect()
+}
+
+fn decode_aave_liquidation(log: &Log) -> Option<Liquidation> {
+    if log.topics.len() != 4 || log.data.len() != 128 {
+        return None;
+    }
+
+    Some(Liquidation {
+        protocol: LendingProtocol::AaveV3,
+        transaction_hash: log.transaction_hash?,
+        transaction_index: log.transaction_index?.as_u64(),
+        log_index: log.log_index?.as_u64(),
+        liquidator: address_word(&log.data, 2),
+        borrower: H160::from(log.topics[3]),
+        debt_token: Some(H160::from(log.topics[2])),
+        debt_repaid: word(&log.data, 0),
+        collateral_token: Some(H160::from(log.topics[1])),
+        collateral_seized: word(&log.data, 1),
+    })
+}
+
+fn decode_compound_liquidation(log: &Log, transfers: &[tokens::Erc20Transfer]) -> Option<Liquidation> {
+    if log.topics.len() != 1 || log.data.len() != 160 {
+        return None;
+    }
+
+    let transaction_hash = log.transaction_hash?;
+    let borrowed_ctoken = log.address;
+    let collateral_ctoken = address_word(&log.data, 3);
+    let repay_amount = word(&log.data, 2);
+    let tx_transfers: Vec<&tokens::Erc20Transfer> = transfers.iter()
+        .filter(|t| t.transaction_hash == Some(transaction_hash))
+        .collect();
+
+    // Repayment flows into the borrowed cToken; a redeem pays the collateral out of the collateral cToken
+    let debt_token = tx_transfers.iter()
+        .find(|t| t.to == borrowed_ctoken && t.value == repay_amount)
+        .map(|t| t.token);
+    let redeemed = tx_transfers.iter()
+        .rfind(|t| t.from == collateral_ctoken && t.token != collateral_ctoken);
+
+    Some(Liquidation {
+        protocol: LendingProtocol::CompoundV2,
+        transaction_hash,
+        transaction_index: log.transaction_index?.as_u64(),
+        log_index: log.log_index?.as_u64(),
+        liquidator: address_word(&log.data, 0),
+        borrower: address_word(&log.data, 1),
+        debt_token,
+        debt_repaid: repay_amount,
+        collateral_token: redeemed.map(|t| t.token),
+        collateral_seized: redeemed.map(|t| t.value).unwrap_or_default(),
+    })
+}
+
+fn word(data: &[u8], index: usize) -> U256 {
+    U256::from_big_endian(&data[index * 32..(index + 1) * 32])
+}
+
+fn address_word(data: &[u8], index: usize) -> H160 {
+    H160::from_slice(&data[index * 32 + 12..(index + 1) * 32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    fn address_topic(address: H160) -> H256 {
+        H256::from(address)
+    }
+
+    fn log(log_index: u64, address: H160, topics: Vec<H256>, words: &[U256]) -> Log {
+        let mut data = Vec::with_capacity(words.len() * 32);
+        for word in words {
+            let mut bytes = [0u8; 32];
+            word.to_big_endian(&mut bytes);
+            data.extend_from_slice(&bytes);
+        }
+        Log {
+            address,
+            topics,
+            data: data.into(),
+            transaction_hash: Some(H256::from_low_u64_be(7)),
+            transaction_index: Some(U64::from(2)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        }
+    }
+
+    fn address_word(address: H160) -> U256 {
+        U256::from_big_endian(address_topic(address).as_bytes())
+    }
+
+    fn transfer(log_index: u64, token: H160, from: H160, to: H160, amount: u64) -> Log {
+        log(log_index, token, vec![tokens::transfer_topic(), address_topic(from), address_topic(to)], &[U256::from(amount)])
+    }
+
+    #[test]
+    fn aave_liquidation_call_names_both_reserves() {
+        let (collateral, debt) = (H160::from_low_u64_be(0xc0), H160::from_low_u64_be(0xd0));
+        let (borrower, liquidator) = (H160::from_low_u64_be(0xb), H160::from_low_u64_be(0x1));
+        let call = log(
+            3,
+            H160::from_low_u64_be(0xaa),
+            vec![aave_liquidation_call_topic(), address_topic(collateral), address_topic(debt), address_topic(borrower)],
+            &[U256::from(18_000u64), U256::from(10u64), address_word(liquidator), U256::zero()],
+        );
+
+        let liquidations = decode_liquidations(&[call]);
+        assert_eq!(liquidations.len(), 1);
+        let liquidation = &liquidations[0];
+        assert_eq!(liquidation.protocol, LendingProtocol::AaveV3);
+        assert_eq!((liquidation.liquidator, liquidation.borrower), (liquidator, borrower));
+        assert_eq!((liquidation.debt_token, liquidation.debt_repaid), (Some(debt), U256::from(18_000u64)));
+        assert_eq!((liquidation.collateral_token, liquidation.collateral_seized), (Some(collateral), U256::from(10u64)));
+        assert_eq!((liquidation.transaction_index, liquidation.log_index), (2, 3));
+    }
+
+    #[test]
+    fn compound_liquidation_recovers_underlying_tokens_from_transfers() {
+        let (c_debt, c_collateral) = (H160::from_low_u64_be(0xcd), H160::from_low_u64_be(0xcc));
+        let (debt, collateral) = (H160::from_low_u64_be(0xd0), H160::from_low_u64_be(0xc0));
+        let (borrower, liquidator) = (H160::from_low_u64_be(0xb), H160::from_low_u64_be(0x1));
+        let logs = vec![
+            transfer(0, debt, liquidator, c_debt, 5_000),
+            log(
+                1,
+                c_debt,
+                vec![compound_liquidate_borrow_topic()],
+                &[address_word(liquidator), address_word(borrower), U256::from(5_000u64), address_word(c_collateral), U256::from(250u64)],
+            ),
+            // Seized cTokens, then redeemed for the underlying collateral
+            transfer(2, c_collateral, borrower, liquidator, 250),
+            transfer(3, collateral, c_collateral, liquidator, 5_400),
+        ];
+
+        let liquidations = decode_liquidations(&logs);
+        assert_eq!(liquidations.len(), 1);
+        let liquidation = &liquidations[0];
+        assert_eq!(liquidation.protocol, LendingProtocol::CompoundV2);
+        assert_eq!((liquidation.liquidator, liquidation.borrower), (liquidator, borrower));
+        assert_eq!((liquidation.debt_token, liquidation.debt_repaid), (Some(debt), U256::from(5_000u64)));
+        assert_eq!((liquidation.collateral_token, liquidation.collateral_seized), (Some(collateral), U256::from(5_400u64)));
+
+        // Without a redeem only cTokens were seized, so no underlying amount is known
+        let unredeemed = decode_liquidations(&logs[..3]);
+        assert_eq!((unredeemed[0].collateral_token, unredeemed[0].collateral_seized), (None, U256::zero()));
+    }
+
+    #[test]
+    fn position_events_decode_user_and_amount() {
+        let (reserve, user, caller) = (H160::from_low_u64_be(0xd0), H160::from_low_u64_be(0xb), H160::from_low_u64_be(0x1));
+        let supply = log(
+            0,
+            H160::from_low_u64_be(0xaa),
+            vec![position_topics()[0], address_topic(reserve), address_topic(user), H256::zero()],
+            &[address_word(caller), U256::from(1_000u64)],
+        );
+        let malformed = log(1, H160::from_low_u64_be(0xaa), vec![position_topics()[1], address_topic(reserve)], &[]);
+
+        let events = decode_position_events(&[supply, malformed]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, LendingAction::Supply);
+        assert_eq!((events[0].reserve, events[0].user, events[0].caller), (reserve, user, caller));
+        assert_eq!(events[0].amount, U256::from(1_000u64));
+    }
+}
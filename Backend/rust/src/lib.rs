@@ -4,7 +4,29 @@
 //! and blockchain analytics for the ZKWatch platform.
 
 pub mod zk_proofs;
+pub mod commitments;
+pub mod stark;
+pub mod proof_envelope;
 pub mod blockchain;
+pub mod block_source;
+pub mod bridges;
+pub mod tokens;
+pub mod dex;
+pub mod exchange_flows;
+pub mod flash_loans;
+pub mod fund_flow;
+pub mod lending;
+pub mod mev;
+pub mod labels;
+pub mod pricing;
+pub mod screening;
+pub mod chain_tracker;
+pub mod checkpoint;
+pub mod database;
+pub mod warehouse;
+pub mod mempool;
+pub mod subscription;
+pub mod clustering;
 pub mod whale_tracker;
 pub mod analytics;
 
@@ -27,6 +49,27 @@ pub enum ZKWatchError {
     #[error("ZK-proof verification failed")]
     ProofVerification,
     
+    #[error("ZK-proof generation failed: {0}")]
+    ProofGeneration(String),
+    
+    #[error("Invalid commitment: {0}")]
+    InvalidCommitment(String),
+    
+    #[error("Unsupported proof envelope version {0}")]
+    UnsupportedProofVersion(u16),
+    
+    #[error("Unknown proof scheme {0}")]
+    UnknownProofScheme(u16),
+    
+    #[error("Unknown verification key {0}")]
+    UnknownVerificationKey(String),
+    
+    #[error("Malformed proof: {0}")]
+    MalformedProof(String),
+    
+    #[error("No USD price for {0}")]
+    MissingPrice(String),
+    
     #[error("Analytics computation error: {0}")]
     Analytics(String),
     
@@ -35,21 +78,82 @@ pub enum ZKWatchError {
     
     #[error("JSON serialization error: {0}")]
     Json(#[from] serde_json::Error),
+    
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    
+    #[error("Data warehouse error: {0}")]
+    Warehouse(#[from] polars::prelude::PolarsError),
 }
 
 /// Whale tracking data structure
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WhaleTransaction {
     pub hash: String,
+    pub network: String,
+    pub chain_id: u64,
     pub from: String,
     pub to: String,
+    /// Raw amount in the smallest unit of the native asset or of `token`
     pub value: u128,
+    /// Part of `value` borrowed in a flash loan and repaid within the transaction
+    #[serde(default)]
+    pub borrowed: u128,
+    /// ERC-20 token moved, `None` for native-asset transfers
+    pub token: Option<tokens::TokenInfo>,
+    /// Position of the transfer log within the block for token transfers
+    pub log_index: Option<u64>,
+    /// USD value at the block timestamp, `None` when the asset has no price
+    pub usd_value: Option<f64>,
+    /// Depth-based confirmation stage of the including block
+    pub confirmation: chain_tracker::ConfirmationStatus,
     pub gas_used: u64,
     pub block_number: u64,
     pub timestamp: DateTime<Utc>,
     pub zk_proof_hash: Option<String>,
     pub risk_score: f64,
     pub pattern_type: TransactionPattern,
+    /// Movement into, out of or between labeled exchange wallets
+    #[serde(default)]
+    pub exchange_flow: exchange_flows::ExchangeFlow,
+}
+
+impl WhaleTransaction {
+    /// Decimals of the transferred asset (18 for native transfers)
+    pub fn decimals(&self) -> u8 {
+        self.token.as_ref().map(|t| t.decimals).unwrap_or(18)
+    }
+
+    /// Amount in whole units of the transferred asset
+    pub fn amount(&self) -> f64 {
+        self.value as f64 / 10f64.powi(self.decimals() as i32)
+    }
+
+    /// Raw amount counted in volume totals; borrowed flash-loan notional is not real capital
+    pub fn volume(&self) -> u128 {
+        self.value.saturating_sub(self.borrowed)
+    }
+
+    /// Whole units of the asset counted in volume totals
+    pub fn volume_amount(&self) -> f64 {
+        self.amount() * self.volume_share()
+    }
+
+    /// USD value counted in volume totals
+    pub fn volume_usd(&self) -> Option<f64> {
+        self.usd_value.map(|usd| usd * self.volume_share())
+    }
+
+    fn volume_share(&self) -> f64 {
+        if self.value == 0 {
+            1.0
+        } else {
+            self.volume() as f64 / self.value as f64
+        }
+    }
 }
 
 /// ZK-proof structure
@@ -67,11 +171,28 @@ pub struct NetworkConfig {
     pub name: String,
     pub chain_id: u64,
     pub rpc_url: String,
+    /// WebSocket endpoint for live subscriptions; networks without one are polled
+    pub ws_url: Option<String>,
     pub explorer_url: String,
+    /// Symbol of the native gas asset, used for price lookups
+    pub native_symbol: String,
+    /// Blocks on top of a transaction's block before it counts as safe
+    pub confirmation_depth: u64,
+    /// Blocks on top of a transaction's block before it counts as final
+    pub finality_depth: u64,
+    /// Average time between blocks, in milliseconds
+    pub block_time_ms: u64,
+}
+
+impl NetworkConfig {
+    /// Blocks produced over a span of time, at least one
+    pub fn blocks_in(&self, span: chrono::Duration) -> u64 {
+        (span.num_milliseconds().max(0) as u64 / self.block_time_ms.max(1)).max(1)
+    }
 }
 
 /// Transaction pattern classification
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum TransactionPattern {
     Standard,
     LargeTransaction,
@@ -87,19 +208,23 @@ pub enum TransactionPattern {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AnalyticsMetrics {
     pub total_whale_transactions: u64,
-    pub total_volume: u128,
-    pub average_transaction_size: f64,
+    /// USD value moved by priced whale transfers; unpriced transfers are left out
+    pub total_volume_usd: f64,
+    /// Mean USD value of the priced whale transfers
+    pub average_transaction_usd: f64,
     pub suspected_manipulation_count: u64,
     pub top_whale_addresses: Vec<String>,
     pub risk_distribution: HashMap<TransactionPattern, u64>,
     pub time_series_data: Vec<TimeSeriesPoint>,
+    /// Net exchange inflow per network and token over time
+    pub exchange_flow_series: Vec<exchange_flows::ExchangeFlowPoint>,
 }
 
 /// Time series data point
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TimeSeriesPoint {
     pub timestamp: DateTime<Utc>,
-    pub volume: u128,
+    pub volume_usd: f64,
     pub transaction_count: u64,
     pub avg_gas_price: f64,
 }
@@ -107,7 +232,8 @@ pub struct TimeSeriesPoint {
 /// Whale tracking configuration
 #[derive(Debug, Clone)]
 pub struct WhaleTrackerConfig {
-    pub min_transaction_threshold: u128,
+    /// Minimum USD value of a whale transfer at its block timestamp
+    pub min_transaction_value_usd: f64,
     pub tracking_networks: Vec<NetworkConfig>,
     pub zk_proof_enabled: bool,
     pub real_time_monitoring: bool,
@@ -117,31 +243,51 @@ pub struct WhaleTrackerConfig {
 impl Default for WhaleTrackerConfig {
     fn default() -> Self {
         Self {
-            min_transaction_threshold: 100_000_000_000_000_000_000u128, // 100 ETH
+            min_transaction_value_usd: 250_000.0,
             tracking_networks: vec![
                 NetworkConfig {
                     name: "Ethereum".to_string(),
                     chain_id: 1,
                     rpc_url: "https://eth-mainnet.g.alchemy.com/v2/demo".to_string(),
+                    ws_url: Some("wss://eth-mainnet.g.alchemy.com/v2/demo".to_string()),
                     explorer_url: "https://etherscan.io".to_string(),
+                    native_symbol: "ETH".to_string(),
+                    confirmation_depth: 12,
+                    finality_depth: 64,
+                    block_time_ms: 12_000,
                 },
                 NetworkConfig {
                     name: "Polygon".to_string(),
                     chain_id: 137,
                     rpc_url: "https://polygon-rpc.com".to_string(),
+                    ws_url: None,
                     explorer_url: "https://polygonscan.com".to_string(),
+                    native_symbol: "MATIC".to_string(),
+                    confirmation_depth: 32,
+                    finality_depth: 256,
+                    block_time_ms: 2_000,
                 },
                 NetworkConfig {
                     name: "Arbitrum".to_string(),
                     chain_id: 42161,
                     rpc_url: "https://arb1.arbitrum.io/rpc".to_string(),
+                    ws_url: Some("wss://arb1.arbitrum.io/ws".to_string()),
                     explorer_url: "https://arbiscan.io".to_string(),
+                    native_symbol: "ETH".to_string(),
+                    confirmation_depth: 20,
+                    finality_depth: 300,
+                    block_time_ms: 250,
                 },
                 NetworkConfig {
                     name: "Optimism".to_string(),
                     chain_id: 10,
                     rpc_url: "https://mainnet.optimism.io".to_string(),
+                    ws_url: None,
                     explorer_url: "https://optimistic.etherscan.io".to_string(),
+                    native_symbol: "ETH".to_string(),
+                    confirmation_depth: 20,
+                    finality_depth: 300,
+                    block_time_ms: 2_000,
                 },
             ],
             zk_proof_enabled: true,
@@ -5,8 +5,10 @@
 //! and blockchain analytics.
 
 use zkwatch_core::*;
+use zkwatch_core::blockchain::MultiChainScanner;
+use zkwatch_core::whale_tracker::{AdvancedWhaleTracker, PredictionAction};
+use chrono::Utc;
 use std::env;
-use tokio;
 
 #[tokio::main]
 async fn main() -> ZKWatchResult<()> {
@@ -20,79 +22,216 @@ async fn main() -> ZKWatchResult<()> {
     match args[1].as_str() {
         "scan" => {
             let config = WhaleTrackerConfig::default();
-            let networks = config.tracking_networks.clone();
-            let mut tracker = AdvancedWhaleTracker::new(config, networks);
+            let min_usd = args.get(2)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(config.min_transaction_value_usd);
+            let mut scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_price_source(load_price_source()?)
+                .with_label_registry(load_label_registry()?)
+                .with_screener(load_screener()?)
+                .with_checkpoints(open_checkpoint_store()?);
             
-            let min_value = args[2].parse::<u128>().unwrap_or(100_000_000_000_000_000_000u128);
+            println!("🔍 Starting whale scanning with minimum value: ${:.0}", min_usd);
             
-            println!("🔍 Starting whale scanning with minimum value: {} ETH", min_value as f64 / 1e18);
+            let transactions = scanner.scan_whale_transactions(min_usd).await?;
+            record_analytics(&transactions).await?;
             
-            let transactions = tracker.scanner.scan_whale_transactions(min_value).await?;
+            println!("📊 Found {} whale transactions:", transactions.len());
+            for tx in &transactions {
+                let symbol = match &tx.token {
+                    Some(token) => token.symbol.clone(),
+                    None => config.tracking_networks.iter()
+                        .find(|n| n.name == tx.network)
+                        .map(|n| n.native_symbol.clone())
+                        .unwrap_or_default(),
+                };
+                println!("  - {:.2} {} (${:.0}) from {} to {} ({}){}", 
+                    tx.amount(),
+                    symbol,
+                    tx.usd_value.unwrap_or_default(),
+                    scanner.labels().display(tx.chain_id, &tx.from), 
+                    scanner.labels().display(tx.chain_id, &tx.to),
+                    tx.network,
+                    flow_tag(&tx.exchange_flow)
+                );
+            }
+            
+            let flags = scanner.screener().screen_transactions(&transactions);
+            if !flags.is_empty() {
+                println!("\n⛔ {} transactions touch sanctioned or watched addresses:", flags.len());
+                for flag in &flags {
+                    println!("  - {}", flag.subject);
+                    print_screening_evidence(&flag.evidence);
+                }
+            }
+        }
+        
+        "backfill" => {
+            let (Some(network), Some(from), Some(to)) = (
+                flag_value(&args, "--network"),
+                flag_value(&args, "--from").and_then(|v| v.parse::<u64>().ok()),
+                flag_value(&args, "--to").and_then(|v| v.parse::<u64>().ok()),
+            ) else {
+                println!("Usage: {} backfill --network <name> --from <block> --to <block> [--min-usd <usd>] [--chunk-size <blocks>] [--concurrency <tasks>]", args[0]);
+                return Ok(());
+            };
+            
+            let config = WhaleTrackerConfig::default();
+            let min_usd = flag_value(&args, "--min-usd")
+                .and_then(|v| v.parse::<f64>().ok())
+                .unwrap_or(config.min_transaction_value_usd);
+            let chunk_size = flag_value(&args, "--chunk-size").and_then(|v| v.parse::<u64>().ok()).unwrap_or(blockchain::DEFAULT_BACKFILL_CHUNK_SIZE);
+            let concurrency = flag_value(&args, "--concurrency").and_then(|v| v.parse::<usize>().ok()).unwrap_or(blockchain::DEFAULT_BACKFILL_CONCURRENCY);
+            let scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_price_source(load_price_source()?)
+                .with_label_registry(load_label_registry()?)
+                .with_checkpoints(open_checkpoint_store()?);
+            
+            println!("⏪ Backfilling {} blocks {}..={} (minimum value: ${:.0})", network, from, to, min_usd);
+            
+            let transactions = scanner.backfill(network, from, to, min_usd, chunk_size, concurrency).await?;
+            record_analytics(&transactions).await?;
             
             println!("📊 Found {} whale transactions:", transactions.len());
             for tx in &transactions {
-                println!("  - {} ETH from {} to {}", 
-                    tx.value as f64 / 1e18, 
-                    &tx.from[..10]..., 
-                    &tx.to[..10]...
+                let symbol = match &tx.token {
+                    Some(token) => token.symbol.clone(),
+                    None => config.tracking_networks.iter()
+                        .find(|n| n.name == tx.network)
+                        .map(|n| n.native_symbol.clone())
+                        .unwrap_or_default(),
+                };
+                println!("  - #{} {:.2} {} (${:.0}) {} → {} {} ({:?})",
+                    tx.block_number,
+                    tx.amount(),
+                    symbol,
+                    tx.usd_value.unwrap_or_default(),
+                    scanner.labels().display(tx.chain_id, &tx.from),
+                    scanner.labels().display(tx.chain_id, &tx.to),
+                    tx.hash,
+                    tx.confirmation
                 );
             }
         }
         
         "analyze" => {
             let config = WhaleTrackerConfig::default();
-            let networks = config.tracking_networks.clone();
-            let mut tracker = AdvancedWhaleTracker::new(config, networks);
+            let scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_label_registry(load_label_registry()?);
+            let mut tracker = AdvancedWhaleTracker::with_scanner(config, scanner)
+                .with_database(open_whale_database()?);
             
             println!("🧠 Running comprehensive analytics...");
             
             let analytics = tracker.generate_comprehensive_analytics().await?;
             
             println!("📈 Analytics Summary:");
-            println!("  - Total Volume: {:.2} ETH", analytics.summary.total_volume as f64 / 1e18);
+            println!("  - Total Volume: ${:.0}", analytics.summary.total_volume_usd);
             println!("  - Transaction Count: {}", analytics.summary.total_whale_transactions);
-            println!("  - Avg Transaction: {:.2} ETH", analytics.summary.average_transaction_size as f64 / 1e18);
+            println!("  - Avg Transaction: ${:.0}", analytics.summary.average_transaction_usd);
             println!("  - Risk Factors: {}", analytics.summary.suspected_manipulation_count);
             
+            if !analytics.summary.exchange_flow_series.is_empty() {
+                println!("\n🏦 Net Exchange Flows:");
+                for point in &analytics.summary.exchange_flow_series {
+                    println!("  - {} {} {}: {:+.2} (in {:.2}, out {:.2}, ${:+.0})",
+                        point.timestamp.format("%Y-%m-%d %H:%M"),
+                        point.network,
+                        point.token,
+                        point.net_flow,
+                        point.inflow,
+                        point.outflow,
+                        point.net_flow_usd
+                    );
+                }
+            }
+            
             // Show detected patterns
             if !analytics.detected_patterns.is_empty() {
                 println!("\n🎯 Detected Patterns:");
                 for pattern in &analytics.detected_patterns {
-                    println!("  - {}: {} (confidence: {:.2}%)", 
+                    println!("  - {:?}: {} (confidence: {:.2}%)", 
                         pattern.pattern_type, pattern.description, pattern.confidence * 100.0);
                 }
             }
         }
         
         "zkproof" => {
-            let mut generator = ZKSNARKGenerator::new();
+            let config = WhaleTrackerConfig::default();
+            let threshold = flag_value(&args, "--threshold")
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(100.0);
+            let mut scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_price_source(load_price_source()?);
+            let mut generator = zk_proofs::ZKSNARKGenerator::new();
+            
+            println!("🔍 Scanning for a whale transaction to prove...");
+            
+            let transactions = scanner.scan_whale_transactions(config.min_transaction_value_usd).await?;
+            let tx = match flag_value(&args, "--hash") {
+                Some(hash) => transactions.iter().find(|tx| tx.hash.eq_ignore_ascii_case(hash)),
+                None => transactions.iter().max_by_key(|tx| tx.value),
+            };
+            let Some(tx) = tx else {
+                println!("❌ No matching whale transaction found");
+                return Ok(());
+            };
+            let symbol = match &tx.token {
+                Some(token) => token.symbol.clone(),
+                None => config.tracking_networks.iter()
+                    .find(|n| n.name == tx.network)
+                    .map(|n| n.native_symbol.clone())
+                    .unwrap_or_default(),
+            };
             
-            println!("🔐 Generating ZK-proof for whale transaction...");
+            println!("🔐 Generating ZK-proof for {} ({})...", tx.hash, tx.network);
             
-            let tx_inputs = WhaleTransactionInputs {
-                tx_hash: "0x1234567890abcdef1234567890abcdef12345678".to_string(),
-                block_number: 18000000,
-                timestamp: Utc::now(),
-                amount_commitment: "commitment_hash_1".to_string(),
-                address_commitment: "commitment_hash_2".to_string(),
-                gas_commitment: "commitment_hash_3".to_string(),
-            };
+            let threshold_units = (threshold * 10f64.powi(zk_proofs::PROOF_AMOUNT_DECIMALS as i32)) as u64;
+            let tx_inputs = zk_proofs::WhaleTransactionInputs::from_transaction(tx, threshold_units)?;
             
             let proof = generator.generate_whale_transaction_proof(&tx_inputs)?;
             
             println!("✅ ZK-proof generated successfully!");
             println!("  - Proof size: {} bytes", proof.proof_data.len());
             println!("  - Public inputs: {}", proof.public_inputs.len());
+            println!("  - Claim: amount ≥ {} {}, commitment {}", threshold, symbol, tx_inputs.amount_commitment);
             println!("  - Verification key: {}", proof.verification_key);
             
             let is_valid = generator.verify_proof(&proof)?;
             println!("  - Verification: {}", if is_valid { "Valid ✅" } else { "Invalid ❌" });
+            
+            if let Some(path) = flag_value(&args, "--out") {
+                let mut registry = load_verification_keys()?;
+                registry.register(generator.verification_key().clone());
+                save_verification_keys(&registry)?;
+                
+                let envelope = proof_envelope::ProofEnvelope::seal(&proof, &registry)?;
+                std::fs::write(path, envelope.encode())?;
+                println!("💾 Proof envelope written to {}", path);
+            }
+        }
+        
+        "zkverify" => {
+            let Some(path) = args.get(2) else {
+                println!("❌ Usage: zkverify <envelope file>");
+                return Ok(());
+            };
+            let registry = load_verification_keys()?;
+            let envelope = proof_envelope::ProofEnvelope::decode(&std::fs::read(path)?)?;
+            
+            println!("🔐 Verifying {:?} proof envelope v{}...", envelope.scheme, envelope.version);
+            println!("  - Created: {}", envelope.created_at);
+            println!("  - Public inputs: {}", envelope.public_inputs.len());
+            
+            let is_valid = envelope.verify(&registry)?;
+            println!("  - Verification: {}", if is_valid { "Valid ✅" } else { "Invalid ❌" });
         }
         
         "mev" => {
             let config = WhaleTrackerConfig::default();
             let networks = config.tracking_networks.clone();
-            let mut scanner = MultiChainScanner::new(networks);
+            let mut scanner = MultiChainScanner::from_rpc(networks)
+                .with_label_registry(load_label_registry()?);
             
             println!("⚡ Detecting MEV opportunities...");
             
@@ -101,11 +240,16 @@ async fn main() -> ZKWatchResult<()> {
             if !opportunities.is_empty() {
                 println!("💰 Found {} MEV opportunities:", opportunities.len());
                 for opp in &opportunities {
-                    println!("  - {}: {:.2} ETH profit (confidence: {:.1}%)", 
+                    println!("  - {:?} in block {} ({}): {:.4} native profit, {:.4} victim loss (confidence: {:.1}%)", 
                         opp.opportunity_type, 
+                        opp.block_number,
+                        opp.network,
                         opp.estimated_profit as f64 / 1e18, 
+                        opp.victim_loss as f64 / 1e18,
                         opp.confidence * 100.0
                     );
+                    let chain_id = chain_id(&config, &opp.network);
+                    println!("    searcher {} txs {}", scanner.labels().display(chain_id, &opp.searcher), opp.transaction_hashes.join(", "));
                 }
             } else {
                 println!("❌ No MEV opportunities detected");
@@ -121,7 +265,9 @@ async fn main() -> ZKWatchResult<()> {
             let address = &args[2];
             let config = WhaleTrackerConfig::default();
             let networks = config.tracking_networks.clone();
-            let mut scanner = MultiChainScanner::new(networks);
+            let mut scanner = MultiChainScanner::from_rpc(networks)
+                .with_label_registry(load_label_registry()?)
+                .with_screener(load_screener()?);
             
             println!("🌉 Analyzing cross-chain activity for: {}", address);
             
@@ -131,11 +277,13 @@ async fn main() -> ZKWatchResult<()> {
             println!("  - Networks analyzed: {}", analysis.networks_analyzed.len());
             println!("  - Total volume: {:.2} ETH", analysis.total_cross_chain_volume as f64 / 1e18);
             println!("  - Risk level: {:?}", analysis.risk_assessment);
+            print_screening_evidence(&analysis.risk_evidence);
             
             if !analysis.bridge_patterns.is_empty() {
                 println!("\n🔗 Bridge Patterns:");
                 for pattern in &analysis.bridge_patterns {
                     println!("  - {} (confidence: {:.1}%)", pattern.pattern_type, pattern.confidence * 100.0);
+                    println!("    {}", pattern.description);
                 }
             }
             
@@ -149,8 +297,13 @@ async fn main() -> ZKWatchResult<()> {
         
         "predict" => {
             let config = WhaleTrackerConfig::default();
-            let networks = config.tracking_networks.clone();
-            let mut tracker = AdvancedWhaleTracker::new(config, networks);
+            let labels = load_label_registry()?;
+            // Predictions are made from the primary network's whales
+            let primary_chain_id = config.tracking_networks.first().map(|n| n.chain_id).unwrap_or_default();
+            let scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_label_registry(labels.clone());
+            let mut tracker = AdvancedWhaleTracker::with_scanner(config, scanner)
+                .with_database(open_whale_database()?);
             
             println!("🔮 Generating movement predictions...");
             
@@ -160,7 +313,7 @@ async fn main() -> ZKWatchResult<()> {
                 println!("🎯 Predictions:");
                 for pred in &predictions {
                     println!("  - {}: {} ({:.1}% confidence)", 
-                        &pred.predicted_address[..10]...,
+                        labels.display(primary_chain_id, &pred.predicted_address),
                         match pred.predicted_action {
                             PredictionAction::LargePurchase => "Large Purchase",
                             PredictionAction::LargeSale => "Large Sale",
@@ -186,7 +339,7 @@ async fn main() -> ZKWatchResult<()> {
             let address = &args[2];
             let config = WhaleTrackerConfig::default();
             let networks = config.tracking_networks.clone();
-            let mut scanner = MultiChainScanner::new(networks);
+            let mut scanner = MultiChainScanner::from_rpc(networks);
             
             println!("🏛️ Analyzing DeFi interactions for: {}", address);
             
@@ -195,12 +348,17 @@ async fn main() -> ZKWatchResult<()> {
             if !interactions.is_empty() {
                 println!("📈 Found {} DeFi interactions:", interactions.len());
                 for interaction in &interactions {
+                    // Unknown tokens have no decimals to scale by; show the raw amount
+                    let amount_out = match interaction.token_out_decimals {
+                        Some(decimals) => format!("{}", interaction.amount_out as f64 / 10f64.powi(decimals as i32)),
+                        None => format!("{} (raw)", interaction.amount_out),
+                    };
                     println!("  - {}: {} {} → {} {} ({})", 
                         interaction.protocol,
                         interaction.action,
                         interaction.token_in,
                         interaction.token_out,
-                        (interaction.amount_out as f64 / 1e6).to_string(),
+                        amount_out,
                         interaction.network
                     );
                 }
@@ -209,30 +367,169 @@ async fn main() -> ZKWatchResult<()> {
             }
         }
         
+        "trace" => {
+            if args.len() < 3 || args[2].starts_with("--") {
+                println!("Usage: {} trace <address|tx-hash> [--network <name>] [--hops <n>] [--policy haircut|fifo] [--json <file>] [--dot <file>]", args[0]);
+                return Ok(());
+            }
+            
+            let config = WhaleTrackerConfig::default();
+            let target = &args[2];
+            // Transaction hashes are 32 bytes, addresses 20
+            let seed = if target.len() == 66 {
+                let network = flag_value(&args, "--network")
+                    .map(str::to_string)
+                    .or_else(|| config.tracking_networks.first().map(|n| n.name.clone()))
+                    .unwrap_or_default();
+                fund_flow::TaintSeed::Transaction { network, hash: target.clone() }
+            } else {
+                fund_flow::TaintSeed::Address(target.to_ascii_lowercase())
+            };
+            let defaults = fund_flow::TraceConfig::default();
+            let trace_config = fund_flow::TraceConfig {
+                max_hops: flag_value(&args, "--hops").and_then(|v| v.parse::<usize>().ok()).unwrap_or(defaults.max_hops),
+                policy: flag_value(&args, "--policy").map(str::parse).transpose()?.unwrap_or(defaults.policy),
+                ..defaults
+            };
+            let scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_label_registry(load_label_registry()?);
+            
+            println!("🕵️ Tracing funds from {} ({:?} taint, {} hops)...", target, trace_config.policy, trace_config.max_hops);
+            
+            let graph = scanner.trace_funds(&seed, &trace_config).await?;
+            
+            println!("📊 Reached {} addresses through {} tainted transfers", graph.nodes.len(), graph.edges.len());
+            for node in graph.nodes.iter().filter(|n| n.hop > 0) {
+                let received: Vec<String> = node.tainted_received.iter()
+                    .map(|(symbol, amount)| format!("{:.4} {}", amount, symbol))
+                    .collect();
+                println!("  - hop {} {:?} {} on {}: {}",
+                    node.hop,
+                    node.kind,
+                    node.label.clone().unwrap_or_else(|| node.address.clone()),
+                    node.network,
+                    received.join(", ")
+                );
+            }
+            
+            if let Some(path) = flag_value(&args, "--json") {
+                std::fs::write(path, graph.to_json()?)?;
+                println!("💾 Flow graph written to {}", path);
+            }
+            if let Some(path) = flag_value(&args, "--dot") {
+                std::fs::write(path, graph.to_dot())?;
+                println!("💾 Graphviz graph written to {}", path);
+            }
+        }
+        
         "realtime" => {
             let config = WhaleTrackerConfig {
                 real_time_monitoring: true,
                 ..WhaleTrackerConfig::default()
             };
             let networks = config.tracking_networks.clone();
-            let mut tracker = AdvancedWhaleTracker::new(config, networks);
+            let labels = load_label_registry()?;
+            let scanner = MultiChainScanner::from_rpc(networks)
+                .with_price_source(load_price_source()?)
+                .with_label_registry(labels.clone())
+                .with_checkpoints(open_checkpoint_store()?);
+            let subscription_config = subscription::SubscriptionConfig {
+                pending_transactions: args.iter().any(|arg| arg == "--pending"),
+                ..subscription::SubscriptionConfig::default()
+            };
+            let mut tracker = AdvancedWhaleTracker::with_scanner(config, scanner)
+                .with_subscription_config(subscription_config)
+                .with_database(open_whale_database()?);
             
             println!("🚀 Starting real-time whale monitoring...");
             println!("Press Ctrl+C to stop");
             
-            let _handle = tracker.start_real_time_monitoring().await?;
+            let mut handle = tracker.start_real_time_monitoring().await?;
+            let mut analytics = open_analytics_engine();
             
-            // Keep the process running
             loop {
-                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-                println!("🔄 Monitoring active... {}", Utc::now().format("%H:%M:%S"));
+                let event = tokio::select! {
+                    event = handle.next_event() => event,
+                    _ = tokio::signal::ctrl_c() => None,
+                };
+                
+                if let Some(event) = &event {
+                    if let Err(e) = tracker.record_event(event) {
+                        println!("⚠️ Failed to store event: {}", e);
+                    }
+                }
+                if let Some(subscription::MonitorEvent::Scan(blockchain::ScanEvent::Detected(tx))) = &event {
+                    if let Err(e) = analytics.process_transaction(tx).await {
+                        println!("⚠️ Failed to record analytics: {}", e);
+                    }
+                }
+                
+                match event {
+                    Some(subscription::MonitorEvent::Scan(blockchain::ScanEvent::Detected(tx))) => {
+                        println!("🐋 {} {:.2} {} (${:.0}) {} → {} in block {} ({}){}",
+                            Utc::now().format("%H:%M:%S"),
+                            tx.amount(),
+                            tx.token.as_ref().map(|t| t.symbol.as_str()).unwrap_or("native"),
+                            tx.usd_value.unwrap_or_default(),
+                            labels.display(tx.chain_id, &tx.from),
+                            labels.display(tx.chain_id, &tx.to),
+                            tx.block_number,
+                            tx.network,
+                            flow_tag(&tx.exchange_flow)
+                        );
+                    }
+                    Some(subscription::MonitorEvent::Scan(blockchain::ScanEvent::StatusChanged { network, hash, status, .. })) => {
+                        println!("✔️ {} {} is now {:?} ({})", Utc::now().format("%H:%M:%S"), hash, status, network);
+                    }
+                    Some(subscription::MonitorEvent::Scan(blockchain::ScanEvent::Retracted { transaction, .. })) => {
+                        println!("↩️ {} {} reorganized out ({})", Utc::now().format("%H:%M:%S"), transaction.hash, transaction.network);
+                    }
+                    Some(subscription::MonitorEvent::Mempool(event)) => {
+                        let tx = &event.transaction;
+                        let status = match &event.status {
+                            mempool::MempoolStatus::Pending => "pending".to_string(),
+                            mempool::MempoolStatus::Replaced { by } => format!("replaced by {}", by),
+                            mempool::MempoolStatus::Landed { block_number } => format!("landed in block {}", block_number),
+                            mempool::MempoolStatus::Dropped => "dropped".to_string(),
+                        };
+                        println!("⏳ {} {:.2} {} (${:.0}) {} → {} {} {} ({}){}",
+                            Utc::now().format("%H:%M:%S"),
+                            tx.amount(),
+                            tx.token.as_ref().map(|t| t.symbol.as_str()).unwrap_or("native"),
+                            tx.usd_value.unwrap_or_default(),
+                            labels.display(tx.chain_id, &tx.from),
+                            labels.display(tx.chain_id, &tx.to),
+                            tx.hash,
+                            status,
+                            tx.network,
+                            flow_tag(&tx.exchange_flow)
+                        );
+                    }
+                    Some(subscription::MonitorEvent::Connected { network }) => {
+                        println!("🔌 Connected to {}", network);
+                    }
+                    Some(subscription::MonitorEvent::Disconnected { network, reason, retry_in_ms }) => {
+                        println!("⚠️ {} disconnected: {} (retrying in {}s)", network, reason, retry_in_ms / 1000);
+                    }
+                    None => break,
+                }
             }
+            
+            handle.stop().await;
+            analytics.flush()?;
+            println!("🛑 Monitoring stopped");
         }
         
         "clusters" => {
             let config = WhaleTrackerConfig::default();
-            let networks = config.tracking_networks.clone();
-            let mut tracker = AdvancedWhaleTracker::new(config, networks);
+            let labels = load_label_registry()?;
+            // Cluster members are listed by their label on the primary network
+            let primary_chain_id = config.tracking_networks.first().map(|n| n.chain_id).unwrap_or_default();
+            let scanner = MultiChainScanner::from_rpc(config.tracking_networks.clone())
+                .with_label_registry(labels.clone())
+                .with_screener(load_screener()?);
+            let mut tracker = AdvancedWhaleTracker::with_scanner(config, scanner)
+                .with_database(open_whale_database()?);
             
             println!("🎭 Detecting whale clusters...");
             
@@ -241,11 +538,24 @@ async fn main() -> ZKWatchResult<()> {
             if !clusters.is_empty() {
                 println!("👥 Found {} whale clusters:", clusters.len());
                 for cluster in &clusters {
-                    println!("  - Cluster {}: {} addresses ({})", 
+                    println!("  - Cluster {}: {} addresses ({}, confidence {:.2})", 
                         cluster.cluster_id,
                         cluster.member_addresses.len(),
-                        cluster.cluster_type
+                        cluster.cluster_type,
+                        cluster.confidence
                     );
+                    println!("    Volume: ${:.0}, active {} to {}",
+                        cluster.total_volume_usd,
+                        cluster.first_observed.format("%Y-%m-%d %H:%M"),
+                        cluster.last_activity.format("%Y-%m-%d %H:%M")
+                    );
+                    for address in &cluster.member_addresses {
+                        println!("    {}", labels.display(primary_chain_id, address));
+                    }
+                    if cluster.risk_assessment == blockchain::RiskAssessment::High {
+                        println!("    ⛔ Risk: {:?}", cluster.risk_assessment);
+                        print_screening_evidence(&cluster.screening_evidence);
+                    }
                 }
             } else {
                 println!("❌ No significant whale clusters detected");
@@ -278,25 +588,42 @@ fn print_usage() {
 Usage: {} <command> [options]
 
 Commands:
-  scan [value]         Scan for whale transactions (default: 100 ETH threshold)
+  scan [usd]           Scan for whale transactions (default: $250,000 threshold)
+  backfill             Scan a historical block range, resuming interrupted runs
+    --network <name> --from <block> --to <block>
+    [--min-usd <usd>] [--chunk-size <blocks>] [--concurrency <tasks>]
   analyze              Run comprehensive whale analytics
-  zkproof              Generate ZK-proof for whale transaction
+  zkproof              Prove a scanned whale transaction's amount exceeds a threshold without revealing it
+    [--hash <tx>] [--threshold <amount>] [--out <file>]
+  zkverify <file>      Verify a proof envelope written by zkproof --out
   mev                  Detect MEV (Maximal Extractable Value) opportunities
   crosschain <addr>    Analyze cross-chain activity for address
   predict              Generate whale movement predictions
   defi <addr>          Analyze DeFi interactions for address
-  realtime             Start real-time whale monitoring
+  trace <addr|tx>      Follow tainted funds across hops and bridges
+    [--network <name>] [--hops <n>] [--policy haircut|fifo] [--json <file>] [--dot <file>]
+  realtime [--pending] Start real-time whale monitoring (--pending also flags whales in the mempool)
   clusters             Detect whale clusters
   version              Show version information
   help                 Show this help message
 
 Examples:
-  {} scan 500                    # Scan for whales with 500+ ETH transactions
+  {} scan 1000000                # Scan for whales moving $1M+
+  {} backfill --network Ethereum --from 18000000 --to 18010000
   {} analyze                     # Run full analytics
   {} crosschain 0x742d...C3F    # Analyze cross-chain activity
   {} defi 0x742d...C3F          # Check DeFi interactions
   {} realtime                    # Start live monitoring
 
+Environment:
+  ZKWATCH_PRICES          Price table (CSV/JSON, static or historical) used to value transfers in USD; must price each native asset
+  ZKWATCH_CHECKPOINT_DIR  Directory for scan checkpoints and backfill progress (default: .zkwatch/checkpoints)
+  ZKWATCH_DATABASE        SQLite database storing scanned whales and detected patterns (default: .zkwatch/whales.db)
+  ZKWATCH_WAREHOUSE       Parquet warehouse of scanned whales for analytics (default: .zkwatch/warehouse)
+  ZKWATCH_VERIFICATION_KEYS Registry of proof verification keys, kept across upgrades (default: .zkwatch/verification_keys.json)
+  ZKWATCH_LABELS          Address label files (JSON/CSV, separated like PATH) overriding the bundled labels
+  ZKWATCH_SCREENING_LISTS OFAC SDN (XML/CSV) and watchlist (CSV address,name,reason) files, separated like PATH
+
 For more information, visit: https://zkwatch.ai
 ", 
     env::args().next().unwrap_or("zkwatch".to_string()),
@@ -304,6 +631,127 @@ For more information, visit: https://zkwatch.ai
     env::args().next().unwrap_or("zkwatch".to_string()),
     env::args().next().unwrap_or("zkwatch".to_string()),
     env::args().next().unwrap_or("zkwatch".to_string()),
+    env::args().next().unwrap_or("zkwatch".to_string()),
     env::args().next().unwrap_or("zkwatch".to_string())
     );
-}
\ No newline at end of file
+}
+
+/// Price source from `ZKWATCH_PRICES`, falling back to stablecoin pegs only;
+/// whale scans then stop with a missing-price error until native prices are configured
+fn load_price_source() -> ZKWatchResult<std::sync::Arc<dyn pricing::PriceSource>> {
+    match env::var("ZKWATCH_PRICES") {
+        Ok(path) => pricing::load_price_file(path),
+        Err(_) => Ok(std::sync::Arc::new(pricing::StaticPriceTable::stablecoins())),
+    }
+}
+
+/// Bundled address labels with the files in `ZKWATCH_LABELS` applied on top, in order
+fn load_label_registry() -> ZKWatchResult<labels::LabelRegistry> {
+    let mut registry = labels::LabelRegistry::with_defaults();
+    if let Some(paths) = env::var_os("ZKWATCH_LABELS") {
+        for path in env::split_paths(&paths) {
+            registry.merge(labels::LabelRegistry::load(path)?);
+        }
+    }
+    Ok(registry)
+}
+
+/// Sanctions and watchlist entries from `ZKWATCH_SCREENING_LISTS`
+fn load_screener() -> ZKWatchResult<screening::Screener> {
+    let mut screener = screening::Screener::new();
+    if let Some(paths) = env::var_os("ZKWATCH_SCREENING_LISTS") {
+        for path in env::split_paths(&paths) {
+            let count = screener.load(&path)?;
+            println!("📋 Loaded {} screening entries from {}", count, path.display());
+        }
+    }
+    Ok(screener)
+}
+
+/// Print the list entries matched by a screening, one per line
+fn print_screening_evidence(evidence: &[screening::ScreeningHit]) {
+    for hit in evidence {
+        println!("    {} on {}: {} ({}), {} hop(s) via {}",
+            hit.entry.address,
+            hit.entry.list,
+            hit.entry.name,
+            hit.entry.entry_id,
+            hit.hops,
+            hit.path.join(" → ")
+        );
+    }
+}
+
+/// Short suffix describing a transfer's exchange flow, empty for non-exchange transfers
+fn flow_tag(flow: &exchange_flows::ExchangeFlow) -> String {
+    match flow {
+        exchange_flows::ExchangeFlow::Inflow { exchange } => format!(" [deposit to {}]", exchange),
+        exchange_flows::ExchangeFlow::Outflow { exchange } => format!(" [withdrawal from {}]", exchange),
+        exchange_flows::ExchangeFlow::InternalShuffle { from_exchange, to_exchange } => {
+            format!(" [exchange shuffle {} → {}]", from_exchange, to_exchange)
+        }
+        exchange_flows::ExchangeFlow::NonExchange => String::new(),
+    }
+}
+
+/// Chain id of a configured network, 0 when unknown
+fn chain_id(config: &WhaleTrackerConfig, network_name: &str) -> u64 {
+    config.tracking_networks.iter()
+        .find(|n| n.name == network_name)
+        .map(|n| n.chain_id)
+        .unwrap_or_default()
+}
+
+/// Checkpoint store under `ZKWATCH_CHECKPOINT_DIR`
+fn open_checkpoint_store() -> ZKWatchResult<checkpoint::CheckpointStore> {
+    let dir = env::var("ZKWATCH_CHECKPOINT_DIR").unwrap_or_else(|_| ".zkwatch/checkpoints".to_string());
+    checkpoint::CheckpointStore::open(dir)
+}
+
+/// Analytics engine storing whales in the warehouse at `ZKWATCH_WAREHOUSE`
+fn open_analytics_engine() -> analytics::AnalyticsEngine {
+    let dir = env::var("ZKWATCH_WAREHOUSE").unwrap_or_else(|_| warehouse::DEFAULT_WAREHOUSE_DIR.to_string());
+    analytics::AnalyticsEngine::new().with_warehouse(warehouse::DataWarehouse::new(dir))
+}
+
+/// Run scanned whales through the analytics engine and write them to the warehouse
+async fn record_analytics(transactions: &[WhaleTransaction]) -> ZKWatchResult<()> {
+    let mut analytics = open_analytics_engine();
+    for transaction in transactions {
+        analytics.process_transaction(transaction).await?;
+    }
+    analytics.flush()
+}
+
+/// Whale database at `ZKWATCH_DATABASE`
+fn open_whale_database() -> ZKWatchResult<database::WhaleDatabase> {
+    let path = env::var("ZKWATCH_DATABASE").unwrap_or_else(|_| ".zkwatch/whales.db".to_string());
+    database::WhaleDatabase::open(path)
+}
+
+/// Path of the verification-key registry from `ZKWATCH_VERIFICATION_KEYS`
+fn verification_keys_path() -> String {
+    env::var("ZKWATCH_VERIFICATION_KEYS").unwrap_or_else(|_| ".zkwatch/verification_keys.json".to_string())
+}
+
+/// Current verification keys plus every key recorded in the local registry
+fn load_verification_keys() -> ZKWatchResult<proof_envelope::VerificationKeyRegistry> {
+    let mut registry = proof_envelope::VerificationKeyRegistry::with_defaults();
+    let path = verification_keys_path();
+    if std::path::Path::new(&path).exists() {
+        registry.load(&path)?;
+    }
+    Ok(registry)
+}
+
+fn save_verification_keys(registry: &proof_envelope::VerificationKeyRegistry) -> ZKWatchResult<()> {
+    registry.save(verification_keys_path())
+}
+
+/// Value following `flag` on the command line
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
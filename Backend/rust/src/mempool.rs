@@ -0,0 +1,400 @@
+//! Mempool whale watching
+//!
+//! Flags whale-sized pending transactions before inclusion, matches fee
+//! replacements by sender and nonce, and reports whether each one lands or is dropped.
+
+use crate::blockchain::{format_address, size_risk_score, u256_to_u128};
+use crate::chain_tracker::ConfirmationStatus;
+use crate::exchange_flows;
+use crate::labels::LabelRegistry;
+use crate::pricing::PriceSource;
+use crate::tokens::TokenRegistry;
+use crate::{NetworkConfig, WhaleTransaction};
+use chrono::{DateTime, Utc};
+use ethers::types::{Block, Transaction, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// How long a pending whale may stay unconfirmed before it is checked for eviction
+pub const DEFAULT_DROP_AFTER_SECS: i64 = 30 * 60;
+
+/// `transfer(address,uint256)`
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+/// `transferFrom(address,address,uint256)`
+const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+
+/// Lifecycle stage of a pending whale transaction
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum MempoolStatus {
+    /// Seen in the mempool, not yet included
+    Pending,
+    /// Superseded by another transaction with the same sender and nonce
+    Replaced { by: String },
+    /// Included in a block
+    Landed { block_number: u64 },
+    /// Evicted from the mempool without being included
+    Dropped,
+}
+
+/// Pending-stage whale transaction with its lifecycle status
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MempoolEvent {
+    pub transaction: WhaleTransaction,
+    pub status: MempoolStatus,
+}
+
+struct PendingWhale {
+    hash: H256,
+    fee: FeeBid,
+    transaction: WhaleTransaction,
+    /// When the whale was first seen, or last found still pending on the node
+    checked_at: DateTime<Utc>,
+}
+
+/// Pending whale transactions of one network, keyed by sender and nonce
+pub struct MempoolWatcher {
+    network: NetworkConfig,
+    token_registry: Arc<TokenRegistry>,
+    price_source: Arc<dyn PriceSource>,
+    labels: Arc<LabelRegistry>,
+    min_usd: f64,
+    drop_after: chrono::Duration,
+    pending: HashMap<(H160, U256), PendingWhale>,
+    by_hash: HashMap<H256, (H160, U256)>,
+}
+
+impl MempoolWatcher {
+    pub fn new(
+        network: NetworkConfig,
+        token_registry: Arc<TokenRegistry>,
+        price_source: Arc<dyn PriceSource>,
+        min_usd: f64,
+    ) -> Self {
+        Self {
+            network,
+            token_registry,
+            price_source,
+            labels: Arc::new(LabelRegistry::new()),
+            min_usd,
+            drop_after: chrono::Duration::seconds(DEFAULT_DROP_AFTER_SECS),
+            pending: HashMap::new(),
+            by_hash: HashMap::new(),
+        }
+    }
+
+    /// Classify pending whales as exchange deposits or withdrawals with these labels
+    pub fn with_labels(mut self, labels: Arc<LabelRegistry>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_drop_after(mut self, drop_after: chrono::Duration) -> Self {
+        self.drop_after = drop_after;
+        self
+    }
+
+    /// Number of whale transactions currently awaiting inclusion
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Record a transaction seen in the mempool
+    ///
+    /// A transaction reusing the sender and nonce of a tracked whale replaces it
+    /// when it raises the fee cap and, for EIP-1559 transactions, the priority
+    /// fee; any other would be rejected by the node and is ignored.
+    pub fn observe(&mut self, tx: &Transaction, seen_at: DateTime<Utc>) -> Vec<MempoolEvent> {
+        let mut events = Vec::new();
+        let key = (tx.from, tx.nonce);
+        let fee = FeeBid::of(tx);
+
+        if let Some(existing) = self.pending.get(&key) {
+            if existing.hash == tx.hash || !fee.outbids(&existing.fee) {
+                return events;
+            }
+            events.extend(self.resolve(&key, MempoolStatus::Replaced { by: format!("{:?}", tx.hash) }));
+        }
+
+        if let Some(transaction) = self.pending_whale(tx, seen_at) {
+            events.push(MempoolEvent {
+                transaction: transaction.clone(),
+                status: MempoolStatus::Pending,
+            });
+            self.by_hash.insert(tx.hash, key);
+            self.pending.insert(key, PendingWhale {
+                hash: tx.hash,
+                fee,
+                transaction,
+                checked_at: seen_at,
+            });
+        }
+
+        events
+    }
+
+    /// Resolve tracked whales against the transactions of a new block
+    pub fn observe_block(&mut self, block: &Block<Transaction>) -> Vec<MempoolEvent> {
+        let block_number = block.number.map(|n| n.as_u64()).unwrap_or_default();
+        let mut events = Vec::new();
+
+        for tx in &block.transactions {
+            let key = (tx.from, tx.nonce);
+            let status = match self.pending.get(&key) {
+                Some(existing) if existing.hash == tx.hash => MempoolStatus::Landed { block_number },
+                // The nonce was consumed by a replacement we never saw in the mempool
+                Some(_) => MempoolStatus::Replaced { by: format!("{:?}", tx.hash) },
+                None => continue,
+            };
+            events.extend(self.resolve(&key, status));
+        }
+
+        events
+    }
+
+    /// Whales pending for longer than the drop timeout since they were seen or
+    /// last checked; callers confirm eviction with [`mark_landed`](Self::mark_landed),
+    /// [`mark_pending`](Self::mark_pending) or [`mark_dropped`](Self::mark_dropped)
+    pub fn stale(&self, now: DateTime<Utc>) -> Vec<H256> {
+        self.pending.values()
+            .filter(|p| now - p.checked_at >= self.drop_after)
+            .map(|p| p.hash)
+            .collect()
+    }
+
+    /// Keep tracking a stale whale the node still holds, restarting its drop timeout
+    pub fn mark_pending(&mut self, hash: H256, checked_at: DateTime<Utc>) {
+        if let Some(pending) = self.by_hash.get(&hash).and_then(|key| self.pending.get_mut(key)) {
+            pending.checked_at = checked_at;
+        }
+    }
+
+    pub fn mark_landed(&mut self, hash: H256, block_number: u64) -> Option<MempoolEvent> {
+        let key = *self.by_hash.get(&hash)?;
+        self.resolve(&key, MempoolStatus::Landed { block_number })
+    }
+
+    pub fn mark_dropped(&mut self, hash: H256) -> Option<MempoolEvent> {
+        let key = *self.by_hash.get(&hash)?;
+        self.resolve(&key, MempoolStatus::Dropped)
+    }
+
+    fn resolve(&mut self, key: &(H160, U256), status: MempoolStatus) -> Option<MempoolEvent> {
+        let pending = self.pending.remove(key)?;
+        self.by_hash.remove(&pending.hash);
+
+        let mut transaction = pending.transaction;
+        if let MempoolStatus::Landed { block_number } = status {
+            transaction.block_number = block_number;
+        }
+        Some(MempoolEvent { transaction, status })
+    }
+
+    /// Whale record for a pending native transfer or tracked-token transfer call
+    fn pending_whale(&self, tx: &Transaction, seen_at: DateTime<Utc>) -> Option<WhaleTransaction> {
+        let (from, to, value, token, usd_value, size_ratio) = if !tx.value.is_zero() {
+            let value = u256_to_u128(tx.value);
+            let usd_value = value as f64 / 1e18 * self.price_source.price_usd(&self.network.native_symbol, seen_at)?;
+            (tx.from, tx.to, value, None, Some(usd_value), usd_value / self.min_usd)
+        } else {
+            let token = self.token_registry.get(self.network.chain_id, tx.to.as_ref()?)?;
+            let (from, to, amount) = decode_transfer_call(tx)?;
+            let value = u256_to_u128(amount);
+            let usd_value = self.price_source.price_usd(&token.info.symbol, seen_at)
+                .map(|price| value as f64 / 10f64.powi(token.info.decimals as i32) * price);
+            let size_ratio = match usd_value {
+                Some(usd) => usd / self.min_usd,
+                None => value as f64 / token.whale_threshold.max(1) as f64,
+            };
+            (from, Some(to), value, Some(token.info.clone()), usd_value, size_ratio)
+        };
+
+        if size_ratio < 1.0 {
+            return None;
+        }
+
+        let mut transaction = WhaleTransaction {
+            hash: format!("{:?}", tx.hash),
+            network: self.network.name.clone(),
+            chain_id: self.network.chain_id,
+            from: format_address(&from),
+            to: to.as_ref().map(format_address).unwrap_or_default(),
+            value,
+            borrowed: 0,
+            token,
+            log_index: None,
+            usd_value,
+            confirmation: ConfirmationStatus::Pending,
+            gas_used: 0,
+            block_number: 0,
+            timestamp: seen_at,
+            zk_proof_hash: None,
+            risk_score: size_risk_score(size_ratio),
+            pattern_type: crate::TransactionPattern::LargeTransaction,
+            exchange_flow: exchange_flows::ExchangeFlow::NonExchange,
+        };
+        transaction.exchange_flow = exchange_flows::classify(&transaction, &self.labels);
+        Some(transaction)
+    }
+}
+
+/// Price per gas the sender bids; replacements must raise both parts
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FeeBid {
+    /// Legacy gas price or EIP-1559 fee cap
+    max_fee: U256,
+    /// EIP-1559 priority fee; the gas price for legacy transactions
+    priority_fee: U256,
+}
+
+impl FeeBid {
+    fn of(tx: &Transaction) -> Self {
+        let max_fee = tx.max_fee_per_gas.or(tx.gas_price).unwrap_or_default();
+        Self {
+            max_fee,
+            priority_fee: tx.max_priority_fee_per_gas.unwrap_or(max_fee),
+        }
+    }
+
+    fn outbids(&self, other: &FeeBid) -> bool {
+        self.max_fee > other.max_fee && self.priority_fee > other.priority_fee
+    }
+}
+
+/// Sender, recipient and amount of an ERC-20 `transfer` or `transferFrom` call
+fn decode_transfer_call(tx: &Transaction) -> Option<(H160, H160, U256)> {
+    let input = tx.input.as_ref();
+    let (selector, args) = (input.get(..4)?, &input[4..]);
+    let word = |index: usize| args.get(index * 32..(index + 1) * 32);
+    let address = |index: usize| word(index).map(|w| H160::from_slice(&w[12..]));
+
+    if selector == TRANSFER_SELECTOR && args.len() == 64 {
+        Some((tx.from, address(0)?, U256::from_big_endian(word(1)?)))
+    } else if selector == TRANSFER_FROM_SELECTOR && args.len() == 96 {
+        Some((address(0)?, address(1)?, U256::from_big_endian(word(2)?)))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::StaticPriceTable;
+    use chrono::TimeZone;
+    use ethers::types::U64;
+
+    fn watcher() -> MempoolWatcher {
+        let network = NetworkConfig {
+            name: "Ethereum".to_string(),
+            chain_id: 1,
+            rpc_url: String::new(),
+            ws_url: None,
+            explorer_url: String::new(),
+            native_symbol: "ETH".to_string(),
+            confirmation_depth: 12,
+            finality_depth: 64,
+            block_time_ms: 12_000,
+        };
+        let prices = StaticPriceTable::new([("ETH".to_string(), 2_000.0)]);
+        MempoolWatcher::new(network, Arc::new(TokenRegistry::with_defaults()), Arc::new(prices), 100_000.0)
+            .with_drop_after(chrono::Duration::minutes(30))
+    }
+
+    /// 1000 ETH transfer from sender 1 with nonce 7, bidding `max_fee` and `priority_fee` gwei
+    fn whale(hash: u64, max_fee: u64, priority_fee: Option<u64>) -> Transaction {
+        let gwei = |fee: u64| U256::from(fee) * U256::exp10(9);
+        Transaction {
+            hash: H256::from_low_u64_be(hash),
+            nonce: U256::from(7),
+            from: H160::from_low_u64_be(1),
+            to: Some(H160::from_low_u64_be(2)),
+            value: U256::exp10(21),
+            gas_price: priority_fee.is_none().then(|| gwei(max_fee)),
+            max_fee_per_gas: priority_fee.map(|_| gwei(max_fee)),
+            max_priority_fee_per_gas: priority_fee.map(gwei),
+            ..Default::default()
+        }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + minutes * 60, 0).unwrap()
+    }
+
+    fn block(number: u64, transactions: Vec<Transaction>) -> Block<Transaction> {
+        Block {
+            number: Some(U64::from(number)),
+            transactions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fee_bump_replaces_by_sender_and_nonce() {
+        let mut watcher = watcher();
+        let events = watcher.observe(&whale(1, 30, None), at(0));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, MempoolStatus::Pending);
+        assert_eq!(events[0].transaction.usd_value, Some(2_000_000.0));
+
+        let events = watcher.observe(&whale(2, 33, None), at(1));
+        let statuses: Vec<_> = events.iter().map(|e| (e.transaction.hash.clone(), e.status.clone())).collect();
+        assert_eq!(statuses, vec![
+            (format!("{:?}", H256::from_low_u64_be(1)), MempoolStatus::Replaced { by: format!("{:?}", H256::from_low_u64_be(2)) }),
+            (format!("{:?}", H256::from_low_u64_be(2)), MempoolStatus::Pending),
+        ]);
+        assert_eq!(watcher.pending_count(), 1);
+    }
+
+    #[test]
+    fn replacement_without_higher_fees_is_ignored() {
+        let mut watcher = watcher();
+        watcher.observe(&whale(1, 30, None), at(0));
+        assert!(watcher.observe(&whale(2, 30, None), at(1)).is_empty());
+        assert!(watcher.observe(&whale(3, 20, None), at(1)).is_empty());
+        assert!(watcher.observe(&whale(1, 30, None), at(2)).is_empty());
+
+        // EIP-1559 replacements must raise the priority fee as well as the fee cap
+        let mut watcher = self::watcher();
+        watcher.observe(&whale(1, 40, Some(2)), at(0));
+        assert!(watcher.observe(&whale(2, 50, Some(2)), at(1)).is_empty());
+        assert!(watcher.observe(&whale(3, 40, Some(3)), at(1)).is_empty());
+        let events = watcher.observe(&whale(4, 50, Some(3)), at(1));
+        assert_eq!(events[0].status, MempoolStatus::Replaced { by: format!("{:?}", H256::from_low_u64_be(4)) });
+    }
+
+    #[test]
+    fn block_inclusion_marks_landed_or_replaced() {
+        let mut watcher = watcher();
+        watcher.observe(&whale(1, 30, None), at(0));
+        let events = watcher.observe_block(&block(100, vec![whale(1, 30, None)]));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, MempoolStatus::Landed { block_number: 100 });
+        assert_eq!(events[0].transaction.block_number, 100);
+
+        // The nonce was consumed by a transaction that never showed up in the mempool
+        watcher.observe(&whale(2, 30, None), at(1));
+        let events = watcher.observe_block(&block(101, vec![whale(9, 10, None)]));
+        assert_eq!(events[0].status, MempoolStatus::Replaced { by: format!("{:?}", H256::from_low_u64_be(9)) });
+        assert_eq!(watcher.pending_count(), 0);
+    }
+
+    #[test]
+    fn stale_whales_are_rechecked_or_dropped() {
+        let mut watcher = watcher();
+        let hash = H256::from_low_u64_be(1);
+        watcher.observe(&whale(1, 30, None), at(0));
+        assert!(watcher.stale(at(29)).is_empty());
+        assert_eq!(watcher.stale(at(30)), vec![hash]);
+
+        // Still held by the node: the drop timeout restarts
+        watcher.mark_pending(hash, at(30));
+        assert!(watcher.stale(at(59)).is_empty());
+        assert_eq!(watcher.stale(at(60)), vec![hash]);
+
+        let event = watcher.mark_dropped(hash).unwrap();
+        assert_eq!(event.status, MempoolStatus::Dropped);
+        assert_eq!(watcher.pending_count(), 0);
+        assert!(watcher.mark_dropped(hash).is_none());
+    }
+}
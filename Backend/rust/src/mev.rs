@@ -0,0 +1,429 @@
+//! MEV detection
+//!
+//! Finds sandwich attacks from the intra-block ordering of decoded DEX swaps
+//! (a searcher buying right before a victim on the same pool and selling right
+//! after) and atomic arbitrage cycling through several pools in one transaction.
+
+use crate::dex::{self, DexProtocol, Swap};
+use ethers::types::{Block, Transaction, H160, H256, U256};
+use std::collections::{HashMap, HashSet};
+
+/// Tolerated mismatch between the amount bought in the front-run and sold in the back-run
+const BACK_RUN_AMOUNT_TOLERANCE: f64 = 0.1;
+
+/// Swap that executed between a front-run and a back-run
+#[derive(Debug, Clone)]
+pub struct SandwichVictim {
+    pub address: H160,
+    pub swap: Swap,
+    /// Output lost to the front-run, in the victim's output token
+    pub loss: U256,
+    /// Exact for constant-product pools, estimated from price moves otherwise
+    pub exact_loss: bool,
+}
+
+/// Front-run and back-run by one searcher around victims on the same pool
+#[derive(Debug, Clone)]
+pub struct Sandwich {
+    pub pool: H160,
+    pub protocol: DexProtocol,
+    /// Sender of the front-run and back-run transactions
+    pub searcher: H160,
+    /// Contract the searcher routed through, if any
+    pub searcher_contract: Option<H160>,
+    pub front_run: Swap,
+    pub victims: Vec<SandwichVictim>,
+    pub back_run: Swap,
+    /// Back-run output minus front-run input, before gas
+    pub profit: U256,
+    /// Token the profit is denominated in (the front-run's input token)
+    pub profit_token: Option<H160>,
+}
+
+impl Sandwich {
+    pub fn transaction_hashes(&self) -> Vec<H256> {
+        std::iter::once(self.front_run.transaction_hash)
+            .chain(self.victims.iter().map(|v| v.swap.transaction_hash))
+            .chain(std::iter::once(self.back_run.transaction_hash))
+            .collect()
+    }
+
+    /// Combined victim loss, in the victims' shared output token
+    pub fn victim_loss(&self) -> U256 {
+        self.victims.iter().fold(U256::zero(), |total, v| total.saturating_add(v.loss))
+    }
+}
+
+/// Swaps in one transaction that start and end in the same token
+#[derive(Debug, Clone)]
+pub struct Arbitrage {
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    /// Sender of the transaction
+    pub searcher: H160,
+    /// Contract the searcher routed through, if any
+    pub searcher_contract: Option<H160>,
+    /// Swaps of the cycle in execution order
+    pub swaps: Vec<Swap>,
+    pub start_token: H160,
+    /// Final output minus initial input in the start token, before gas
+    pub profit: U256,
+}
+
+/// Detect sandwiches among a block's swaps
+///
+/// A sandwich is a front-run swap, one or more swaps in the same direction by
+/// other senders, then a back-run in the opposite direction that sells roughly
+/// what the front-run bought, sent by the same searcher (same sender, or same
+/// contract when that contract is not the victims' router) and closing at a profit.
+pub fn detect_sandwiches(block: &Block<Transaction>, swaps: &[Swap]) -> Vec<Sandwich> {
+    let transactions: HashMap<H256, &Transaction> = block.transactions.iter().map(|tx| (tx.hash, tx)).collect();
+
+    let mut by_pool: HashMap<H160, Vec<&Swap>> = HashMap::new();
+    for swap in swaps {
+        by_pool.entry(swap.pool).or_default().push(swap);
+    }
+
+    let mut sandwiches = Vec::new();
+    for (pool, mut pool_swaps) in by_pool {
+        pool_swaps.sort_by_key(|s| (s.transaction_index, s.log_index));
+
+        // Transactions swapping several times on one pool are routing, not sandwich legs
+        let mut per_tx: HashMap<H256, usize> = HashMap::new();
+        for swap in &pool_swaps {
+            *per_tx.entry(swap.transaction_hash).or_default() += 1;
+        }
+        pool_swaps.retain(|s| per_tx[&s.transaction_hash] == 1);
+
+        let mut used: HashSet<H256> = HashSet::new();
+        for (front_index, front) in pool_swaps.iter().enumerate() {
+            if used.contains(&front.transaction_hash) {
+                continue;
+            }
+            let Some(front_tx) = transactions.get(&front.transaction_hash) else {
+                continue;
+            };
+
+            if let Some(sandwich) = match_sandwich(pool, &pool_swaps, front_index, front_tx, &transactions, &used) {
+                used.extend(sandwich.transaction_hashes());
+                sandwiches.push(sandwich);
+            }
+        }
+    }
+
+    sandwiches.sort_by_key(|s| s.front_run.transaction_index);
+    sandwiches
+}
+
+fn match_sandwich(
+    pool: H160,
+    pool_swaps: &[&Swap],
+    front_index: usize,
+    front_tx: &Transaction,
+    transactions: &HashMap<H256, &Transaction>,
+    used: &HashSet<H256>,
+) -> Option<Sandwich> {
+    let front = pool_swaps[front_index];
+    let mut victims = Vec::new();
+
+    for candidate in &pool_swaps[front_index + 1..] {
+        let Some(candidate_tx) = transactions.get(&candidate.transaction_hash) else {
+            continue;
+        };
+        if used.contains(&candidate.transaction_hash) {
+            continue;
+        }
+
+        if candidate.zero_for_one == front.zero_for_one {
+            if candidate_tx.from != front_tx.from {
+                victims.push((*candidate, candidate_tx));
+            }
+            continue;
+        }
+
+        let back = *candidate;
+        let routers: HashSet<Option<H160>> = victims.iter().map(|(_, tx)| tx.to).collect();
+        let same_searcher = candidate_tx.from == front_tx.from
+            || (front_tx.to.is_some() && candidate_tx.to == front_tx.to && !routers.contains(&front_tx.to));
+        // Opposite trades by other senders only move the price; keep looking for the back-run
+        if !same_searcher {
+            continue;
+        }
+        if victims.is_empty() || !closes_position(front, back) {
+            return None;
+        }
+
+        let profit = back.amount_out.checked_sub(front.amount_in).filter(|p| !p.is_zero())?;
+        let price_before = front_index.checked_sub(1)
+            .and_then(|i| pool_swaps[i].sqrt_price_after)
+            .or(back.sqrt_price_after);
+
+        let victims = victims.into_iter()
+            .map(|(swap, tx)| {
+                let (loss, exact_loss) = victim_loss(front, swap, price_before);
+                SandwichVictim {
+                    address: tx.from,
+                    swap: swap.clone(),
+                    loss,
+                    exact_loss,
+                }
+            })
+            .collect();
+
+        return Some(Sandwich {
+            pool,
+            protocol: front.protocol,
+            searcher: front_tx.from,
+            searcher_contract: front_tx.to,
+            front_run: front.clone(),
+            victims,
+            back_run: back.clone(),
+            profit,
+            profit_token: front.token_in.or(back.token_out),
+        });
+    }
+
+    None
+}
+
+/// Back-run sells the token the front-run bought, in roughly the same amount
+fn closes_position(front: &Swap, back: &Swap) -> bool {
+    let tokens_match = match (front.token_out, back.token_in, front.token_in, back.token_out) {
+        (Some(bought), Some(sold), Some(paid), Some(received)) => bought == sold && paid == received,
+        _ => true,
+    };
+
+    let bought = dex::u256_to_f64(front.amount_out);
+    let sold = dex::u256_to_f64(back.amount_in);
+    tokens_match && bought > 0.0 && ((sold - bought) / bought).abs() <= BACK_RUN_AMOUNT_TOLERANCE
+}
+
+/// Output the victim would have additionally received without the front-run
+fn victim_loss(front: &Swap, victim: &Swap, sqrt_price_before_front: Option<U256>) -> (U256, bool) {
+    // Constant product: replay the victim against its reserves with the front-run's deltas undone
+    if let Some((reserve0, reserve1)) = victim.reserves_before {
+        let (reserve_in, reserve_out) = if victim.zero_for_one { (reserve0, reserve1) } else { (reserve1, reserve0) };
+        if let (Some(clean_in), Some(clean_out)) = (
+            reserve_in.checked_sub(front.amount_in),
+            reserve_out.checked_add(front.amount_out),
+        ) {
+            let with_front_run = dex::constant_product_amount_out(victim.amount_in, reserve_in, reserve_out);
+            let without_front_run = dex::constant_product_amount_out(victim.amount_in, clean_in, clean_out);
+            return (without_front_run.saturating_sub(with_front_run), true);
+        }
+    }
+
+    // Concentrated liquidity: scale the victim's fill by the price shift the front-run caused
+    let (Some(before), Some(after_front)) = (sqrt_price_before_front, front.sqrt_price_after) else {
+        return (U256::zero(), false);
+    };
+    let (before, after_front) = (dex::sqrt_price_to_price(before), dex::sqrt_price_to_price(after_front));
+    if before <= 0.0 || after_front <= 0.0 {
+        return (U256::zero(), false);
+    }
+
+    let shift = if victim.zero_for_one { before / after_front } else { after_front / before };
+    let loss = dex::u256_to_f64(victim.amount_out) * (shift - 1.0).max(0.0);
+    (U256::from(loss.min(u128::MAX as f64) as u128), false)
+}
+
+/// Detect atomic arbitrage among a block's swaps
+///
+/// A transaction qualifies when its swaps chain through at least two distinct
+/// pools, each selling the token the previous one bought, and the last swap
+/// returns more of the first swap's input token than went in.
+pub fn detect_arbitrages(block: &Block<Transaction>, swaps: &[Swap]) -> Vec<Arbitrage> {
+    let mut by_transaction: HashMap<H256, Vec<&Swap>> = HashMap::new();
+    for swap in swaps {
+        by_transaction.entry(swap.transaction_hash).or_default().push(swap);
+    }
+
+    let mut arbitrages: Vec<Arbitrage> = block.transactions.iter()
+        .filter_map(|tx| {
+            let mut tx_swaps = by_transaction.remove(&tx.hash)?;
+            tx_swaps.sort_by_key(|s| s.log_index);
+            match_cycle(&tx_swaps).map(|(start_token, profit)| Arbitrage {
+                transaction_hash: tx.hash,
+                transaction_index: tx_swaps[0].transaction_index,
+                searcher: tx.from,
+                searcher_contract: tx.to,
+                swaps: tx_swaps.into_iter().cloned().collect(),
+                start_token,
+                profit,
+            })
+        })
+        .collect();
+
+    arbitrages.sort_by_key(|a| a.transaction_index);
+    arbitrages
+}
+
+/// Start token and profit of swaps forming one closed, profitable token cycle
+fn match_cycle(swaps: &[&Swap]) -> Option<(H160, U256)> {
+    let pools: HashSet<H160> = swaps.iter().map(|s| s.pool).collect();
+    if swaps.len() < 2 || pools.len() != swaps.len() {
+        return None;
+    }
+
+    let chained = swaps.windows(2).all(|pair| match (pair[0].token_out, pair[1].token_in) {
+        (Some(bought), Some(sold)) => bought == sold,
+        _ => false,
+    });
+
+    let (first, last) = (swaps.first()?, swaps.last()?);
+    let start_token = first.token_in?;
+    if !chained || last.token_out != Some(start_token) {
+        return None;
+    }
+
+    let profit = last.amount_out.checked_sub(first.amount_in).filter(|p| !p.is_zero())?;
+    Some((start_token, profit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    const SEARCHER: u64 = 0x5;
+    const SEARCHER_CONTRACT: u64 = 0x5c;
+    const VICTIM: u64 = 0x6;
+    const OTHER_TRADER: u64 = 0x7;
+    const ROUTER: u64 = 0x9;
+    const POOL: u64 = 0x100;
+
+    fn token(zero: bool) -> H160 {
+        H160::from_low_u64_be(if zero { 0xa } else { 0xb })
+    }
+
+    fn swap(transaction: u64, pool: u64, zero_for_one: bool, amount_in: U256, amount_out: U256) -> Swap {
+        Swap {
+            protocol: DexProtocol::UniswapV2,
+            pool: H160::from_low_u64_be(pool),
+            transaction_hash: H256::from_low_u64_be(transaction),
+            transaction_index: transaction,
+            log_index: transaction * 10,
+            zero_for_one,
+            amount_in,
+            amount_out,
+            token_in: Some(token(zero_for_one)),
+            token_out: Some(token(!zero_for_one)),
+            reserves_before: None,
+            sqrt_price_after: None,
+        }
+    }
+
+    /// Block whose transaction `n` is sent by the `n`th sender; the searcher routes through its own contract
+    fn block(senders: &[u64]) -> Block<Transaction> {
+        Block {
+            number: Some(U64::from(100)),
+            transactions: senders.iter().enumerate()
+                .map(|(index, &from)| Transaction {
+                    hash: H256::from_low_u64_be(index as u64),
+                    from: H160::from_low_u64_be(from),
+                    to: Some(H160::from_low_u64_be(if from == SEARCHER { SEARCHER_CONTRACT } else { ROUTER })),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Constant-product swap of `amount_in` against `reserves`, returning the swap and the reserves after it
+    fn v2_swap(transaction: u64, zero_for_one: bool, amount_in: U256, reserves: (U256, U256)) -> (Swap, (U256, U256)) {
+        let (reserve_in, reserve_out) = if zero_for_one { reserves } else { (reserves.1, reserves.0) };
+        let amount_out = dex::constant_product_amount_out(amount_in, reserve_in, reserve_out);
+        let mut swap = swap(transaction, POOL, zero_for_one, amount_in, amount_out);
+        swap.reserves_before = Some(reserves);
+
+        let (reserve_in, reserve_out) = (reserve_in + amount_in, reserve_out - amount_out);
+        let after = if zero_for_one { (reserve_in, reserve_out) } else { (reserve_out, reserve_in) };
+        (swap, after)
+    }
+
+    #[test]
+    fn constant_product_sandwich_has_exact_victim_loss() {
+        let reserves = (U256::exp10(24), U256::exp10(24));
+        let (front, after_front) = v2_swap(0, true, U256::exp10(23), reserves);
+        let (victim, after_victim) = v2_swap(1, true, U256::exp10(22) * 5, after_front);
+        let (back, _) = v2_swap(2, false, front.amount_out, after_victim);
+
+        let sandwiches = detect_sandwiches(&block(&[SEARCHER, VICTIM, SEARCHER]), &[front.clone(), victim.clone(), back.clone()]);
+        assert_eq!(sandwiches.len(), 1);
+        let sandwich = &sandwiches[0];
+        assert_eq!(sandwich.searcher, H160::from_low_u64_be(SEARCHER));
+        assert_eq!(sandwich.searcher_contract, Some(H160::from_low_u64_be(SEARCHER_CONTRACT)));
+        assert_eq!(sandwich.profit, back.amount_out - front.amount_in);
+        assert_eq!(sandwich.profit_token, Some(token(true)));
+        assert_eq!(sandwich.transaction_hashes(), vec![H256::from_low_u64_be(0), H256::from_low_u64_be(1), H256::from_low_u64_be(2)]);
+
+        // Without the front-run the victim would have traded against the original reserves
+        let clean_out = dex::constant_product_amount_out(victim.amount_in, reserves.0, reserves.1);
+        assert_eq!(sandwich.victims.len(), 1);
+        assert_eq!(sandwich.victims[0].address, H160::from_low_u64_be(VICTIM));
+        assert!(sandwich.victims[0].exact_loss);
+        assert_eq!(sandwich.victim_loss(), clean_out - victim.amount_out);
+    }
+
+    #[test]
+    fn concentrated_liquidity_sandwich_estimates_victim_loss() {
+        let q96 = |ratio: f64| U256::from((ratio * 2f64.powi(64)) as u128) << 32;
+        let mut front = swap(0, POOL, true, U256::from(1_000_000u64), U256::from(990_000u64));
+        front.protocol = DexProtocol::UniswapV3;
+        front.sqrt_price_after = Some(q96(0.95));
+        let mut victim = swap(1, POOL, true, U256::from(500_000u64), U256::from(450_000u64));
+        victim.protocol = DexProtocol::UniswapV3;
+        victim.sqrt_price_after = Some(q96(0.93));
+        let mut back = swap(2, POOL, false, U256::from(990_000u64), U256::from(1_010_000u64));
+        back.protocol = DexProtocol::UniswapV3;
+        back.sqrt_price_after = Some(q96(1.0));
+
+        let sandwiches = detect_sandwiches(&block(&[SEARCHER, VICTIM, SEARCHER]), &[front, victim, back]);
+        assert_eq!(sandwiches.len(), 1);
+        let victim = &sandwiches[0].victims[0];
+        assert!(!victim.exact_loss);
+
+        // The front-run moved the price from 1.0 to 0.95² before the victim sold token0
+        let expected = 450_000.0 * (1.0 / (0.95f64 * 0.95) - 1.0);
+        assert!((victim.loss.as_u64() as f64 - expected).abs() < 2.0, "loss {}", victim.loss);
+        assert_eq!(sandwiches[0].profit, U256::from(10_000u64));
+    }
+
+    #[test]
+    fn opposite_trade_by_another_sender_is_not_a_back_run() {
+        let front = swap(0, POOL, true, U256::from(1_000u64), U256::from(990u64));
+        let victim = swap(1, POOL, true, U256::from(500u64), U256::from(480u64));
+        let other = swap(2, POOL, false, U256::from(990u64), U256::from(1_020u64));
+        let swaps = vec![front.clone(), victim.clone(), other.clone()];
+        assert!(detect_sandwiches(&block(&[SEARCHER, VICTIM, OTHER_TRADER]), &swaps).is_empty());
+
+        // A later back-run by the searcher still closes the sandwich; the other trader is no victim
+        let back = swap(3, POOL, false, U256::from(990u64), U256::from(1_020u64));
+        let sandwiches = detect_sandwiches(&block(&[SEARCHER, VICTIM, OTHER_TRADER, SEARCHER]), &[front, victim, other, back]);
+        assert_eq!(sandwiches.len(), 1);
+        assert_eq!(sandwiches[0].victims.iter().map(|v| v.address).collect::<Vec<_>>(), vec![H160::from_low_u64_be(VICTIM)]);
+        assert_eq!(sandwiches[0].back_run.transaction_hash, H256::from_low_u64_be(3));
+    }
+
+    #[test]
+    fn two_pool_cycle_is_an_arbitrage() {
+        let first = swap(0, POOL, true, U256::from(1_000u64), U256::from(2_000u64));
+        let mut second = swap(0, POOL + 1, false, U256::from(2_000u64), U256::from(1_050u64));
+        second.log_index = first.log_index + 1;
+
+        let arbitrages = detect_arbitrages(&block(&[SEARCHER]), &[second.clone(), first.clone()]);
+        assert_eq!(arbitrages.len(), 1);
+        let arbitrage = &arbitrages[0];
+        assert_eq!(arbitrage.start_token, token(true));
+        assert_eq!(arbitrage.profit, U256::from(50u64));
+        assert_eq!(arbitrage.swaps.iter().map(|s| s.pool).collect::<Vec<_>>(), vec![first.pool, second.pool]);
+        assert_eq!(arbitrage.searcher_contract, Some(H160::from_low_u64_be(SEARCHER_CONTRACT)));
+
+        // Losing cycles and round trips through a single pool are not arbitrage
+        second.amount_out = U256::from(990u64);
+        assert!(detect_arbitrages(&block(&[SEARCHER]), &[first.clone(), second]).is_empty());
+        let mut back = swap(0, POOL, false, U256::from(2_000u64), U256::from(1_050u64));
+        back.log_index = first.log_index + 1;
+        assert!(detect_arbitrages(&block(&[SEARCHER]), &[first, back]).is_empty());
+    }
+}
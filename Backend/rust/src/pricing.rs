@@ -0,0 +1,316 @@
+//! Price oracle subsystem
+//!
+//! Resolves USD prices for native assets and tokens at a point in time from
+//! local price tables, so whale thresholds can be expressed in USD across chains.
+
+use crate::{ZKWatchResult, ZKWatchError};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Source of USD prices keyed by asset symbol
+pub trait PriceSource: Send + Sync {
+    /// USD price of one whole unit of `symbol` at `at`, if known
+    fn price_usd(&self, symbol: &str, at: DateTime<Utc>) -> Option<f64>;
+}
+
+/// Wrapped assets are priced like their underlying asset
+pub(crate) fn price_symbol(symbol: &str) -> String {
+    let symbol = symbol.to_ascii_uppercase();
+    match symbol.as_str() {
+        "WETH" => "ETH".to_string(),
+        "WMATIC" => "MATIC".to_string(),
+        "WBTC" => "BTC".to_string(),
+        _ => symbol,
+    }
+}
+
+/// Time-independent price table
+///
+/// Loaded from CSV (`symbol,price_usd`) or JSON (`{"ETH": 3150.0, ...}`).
+#[derive(Debug, Clone, Default)]
+pub struct StaticPriceTable {
+    prices: HashMap<String, f64>,
+}
+
+impl StaticPriceTable {
+    pub fn new(prices: impl IntoIterator<Item = (String, f64)>) -> Self {
+        Self {
+            prices: prices.into_iter().map(|(symbol, price)| (price_symbol(&symbol), price)).collect(),
+        }
+    }
+
+    /// Dollar-pegged stablecoins only; everything else is unpriced
+    pub fn stablecoins() -> Self {
+        Self::new(["USDC", "USDT", "DAI"].iter().map(|s| (s.to_string(), 1.0)))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        if is_json(path) {
+            let prices: HashMap<String, f64> = serde_json::from_str(&contents)?;
+            return Ok(Self::new(prices));
+        }
+
+        let mut prices = Vec::new();
+        for (line_number, fields) in csv_records(&contents) {
+            match fields.as_slice() {
+                [symbol, price] => prices.push((symbol.to_string(), parse_price(path, line_number, price)?)),
+                _ => return Err(price_file_error(path, line_number, "expected `symbol,price_usd`")),
+            }
+        }
+
+        Ok(Self::new(prices))
+    }
+
+    pub fn set_price(&mut self, symbol: &str, price_usd: f64) {
+        self.prices.insert(price_symbol(symbol), price_usd);
+    }
+}
+
+impl PriceSource for StaticPriceTable {
+    fn price_usd(&self, symbol: &str, _at: DateTime<Utc>) -> Option<f64> {
+        self.prices.get(&price_symbol(symbol)).copied()
+    }
+}
+
+/// Price history sampled at discrete times
+///
+/// Loaded from CSV (`timestamp,symbol,price_usd`, timestamps as unix seconds
+/// or RFC 3339) or JSON (`[{"timestamp": ..., "symbol": ..., "price_usd": ...}]`).
+/// A lookup returns the latest sample at or before the requested time, as long
+/// as it is no older than the maximum staleness.
+#[derive(Debug, Clone, Default)]
+pub struct HistoricalPriceFile {
+    series: HashMap<String, BTreeMap<i64, f64>>,
+    max_staleness: Option<chrono::Duration>,
+}
+
+#[derive(Deserialize)]
+struct PriceSample {
+    timestamp: serde_json::Value,
+    symbol: String,
+    price_usd: f64,
+}
+
+impl HistoricalPriceFile {
+    pub fn load(path: impl AsRef<Path>) -> ZKWatchResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let mut history = Self::default();
+
+        if is_json(path) {
+            let samples: Vec<PriceSample> = serde_json::from_str(&contents)?;
+            for (index, sample) in samples.into_iter().enumerate() {
+                let timestamp = match &sample.timestamp {
+                    serde_json::Value::Number(n) => n.as_i64().and_then(|t| Utc.timestamp_opt(t, 0).single()),
+                    serde_json::Value::String(s) => parse_timestamp(s),
+                    _ => None,
+                }.ok_or_else(|| price_file_error(path, index + 1, "invalid timestamp"))?;
+                history.insert(&sample.symbol, timestamp, sample.price_usd);
+            }
+            return Ok(history);
+        }
+
+        for (line_number, fields) in csv_records(&contents) {
+            match fields.as_slice() {
+                [timestamp, symbol, price] => {
+                    let timestamp = parse_timestamp(timestamp)
+                        .ok_or_else(|| price_file_error(path, line_number, "invalid timestamp"))?;
+                    history.insert(symbol, timestamp, parse_price(path, line_number, price)?);
+                }
+                _ => return Err(price_file_error(path, line_number, "expected `timestamp,symbol,price_usd`")),
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// Ignore samples older than `max_staleness` relative to the lookup time
+    pub fn with_max_staleness(mut self, max_staleness: chrono::Duration) -> Self {
+        self.max_staleness = Some(max_staleness);
+        self
+    }
+
+    pub fn insert(&mut self, symbol: &str, at: DateTime<Utc>, price_usd: f64) {
+        self.series.entry(price_symbol(symbol)).or_default().insert(at.timestamp(), price_usd);
+    }
+}
+
+impl PriceSource for HistoricalPriceFile {
+    fn price_usd(&self, symbol: &str, at: DateTime<Utc>) -> Option<f64> {
+        let series = self.series.get(&price_symbol(symbol))?;
+        let (&sampled_at, &price) = series.range(..=at.timestamp()).next_back()?;
+
+        match self.max_staleness {
+            Some(max) if at.timestamp() - sampled_at > max.num_seconds() => None,
+            _ => Some(price),
+        }
+    }
+}
+
+/// Load a price file, picking the historical format when it has a timestamp column
+pub fn load_price_file(path: impl AsRef<Path>) -> ZKWatchResult<Arc<dyn PriceSource>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)?;
+
+    let historical = if is_json(path) {
+        contents.trim_start().starts_with('[')
+    } else {
+        csv_records(&contents).next().is_some_and(|(_, fields)| fields.len() == 3)
+    };
+
+    if historical {
+        Ok(Arc::new(HistoricalPriceFile::load(path)?))
+    } else {
+        Ok(Arc::new(StaticPriceTable::load(path)?))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Non-empty, non-comment CSV lines split into trimmed fields, skipping a header row
+fn csv_records(contents: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    contents.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| (line_number, line.split(',').map(str::trim).collect::<Vec<_>>()))
+        .filter(|(_, fields)| !fields.iter().any(|f| f.eq_ignore_ascii_case("price_usd")))
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Utc.timestamp_opt(seconds, 0).single();
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+fn parse_price(path: &Path, line_number: usize, value: &str) -> ZKWatchResult<f64> {
+    value.parse::<f64>()
+        .ok()
+        .filter(|price| price.is_finite() && *price >= 0.0)
+        .ok_or_else(|| price_file_error(path, line_number, "invalid price"))
+}
+
+fn price_file_error(path: &Path, line_number: usize, message: &str) -> ZKWatchError {
+    ZKWatchError::Analytics(format!("{}:{}: {}", path.display(), line_number, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_source::InMemoryBlockSource;
+    use crate::blockchain::MultiChainScanner;
+    use crate::NetworkConfig;
+    use ethers::types::{Block, Transaction, H160, H256, U256, U64};
+
+    fn price_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("zkwatch-pricing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn static_table_loads_csv_and_json() {
+        let csv = price_file("static.csv", "symbol,price_usd\n# spot prices\nETH, 3150.5\nusdc,1\n");
+        let table = StaticPriceTable::load(&csv).unwrap();
+        assert_eq!(table.price_usd("ETH", at(0)), Some(3150.5));
+        assert_eq!(table.price_usd("WETH", at(0)), Some(3150.5));
+        assert_eq!(table.price_usd("USDC", at(0)), Some(1.0));
+        assert_eq!(table.price_usd("DAI", at(0)), None);
+
+        let json = price_file("static.json", r#"{"MATIC": 0.72, "wbtc": 64000.0}"#);
+        let table = StaticPriceTable::load(&json).unwrap();
+        assert_eq!(table.price_usd("WMATIC", at(0)), Some(0.72));
+        assert_eq!(table.price_usd("BTC", at(0)), Some(64000.0));
+
+        // Two columns mean a time-independent table
+        assert_eq!(load_price_file(&csv).unwrap().price_usd("eth", at(1_700_000_000)), Some(3150.5));
+    }
+
+    #[test]
+    fn malformed_price_rows_name_the_line() {
+        let path = price_file("malformed.csv", "ETH,3150\nBTC,-1\n");
+        let error = StaticPriceTable::load(&path).unwrap_err();
+        assert!(matches!(&error, ZKWatchError::Analytics(message) if message.ends_with(":2: invalid price")));
+
+        let path = price_file("columns.csv", "ETH\n");
+        assert!(StaticPriceTable::load(&path).is_err());
+    }
+
+    #[test]
+    fn historical_lookup_takes_latest_sample_at_or_before_time() {
+        let csv = price_file("history.csv", "timestamp,symbol,price_usd\n1700000000,ETH,2000\n2023-11-14T23:13:20Z,ETH,2100\n");
+        let history = HistoricalPriceFile::load(&csv).unwrap();
+        assert_eq!(history.price_usd("ETH", at(1_699_999_999)), None);
+        assert_eq!(history.price_usd("ETH", at(1_700_000_000)), Some(2000.0));
+        assert_eq!(history.price_usd("WETH", at(1_700_000_500)), Some(2000.0));
+        assert_eq!(history.price_usd("ETH", at(1_700_003_600)), Some(2100.0));
+        assert_eq!(history.price_usd("BTC", at(1_700_001_000)), None);
+
+        let json = price_file("history.json", r#"[
+            {"timestamp": 1700000000, "symbol": "BTC", "price_usd": 37000.0},
+            {"timestamp": "2023-11-14T23:13:20Z", "symbol": "BTC", "price_usd": 37500.0}
+        ]"#);
+        let history = load_price_file(&json).unwrap();
+        assert_eq!(history.price_usd("WBTC", at(1_700_000_999)), Some(37000.0));
+        assert_eq!(history.price_usd("BTC", at(1_700_004_000)), Some(37500.0));
+    }
+
+    #[test]
+    fn stale_samples_are_not_used() {
+        let mut history = HistoricalPriceFile::default().with_max_staleness(chrono::Duration::hours(1));
+        history.insert("ETH", at(1_700_000_000), 2000.0);
+
+        assert_eq!(history.price_usd("ETH", at(1_700_003_600)), Some(2000.0));
+        assert_eq!(history.price_usd("ETH", at(1_700_003_601)), None);
+    }
+
+    #[tokio::test]
+    async fn unpriced_native_asset_fails_the_scan() {
+        let source = InMemoryBlockSource::new();
+        source.insert_block(Block {
+            number: Some(U64::from(10)),
+            hash: Some(H256::from_low_u64_be(10)),
+            timestamp: U256::from(1_700_000_000),
+            transactions: vec![Transaction {
+                hash: H256::from_low_u64_be(1),
+                from: H160::from_low_u64_be(1),
+                to: Some(H160::from_low_u64_be(2)),
+                value: U256::exp10(21),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let network = NetworkConfig {
+            name: "Ethereum".to_string(),
+            chain_id: 1,
+            rpc_url: String::new(),
+            ws_url: None,
+            explorer_url: String::new(),
+            native_symbol: "ETH".to_string(),
+            confirmation_depth: 12,
+            finality_depth: 64,
+            block_time_ms: 12_000,
+        };
+        let mut scanner = MultiChainScanner::new(vec![(network, Arc::new(source))])
+            .with_scan_depth(1)
+            .with_price_source(Arc::new(StaticPriceTable::stablecoins()));
+
+        let error = scanner.scan_whale_transactions(100_000.0).await.unwrap_err();
+        assert!(matches!(&error, ZKWatchError::MissingPrice(message) if message.starts_with("ETH on Ethereum")));
+    }
+}
@@ -0,0 +1,458 @@
+//! Versioned proof envelopes and verification keys
+//!
+//! Wraps proofs in a self-describing binary format tagged with a scheme ID and
+//! the hash of the verification-key parameters, and keeps a local registry of
+//! keys so envelopes produced under older parameters stay verifiable.
+
+use crate::commitments::{hex_decode, hex_encode};
+use crate::stark::StarkParams;
+use crate::zk_proofs::{
+    verify_aggregate_threshold_stark, verify_aggregated_whale_threshold, verify_whale_threshold, RangeProofParams,
+    ZKSNARKGenerator, ZKSTARKGenerator, DEFAULT_STARK_SECURITY_LEVEL,
+};
+use crate::{ZKProof, ZKWatchError, ZKWatchResult};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use chrono::{DateTime, SubsecRound, TimeZone, Utc};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Leading bytes of every envelope
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"ZKWP";
+
+/// Envelope layout written by this version
+pub const ENVELOPE_VERSION: u16 = 1;
+
+/// Domain separator of verification-key hashes
+const KEY_HASH_DOMAIN: &[u8] = b"zkwatch.verification_key.v1";
+
+/// Proof system an envelope was produced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProofScheme {
+    /// Bulletproofs range proof that a committed amount meets a threshold
+    BulletproofsRange,
+    /// One Bulletproofs range proof covering the thresholds of several transactions
+    BulletproofsAggregatedRange,
+    /// STARK that the transfers behind a public commitment add up to at least a threshold
+    StarkAggregateThreshold,
+}
+
+impl ProofScheme {
+    /// Stable wire identifier; never reuse a retired ID
+    pub fn id(self) -> u16 {
+        match self {
+            ProofScheme::BulletproofsRange => 1,
+            ProofScheme::BulletproofsAggregatedRange => 2,
+            ProofScheme::StarkAggregateThreshold => 3,
+        }
+    }
+
+    pub fn from_id(id: u16) -> ZKWatchResult<Self> {
+        match id {
+            1 => Ok(ProofScheme::BulletproofsRange),
+            2 => Ok(ProofScheme::BulletproofsAggregatedRange),
+            3 => Ok(ProofScheme::StarkAggregateThreshold),
+            other => Err(ZKWatchError::UnknownProofScheme(other)),
+        }
+    }
+}
+
+/// Scheme and canonical parameter encoding a proof verifies under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationKey {
+    pub scheme: ProofScheme,
+    pub params: Vec<u8>,
+}
+
+impl VerificationKey {
+    pub fn new(scheme: ProofScheme, params: Vec<u8>) -> Self {
+        Self { scheme, params }
+    }
+
+    /// SHA-256 over the scheme ID and parameters
+    pub fn hash(&self) -> [u8; 32] {
+        let mut context = digest::Context::new(&digest::SHA256);
+        context.update(KEY_HASH_DOMAIN);
+        context.update(&self.scheme.id().to_be_bytes());
+        context.update(&self.params);
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(context.finish().as_ref());
+        hash
+    }
+
+    /// Hex key hash, as carried in `ZKProof::verification_key`
+    pub fn id(&self) -> String {
+        hex_encode(&self.hash())
+    }
+}
+
+/// Stored form of a verification key; the hash is recomputed on load
+#[derive(Serialize, Deserialize)]
+struct StoredKey {
+    scheme: u16,
+    params: String,
+}
+
+/// Verification keys by hash
+///
+/// Bulletproofs generators of range-proof keys are built once on registration
+/// and shared by every verification under that key.
+#[derive(Clone, Default)]
+pub struct VerificationKeyRegistry {
+    keys: HashMap<[u8; 32], VerificationKey>,
+    bulletproof_gens: HashMap<[u8; 32], Arc<BulletproofGens>>,
+}
+
+impl fmt::Debug for VerificationKeyRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerificationKeyRegistry").field("keys", &self.keys).finish_non_exhaustive()
+    }
+}
+
+impl VerificationKeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry holding the keys of the current generators
+    pub fn with_defaults() -> Self {
+        let generator = ZKSNARKGenerator::new();
+        let mut registry = Self::new();
+        registry.register(generator.verification_key().clone());
+        registry.register(generator.aggregate_verification_key().clone());
+        registry.register(ZKSTARKGenerator::new(DEFAULT_STARK_SECURITY_LEVEL).verification_key().clone());
+        registry
+    }
+
+    /// Add a key, returning its hash
+    pub fn register(&mut self, key: VerificationKey) -> [u8; 32] {
+        let hash = key.hash();
+        if let Ok(params) = RangeProofParams::from_bytes(&key.params) {
+            if params.scheme() == key.scheme {
+                self.bulletproof_gens
+                    .entry(hash)
+                    .or_insert_with(|| Arc::new(BulletproofGens::new(params.bits, params.parties)));
+            }
+        }
+        self.keys.insert(hash, key);
+        hash
+    }
+
+    pub fn get(&self, hash: &[u8; 32]) -> ZKWatchResult<&VerificationKey> {
+        self.keys
+            .get(hash)
+            .ok_or_else(|| ZKWatchError::UnknownVerificationKey(hex_encode(hash)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Add the keys stored in a JSON file written by `save`
+    pub fn load(&mut self, path: impl AsRef<Path>) -> ZKWatchResult<usize> {
+        let stored: Vec<StoredKey> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        for key in &stored {
+            let params = hex_decode(&key.params)
+                .ok_or_else(|| ZKWatchError::MalformedProof(format!("key parameters are not hex: {}", key.params)))?;
+            self.register(VerificationKey::new(ProofScheme::from_id(key.scheme)?, params));
+        }
+        Ok(stored.len())
+    }
+
+    /// Write every key to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> ZKWatchResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut stored: Vec<StoredKey> = self
+            .keys
+            .values()
+            .map(|key| StoredKey { scheme: key.scheme.id(), params: hex_encode(&key.params) })
+            .collect();
+        stored.sort_by(|a, b| (a.scheme, &a.params).cmp(&(b.scheme, &b.params)));
+        std::fs::write(path, serde_json::to_string_pretty(&stored)?)?;
+        Ok(())
+    }
+}
+
+/// Self-describing proof: scheme, key hash, public inputs and proof bytes
+///
+/// Wire layout (integers big-endian): magic, version `u16`, scheme `u16`,
+/// key hash `[u8; 32]`, creation time in unix milliseconds `i64`, input count
+/// `u32` followed by `u32`-length-prefixed UTF-8 inputs, and `u32`-length-prefixed
+/// proof bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofEnvelope {
+    pub version: u16,
+    pub scheme: ProofScheme,
+    pub params_hash: [u8; 32],
+    pub created_at: DateTime<Utc>,
+    pub public_inputs: Vec<String>,
+    pub proof: Vec<u8>,
+}
+
+impl ProofEnvelope {
+    /// Wrap a proof whose `verification_key` is a key hash known to the registry
+    pub fn seal(proof: &ZKProof, registry: &VerificationKeyRegistry) -> ZKWatchResult<Self> {
+        let params_hash: [u8; 32] = hex_decode(&proof.verification_key)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| ZKWatchError::UnknownVerificationKey(proof.verification_key.clone()))?;
+        let key = registry.get(&params_hash)?;
+
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            scheme: key.scheme,
+            params_hash,
+            // The wire format keeps millisecond precision
+            created_at: proof.timestamp.trunc_subsecs(3),
+            public_inputs: proof.public_inputs.clone(),
+            proof: proof.proof_data.clone(),
+        })
+    }
+
+    pub fn to_proof(&self) -> ZKProof {
+        ZKProof {
+            proof_data: self.proof.clone(),
+            public_inputs: self.public_inputs.clone(),
+            verification_key: hex_encode(&self.params_hash),
+            timestamp: self.created_at,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64 + self.proof.len());
+        bytes.extend_from_slice(ENVELOPE_MAGIC);
+        bytes.extend_from_slice(&ENVELOPE_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&self.scheme.id().to_be_bytes());
+        bytes.extend_from_slice(&self.params_hash);
+        bytes.extend_from_slice(&self.created_at.timestamp_millis().to_be_bytes());
+        bytes.extend_from_slice(&(self.public_inputs.len() as u32).to_be_bytes());
+        for input in &self.public_inputs {
+            bytes.extend_from_slice(&(input.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(input.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.proof.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.proof);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> ZKWatchResult<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(ENVELOPE_MAGIC.len())? != ENVELOPE_MAGIC {
+            return Err(ZKWatchError::MalformedProof("not a ZKWatch proof envelope".to_string()));
+        }
+
+        match reader.u16()? {
+            1 => Self::decode_v1(&mut reader),
+            version => Err(ZKWatchError::UnsupportedProofVersion(version)),
+        }
+    }
+
+    fn decode_v1(reader: &mut Reader<'_>) -> ZKWatchResult<Self> {
+        let scheme = ProofScheme::from_id(reader.u16()?)?;
+        let params_hash: [u8; 32] = reader
+            .take(32)?
+            .try_into()
+            .map_err(|_| ZKWatchError::MalformedProof("truncated key hash".to_string()))?;
+        let millis = reader.i64()?;
+        let created_at = Utc
+            .timestamp_millis_opt(millis)
+            .single()
+            .ok_or_else(|| ZKWatchError::MalformedProof(format!("invalid creation time {}", millis)))?;
+
+        let count = reader.u32()? as usize;
+        let mut public_inputs = Vec::with_capacity(count.min(64));
+        for _ in 0..count {
+            let len = reader.u32()? as usize;
+            let input = std::str::from_utf8(reader.take(len)?)
+                .map_err(|_| ZKWatchError::MalformedProof("public input is not UTF-8".to_string()))?;
+            public_inputs.push(input.to_string());
+        }
+
+        let len = reader.u32()? as usize;
+        let proof = reader.take(len)?.to_vec();
+        if reader.position != reader.bytes.len() {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "{} trailing bytes after proof",
+                reader.bytes.len() - reader.position
+            )));
+        }
+
+        Ok(Self {
+            version: 1,
+            scheme,
+            params_hash,
+            created_at,
+            public_inputs,
+            proof,
+        })
+    }
+
+    /// Verify under the registered key the envelope names
+    ///
+    /// Unknown keys and parameters that do not match the scheme are errors;
+    /// a well-formed proof that fails verification is `false`.
+    pub fn verify(&self, registry: &VerificationKeyRegistry) -> ZKWatchResult<bool> {
+        let key = registry.get(&self.params_hash)?;
+        if key.scheme != self.scheme {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "envelope scheme {:?} does not match key scheme {:?}",
+                self.scheme, key.scheme
+            )));
+        }
+
+        match self.scheme {
+            ProofScheme::BulletproofsRange | ProofScheme::BulletproofsAggregatedRange => {
+                self.verify_range_proof(key, registry)
+            }
+            ProofScheme::StarkAggregateThreshold => {
+                let params = StarkParams::from_bytes(&key.params)?;
+                Ok(verify_aggregate_threshold_stark(&params, &self.public_inputs, &self.proof))
+            }
+        }
+    }
+
+    fn verify_range_proof(&self, key: &VerificationKey, registry: &VerificationKeyRegistry) -> ZKWatchResult<bool> {
+        let params = RangeProofParams::from_bytes(&key.params)?;
+        if params.scheme() != self.scheme {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "{} parties do not match scheme {:?}",
+                params.parties, self.scheme
+            )));
+        }
+        let bp_gens = registry
+            .bulletproof_gens
+            .get(&self.params_hash)
+            .ok_or_else(|| ZKWatchError::UnknownVerificationKey(hex_encode(&self.params_hash)))?;
+        let verify = if params.parties == 1 { verify_whale_threshold } else { verify_aggregated_whale_threshold };
+        Ok(verify(&PedersenGens::default(), bp_gens, params.bits, &self.public_inputs, &self.proof))
+    }
+}
+
+/// Bounds-checked cursor over envelope bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> ZKWatchResult<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| ZKWatchError::MalformedProof("envelope is truncated".to_string()))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> ZKWatchResult<u16> {
+        let mut buf = [0u8; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    fn u32(&mut self) -> ZKWatchResult<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn i64(&mut self) -> ZKWatchResult<i64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stark_key() -> VerificationKey {
+        VerificationKey::new(ProofScheme::StarkAggregateThreshold, StarkParams::from_security_level(DEFAULT_STARK_SECURITY_LEVEL).to_bytes())
+    }
+
+    fn envelope(registry: &mut VerificationKeyRegistry) -> ProofEnvelope {
+        let proof = ZKProof {
+            proof_data: vec![7, 0, 255, 3],
+            public_inputs: vec!["3".to_string(), "1000".to_string(), "héllo".to_string(), String::new()],
+            verification_key: hex_encode(&registry.register(stark_key())),
+            timestamp: Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap(),
+        };
+        ProofEnvelope::seal(&proof, registry).unwrap()
+    }
+
+    #[test]
+    fn envelope_round_trips_through_bytes() {
+        let mut registry = VerificationKeyRegistry::new();
+        let envelope = envelope(&mut registry);
+        assert_eq!(envelope.scheme, ProofScheme::StarkAggregateThreshold);
+        assert_eq!(envelope.created_at.timestamp_subsec_millis(), 123);
+
+        let bytes = envelope.encode();
+        assert_eq!(&bytes[..4], ENVELOPE_MAGIC);
+        let decoded = ProofEnvelope::decode(&bytes).unwrap();
+        assert_eq!(decoded, envelope);
+
+        let proof = decoded.to_proof();
+        assert_eq!(proof.verification_key, stark_key().id());
+        assert_eq!(ProofEnvelope::seal(&proof, &registry).unwrap(), envelope);
+    }
+
+    #[test]
+    fn malformed_envelopes_are_rejected() {
+        let bytes = envelope(&mut VerificationKeyRegistry::new()).encode();
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(matches!(ProofEnvelope::decode(&magic), Err(ZKWatchError::MalformedProof(_))));
+
+        let mut version = bytes.clone();
+        version[4..6].copy_from_slice(&9u16.to_be_bytes());
+        assert!(matches!(ProofEnvelope::decode(&version), Err(ZKWatchError::UnsupportedProofVersion(9))));
+
+        let mut scheme = bytes.clone();
+        scheme[6..8].copy_from_slice(&77u16.to_be_bytes());
+        assert!(matches!(ProofEnvelope::decode(&scheme), Err(ZKWatchError::UnknownProofScheme(77))));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(ProofEnvelope::decode(&trailing).is_err());
+        for len in [5, 40, bytes.len() - 1] {
+            assert!(ProofEnvelope::decode(&bytes[..len]).is_err(), "truncated to {}", len);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_cannot_be_sealed_or_verified() {
+        let envelope = envelope(&mut VerificationKeyRegistry::new());
+        let empty = VerificationKeyRegistry::new();
+        assert!(matches!(ProofEnvelope::seal(&envelope.to_proof(), &empty), Err(ZKWatchError::UnknownVerificationKey(_))));
+        assert!(matches!(envelope.verify(&empty), Err(ZKWatchError::UnknownVerificationKey(_))));
+    }
+
+    #[test]
+    fn registry_survives_save_and_load() {
+        let path = std::env::temp_dir().join(format!("zkwatch-keys-{}.json", std::process::id()));
+        let mut registry = VerificationKeyRegistry::new();
+        let hash = registry.register(stark_key());
+        registry.register(VerificationKey::new(ProofScheme::BulletproofsRange, vec![1, 2, 3]));
+        registry.save(&path).unwrap();
+
+        let mut loaded = VerificationKeyRegistry::new();
+        assert_eq!(loaded.load(&path).unwrap(), 2);
+        assert_eq!(loaded.get(&hash).unwrap(), &stark_key());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,502 @@
+//! Sanctions and watchlist screening
+//!
+//! Loads OFAC SDN digital currency addresses (XML or CSV) and internal
+//! watchlists, and flags whale transactions, clusters and addresses that touch
+//! a listed address directly or within a few hops of the transfer graph.
+
+use crate::blockchain::RiskAssessment;
+use crate::labels::{LabelCategory, LabelRegistry};
+use crate::whale_tracker::WhaleCluster;
+use crate::{WhaleTransaction, ZKWatchError, ZKWatchResult};
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+/// Hops searched around a screened address by default
+pub const DEFAULT_SCREENING_HOPS: usize = 2;
+
+/// Prefix of OFAC identifiers that carry a digital currency address
+const OFAC_ADDRESS_PREFIX: &str = "Digital Currency Address - ";
+
+/// Listed address and the list entry it belongs to
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ListEntry {
+    /// List the entry came from, such as `OFAC SDN` or a watchlist's file name
+    pub list: String,
+    /// Identifier of the entry in its list (OFAC uid, watchlist line)
+    pub entry_id: String,
+    pub name: String,
+    pub address: String,
+    /// Sanctions programs or watchlist reason
+    pub programs: Vec<String>,
+}
+
+/// Listed address found near a screened address
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ScreeningHit {
+    pub screened_address: String,
+    pub entry: ListEntry,
+    /// Transfers between the screened and the listed address; 0 when it is listed itself
+    pub hops: usize,
+    /// Addresses from the screened address to the listed one
+    pub path: Vec<String>,
+}
+
+/// Screening result of a transaction, cluster or address
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScreeningFlag {
+    /// Transaction hash, cluster id or address
+    pub subject: String,
+    pub risk: RiskAssessment,
+    pub evidence: Vec<ScreeningHit>,
+}
+
+/// Undirected graph of which addresses transferred funds to each other
+#[derive(Debug, Clone, Default)]
+pub struct TransferGraph {
+    neighbors: HashMap<String, BTreeSet<String>>,
+}
+
+impl TransferGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_transactions(transactions: &[WhaleTransaction]) -> Self {
+        let mut graph = Self::new();
+        for transaction in transactions {
+            graph.add(&transaction.from, &transaction.to);
+        }
+        graph
+    }
+
+    pub fn add(&mut self, from: &str, to: &str) {
+        let (from, to) = (from.to_ascii_lowercase(), to.to_ascii_lowercase());
+        if from.is_empty() || to.is_empty() || from == to {
+            return;
+        }
+        self.neighbors.entry(from.clone()).or_default().insert(to.clone());
+        self.neighbors.entry(to).or_default().insert(from);
+    }
+
+    fn neighbors(&self, address: &str) -> impl Iterator<Item = &String> {
+        self.neighbors.get(address).into_iter().flatten()
+    }
+}
+
+/// Sanctioned and watched addresses with the services screening does not search through
+#[derive(Debug, Clone)]
+pub struct Screener {
+    entries: HashMap<H160, Vec<ListEntry>>,
+    /// Labeled exchanges, routers, bridges and pools, which connect unrelated users
+    services: HashSet<H160>,
+    max_hops: usize,
+}
+
+impl Default for Screener {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            services: HashSet::new(),
+            max_hops: DEFAULT_SCREENING_HOPS,
+        }
+    }
+}
+
+impl Screener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sanctioned and mixer addresses of a label registry
+    pub fn from_labels(labels: &LabelRegistry) -> Self {
+        let mut screener = Self::new();
+        screener.add_labels(labels);
+        screener
+    }
+
+    /// Set how many transfers away from a screened address listed addresses are searched
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// List sanctioned and mixer labels, and stop searches at other labeled addresses
+    pub fn add_labels(&mut self, labels: &LabelRegistry) {
+        for (_, address, label) in labels.iter() {
+            let listed = [LabelCategory::Sanctioned, LabelCategory::Mixer]
+                .into_iter()
+                .filter(|category| label.has(*category))
+                .map(|category| match category {
+                    LabelCategory::Sanctioned => "sanctioned".to_string(),
+                    _ => "mixer".to_string(),
+                })
+                .collect::<Vec<_>>();
+            if listed.is_empty() {
+                self.services.insert(*address);
+                continue;
+            }
+            self.insert(*address, ListEntry {
+                list: "labels".to_string(),
+                entry_id: label.name.clone(),
+                name: label.entity.clone().unwrap_or_else(|| label.name.clone()),
+                address: format!("{:?}", address),
+                programs: listed,
+            });
+        }
+    }
+
+    /// Read a list file: OFAC SDN XML, OFAC SDN CSV (recognized by its digital
+    /// currency remarks) or a watchlist CSV
+    pub fn load(&mut self, path: impl AsRef<Path>) -> ZKWatchResult<usize> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let origin = path.display().to_string();
+
+        let entries = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("xml")) {
+            parse_ofac_xml(&contents)
+        } else if contents.contains(OFAC_ADDRESS_PREFIX) {
+            parse_ofac_csv(&contents)
+        } else {
+            let list = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or(origin.clone());
+            parse_watchlist(&contents, &list, &origin)?
+        };
+
+        let count = entries.len();
+        for entry in entries {
+            if let Ok(address) = entry.address.parse::<H160>() {
+                self.insert(address, entry);
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn insert(&mut self, address: H160, entry: ListEntry) {
+        let entries = self.entries.entry(address).or_default();
+        if !entries.iter().any(|e| e.list == entry.list && e.entry_id == entry.entry_id) {
+            entries.push(entry);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Transfers searched away from a screened address
+    pub fn max_hops(&self) -> usize {
+        self.max_hops
+    }
+
+    /// Entries listing an address
+    pub fn lookup(&self, address: &str) -> &[ListEntry] {
+        address.parse::<H160>().ok()
+            .and_then(|address| self.entries.get(&address))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Listed addresses at most `max_hops` transfers from `address`, nearest first
+    ///
+    /// Searches do not pass through labeled services, so sharing an exchange
+    /// with a sanctioned address does not count as touching it.
+    pub fn screen_address(&self, address: &str, graph: &TransferGraph) -> Vec<ScreeningHit> {
+        let start = address.to_ascii_lowercase();
+        let mut hits = Vec::new();
+        let mut visited = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([vec![start.clone()]]);
+
+        while let Some(path) = queue.pop_front() {
+            let current = path.last().cloned().unwrap_or_default();
+            for entry in self.lookup(&current) {
+                hits.push(ScreeningHit {
+                    screened_address: start.clone(),
+                    entry: entry.clone(),
+                    hops: path.len() - 1,
+                    path: path.clone(),
+                });
+            }
+            if path.len() > self.max_hops || (path.len() > 1 && self.is_service(&current)) {
+                continue;
+            }
+            for neighbor in graph.neighbors(&current) {
+                if visited.insert(neighbor.clone()) {
+                    let mut next = path.clone();
+                    next.push(neighbor.clone());
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        hits
+    }
+
+    /// Flag a transaction whose sender or recipient touches a listed address
+    pub fn screen_transaction(&self, transaction: &WhaleTransaction, graph: &TransferGraph) -> Option<ScreeningFlag> {
+        self.flag(&transaction.hash, [transaction.from.as_str(), transaction.to.as_str()], graph)
+    }
+
+    /// Flag every transaction touching a listed address, searching the graph of the transactions themselves
+    pub fn screen_transactions(&self, transactions: &[WhaleTransaction]) -> Vec<ScreeningFlag> {
+        let graph = TransferGraph::from_transactions(transactions);
+        transactions.iter()
+            .filter_map(|transaction| self.screen_transaction(transaction, &graph))
+            .collect()
+    }
+
+    /// Flag a cluster any member of which touches a listed address
+    pub fn screen_cluster(&self, cluster: &WhaleCluster, graph: &TransferGraph) -> Option<ScreeningFlag> {
+        self.flag(&cluster.cluster_id, cluster.member_addresses.iter().map(String::as_str), graph)
+    }
+
+    fn flag<'a>(&self, subject: &str, addresses: impl IntoIterator<Item = &'a str>, graph: &TransferGraph) -> Option<ScreeningFlag> {
+        let mut evidence: Vec<ScreeningHit> = Vec::new();
+        for address in addresses {
+            for hit in self.screen_address(address, graph) {
+                if !evidence.iter().any(|e| e.entry == hit.entry && e.hops <= hit.hops) {
+                    evidence.push(hit);
+                }
+            }
+        }
+        if evidence.is_empty() {
+            return None;
+        }
+        evidence.sort_by_key(|hit| hit.hops);
+        Some(ScreeningFlag {
+            subject: subject.to_string(),
+            risk: RiskAssessment::High,
+            evidence,
+        })
+    }
+
+    /// Whether searches stop at the address, a labeled service connecting unrelated users
+    pub fn is_service(&self, address: &str) -> bool {
+        address.parse::<H160>().is_ok_and(|address| self.services.contains(&address))
+    }
+}
+
+/// EVM addresses of `<sdnEntry>` elements in the OFAC SDN XML
+fn parse_ofac_xml(contents: &str) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for entry in elements(contents, "sdnEntry") {
+        let uid = element(entry, "uid").unwrap_or_default();
+        let name = [element(entry, "firstName"), element(entry, "lastName")]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let programs: Vec<String> = elements(entry, "program").map(unescape_xml).collect();
+
+        for id in elements(entry, "id") {
+            let id_type = element(id, "idType").unwrap_or_default();
+            let Some(asset) = id_type.strip_prefix(OFAC_ADDRESS_PREFIX) else {
+                continue;
+            };
+            let address = element(id, "idNumber").unwrap_or_default();
+            if is_evm_address(&address) {
+                entries.push(ListEntry {
+                    list: "OFAC SDN".to_string(),
+                    entry_id: format!("{} ({})", uid, asset.trim()),
+                    name: name.clone(),
+                    address: address.to_ascii_lowercase(),
+                    programs: programs.clone(),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// EVM addresses in the remarks of OFAC `sdn.csv` rows
+///
+/// Rows are `ent_num,SDN_Name,SDN_Type,Program,...,Remarks`, with addresses
+/// written as `Digital Currency Address - ETH 0x...;` in the remarks.
+fn parse_ofac_csv(contents: &str) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for line in contents.lines() {
+        let fields = split_csv_line(line);
+        let (Some(uid), Some(name), Some(remarks)) = (fields.first(), fields.get(1), fields.last()) else {
+            continue;
+        };
+        let programs: Vec<String> = fields.get(3)
+            .map(|p| p.split(']').map(|p| p.trim_matches(|c: char| c == '[' || c.is_whitespace()).to_string()).filter(|p| !p.is_empty() && p != "-0-").collect())
+            .unwrap_or_default();
+
+        for remark in remarks.split(';') {
+            // Additional addresses are remarked as `alt. Digital Currency Address - ...`
+            let Some(rest) = remark.find(OFAC_ADDRESS_PREFIX).map(|start| &remark[start + OFAC_ADDRESS_PREFIX.len()..]) else {
+                continue;
+            };
+            let mut parts = rest.split_whitespace();
+            let (Some(asset), Some(address)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let address = address.trim_end_matches('.');
+            if is_evm_address(address) {
+                entries.push(ListEntry {
+                    list: "OFAC SDN".to_string(),
+                    entry_id: format!("{} ({})", uid, asset),
+                    name: name.clone(),
+                    address: address.to_ascii_lowercase(),
+                    programs: programs.clone(),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// Watchlist rows of `address,name[,reason]`, with `#` comments and an optional header
+fn parse_watchlist(contents: &str, list: &str, origin: &str) -> ZKWatchResult<Vec<ListEntry>> {
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let fields = split_csv_line(line);
+        if line.is_empty() || line.starts_with('#') || fields[0].eq_ignore_ascii_case("address") {
+            continue;
+        }
+        if !is_evm_address(&fields[0]) {
+            return Err(ZKWatchError::Analytics(format!("{}:{}: invalid address {}", origin, index + 1, fields[0])));
+        }
+        entries.push(ListEntry {
+            list: list.to_string(),
+            entry_id: format!("line {}", index + 1),
+            name: fields.get(1).cloned().unwrap_or_default(),
+            address: fields[0].to_ascii_lowercase(),
+            programs: fields.get(2).filter(|reason| !reason.is_empty()).cloned().into_iter().collect(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Bodies of every `<tag>...</tag>` element, without nesting of the same tag
+fn elements<'a>(contents: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut rest = contents;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let end = start + rest[start..].find(&close)?;
+        let body = &rest[start..end];
+        rest = &rest[end + close.len()..];
+        Some(body)
+    })
+}
+
+fn element(contents: &str, tag: &str) -> Option<String> {
+    elements(contents, tag).next().map(|body| unescape_xml(body.trim()))
+}
+
+fn unescape_xml(value: &str) -> String {
+    value.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Fields of a CSV line, honoring double-quoted fields with `""` escapes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().expect("at least one field").push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().expect("at least one field").push(c),
+        }
+    }
+    fields.into_iter().map(|field| field.trim().to_string()).collect()
+}
+
+fn is_evm_address(value: &str) -> bool {
+    value.len() == 42 && value.starts_with("0x") && value.parse::<H160>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTED: &str = "0x8589427373d6d84e98730d7795d8f6f8731fda16";
+    const OTHER: &str = "0x722122df12d4e14e13ac3b6895a86e84145b6967";
+
+    const SDN_XML: &str = r#"<sdnList>
+  <sdnEntry>
+    <uid>25000</uid>
+    <lastName>Example Mixer &amp; Co</lastName>
+    <sdnType>Entity</sdnType>
+    <programList><program>CYBER2</program><program>DPRKNK</program></programList>
+    <idList>
+      <id><uid>1001</uid><idType>Digital Currency Address - XBT</idType><idNumber>1BitcoinAddressNotEvm</idNumber></id>
+      <id><uid>1002</uid><idType>Digital Currency Address - ETH</idType><idNumber>0x8589427373D6D84E98730D7795D8f6f8731FDA16</idNumber></id>
+      <id><uid>1003</uid><idType>Passport</idType><idNumber>0x722122df12d4e14e13ac3b6895a86e84145b6967</idNumber></id>
+    </idList>
+  </sdnEntry>
+</sdnList>"#;
+
+    #[test]
+    fn ofac_xml_yields_evm_currency_addresses() {
+        let entries = parse_ofac_xml(SDN_XML);
+        assert_eq!(entries, vec![ListEntry {
+            list: "OFAC SDN".to_string(),
+            entry_id: "25000 (ETH)".to_string(),
+            name: "Example Mixer & Co".to_string(),
+            address: LISTED.to_string(),
+            programs: vec!["CYBER2".to_string(), "DPRKNK".to_string()],
+        }]);
+    }
+
+    #[test]
+    fn ofac_csv_reads_addresses_from_remarks() {
+        let csv = format!(
+            "25000,\"MIXER, EXAMPLE\",-0- ,\"CYBER2] [DPRKNK\",-0- ,-0- ,-0- ,-0- ,-0- ,-0- ,-0- ,\"Digital Currency Address - XBT 1BitcoinAddressNotEvm; Digital Currency Address - ETH {}; alt. Digital Currency Address - USDT {}.\"\n\
+             25001,\"PERSON, EXAMPLE\",individual,SDGT,-0- ,-0- ,-0- ,-0- ,-0- ,-0- ,-0- ,\"DOB 01 Jan 1970.\"",
+            LISTED.to_ascii_uppercase().replacen("0X", "0x", 1),
+            OTHER,
+        );
+        let entries = parse_ofac_csv(&csv);
+
+        let addresses: Vec<(&str, &str)> = entries.iter().map(|e| (e.entry_id.as_str(), e.address.as_str())).collect();
+        assert_eq!(addresses, vec![("25000 (ETH)", LISTED), ("25000 (USDT)", OTHER)]);
+        assert_eq!(entries[0].name, "MIXER, EXAMPLE");
+        assert_eq!(entries[0].programs, vec!["CYBER2".to_string(), "DPRKNK".to_string()]);
+    }
+
+    #[test]
+    fn watchlist_rejects_invalid_addresses() {
+        let entries = parse_watchlist(&format!("address,name,reason\n# internal\n{},Desk,fraud\n", OTHER), "desk", "desk.csv").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entry_id, "line 3");
+        assert_eq!(entries[0].programs, vec!["fraud".to_string()]);
+
+        assert!(parse_watchlist("0x1234,Short\n", "desk", "desk.csv").is_err());
+    }
+
+    #[test]
+    fn listed_address_is_found_within_max_hops() {
+        let mut screener = Screener::new();
+        for entry in parse_ofac_xml(SDN_XML) {
+            screener.insert(entry.address.parse().unwrap(), entry);
+        }
+        let hop = format!("0x{:040x}", 1);
+        let mut graph = TransferGraph::new();
+        graph.add(OTHER, &hop);
+        graph.add(&hop, LISTED);
+
+        let hits = screener.screen_address(OTHER, &graph);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].hops, 2);
+        assert_eq!(hits[0].path, vec![OTHER.to_string(), hop.clone(), LISTED.to_string()]);
+
+        assert!(screener.with_max_hops(1).screen_address(OTHER, &graph).is_empty());
+    }
+}
@@ -0,0 +1,1100 @@
+//! Minimal STARK over the Goldilocks field
+//!
+//! Proves that the transfers behind a public commitment add up to at least a
+//! public threshold. Every transfer fills a block of 32 trace rows holding its
+//! transaction hash and the bits of both 32-bit limbs of its amount; the
+//! commitment is the Merkle root of those columns' low-degree extension, so
+//! anyone holding the transfers can recompute it. The remaining columns keep the
+//! running total in normalized 32-bit limbs and decompose the excess over the
+//! threshold, so no sum wraps around the field modulus.
+//!
+//! The execution trace is low-degree extended onto a coset and committed in
+//! SHA-256 Merkle trees, the constraint composition is shown to be of low degree
+//! with FRI, and every challenge is drawn from a merlin transcript into the
+//! quadratic extension of the field.
+//!
+//! Proofs are succinct but not zero-knowledge: query openings reveal
+//! evaluations of the trace polynomials.
+
+use crate::{ZKWatchError, ZKWatchResult};
+use merlin::Transcript;
+use rayon::prelude::*;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, Mul, Sub};
+
+/// Goldilocks prime, 2^64 - 2^32 + 1
+pub const FIELD_MODULUS: u64 = 0xffff_ffff_0000_0001;
+
+/// Generator of the multiplicative group, also the offset of the LDE coset
+const GENERATOR: u64 = 7;
+
+/// `p - 1 = 2^32 · (2^32 - 1)`, so power-of-two domains hold at most 2^32 points
+const TWO_ADICITY: u32 = 32;
+
+/// Non-residue `W` of the challenge field `F[X] / (X^2 - W)`
+const EXTENSION_W: u64 = 7;
+
+/// Bits of an amount limb; one trace row per bit, so also the rows of a transfer block
+const LIMB_BITS: usize = 32;
+
+/// 32-bit limbs of a transaction hash, stored in the first rows of its block
+const HASH_LIMBS: usize = 8;
+
+/// Bits of the high limb of the excess over the threshold; the high limb of
+/// the total stays below 2^(32 + log2 MAX_STARK_TRANSFERS + 1)
+const EXCESS_HI_BITS: usize = 62;
+
+/// Shortest trace, long enough to decompose the excess
+const MIN_TRACE_LENGTH: usize = 64;
+
+/// Most transfers one STARK covers, bounding proving memory and verifier work
+pub const MAX_STARK_TRANSFERS: usize = 1 << 10;
+
+/// Size of the LDE domain relative to the trace
+pub const STARK_BLOWUP: usize = 16;
+
+/// Highest security level the parameters claim; challenge-field, commitment
+/// and hash soundness all stay above it for the largest supported domain
+pub const MAX_STARK_SECURITY_LEVEL: u32 = 100;
+
+/// Domain separator of the whale aggregate STARK transcript
+const WHALE_AGGREGATE_DOMAIN: &[u8] = b"zkwatch.stark.whale_aggregate.v2";
+
+/// Trace columns; the first `TRANSFER_WIDTH` are fixed by the transfers and
+/// form the public commitment, the rest depend on the threshold
+const TRACE_WIDTH: usize = 15;
+const TRANSFER_WIDTH: usize = 5;
+/// Transaction hash limbs, zero past the first `HASH_LIMBS` rows of a block
+const TX: usize = 0;
+/// Bits of the amount's high and low limbs, most significant first
+const HI_BIT: usize = 1;
+const LO_BIT: usize = 2;
+/// Limbs accumulated from their bits, complete on the last row of a block
+const HI: usize = 3;
+const LO: usize = 4;
+/// Bits and accumulator of the normalized low limb of the running total
+const SUM_BIT: usize = 5;
+const SUM_LO: usize = 6;
+/// Carry out of the low limb when a block's amount is added
+const CARRY: usize = 7;
+/// Low and high limbs of the total of all previous blocks
+const PREV_LO: usize = 8;
+const TOTAL_HI: usize = 9;
+/// Bits and accumulators of the excess limbs, carried to the last row
+const EXCESS_LO_BIT: usize = 10;
+const EXCESS_LO: usize = 11;
+const EXCESS_HI_BIT: usize = 12;
+const EXCESS_HI: usize = 13;
+/// Borrow from the high limb when the excess is subtracted from the total
+const BORROW: usize = 14;
+
+/// Boundary and transition constraints of the AIR
+const CONSTRAINTS: usize = 28;
+
+type Hash = [u8; 32];
+type Row = [Felt; TRACE_WIDTH];
+
+/// Transaction hash and amount of one transfer in a STARK aggregate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StarkTransfer {
+    pub transaction_hash: [u8; 32],
+    pub amount: u64,
+}
+
+/// Element of the Goldilocks field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Felt(u64);
+
+impl Felt {
+    const ZERO: Felt = Felt(0);
+    const ONE: Felt = Felt(1);
+
+    fn new(value: u64) -> Self {
+        Self(value % FIELD_MODULUS)
+    }
+
+    fn from_u128(value: u128) -> Self {
+        Self((value % FIELD_MODULUS as u128) as u64)
+    }
+
+    /// Decode a canonical big-endian element
+    fn from_bytes(bytes: [u8; 8]) -> Option<Self> {
+        let value = u64::from_be_bytes(bytes);
+        (value < FIELD_MODULUS).then_some(Self(value))
+    }
+
+    fn pow(self, mut exponent: u64) -> Self {
+        let (mut base, mut result) = (self, Felt::ONE);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// Multiplicative inverse; zero maps to zero
+    fn inverse(self) -> Self {
+        self.pow(FIELD_MODULUS - 2)
+    }
+
+    /// Generator of the subgroup of power-of-two `order`
+    fn root_of_unity(order: usize) -> Self {
+        debug_assert!(order.is_power_of_two() && order.trailing_zeros() <= TWO_ADICITY);
+        Felt(GENERATOR).pow((FIELD_MODULUS - 1) / order as u64)
+    }
+}
+
+impl Add for Felt {
+    type Output = Felt;
+
+    fn add(self, rhs: Felt) -> Felt {
+        Felt::from_u128(self.0 as u128 + rhs.0 as u128)
+    }
+}
+
+impl Sub for Felt {
+    type Output = Felt;
+
+    fn sub(self, rhs: Felt) -> Felt {
+        Felt::from_u128(self.0 as u128 + FIELD_MODULUS as u128 - rhs.0 as u128)
+    }
+}
+
+impl Mul for Felt {
+    type Output = Felt;
+
+    fn mul(self, rhs: Felt) -> Felt {
+        Felt::from_u128(self.0 as u128 * rhs.0 as u128)
+    }
+}
+
+/// Element `a + b·X` of the quadratic extension, holding challenges and
+/// everything combined with them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ext(Felt, Felt);
+
+impl Ext {
+    const ZERO: Ext = Ext(Felt::ZERO, Felt::ZERO);
+
+    fn scale(self, factor: Felt) -> Ext {
+        Ext(self.0 * factor, self.1 * factor)
+    }
+}
+
+impl Add for Ext {
+    type Output = Ext;
+
+    fn add(self, rhs: Ext) -> Ext {
+        Ext(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl Sub for Ext {
+    type Output = Ext;
+
+    fn sub(self, rhs: Ext) -> Ext {
+        Ext(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+
+impl Mul for Ext {
+    type Output = Ext;
+
+    fn mul(self, rhs: Ext) -> Ext {
+        Ext(
+            self.0 * rhs.0 + Felt(EXTENSION_W) * self.1 * rhs.1,
+            self.0 * rhs.1 + self.1 * rhs.0,
+        )
+    }
+}
+
+/// FRI queries and blowup of a STARK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StarkParams {
+    pub blowup: usize,
+    pub queries: usize,
+}
+
+impl StarkParams {
+    /// Length of the encoded parameters: modulus, blowup and queries
+    const ENCODED_LEN: usize = 8 + 4 + 4;
+
+    /// Enough queries for `security_level` bits of conjectured FRI soundness,
+    /// clamped to `MAX_STARK_SECURITY_LEVEL`
+    ///
+    /// The composition polynomial has twice the trace degree, so the FRI rate
+    /// is `2 / blowup` and each query contributes `log2(blowup / 2)` bits.
+    pub fn from_security_level(security_level: u32) -> Self {
+        let bits_per_query = (STARK_BLOWUP / 2).trailing_zeros();
+        Self {
+            blowup: STARK_BLOWUP,
+            queries: security_level.min(MAX_STARK_SECURITY_LEVEL).div_ceil(bits_per_query).max(1) as usize,
+        }
+    }
+
+    /// Conjectured security, in bits, of proofs under these parameters
+    pub fn security_level(&self) -> u32 {
+        let bits_per_query = (self.blowup / 2).trailing_zeros();
+        (self.queries as u32 * bits_per_query).min(MAX_STARK_SECURITY_LEVEL)
+    }
+
+    /// Field modulus followed by blowup and queries, so a change of field changes the key
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::ENCODED_LEN);
+        bytes.extend_from_slice(&FIELD_MODULUS.to_be_bytes());
+        bytes.extend_from_slice(&(self.blowup as u32).to_be_bytes());
+        bytes.extend_from_slice(&(self.queries as u32).to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> ZKWatchResult<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "STARK parameters must be {} bytes, got {}",
+                Self::ENCODED_LEN,
+                bytes.len()
+            )));
+        }
+        if u64::from_be_bytes(bytes[0..8].try_into().unwrap_or_default()) != FIELD_MODULUS {
+            return Err(ZKWatchError::MalformedProof("STARK uses an unknown field".to_string()));
+        }
+        let blowup = u32::from_be_bytes(bytes[8..12].try_into().unwrap_or_default()) as usize;
+        let queries = u32::from_be_bytes(bytes[12..16].try_into().unwrap_or_default()) as usize;
+        if !blowup.is_power_of_two() || !(4..=256).contains(&blowup) || !(1..=1024).contains(&queries) {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "unsupported STARK parameters: blowup {}, {} queries",
+                blowup, queries
+            )));
+        }
+        Ok(Self { blowup, queries })
+    }
+}
+
+/// Trace and LDE sizes for `transfers` transfers
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    transfers: usize,
+    trace_length: usize,
+    /// Size of the LDE domain
+    domain_size: usize,
+}
+
+impl Layout {
+    fn new(transfers: usize, params: &StarkParams) -> Option<Self> {
+        if !(1..=MAX_STARK_TRANSFERS).contains(&transfers) {
+            return None;
+        }
+        let trace_length = (transfers.next_power_of_two() * LIMB_BITS).max(MIN_TRACE_LENGTH);
+        let domain_size = trace_length.checked_mul(params.blowup)?;
+        (domain_size.trailing_zeros() <= TWO_ADICITY).then_some(Self {
+            transfers,
+            trace_length,
+            domain_size,
+        })
+    }
+}
+
+/// Public statement: the committed transfers add up to at least `threshold`
+struct Statement {
+    layout: Layout,
+    threshold: u64,
+    commitment: Hash,
+}
+
+impl Statement {
+    /// FRI folds that bring the composition's degree bound of `2 · trace_length` down to a constant
+    fn fri_layers(&self) -> usize {
+        (2 * self.layout.trace_length).trailing_zeros() as usize
+    }
+
+    fn transcript(&self, params: &StarkParams) -> Transcript {
+        let mut transcript = Transcript::new(WHALE_AGGREGATE_DOMAIN);
+        transcript.append_u64(b"blowup", params.blowup as u64);
+        transcript.append_u64(b"queries", params.queries as u64);
+        transcript.append_u64(b"transfers", self.layout.transfers as u64);
+        transcript.append_u64(b"threshold", self.threshold);
+        transcript.append_message(b"transfer_commitment", &self.commitment);
+        transcript
+    }
+
+    /// Point of the LDE coset at `index`
+    fn domain_point(&self, index: usize) -> Felt {
+        Felt(GENERATOR) * Felt::root_of_unity(self.layout.domain_size).pow(index as u64)
+    }
+
+    /// Last row of the trace domain, `g^(T-1)`
+    fn last_row(&self) -> Felt {
+        let length = self.layout.trace_length;
+        Felt::root_of_unity(length).pow(length as u64 - 1)
+    }
+
+    /// `x^(T/32)`, which is one exactly on the first row of every block
+    fn block_position(&self, x: Felt) -> Felt {
+        x.pow((self.layout.trace_length / LIMB_BITS) as u64)
+    }
+
+    /// `block_position` on the last row of every block
+    fn block_end() -> Felt {
+        Felt::root_of_unity(LIMB_BITS).pow(LIMB_BITS as u64 - 1)
+    }
+
+    /// Constraint divisors at a single point, for the verifier
+    fn divisors_at(&self, x: Felt) -> Divisors {
+        let rows = Felt::root_of_unity(self.layout.trace_length);
+        let vanishing = |count: usize| powers(rows, count).into_iter().fold(Felt::ONE, |product, row| product * (x - row));
+        let block = self.block_position(x);
+        let last = x - self.last_row();
+        let block_end = block - Self::block_end();
+        let (low_shift, high_shift) = (vanishing(LIMB_BITS - 1), vanishing(EXCESS_HI_BITS - 1));
+        Divisors {
+            first_inv: (x - Felt::ONE).inverse(),
+            last,
+            last_inv: last.inverse(),
+            all_inv: (x.pow(self.layout.trace_length as u64) - Felt::ONE).inverse(),
+            block_start_inv: (block - Felt::ONE).inverse(),
+            block_end,
+            block_end_inv: block_end.inverse(),
+            low_bits_inv: vanishing(LIMB_BITS).inverse(),
+            low_shift,
+            low_shift_inv: low_shift.inverse(),
+            high_bits_inv: vanishing(EXCESS_HI_BITS).inverse(),
+            high_shift,
+            high_shift_inv: high_shift.inverse(),
+        }
+    }
+
+    /// Constraint divisors over the whole LDE coset, for the prover
+    fn divisors_on_domain(&self) -> Vec<Divisors> {
+        let n = self.layout.domain_size;
+        let points: Vec<Felt> = powers(Felt::root_of_unity(n), n).into_iter().map(|w| Felt(GENERATOR) * w).collect();
+        let last_row = self.last_row();
+        let last: Vec<Felt> = points.par_iter().map(|x| *x - last_row).collect();
+        let block: Vec<Felt> = points.par_iter().map(|x| self.block_position(*x)).collect();
+        let block_end: Vec<Felt> = block.iter().map(|y| *y - Self::block_end()).collect();
+        let first_inv = batch_inverse(&points.iter().map(|x| *x - Felt::ONE).collect::<Vec<_>>());
+        let last_inv = batch_inverse(&last);
+        let all_inv = batch_inverse(&points.par_iter().map(|x| x.pow(self.layout.trace_length as u64) - Felt::ONE).collect::<Vec<_>>());
+        let block_start_inv = batch_inverse(&block.iter().map(|y| *y - Felt::ONE).collect::<Vec<_>>());
+        let block_end_inv = batch_inverse(&block_end);
+
+        let rows = Felt::root_of_unity(self.layout.trace_length);
+        let vanishing = |count: usize| {
+            let coefficients = vanishing_polynomial(&powers(rows, count));
+            coset_evaluations(&coefficients, n)
+        };
+        let low_bits_inv = batch_inverse(&vanishing(LIMB_BITS));
+        let low_shift = vanishing(LIMB_BITS - 1);
+        let low_shift_inv = batch_inverse(&low_shift);
+        let high_bits_inv = batch_inverse(&vanishing(EXCESS_HI_BITS));
+        let high_shift = vanishing(EXCESS_HI_BITS - 1);
+        let high_shift_inv = batch_inverse(&high_shift);
+
+        (0..n)
+            .map(|i| Divisors {
+                first_inv: first_inv[i],
+                last: last[i],
+                last_inv: last_inv[i],
+                all_inv: all_inv[i],
+                block_start_inv: block_start_inv[i],
+                block_end: block_end[i],
+                block_end_inv: block_end_inv[i],
+                low_bits_inv: low_bits_inv[i],
+                low_shift: low_shift[i],
+                low_shift_inv: low_shift_inv[i],
+                high_bits_inv: high_bits_inv[i],
+                high_shift: high_shift[i],
+                high_shift_inv: high_shift_inv[i],
+            })
+            .collect()
+    }
+
+    /// Random linear combination of the constraint quotients at a point
+    ///
+    /// With `x` on the LDE coset, `row` holds the trace at `x` and `next` at `g·x`.
+    fn composition(&self, row: &Row, next: &Row, divisors: &Divisors, alphas: &[Ext; CONSTRAINTS]) -> Ext {
+        let d = divisors;
+        let binary = |column: usize| row[column] * row[column] - row[column];
+        let shift = |accumulator: usize, bit: usize| next[accumulator] - Felt(2) * row[accumulator] - next[bit];
+        let copy = |column: usize| next[column] - row[column];
+        let limb = Felt(1 << LIMB_BITS);
+        let (threshold_hi, threshold_lo) = (Felt(self.threshold >> LIMB_BITS), Felt(self.threshold & (u32::MAX as u64)));
+        let within_block = d.block_end * d.all_inv;
+        let quotients = [
+            // All rows: amount and running-total bits are 0 or 1
+            binary(HI_BIT) * d.all_inv,
+            binary(LO_BIT) * d.all_inv,
+            binary(SUM_BIT) * d.all_inv,
+            // First row of a block: accumulators start at the most significant bit
+            (row[HI] - row[HI_BIT]) * d.block_start_inv,
+            (row[LO] - row[LO_BIT]) * d.block_start_inv,
+            (row[SUM_LO] - row[SUM_BIT]) * d.block_start_inv,
+            // Within a block: accumulators shift in the next bit, previous totals stay put
+            shift(HI, HI_BIT) * within_block,
+            shift(LO, LO_BIT) * within_block,
+            shift(SUM_LO, SUM_BIT) * within_block,
+            copy(PREV_LO) * within_block,
+            copy(TOTAL_HI) * within_block,
+            // Row 0: nothing precedes the first block
+            row[PREV_LO] * d.first_inv,
+            row[TOTAL_HI] * d.first_inv,
+            // Last row of a block: the low limbs add up with a binary carry
+            binary(CARRY) * d.block_end_inv,
+            (row[SUM_LO] + row[CARRY] * limb - row[PREV_LO] - row[LO]) * d.block_end_inv,
+            // Last row of every block but the last: the next block starts from the new total
+            (next[PREV_LO] - row[SUM_LO]) * d.last * d.block_end_inv,
+            (next[TOTAL_HI] - row[TOTAL_HI] - row[HI] - row[CARRY]) * d.last * d.block_end_inv,
+            // Rows 0..32: the low excess limb is decomposed into bits, then carried to the last row
+            binary(EXCESS_LO_BIT) * d.low_bits_inv,
+            (row[EXCESS_LO] - row[EXCESS_LO_BIT]) * d.first_inv,
+            shift(EXCESS_LO, EXCESS_LO_BIT) * d.low_shift_inv,
+            copy(EXCESS_LO) * d.last * d.low_shift * d.all_inv,
+            // Rows 0..EXCESS_HI_BITS: likewise for the high excess limb
+            binary(EXCESS_HI_BIT) * d.high_bits_inv,
+            (row[EXCESS_HI] - row[EXCESS_HI_BIT]) * d.first_inv,
+            shift(EXCESS_HI, EXCESS_HI_BIT) * d.high_shift_inv,
+            copy(EXCESS_HI) * d.last * d.high_shift * d.all_inv,
+            // Last row: total = threshold + excess, limb by limb with a binary borrow
+            binary(BORROW) * d.last_inv,
+            (row[SUM_LO] + row[BORROW] * limb - threshold_lo - row[EXCESS_LO]) * d.last_inv,
+            (row[TOTAL_HI] + row[HI] + row[CARRY] - threshold_hi - row[EXCESS_HI] - row[BORROW]) * d.last_inv,
+        ];
+        quotients.iter().zip(alphas).fold(Ext::ZERO, |sum, (q, alpha)| sum + alpha.scale(*q))
+    }
+}
+
+/// Divisor values at one point, inverted where the quotient divides by them
+struct Divisors {
+    first_inv: Felt,
+    last: Felt,
+    last_inv: Felt,
+    all_inv: Felt,
+    block_start_inv: Felt,
+    block_end: Felt,
+    block_end_inv: Felt,
+    low_bits_inv: Felt,
+    low_shift: Felt,
+    low_shift_inv: Felt,
+    high_bits_inv: Felt,
+    high_shift: Felt,
+    high_shift_inv: Felt,
+}
+
+/// Public commitment to `transfers`: the Merkle root of the low-degree
+/// extension of their transaction hash and amount columns
+pub fn commit_transfers(params: &StarkParams, transfers: &[StarkTransfer]) -> ZKWatchResult<[u8; 32]> {
+    let layout = transfer_layout(params, transfers)?;
+    let columns = transfer_columns(&layout, transfers);
+    let rows = extend(&layout, &columns);
+    Ok(transfer_tree(&rows).root())
+}
+
+/// Prove that `transfers` add up to at least `threshold`
+///
+/// Returns the transfer commitment the proof is bound to, along with the proof.
+pub fn prove(params: &StarkParams, transfers: &[StarkTransfer], threshold: u64) -> ZKWatchResult<([u8; 32], Vec<u8>)> {
+    let layout = transfer_layout(params, transfers)?;
+    let total: u128 = transfers.iter().map(|t| t.amount as u128).sum();
+    if total < threshold as u128 {
+        return Err(ZKWatchError::ProofGeneration(format!(
+            "total of {} is below the threshold of {}",
+            total, threshold
+        )));
+    }
+
+    let mut columns = transfer_columns(&layout, transfers).to_vec();
+    columns.extend(total_columns(&layout, transfers, threshold));
+    let columns: [Vec<Felt>; TRACE_WIDTH] = columns.try_into().expect("trace width");
+    let rows = extend(&layout, &columns);
+    let commitment = transfer_tree(&rows).root();
+    let statement = Statement { layout, threshold, commitment };
+    Ok((commitment, prove_rows(params, &statement, &rows)))
+}
+
+fn transfer_layout(params: &StarkParams, transfers: &[StarkTransfer]) -> ZKWatchResult<Layout> {
+    Layout::new(transfers.len(), params).ok_or_else(|| {
+        ZKWatchError::ProofGeneration(format!(
+            "a STARK covers 1 to {} transfers, got {}",
+            MAX_STARK_TRANSFERS,
+            transfers.len()
+        ))
+    })
+}
+
+/// Low-degree extension of the trace columns, as rows of the LDE domain
+fn extend<const W: usize>(layout: &Layout, columns: &[Vec<Felt>; W]) -> Vec<[Felt; W]> {
+    let n = layout.domain_size;
+    let lde: Vec<Vec<Felt>> = columns.par_iter().map(|column| coset_evaluations(&interpolate(column), n)).collect();
+    (0..n).map(|i| std::array::from_fn(|column| lde[column][i])).collect()
+}
+
+fn transfer_tree<const W: usize>(rows: &[[Felt; W]]) -> MerkleTree {
+    MerkleTree::new(rows.par_iter().map(|row| hash_leaf(&row[..TRANSFER_WIDTH])).collect())
+}
+
+fn total_tree(rows: &[Row]) -> MerkleTree {
+    MerkleTree::new(rows.par_iter().map(|row| hash_leaf(&row[TRANSFER_WIDTH..])).collect())
+}
+
+/// Commit to the extended trace, then prove the composition is of low degree
+fn prove_rows(params: &StarkParams, statement: &Statement, rows: &[Row]) -> Vec<u8> {
+    let n = statement.layout.domain_size;
+    let transfer_tree = transfer_tree(rows);
+    let total_tree = total_tree(rows);
+
+    let mut transcript = statement.transcript(params);
+    transcript.append_message(b"trace_root", &total_tree.root());
+    let alphas = constraint_challenges(&mut transcript);
+
+    let divisors = statement.divisors_on_domain();
+    let mut layer: Vec<Ext> = (0..n)
+        .into_par_iter()
+        .map(|i| statement.composition(&rows[i], &rows[(i + params.blowup) % n], &divisors[i], &alphas))
+        .collect();
+    drop(divisors);
+
+    // FRI commit phase: each fold halves the domain and the degree bound
+    let mut layers = Vec::with_capacity(statement.fri_layers());
+    let mut offset = Felt(GENERATOR);
+    for _ in 0..statement.fri_layers() {
+        let tree = MerkleTree::new(layer.par_iter().map(|value| hash_leaf(&[value.0, value.1])).collect());
+        transcript.append_message(b"fri_root", &tree.root());
+        let beta = challenge(&mut transcript, b"fri_beta");
+        let folded = fold(&layer, beta, offset);
+        offset = offset * offset;
+        layers.push((std::mem::replace(&mut layer, folded), tree));
+    }
+    let final_value = layer[0];
+    append_ext(&mut transcript, b"fri_final", final_value);
+
+    let mut proof = Vec::new();
+    proof.extend_from_slice(&total_tree.root());
+    for (_, tree) in &layers {
+        proof.extend_from_slice(&tree.root());
+    }
+    write_ext(&mut proof, final_value);
+
+    for index in query_indices(&mut transcript, params.queries, n) {
+        for position in [index, (index + params.blowup) % n] {
+            for (range, tree) in [(0..TRANSFER_WIDTH, &transfer_tree), (TRANSFER_WIDTH..TRACE_WIDTH, &total_tree)] {
+                for value in &rows[position][range] {
+                    proof.extend_from_slice(&value.0.to_be_bytes());
+                }
+                tree.write_path(position, &mut proof);
+            }
+        }
+        for (values, tree) in &layers {
+            let half = values.len() / 2;
+            for position in [index % half, index % half + half] {
+                write_ext(&mut proof, values[position]);
+                tree.write_path(position, &mut proof);
+            }
+        }
+    }
+    proof
+}
+
+/// Check a proof that the `transfers` transfers behind `commitment` add up to at least `threshold`
+///
+/// Malformed proofs and statements outside the supported sizes verify as `false`.
+pub fn verify(params: &StarkParams, transfers: usize, threshold: u64, commitment: &[u8; 32], proof: &[u8]) -> bool {
+    let Some(layout) = Layout::new(transfers, params) else {
+        return false;
+    };
+    let statement = Statement { layout, threshold, commitment: *commitment };
+    let mut reader = ProofReader { bytes: proof, position: 0 };
+    let Some(commitments) = Commitments::read(&mut reader, &statement) else {
+        return false;
+    };
+
+    let mut transcript = statement.transcript(params);
+    transcript.append_message(b"trace_root", &commitments.trace_root);
+    let alphas = constraint_challenges(&mut transcript);
+    let betas: Vec<Ext> = commitments
+        .fri_roots
+        .iter()
+        .map(|root| {
+            transcript.append_message(b"fri_root", root);
+            challenge(&mut transcript, b"fri_beta")
+        })
+        .collect();
+    append_ext(&mut transcript, b"fri_final", commitments.final_value);
+
+    let indices = query_indices(&mut transcript, params.queries, layout.domain_size);
+    let verified = indices.into_iter().all(|index| {
+        verify_query(&statement, params, &commitments, &alphas, &betas, index, &mut reader).unwrap_or(false)
+    });
+    verified && reader.position == proof.len()
+}
+
+/// Roots and final FRI value at the start of a proof
+struct Commitments {
+    trace_root: Hash,
+    fri_roots: Vec<Hash>,
+    final_value: Ext,
+}
+
+impl Commitments {
+    fn read(reader: &mut ProofReader<'_>, statement: &Statement) -> Option<Self> {
+        Some(Self {
+            trace_root: reader.hash()?,
+            fri_roots: (0..statement.fri_layers()).map(|_| reader.hash()).collect::<Option<_>>()?,
+            final_value: reader.ext()?,
+        })
+    }
+}
+
+/// Check the trace and FRI openings of one query against the commitments
+fn verify_query(
+    statement: &Statement,
+    params: &StarkParams,
+    commitments: &Commitments,
+    alphas: &[Ext; CONSTRAINTS],
+    betas: &[Ext],
+    index: usize,
+    reader: &mut ProofReader<'_>,
+) -> Option<bool> {
+    let n = statement.layout.domain_size;
+    let mut rows = [[Felt::ZERO; TRACE_WIDTH]; 2];
+    for (row, position) in rows.iter_mut().zip([index, (index + params.blowup) % n]) {
+        let (transfer, total) = row.split_at_mut(TRANSFER_WIDTH);
+        for (values, root) in [(transfer, &statement.commitment), (total, &commitments.trace_root)] {
+            for value in values.iter_mut() {
+                *value = reader.felt()?;
+            }
+            if !verify_path(root, hash_leaf(values), position, &reader.path(n)?) {
+                return Some(false);
+            }
+        }
+    }
+
+    let x = statement.domain_point(index);
+    let mut expected = statement.composition(&rows[0], &rows[1], &statement.divisors_at(x), alphas);
+    let mut offset = Felt(GENERATOR);
+    for (layer, (root, beta)) in commitments.fri_roots.iter().zip(betas).enumerate() {
+        let size = n >> layer;
+        let half = size / 2;
+        let low = index % half;
+        let mut values = [Ext::ZERO; 2];
+        for (value, position) in values.iter_mut().zip([low, low + half]) {
+            *value = reader.ext()?;
+            if !verify_path(root, hash_leaf(&[value.0, value.1]), position, &reader.path(size)?) {
+                return Some(false);
+            }
+        }
+        // The opened pair must agree with the previous layer's fold at this query
+        if values[usize::from(index % size >= half)] != expected {
+            return Some(false);
+        }
+        let x = offset * Felt::root_of_unity(size).pow(low as u64);
+        expected = fold_pair(values[0], values[1], *beta, x.inverse());
+        offset = offset * offset;
+    }
+    Some(expected == commitments.final_value)
+}
+
+/// Columns fixed by the transfers: each block holds a transaction hash in
+/// 32-bit limbs and the bits of its amount's limbs, accumulated most significant first
+fn transfer_columns(layout: &Layout, transfers: &[StarkTransfer]) -> [Vec<Felt>; TRANSFER_WIDTH] {
+    let mut columns = [(); TRANSFER_WIDTH].map(|_| Vec::with_capacity(layout.trace_length));
+    for block in 0..layout.trace_length / LIMB_BITS {
+        let transfer = transfers.get(block).copied().unwrap_or(StarkTransfer { transaction_hash: [0; 32], amount: 0 });
+        let (hi, lo) = (transfer.amount >> LIMB_BITS, transfer.amount & u32::MAX as u64);
+        for row in 0..LIMB_BITS {
+            let shift = LIMB_BITS - 1 - row;
+            let tx = if row < HASH_LIMBS {
+                u32::from_be_bytes(transfer.transaction_hash[4 * row..4 * row + 4].try_into().expect("4-byte limb"))
+            } else {
+                0
+            };
+            columns[TX].push(Felt(tx as u64));
+            columns[HI_BIT].push(Felt((hi >> shift) & 1));
+            columns[LO_BIT].push(Felt((lo >> shift) & 1));
+            columns[HI].push(Felt(hi >> shift));
+            columns[LO].push(Felt(lo >> shift));
+        }
+    }
+    columns
+}
+
+/// Columns proving the total reaches the threshold: the running total in a
+/// normalized low limb and a high limb, and the excess decomposed into bits
+fn total_columns(layout: &Layout, transfers: &[StarkTransfer], threshold: u64) -> Vec<Vec<Felt>> {
+    let length = layout.trace_length;
+    let mut columns: Vec<Vec<Felt>> = (TRANSFER_WIDTH..TRACE_WIDTH).map(|_| Vec::with_capacity(length)).collect();
+    let column = |index: usize| index - TRANSFER_WIDTH;
+    let (mut prev_lo, mut total_hi) = (0u64, 0u64);
+    for block in 0..length / LIMB_BITS {
+        let amount = transfers.get(block).map_or(0, |t| t.amount);
+        let sum = prev_lo + (amount & u32::MAX as u64);
+        let (carry, sum_lo) = (sum >> LIMB_BITS, sum & u32::MAX as u64);
+        for row in 0..LIMB_BITS {
+            let shift = LIMB_BITS - 1 - row;
+            columns[column(SUM_BIT)].push(Felt((sum_lo >> shift) & 1));
+            columns[column(SUM_LO)].push(Felt(sum_lo >> shift));
+            columns[column(CARRY)].push(Felt(carry));
+            columns[column(PREV_LO)].push(Felt(prev_lo));
+            columns[column(TOTAL_HI)].push(Felt(total_hi));
+        }
+        total_hi += (amount >> LIMB_BITS) + carry;
+        prev_lo = sum_lo;
+    }
+
+    let (threshold_hi, threshold_lo) = (threshold >> LIMB_BITS, threshold & u32::MAX as u64);
+    let borrow = u64::from(prev_lo < threshold_lo);
+    let excess_lo = prev_lo + (borrow << LIMB_BITS) - threshold_lo;
+    let excess_hi = total_hi - threshold_hi - borrow;
+    for row in 0..length {
+        for (bits, bit_column, accumulator, excess) in [
+            (LIMB_BITS, EXCESS_LO_BIT, EXCESS_LO, excess_lo),
+            (EXCESS_HI_BITS, EXCESS_HI_BIT, EXCESS_HI, excess_hi),
+        ] {
+            let shift = (bits - 1).saturating_sub(row);
+            let bit = if row < bits { (excess >> shift) & 1 } else { 0 };
+            columns[column(bit_column)].push(Felt(bit));
+            columns[column(accumulator)].push(Felt(excess >> shift));
+        }
+        columns[column(BORROW)].push(Felt(borrow));
+    }
+    columns
+}
+
+/// One FRI fold: `f(x)` and `f(-x)` become `g(x^2)` for the challenge `beta`
+fn fold(values: &[Ext], beta: Ext, offset: Felt) -> Vec<Ext> {
+    let half = values.len() / 2;
+    let root_inv = Felt::root_of_unity(values.len()).inverse();
+    let mut x_inv = offset.inverse();
+    let mut folded = Vec::with_capacity(half);
+    for i in 0..half {
+        folded.push(fold_pair(values[i], values[i + half], beta, x_inv));
+        x_inv = x_inv * root_inv;
+    }
+    folded
+}
+
+/// `(f(x) + f(-x)) / 2 + beta · (f(x) - f(-x)) / 2x`
+fn fold_pair(at_x: Ext, at_neg_x: Ext, beta: Ext, x_inv: Felt) -> Ext {
+    let half = Felt(2).inverse();
+    ((at_x + at_neg_x) + beta * (at_x - at_neg_x).scale(x_inv)).scale(half)
+}
+
+fn challenge(transcript: &mut Transcript, label: &'static [u8]) -> Ext {
+    let mut bytes = [0u8; 32];
+    transcript.challenge_bytes(label, &mut bytes);
+    let limb = |half: &[u8]| Felt::from_u128(u128::from_be_bytes(half.try_into().unwrap_or_default()));
+    Ext(limb(&bytes[..16]), limb(&bytes[16..]))
+}
+
+fn constraint_challenges(transcript: &mut Transcript) -> [Ext; CONSTRAINTS] {
+    [(); CONSTRAINTS].map(|_| challenge(transcript, b"constraint_alpha"))
+}
+
+fn append_ext(transcript: &mut Transcript, label: &'static [u8], value: Ext) {
+    let mut bytes = Vec::with_capacity(16);
+    write_ext(&mut bytes, value);
+    transcript.append_message(label, &bytes);
+}
+
+fn write_ext(out: &mut Vec<u8>, value: Ext) {
+    out.extend_from_slice(&value.0 .0.to_be_bytes());
+    out.extend_from_slice(&value.1 .0.to_be_bytes());
+}
+
+/// Query positions in a power-of-two domain
+fn query_indices(transcript: &mut Transcript, queries: usize, domain_size: usize) -> Vec<usize> {
+    (0..queries)
+        .map(|_| {
+            let mut bytes = [0u8; 8];
+            transcript.challenge_bytes(b"query_index", &mut bytes);
+            (u64::from_be_bytes(bytes) % domain_size as u64) as usize
+        })
+        .collect()
+}
+
+/// `[1, base, base^2, ..]` of length `count`
+fn powers(base: Felt, count: usize) -> Vec<Felt> {
+    std::iter::successors(Some(Felt::ONE), |power| Some(*power * base)).take(count).collect()
+}
+
+/// Invert every element with a single field inversion; zeros stay zero
+fn batch_inverse(values: &[Felt]) -> Vec<Felt> {
+    let mut prefix = Vec::with_capacity(values.len());
+    let mut product = Felt::ONE;
+    for value in values {
+        prefix.push(product);
+        if *value != Felt::ZERO {
+            product = product * *value;
+        }
+    }
+    let mut inverse = product.inverse();
+    let mut inverses = vec![Felt::ZERO; values.len()];
+    for i in (0..values.len()).rev() {
+        if values[i] != Felt::ZERO {
+            inverses[i] = prefix[i] * inverse;
+            inverse = inverse * values[i];
+        }
+    }
+    inverses
+}
+
+/// Coefficients of `∏ (x - root)`, lowest degree first
+fn vanishing_polynomial(roots: &[Felt]) -> Vec<Felt> {
+    let mut coefficients = vec![Felt::ONE];
+    for root in roots {
+        coefficients.push(Felt::ZERO);
+        for i in (1..coefficients.len()).rev() {
+            coefficients[i] = coefficients[i - 1] - *root * coefficients[i];
+        }
+        coefficients[0] = Felt::ZERO - *root * coefficients[0];
+    }
+    coefficients
+}
+
+/// In-place radix-2 NTT: coefficients become evaluations at the powers of `root`
+fn ntt(values: &mut [Felt], root: Felt) {
+    let n = values.len();
+    if n <= 1 {
+        return;
+    }
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let step = root.pow((n / len) as u64);
+        for start in (0..n).step_by(len) {
+            let mut twiddle = Felt::ONE;
+            for k in 0..len / 2 {
+                let even = values[start + k];
+                let odd = values[start + k + len / 2] * twiddle;
+                values[start + k] = even + odd;
+                values[start + k + len / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Coefficients of the polynomial taking `values` on the power-of-two subgroup
+fn interpolate(values: &[Felt]) -> Vec<Felt> {
+    let mut coefficients = values.to_vec();
+    ntt(&mut coefficients, Felt::root_of_unity(values.len()).inverse());
+    let scale = Felt::new(values.len() as u64).inverse();
+    coefficients.iter_mut().for_each(|c| *c = *c * scale);
+    coefficients
+}
+
+/// Evaluations on the coset `GENERATOR · <ω>` of power-of-two `size`
+fn coset_evaluations(coefficients: &[Felt], size: usize) -> Vec<Felt> {
+    let mut values: Vec<Felt> = coefficients
+        .iter()
+        .zip(powers(Felt(GENERATOR), coefficients.len()))
+        .map(|(c, shift)| *c * shift)
+        .collect();
+    values.resize(size, Felt::ZERO);
+    ntt(&mut values, Felt::root_of_unity(size));
+    values
+}
+
+fn hash_leaf(values: &[Felt]) -> Hash {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(&[0]);
+    for value in values {
+        context.update(&value.0.to_be_bytes());
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(context.finish().as_ref());
+    hash
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(&[1]);
+    context.update(left);
+    context.update(right);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(context.finish().as_ref());
+    hash
+}
+
+/// SHA-256 Merkle tree over a power-of-two number of leaves
+struct MerkleTree {
+    /// Leaf hashes first, root last
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    fn new(leaves: Vec<Hash>) -> Self {
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1].chunks(2).map(|pair| hash_node(&pair[0], &pair[1])).collect();
+            levels.push(level);
+        }
+        Self { levels }
+    }
+
+    fn root(&self) -> Hash {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Append the sibling hashes from leaf `index` up to the root
+    fn write_path(&self, mut index: usize, out: &mut Vec<u8>) {
+        for level in &self.levels[..self.levels.len() - 1] {
+            out.extend_from_slice(&level[index ^ 1]);
+            index >>= 1;
+        }
+    }
+}
+
+fn verify_path(root: &Hash, leaf: Hash, mut index: usize, path: &[Hash]) -> bool {
+    let computed = path.iter().fold(leaf, |hash, sibling| {
+        let parent = if index & 1 == 0 { hash_node(&hash, sibling) } else { hash_node(sibling, &hash) };
+        index >>= 1;
+        parent
+    });
+    computed == *root
+}
+
+/// Cursor over proof bytes; every read fails on truncation
+struct ProofReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ProofReader<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.bytes.get(self.position..self.position.checked_add(N)?)?;
+        self.position += N;
+        bytes.try_into().ok()
+    }
+
+    fn hash(&mut self) -> Option<Hash> {
+        self.take()
+    }
+
+    fn felt(&mut self) -> Option<Felt> {
+        Felt::from_bytes(self.take()?)
+    }
+
+    fn ext(&mut self) -> Option<Ext> {
+        Some(Ext(self.felt()?, self.felt()?))
+    }
+
+    /// Authentication path of a tree with `leaves` leaves
+    fn path(&mut self, leaves: usize) -> Option<Vec<Hash>> {
+        (0..leaves.trailing_zeros()).map(|_| self.hash()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> StarkParams {
+        StarkParams::from_security_level(MAX_STARK_SECURITY_LEVEL)
+    }
+
+    fn transfer(seed: u8, amount: u64) -> StarkTransfer {
+        StarkTransfer { transaction_hash: [seed; 32], amount }
+    }
+
+    fn transfers() -> Vec<StarkTransfer> {
+        vec![transfer(1, u64::MAX), transfer(2, 7 << 32 | 5), transfer(3, u32::MAX as u64)]
+    }
+
+    #[test]
+    fn proof_verifies_against_recomputed_commitment() {
+        let transfers = transfers();
+        let threshold = u64::MAX;
+        let (commitment, proof) = prove(&params(), &transfers, threshold).unwrap();
+
+        assert_eq!(commit_transfers(&params(), &transfers).unwrap(), commitment);
+        assert!(verify(&params(), transfers.len(), threshold, &commitment, &proof));
+    }
+
+    #[test]
+    fn threshold_equal_to_total_verifies() {
+        let transfers = vec![transfer(1, 1 << 40), transfer(2, 3)];
+        let (commitment, proof) = prove(&params(), &transfers, (1 << 40) + 3).unwrap();
+        assert!(verify(&params(), 2, (1 << 40) + 3, &commitment, &proof));
+    }
+
+    #[test]
+    fn proof_does_not_verify_for_another_statement() {
+        let transfers = transfers();
+        let (commitment, proof) = prove(&params(), &transfers, 1_000).unwrap();
+        let mut other = transfers.clone();
+        other[1].amount += 1;
+        let other_commitment = commit_transfers(&params(), &other).unwrap();
+
+        assert!(!verify(&params(), transfers.len(), 1_001, &commitment, &proof));
+        assert!(!verify(&params(), transfers.len() + 1, 1_000, &commitment, &proof));
+        assert!(!verify(&params(), transfers.len(), 1_000, &other_commitment, &proof));
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected() {
+        let transfers = transfers();
+        let (commitment, proof) = prove(&params(), &transfers, 1_000).unwrap();
+
+        for position in (0..proof.len()).step_by(proof.len() / 64) {
+            let mut tampered = proof.clone();
+            tampered[position] ^= 1;
+            assert!(!verify(&params(), transfers.len(), 1_000, &commitment, &tampered), "byte {}", position);
+        }
+        assert!(!verify(&params(), transfers.len(), 1_000, &commitment, &proof[..proof.len() - 1]));
+        let mut extended = proof.clone();
+        extended.push(0);
+        assert!(!verify(&params(), transfers.len(), 1_000, &commitment, &extended));
+    }
+
+    #[test]
+    fn total_below_threshold_cannot_be_proven() {
+        assert!(prove(&params(), &[transfer(1, 10), transfer(2, 20)], 31).is_err());
+    }
+
+    #[test]
+    fn forged_excess_is_rejected() {
+        // Committed transfers total 30; claim 31 by solving the final row in the field
+        let transfers = vec![transfer(1, 10), transfer(2, 20)];
+        let threshold = 31;
+        let layout = Layout::new(transfers.len(), &params()).unwrap();
+        let mut columns = transfer_columns(&layout, &transfers).to_vec();
+        columns.extend(total_columns(&layout, &transfers, 0));
+        let mut columns: [Vec<Felt>; TRACE_WIDTH] = columns.try_into().unwrap();
+        columns[EXCESS_LO].fill(Felt(u32::MAX as u64));
+        columns[EXCESS_HI].fill(Felt::ZERO - Felt(1));
+        columns[BORROW].fill(Felt::ONE);
+
+        let rows = extend(&layout, &columns);
+        let commitment = commit_transfers(&params(), &transfers).unwrap();
+        let statement = Statement { layout, threshold, commitment };
+        let proof = prove_rows(&params(), &statement, &rows);
+        assert!(!verify(&params(), transfers.len(), threshold, &commitment, &proof));
+    }
+
+    #[test]
+    fn transfer_counts_outside_the_supported_range_are_rejected() {
+        let (commitment, proof) = prove(&params(), &transfers(), 1).unwrap();
+        assert!(!verify(&params(), 0, 1, &commitment, &proof));
+        assert!(!verify(&params(), MAX_STARK_TRANSFERS + 1, 1, &commitment, &proof));
+        assert!(!verify(&params(), 1 << 27, 1, &commitment, &proof));
+        assert!(prove(&params(), &vec![transfer(0, 1); MAX_STARK_TRANSFERS + 1], 1).is_err());
+    }
+
+    #[test]
+    fn security_level_is_clamped() {
+        let params = StarkParams::from_security_level(128);
+        assert_eq!(params, StarkParams::from_security_level(MAX_STARK_SECURITY_LEVEL));
+        assert_eq!(params.security_level(), MAX_STARK_SECURITY_LEVEL);
+        assert_eq!(StarkParams::from_bytes(&params.to_bytes()).unwrap(), params);
+    }
+}
@@ -0,0 +1,387 @@
+//! Live chain subscriptions
+//!
+//! Follows each network's head over a WebSocket `newHeads` subscription (or by
+//! polling when no endpoint is configured), feeds every block through whale
+//! detection and catches up on blocks missed while disconnected.
+
+use crate::blockchain::{MultiChainScanner, ScanEvent};
+use crate::mempool::{MempoolEvent, MempoolWatcher};
+use crate::{NetworkConfig, ZKWatchError, ZKWatchResult};
+use chrono::Utc;
+use ethers::providers::{Middleware, Provider, StreamExt, Ws};
+use ethers::types::H256;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Reconnect and polling behaviour of network subscriptions
+#[derive(Debug, Clone)]
+pub struct SubscriptionConfig {
+    /// Also subscribe to `newPendingTransactions` and watch whales before inclusion
+    pub pending_transactions: bool,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Head polling interval for networks without a WebSocket endpoint
+    pub poll_interval: Duration,
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            pending_transactions: false,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(12),
+        }
+    }
+}
+
+/// Event emitted by a live network subscription
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum MonitorEvent {
+    /// Whale detection, confirmation or retraction caused by a new head
+    Scan(ScanEvent),
+    /// Lifecycle change of a whale transaction seen in the mempool
+    Mempool(MempoolEvent),
+    /// Subscription established; blocks missed since the last processed head are scanned first
+    Connected {
+        network: String,
+    },
+    /// Subscription lost; the next attempt starts after `retry_in_ms`
+    Disconnected {
+        network: String,
+        reason: String,
+        retry_in_ms: u64,
+    },
+}
+
+/// Follows one network's head and turns every new block into monitor events
+///
+/// Any `ws://` endpoint works, so a local node (anvil, hardhat) or a scripted
+/// WebSocket server can stand in for a live network.
+pub struct NetworkSubscription {
+    network: NetworkConfig,
+    scanner: MultiChainScanner,
+    min_usd: f64,
+    config: SubscriptionConfig,
+    mempool: Option<MempoolWatcher>,
+}
+
+impl NetworkSubscription {
+    /// `scanner` must cover `network`, see [`MultiChainScanner::for_network`]
+    pub fn new(network: NetworkConfig, scanner: MultiChainScanner, min_usd: f64) -> Self {
+        Self {
+            network,
+            scanner,
+            min_usd,
+            config: SubscriptionConfig::default(),
+            mempool: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: SubscriptionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Follow the network until the receiving side of `events` is dropped
+    pub async fn run(mut self, events: mpsc::Sender<MonitorEvent>) {
+        let mut backoff = self.config.initial_backoff;
+
+        loop {
+            let result = match self.network.ws_url.clone() {
+                Some(url) => self.follow_websocket(&url, &events, &mut backoff).await,
+                None => self.follow_polling(&events, &mut backoff).await,
+            };
+
+            // Followers only return successfully once nobody is listening anymore
+            let reason = match result {
+                Ok(()) => return,
+                Err(e) => e.to_string(),
+            };
+
+            let disconnected = MonitorEvent::Disconnected {
+                network: self.network.name.clone(),
+                reason,
+                retry_in_ms: backoff.as_millis() as u64,
+            };
+            if events.send(disconnected).await.is_err() {
+                return;
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+
+    async fn follow_websocket(
+        &mut self,
+        url: &str,
+        events: &mpsc::Sender<MonitorEvent>,
+        backoff: &mut Duration,
+    ) -> ZKWatchResult<()> {
+        let provider = Provider::<Ws>::connect(url).await
+            .map_err(|e| ZKWatchError::Blockchain(format!("WebSocket connection to {} failed: {}", url, e)))?;
+        let mut heads = provider.subscribe_blocks().await
+            .map_err(|e| ZKWatchError::Blockchain(format!("newHeads subscription failed: {}", e)))?;
+        let mut pending = match self.config.pending_transactions {
+            true => Some(provider.subscribe_pending_txs().await
+                .map_err(|e| ZKWatchError::Blockchain(format!("newPendingTransactions subscription failed: {}", e)))?),
+            false => None,
+        };
+
+        // Pending whales survive reconnects; ones resolved while offline are caught as stale
+        if pending.is_some() && self.mempool.is_none() {
+            self.mempool = self.scanner.mempool_watcher(&self.network.name, self.min_usd);
+        }
+
+        if !self.connected(events, backoff).await? {
+            return Ok(());
+        }
+
+        loop {
+            tokio::select! {
+                head = heads.next() => match head {
+                    Some(head) => {
+                        if !self.process_head(events).await? || !self.process_mempool_head(&provider, head.hash, events).await? {
+                            return Ok(());
+                        }
+                    }
+                    None => return Err(ZKWatchError::Blockchain(format!("newHeads subscription to {} closed", url))),
+                },
+                hash = async {
+                    match pending.as_mut() {
+                        Some(stream) => stream.next().await,
+                        None => std::future::pending().await,
+                    }
+                } => match hash {
+                    Some(hash) => if !self.process_pending(&provider, hash, events).await? {
+                        return Ok(());
+                    },
+                    None => return Err(ZKWatchError::Blockchain(format!("newPendingTransactions subscription to {} closed", url))),
+                },
+            }
+        }
+    }
+
+    async fn follow_polling(
+        &mut self,
+        events: &mpsc::Sender<MonitorEvent>,
+        backoff: &mut Duration,
+    ) -> ZKWatchResult<()> {
+        if !self.connected(events, backoff).await? {
+            return Ok(());
+        }
+
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if events.is_closed() || !self.process_head(events).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Announce the subscription and scan up to the current head, filling any gap
+    async fn connected(&mut self, events: &mpsc::Sender<MonitorEvent>, backoff: &mut Duration) -> ZKWatchResult<bool> {
+        let connected = MonitorEvent::Connected {
+            network: self.network.name.clone(),
+        };
+        if events.send(connected).await.is_err() {
+            return Ok(false);
+        }
+
+        let listening = self.process_head(events).await?;
+        *backoff = self.config.initial_backoff;
+        Ok(listening)
+    }
+
+    /// Scan every block since the last processed head; `false` once the receiver is gone
+    async fn process_head(&mut self, events: &mpsc::Sender<MonitorEvent>) -> ZKWatchResult<bool> {
+        self.scanner.scan_whale_transactions(self.min_usd).await?;
+
+        for event in self.scanner.drain_events() {
+            if events.send(MonitorEvent::Scan(event)).await.is_err() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Run a newly announced transaction through the mempool watcher
+    async fn process_pending(&mut self, provider: &Provider<Ws>, hash: H256, events: &mpsc::Sender<MonitorEvent>) -> ZKWatchResult<bool> {
+        let Some(mempool) = self.mempool.as_mut() else {
+            return Ok(true);
+        };
+
+        // The node may already have evicted or mined it by the time we ask
+        let tx = match provider.get_transaction(hash).await {
+            Ok(Some(tx)) if tx.block_number.is_none() => tx,
+            Ok(_) => return Ok(true),
+            Err(e) => return Err(ZKWatchError::Blockchain(format!("eth_getTransactionByHash failed: {}", e))),
+        };
+
+        send_mempool_events(events, mempool.observe(&tx, Utc::now())).await
+    }
+
+    /// Resolve pending whales against a new head and confirm stale ones by receipt,
+    /// dropping only those the node no longer knows
+    async fn process_mempool_head(&mut self, provider: &Provider<Ws>, head: Option<H256>, events: &mpsc::Sender<MonitorEvent>) -> ZKWatchResult<bool> {
+        let (Some(mempool), Some(head)) = (self.mempool.as_mut(), head) else {
+            return Ok(true);
+        };
+
+        let block = provider.get_block_with_txs(head).await
+            .map_err(|e| ZKWatchError::Blockchain(format!("eth_getBlockByHash failed: {}", e)))?;
+        let mut resolved = block.map(|b| mempool.observe_block(&b)).unwrap_or_default();
+
+        for hash in mempool.stale(Utc::now()) {
+            let receipt = provider.get_transaction_receipt(hash).await
+                .map_err(|e| ZKWatchError::Blockchain(format!("eth_getTransactionReceipt failed: {}", e)))?;
+            if let Some(block_number) = receipt.and_then(|r| r.block_number) {
+                resolved.extend(mempool.mark_landed(hash, block_number.as_u64()));
+                continue;
+            }
+
+            // No receipt yet; a transaction still in the node's pool is only slow
+            let transaction = provider.get_transaction(hash).await
+                .map_err(|e| ZKWatchError::Blockchain(format!("eth_getTransactionByHash failed: {}", e)))?;
+            match transaction {
+                Some(tx) => match tx.block_number {
+                    Some(block_number) => resolved.extend(mempool.mark_landed(hash, block_number.as_u64())),
+                    None => mempool.mark_pending(hash, Utc::now()),
+                },
+                None => resolved.extend(mempool.mark_dropped(hash)),
+            }
+        }
+
+        send_mempool_events(events, resolved).await
+    }
+}
+
+async fn send_mempool_events(events: &mpsc::Sender<MonitorEvent>, mempool_events: Vec<MempoolEvent>) -> ZKWatchResult<bool> {
+    for event in mempool_events {
+        if events.send(MonitorEvent::Mempool(event)).await.is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_source::InMemoryBlockSource;
+    use crate::pricing::StaticPriceTable;
+    use crate::WhaleTrackerConfig;
+    use ethers::types::{Block, Transaction, TxHash, H160, U256, U64};
+    use futures_util::SinkExt;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn whale_block(number: u64) -> Block<Transaction> {
+        let transaction = Transaction {
+            hash: H256::from_low_u64_be(100 + number),
+            from: H160::from_low_u64_be(1),
+            to: Some(H160::from_low_u64_be(2)),
+            value: U256::exp10(21),
+            block_number: Some(U64::from(number)),
+            ..Default::default()
+        };
+        Block {
+            number: Some(U64::from(number)),
+            hash: Some(H256::from_low_u64_be(number)),
+            parent_hash: H256::from_low_u64_be(number - 1),
+            timestamp: U256::from(1_700_000_000 + number * 12),
+            transactions: vec![transaction],
+            ..Default::default()
+        }
+    }
+
+    /// WebSocket node that accepts one `newHeads` subscription, announces one head
+    /// once `announce` fires and then hangs up
+    async fn scripted_node(announce: oneshot::Receiver<u64>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let request = loop {
+                match socket.next().await {
+                    Some(Ok(Message::Text(text))) => break serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+                    Some(Ok(_)) => continue,
+                    _ => return,
+                }
+            };
+            assert_eq!(request["method"], "eth_subscribe");
+            assert_eq!(request["params"][0], "newHeads");
+            let subscribed = serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x1" });
+            socket.send(Message::Text(subscribed.to_string())).await.unwrap();
+
+            let Ok(number) = announce.await else {
+                return;
+            };
+            let head = Block::<TxHash> {
+                number: Some(U64::from(number)),
+                hash: Some(H256::from_low_u64_be(number)),
+                parent_hash: H256::from_low_u64_be(number - 1),
+                ..Default::default()
+            };
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": { "subscription": "0x1", "result": head },
+            });
+            socket.send(Message::Text(notification.to_string())).await.unwrap();
+            socket.close(None).await.unwrap();
+        });
+        url
+    }
+
+    fn detected_hash(event: Option<MonitorEvent>) -> String {
+        match event {
+            Some(MonitorEvent::Scan(ScanEvent::Detected(transaction))) => transaction.hash,
+            other => panic!("expected a detection, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn websocket_heads_drive_scans_until_the_node_hangs_up() {
+        let (announce, announced) = oneshot::channel();
+        let mut network = WhaleTrackerConfig::default().tracking_networks.remove(0);
+        network.ws_url = Some(scripted_node(announced).await);
+
+        let source = Arc::new(InMemoryBlockSource::new());
+        source.insert_block(whale_block(1));
+        let scanner = MultiChainScanner::new(vec![(network.clone(), source.clone())])
+            .with_price_source(Arc::new(StaticPriceTable::new([("ETH".to_string(), 2_000.0)])));
+        let config = SubscriptionConfig {
+            initial_backoff: Duration::from_secs(60),
+            ..SubscriptionConfig::default()
+        };
+
+        let (events, mut received) = mpsc::channel(16);
+        let subscription = tokio::spawn(NetworkSubscription::new(network, scanner, 1_000_000.0).with_config(config).run(events));
+
+        assert!(matches!(received.recv().await, Some(MonitorEvent::Connected { .. })));
+        assert_eq!(detected_hash(received.recv().await), format!("{:?}", H256::from_low_u64_be(101)));
+
+        // The announced head is scanned from the block source
+        source.insert_block(whale_block(2));
+        announce.send(2).unwrap();
+        assert_eq!(detected_hash(received.recv().await), format!("{:?}", H256::from_low_u64_be(102)));
+
+        match received.recv().await {
+            Some(MonitorEvent::Disconnected { retry_in_ms, .. }) => assert_eq!(retry_in_ms, 60_000),
+            other => panic!("expected a disconnect, got {:?}", other),
+        }
+        drop(received);
+        subscription.abort();
+    }
+}
+
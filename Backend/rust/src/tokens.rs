@@ -0,0 +1,161 @@
+//! ERC-20 token support
+//!
+//! Decodes `Transfer(address,address,uint256)` and wrapped-native
+//! `Withdrawal(address,uint256)` logs and keeps a per-chain
+//! registry of tracked tokens with their whale thresholds.
+
+use ethers::types::{Log, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// keccak256("Transfer(address,address,uint256)")
+pub const TRANSFER_EVENT_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// keccak256("Withdrawal(address,uint256)"), emitted by WETH-style wrappers on unwrap
+pub const WITHDRAWAL_EVENT_TOPIC: &str = "0x7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b65";
+
+/// Token identity carried by whale transactions
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct TokenInfo {
+    pub address: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Decoded ERC-20 transfer
+#[derive(Debug, Clone)]
+pub struct Erc20Transfer {
+    pub token: H160,
+    pub from: H160,
+    pub to: H160,
+    pub value: U256,
+    pub transaction_hash: Option<H256>,
+    pub block_number: Option<u64>,
+    pub log_index: Option<u64>,
+}
+
+pub fn transfer_topic() -> H256 {
+    H256::from_str(TRANSFER_EVENT_TOPIC).expect("valid Transfer topic")
+}
+
+pub fn withdrawal_topic() -> H256 {
+    H256::from_str(WITHDRAWAL_EVENT_TOPIC).expect("valid Withdrawal topic")
+}
+
+/// Decode an ERC-20 `Transfer` log
+///
+/// ERC-721 shares the event signature but indexes the token id as a fourth
+/// topic, so only logs with exactly three topics and a 32-byte payload match.
+pub fn decode_transfer(log: &Log) -> Option<Erc20Transfer> {
+    if log.topics.len() != 3 || log.topics[0] != transfer_topic() || log.data.len() != 32 {
+        return None;
+    }
+
+    Some(Erc20Transfer {
+        token: log.address,
+        from: H160::from(log.topics[1]),
+        to: H160::from(log.topics[2]),
+        value: U256::from_big_endian(&log.data),
+        transaction_hash: log.transaction_hash,
+        block_number: log.block_number.map(|n| n.as_u64()),
+        log_index: log.log_index.map(|i| i.as_u64()),
+    })
+}
+
+/// Unwrapping account and amount of a wrapped-native `Withdrawal` log
+pub fn decode_withdrawal(log: &Log) -> Option<(H160, U256)> {
+    if log.topics.len() != 2 || log.topics[0] != withdrawal_topic() || log.data.len() != 32 {
+        return None;
+    }
+
+    Some((H160::from(log.topics[1]), U256::from_big_endian(&log.data)))
+}
+
+/// Tracked token with its whale threshold in raw token units
+#[derive(Debug, Clone)]
+pub struct TrackedToken {
+    pub info: TokenInfo,
+    pub whale_threshold: u128,
+}
+
+/// Tokens tracked per chain, keyed by contract address
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<(u64, H160), TrackedToken>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Major stablecoins and wrapped ether on the default networks
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+
+        let stable_threshold = 1_000_000u128; // $1M
+        let weth_threshold = 100u128; // 100 WETH
+
+        let defaults: [(u64, &str, &str, u8, u128); 14] = [
+            (1, "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC", 6, stable_threshold),
+            (1, "0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT", 6, stable_threshold),
+            (1, "0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI", 18, stable_threshold),
+            (1, "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH", 18, weth_threshold),
+            (137, "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359", "USDC", 6, stable_threshold),
+            (137, "0xc2132D05D31c914a87C6611C10748AEb04B58e8F", "USDT", 6, stable_threshold),
+            (137, "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", "WETH", 18, weth_threshold),
+            (42161, "0xaf88d065e77c8cC2239327C5EDb3A432268e5831", "USDC", 6, stable_threshold),
+            (42161, "0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9", "USDT", 6, stable_threshold),
+            (42161, "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1", "WETH", 18, weth_threshold),
+            (10, "0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85", "USDC", 6, stable_threshold),
+            (10, "0x94b008aA00579c1307B0EF2c499aD98a8ce58e58", "USDT", 6, stable_threshold),
+            (10, "0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1", "DAI", 18, stable_threshold),
+            (10, "0x4200000000000000000000000000000000000006", "WETH", 18, weth_threshold),
+        ];
+
+        for (chain_id, address, symbol, decimals, whole_units) in defaults {
+            let address = H160::from_str(address).expect("valid default token address");
+            registry.insert(chain_id, address, symbol, decimals, whole_units * 10u128.pow(decimals as u32));
+        }
+
+        registry
+    }
+
+    /// Track a token with a threshold in raw token units
+    pub fn insert(&mut self, chain_id: u64, address: H160, symbol: &str, decimals: u8, whale_threshold: u128) {
+        self.tokens.insert((chain_id, address), TrackedToken {
+            info: TokenInfo {
+                address: format!("{:?}", address),
+                symbol: symbol.to_string(),
+                decimals,
+            },
+            whale_threshold,
+        });
+    }
+
+    /// Change the whale threshold of an already tracked token
+    pub fn set_threshold(&mut self, chain_id: u64, address: H160, whale_threshold: u128) -> bool {
+        match self.tokens.get_mut(&(chain_id, address)) {
+            Some(token) => {
+                token.whale_threshold = whale_threshold;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, chain_id: u64, address: &H160) -> Option<&TrackedToken> {
+        self.tokens.get(&(chain_id, *address))
+    }
+
+    /// Contract addresses tracked on a chain
+    pub fn addresses(&self, chain_id: u64) -> Vec<H160> {
+        let mut addresses: Vec<H160> = self.tokens.keys()
+            .filter(|(chain, _)| *chain == chain_id)
+            .map(|(_, address)| *address)
+            .collect();
+        addresses.sort();
+        addresses
+    }
+}
@@ -0,0 +1,444 @@
+//! Columnar analytics warehouse
+//!
+//! Appends whale transactions to Parquet files partitioned by date and
+//! network, and reads time ranges back through lazy polars scans that prune
+//! date partitions and push the timestamp predicate down to row groups.
+
+use crate::analytics::TimeRange;
+use crate::chain_tracker::ConfirmationStatus;
+use crate::exchange_flows::ExchangeFlow;
+use crate::tokens::TokenInfo;
+use crate::{WhaleTransaction, ZKWatchError, ZKWatchResult};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use polars::prelude::*;
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Warehouse location used when none is configured
+pub const DEFAULT_WAREHOUSE_DIR: &str = ".zkwatch/warehouse";
+
+/// Buffered transactions that trigger a flush to Parquet
+pub const DEFAULT_BATCH_SIZE: usize = 50_000;
+
+/// Rows per Parquet row group; smaller groups let timestamp statistics skip more data
+const ROW_GROUP_SIZE: usize = 128 * 1024;
+
+/// Distinguishes part files written within the same nanosecond
+static PART_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Whale transactions in Parquet files under `date=YYYY-MM-DD/network=<name>/`
+pub struct DataWarehouse {
+    root: PathBuf,
+    batch_size: usize,
+    /// Transactions not yet written, keyed by partition
+    buffer: BTreeMap<(NaiveDate, String), Vec<WhaleTransaction>>,
+    buffered: usize,
+}
+
+impl DataWarehouse {
+    /// Warehouse rooted at `root`; directories are created on the first flush
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            buffer: BTreeMap::new(),
+            buffered: 0,
+        }
+    }
+
+    /// Set how many transactions are buffered before they are written out
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Buffer a transaction, flushing once the batch is full
+    pub async fn store_transaction(&mut self, transaction: &WhaleTransaction) -> ZKWatchResult<()> {
+        self.append(std::slice::from_ref(transaction))
+    }
+
+    /// Buffer transactions, flushing once the batch is full
+    pub fn append(&mut self, transactions: &[WhaleTransaction]) -> ZKWatchResult<()> {
+        for transaction in transactions {
+            self.buffer
+                .entry((transaction.timestamp.date_naive(), transaction.network.clone()))
+                .or_default()
+                .push(transaction.clone());
+            self.buffered += 1;
+        }
+
+        if self.buffered >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write every buffered partition to a new Parquet part file
+    ///
+    /// Partitions that fail to write stay buffered for the next flush.
+    pub fn flush(&mut self) -> ZKWatchResult<()> {
+        while let Some(((date, network), transactions)) = self.buffer.pop_first() {
+            if let Err(e) = self.write_partition(date, &network, &transactions) {
+                self.buffer.insert((date, network), transactions);
+                return Err(e);
+            }
+            self.buffered -= transactions.len();
+        }
+        Ok(())
+    }
+
+    fn write_partition(&self, date: NaiveDate, network: &str, transactions: &[WhaleTransaction]) -> ZKWatchResult<()> {
+        let dir = self.partition_dir(date, network);
+        std::fs::create_dir_all(&dir)?;
+
+        let mut frame = to_frame(transactions)?;
+        let part = format!(
+            "part-{}-{}.parquet",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            PART_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        ParquetWriter::new(File::create(dir.join(part))?)
+            .with_compression(ParquetCompression::Zstd(None))
+            .with_statistics(true)
+            .with_row_group_size(Some(ROW_GROUP_SIZE))
+            .finish(&mut frame)?;
+        Ok(())
+    }
+
+    /// Lazy scan of the stored transactions with `start <= timestamp <= end`
+    ///
+    /// Unflushed transactions are not included.
+    pub fn scan(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> ZKWatchResult<Option<LazyFrame>> {
+        let mut scans = Vec::new();
+        for date_dir in self.date_partitions(start.date_naive(), end.date_naive())? {
+            let pattern = date_dir.join("*").join("*.parquet");
+            scans.push(LazyFrame::scan_parquet(pattern, ScanArgsParquet::default())?);
+        }
+        if scans.is_empty() {
+            return Ok(None);
+        }
+
+        let in_range = col("timestamp")
+            .gt_eq(lit(start.timestamp_millis()))
+            .and(col("timestamp").lt_eq(lit(end.timestamp_millis())));
+        Ok(Some(concat(scans, UnionArgs::default())?.filter(in_range)))
+    }
+
+    /// Lazy query over the stored and buffered transactions in the range
+    ///
+    /// Nothing is read until the query is collected, so filters, projections
+    /// and aggregations added on top run inside the Parquet scan.
+    pub async fn get_data_in_range(&mut self, time_range: &TimeRange) -> ZKWatchResult<LazyFrame> {
+        self.flush()?;
+
+        match self.scan(time_range.start_date, time_range.end_date)? {
+            Some(scan) => Ok(scan),
+            None => Ok(to_frame(&[])?.lazy()),
+        }
+    }
+
+    fn partition_dir(&self, date: NaiveDate, network: &str) -> PathBuf {
+        self.root
+            .join(format!("date={}", date.format("%Y-%m-%d")))
+            .join(format!("network={}", network))
+    }
+
+    /// Date partition directories within `[from, to]`
+    fn date_partitions(&self, from: NaiveDate, to: NaiveDate) -> ZKWatchResult<Vec<PathBuf>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut partitions = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            let date = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("date="))
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            if matches!(date, Some(date) if date >= from && date <= to) && path.is_dir() {
+                partitions.push(path);
+            }
+        }
+        partitions.sort();
+        Ok(partitions)
+    }
+}
+
+impl Drop for DataWarehouse {
+    /// Write out what is still buffered; call [`flush`](Self::flush) first to handle errors
+    fn drop(&mut self) {
+        if self.buffered > 0 {
+            if let Err(e) = self.flush() {
+                log::warn!("Failed to flush {} buffered transactions to {}: {}", self.buffered, self.root.display(), e);
+            }
+        }
+    }
+}
+
+/// Run a warehouse query into a single-chunk frame
+pub async fn collect(query: LazyFrame) -> ZKWatchResult<DataFrame> {
+    // The streaming Parquet source blocks on polars' own runtime, which panics on a tokio worker
+    let mut frame = tokio::task::spawn_blocking(move || query.with_streaming(true).collect())
+        .await
+        .map_err(|e| ZKWatchError::Analytics(format!("Warehouse scan failed: {}", e)))??;
+    frame.as_single_chunk_par();
+    Ok(frame)
+}
+
+/// Transactions selected by a query over the full warehouse columns, oldest first
+pub async fn collect_transactions(query: LazyFrame) -> ZKWatchResult<Vec<WhaleTransaction>> {
+    from_frame(&collect(query.sort("timestamp", SortOptions::default())).await?)
+}
+
+/// Columnar layout of whale transactions; raw values are decimal strings since Parquet has no u128
+fn to_frame(transactions: &[WhaleTransaction]) -> ZKWatchResult<DataFrame> {
+    let strings = |f: fn(&WhaleTransaction) -> String| transactions.iter().map(f).collect::<Vec<_>>();
+    let exchange_flows = transactions
+        .iter()
+        .map(|t| serde_json::to_string(&t.exchange_flow))
+        .collect::<Result<Vec<_>, _>>()?;
+    let confirmations = transactions
+        .iter()
+        .map(|t| variant_name(&t.confirmation))
+        .collect::<ZKWatchResult<Vec<_>>>()?;
+    let patterns = transactions
+        .iter()
+        .map(|t| variant_name(&t.pattern_type))
+        .collect::<ZKWatchResult<Vec<_>>>()?;
+
+    Ok(DataFrame::new(vec![
+        Series::new("hash", strings(|t| t.hash.clone())),
+        Series::new("network", strings(|t| t.network.clone())),
+        Series::new("chain_id", transactions.iter().map(|t| t.chain_id).collect::<Vec<_>>()),
+        Series::new("from", strings(|t| t.from.clone())),
+        Series::new("to", strings(|t| t.to.clone())),
+        Series::new("value", strings(|t| t.value.to_string())),
+        Series::new("borrowed", strings(|t| t.borrowed.to_string())),
+        Series::new("token_address", transactions.iter().map(|t| t.token.as_ref().map(|token| token.address.clone())).collect::<Vec<_>>()),
+        Series::new("token_symbol", transactions.iter().map(|t| t.token.as_ref().map(|token| token.symbol.clone())).collect::<Vec<_>>()),
+        Series::new("token_decimals", transactions.iter().map(|t| t.token.as_ref().map(|token| token.decimals as u32)).collect::<Vec<_>>()),
+        Series::new("log_index", transactions.iter().map(|t| t.log_index).collect::<Vec<_>>()),
+        Series::new("usd_value", transactions.iter().map(|t| t.usd_value).collect::<Vec<_>>()),
+        Series::new("confirmation", confirmations),
+        Series::new("gas_used", transactions.iter().map(|t| t.gas_used).collect::<Vec<_>>()),
+        Series::new("block_number", transactions.iter().map(|t| t.block_number).collect::<Vec<_>>()),
+        Series::new("timestamp", transactions.iter().map(|t| t.timestamp.timestamp_millis()).collect::<Vec<_>>()),
+        Series::new("zk_proof_hash", transactions.iter().map(|t| t.zk_proof_hash.clone()).collect::<Vec<_>>()),
+        Series::new("risk_score", transactions.iter().map(|t| t.risk_score).collect::<Vec<_>>()),
+        Series::new("pattern_type", patterns),
+        Series::new("exchange_flow", exchange_flows),
+    ])?)
+}
+
+/// Rebuild transactions from a single-chunk frame written by `to_frame`
+fn from_frame(frame: &DataFrame) -> ZKWatchResult<Vec<WhaleTransaction>> {
+    let hash = frame.column("hash")?.utf8()?;
+    let network = frame.column("network")?.utf8()?;
+    let chain_id = frame.column("chain_id")?.u64()?;
+    let from = frame.column("from")?.utf8()?;
+    let to = frame.column("to")?.utf8()?;
+    let value = frame.column("value")?.utf8()?;
+    let borrowed = frame.column("borrowed")?.utf8()?;
+    let token_address = frame.column("token_address")?.utf8()?;
+    let token_symbol = frame.column("token_symbol")?.utf8()?;
+    let token_decimals = frame.column("token_decimals")?.u32()?;
+    let log_index = frame.column("log_index")?.u64()?;
+    let usd_value = frame.column("usd_value")?.f64()?;
+    let confirmation = frame.column("confirmation")?.utf8()?;
+    let gas_used = frame.column("gas_used")?.u64()?;
+    let block_number = frame.column("block_number")?.u64()?;
+    let timestamp = frame.column("timestamp")?.i64()?;
+    let zk_proof_hash = frame.column("zk_proof_hash")?.utf8()?;
+    let risk_score = frame.column("risk_score")?.f64()?;
+    let pattern_type = frame.column("pattern_type")?.utf8()?;
+    let exchange_flow = frame.column("exchange_flow")?.utf8()?;
+
+    let missing = |column: &str, row: usize| ZKWatchError::Analytics(format!("Warehouse row {} has no {}", row, column));
+
+    (0..frame.height())
+        .into_par_iter()
+        .map(|row| -> ZKWatchResult<WhaleTransaction> {
+            let token = match (token_address.get(row), token_symbol.get(row), token_decimals.get(row)) {
+                (Some(address), Some(symbol), Some(decimals)) => Some(TokenInfo {
+                    address: address.to_string(),
+                    symbol: symbol.to_string(),
+                    decimals: decimals as u8,
+                }),
+                _ => None,
+            };
+            let millis = timestamp.get(row).ok_or_else(|| missing("timestamp", row))?;
+            let exchange_flow: ExchangeFlow = match exchange_flow.get(row) {
+                Some(json) => serde_json::from_str(json)?,
+                None => ExchangeFlow::default(),
+            };
+            let confirmation: ConfirmationStatus =
+                from_variant_name(confirmation.get(row).ok_or_else(|| missing("confirmation", row))?)?;
+
+            Ok(WhaleTransaction {
+                hash: hash.get(row).ok_or_else(|| missing("hash", row))?.to_string(),
+                network: network.get(row).ok_or_else(|| missing("network", row))?.to_string(),
+                chain_id: chain_id.get(row).unwrap_or_default(),
+                from: from.get(row).unwrap_or_default().to_string(),
+                to: to.get(row).unwrap_or_default().to_string(),
+                value: value
+                    .get(row)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| missing("value", row))?,
+                borrowed: borrowed.get(row).and_then(|v| v.parse().ok()).unwrap_or_default(),
+                token,
+                log_index: log_index.get(row),
+                usd_value: usd_value.get(row),
+                confirmation,
+                gas_used: gas_used.get(row).unwrap_or_default(),
+                block_number: block_number.get(row).unwrap_or_default(),
+                timestamp: Utc
+                    .timestamp_millis_opt(millis)
+                    .single()
+                    .ok_or_else(|| missing("timestamp", row))?,
+                zk_proof_hash: zk_proof_hash.get(row).map(str::to_string),
+                risk_score: risk_score.get(row).unwrap_or_default(),
+                pattern_type: from_variant_name(pattern_type.get(row).ok_or_else(|| missing("pattern_type", row))?)?,
+                exchange_flow,
+            })
+        })
+        .collect()
+}
+
+/// Serialized name of a unit enum variant
+fn variant_name<T: Serialize>(value: &T) -> ZKWatchResult<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Enum variant stored under its serialized name, as in the `pattern_type` column
+pub(crate) fn from_variant_name<T: DeserializeOwned>(name: &str) -> ZKWatchResult<T> {
+    Ok(serde_json::from_value(serde_json::Value::String(name.to_string()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TransactionPattern;
+
+    fn transaction(network: &str, minutes: i64) -> WhaleTransaction {
+        WhaleTransaction {
+            hash: format!("0x{:064x}", minutes),
+            network: network.to_string(),
+            chain_id: 1,
+            from: format!("0x{:040x}", 1),
+            to: format!("0x{:040x}", 2),
+            value: 10u128.pow(24),
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: Some(3_000_000.0),
+            confirmation: ConfirmationStatus::Safe,
+            gas_used: 21_000,
+            block_number: 18_000_000 + minutes as u64,
+            timestamp: Utc.timestamp_opt(1_700_000_000 + minutes * 60, 0).unwrap(),
+            zk_proof_hash: None,
+            risk_score: 0.2,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zkwatch-warehouse-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn dropping_flushes_the_buffer() {
+        let root = scratch_dir("drop");
+        {
+            let mut warehouse = DataWarehouse::new(&root);
+            warehouse.append(&[transaction("Ethereum", 0), transaction("Arbitrum", 1), transaction("Ethereum", 24 * 60)]).unwrap();
+            assert!(warehouse.scan(Utc.timestamp_opt(0, 0).unwrap(), Utc::now()).unwrap().is_none());
+        }
+
+        let mut warehouse = DataWarehouse::new(&root);
+        let range = TimeRange {
+            start_date: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            end_date: Utc.timestamp_opt(1_700_000_000 + 60, 0).unwrap(),
+        };
+        let stored = collect_transactions(warehouse.get_data_in_range(&range).await.unwrap()).await.unwrap();
+        assert_eq!(stored.iter().map(|t| t.network.as_str()).collect::<Vec<_>>(), vec!["Ethereum", "Arbitrum"]);
+        assert_eq!(stored[0].value, 10u128.pow(24));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn day_range(first_day: i64, last_day: i64) -> TimeRange {
+        TimeRange {
+            start_date: Utc.timestamp_opt(1_700_000_000 + first_day * 86_400, 0).unwrap(),
+            end_date: Utc.timestamp_opt(1_700_000_000 + last_day * 86_400, 0).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scans_skip_date_partitions_outside_the_range() {
+        let root = scratch_dir("pruning");
+        let mut warehouse = DataWarehouse::new(&root);
+        warehouse.append(&(0..3).map(|day| transaction("Ethereum", day * 24 * 60)).collect::<Vec<_>>()).unwrap();
+
+        // An unreadable part file in a later partition only breaks scans that include its date
+        let unreadable = root.join("date=2023-11-20").join("network=Ethereum");
+        std::fs::create_dir_all(&unreadable).unwrap();
+        std::fs::write(unreadable.join("part-0-0.parquet"), b"not parquet").unwrap();
+
+        let scan = warehouse.get_data_in_range(&day_range(0, 1)).await.unwrap();
+        let plan = scan.describe_optimized_plan().unwrap();
+        assert!(plan.contains("date=2023-11-14") && plan.contains("date=2023-11-15"), "{}", plan);
+        assert!(!plan.contains("date=2023-11-16") && !plan.contains("date=2023-11-20"), "{}", plan);
+        let stored = collect_transactions(scan).await.unwrap();
+        assert_eq!(stored.iter().map(|t| t.block_number).collect::<Vec<_>>(), vec![18_000_000, 18_001_440]);
+
+        // Parquet metadata is read when the scan is set up
+        assert!(warehouse.get_data_in_range(&day_range(0, 6)).await.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn range_filter_and_projections_run_inside_the_parquet_scan() {
+        let root = scratch_dir("pushdown");
+        let mut warehouse = DataWarehouse::new(&root);
+        warehouse.append(&[transaction("Ethereum", 0), transaction("Arbitrum", 30), transaction("Ethereum", 90), transaction("Ethereum", 600)]).unwrap();
+
+        // Minutes 0 to 120 of the first day
+        let range = TimeRange {
+            start_date: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            end_date: Utc.timestamp_opt(1_700_000_000 + 120 * 60, 0).unwrap(),
+        };
+        let query = warehouse.get_data_in_range(&range).await.unwrap()
+            .group_by([col("network")])
+            .agg([col("hash").count().alias("transactions")])
+            .sort("network", SortOptions::default());
+
+        let plan = query.describe_optimized_plan().unwrap();
+        assert!(plan.contains("PROJECT 3/20 COLUMNS"), "{}", plan);
+        assert!(plan.contains("SELECTION: [([(col(\"timestamp\")) >= (1700000000000)]) & ([(col(\"timestamp\")) <= (1700007200000)])]"), "{}", plan);
+        assert!(!plan.contains("FILTER"), "{}", plan);
+
+        let counts = collect(query).await.unwrap();
+        assert_eq!(counts.column("network").unwrap().utf8().unwrap().into_no_null_iter().collect::<Vec<_>>(), vec!["Arbitrum", "Ethereum"]);
+        assert_eq!(counts.column("transactions").unwrap().cast(&DataType::UInt64).unwrap().u64().unwrap().into_no_null_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -6,22 +6,39 @@
 use crate::{ZKWatchResult, WhaleTransaction, WhaleTrackerConfig, AnalyticsMetrics, ZKWatchError};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, BTreeMap};
-use chrono::{DateTime, Utc, Duration};
-use crate::blockchain::MultiChainScanner;
+use chrono::{DateTime, Timelike, Utc, Duration};
+use crate::blockchain::{MultiChainScanner, RiskAssessment, ScanEvent, DEFAULT_FUNDING_LOOKBACK};
+use crate::clustering::{ClusterHeuristic, EntityResolver};
+use crate::database::WhaleDatabase;
+use crate::exchange_flows;
+use crate::labels::LabelCategory;
+use crate::screening::{ScreeningFlag, ScreeningHit, TransferGraph};
+use crate::subscription::{MonitorEvent, NetworkSubscription, SubscriptionConfig};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// USD moved within one hour by three or more whales before it counts as coordinated
+const COORDINATED_MOVEMENT_MIN_USD: f64 = 2_500_000.0;
 
 /// Advanced whale tracker with ML-based detection
 pub struct AdvancedWhaleTracker {
     config: WhaleTrackerConfig,
     scanner: MultiChainScanner,
-    whale_database: WhaleDatabase,
+    whale_database: Arc<WhaleDatabase>,
     prediction_models: HashMap<String, PredictionModel>,
     alert_manager: AlertManager,
+    subscription_config: SubscriptionConfig,
+    funding_lookback: u64,
 }
 
 impl AdvancedWhaleTracker {
     pub fn new(config: WhaleTrackerConfig, networks: Vec<crate::NetworkConfig>) -> Self {
-        let scanner = MultiChainScanner::new(networks);
-        let whale_database = WhaleDatabase::new();
+        Self::with_scanner(config, MultiChainScanner::from_rpc(networks))
+    }
+
+    /// Build a tracker on top of a scanner with custom block sources
+    pub fn with_scanner(config: WhaleTrackerConfig, scanner: MultiChainScanner) -> Self {
+        let whale_database = Arc::new(WhaleDatabase::open_in_memory().expect("in-memory whale database"));
         let prediction_models = initialize_prediction_models();
         let alert_manager = AlertManager::new();
         
@@ -31,41 +48,107 @@ impl AdvancedWhaleTracker {
             whale_database,
             prediction_models,
             alert_manager,
+            subscription_config: SubscriptionConfig::default(),
+            funding_lookback: DEFAULT_FUNDING_LOOKBACK,
         }
     }
 
+    /// Reconnect, polling and pending-transaction settings for real-time monitoring
+    pub fn with_subscription_config(mut self, subscription_config: SubscriptionConfig) -> Self {
+        self.subscription_config = subscription_config;
+        self
+    }
+
+    /// Store scanned transactions and detected patterns in a persistent database
+    /// instead of the default in-memory one
+    pub fn with_database(mut self, whale_database: WhaleDatabase) -> Self {
+        self.whale_database = Arc::new(whale_database);
+        self
+    }
+
+    pub fn database(&self) -> &WhaleDatabase {
+        &self.whale_database
+    }
+
+    /// Set how many recent blocks per network are searched for the gas funders of clustered addresses
+    pub fn with_funding_lookback(mut self, funding_lookback: u64) -> Self {
+        self.funding_lookback = funding_lookback.max(1);
+        self
+    }
+
     /// Start real-time whale monitoring
+    ///
+    /// Spawns one subscription task per network; their events are read from the
+    /// returned handle.
     pub async fn start_real_time_monitoring(&mut self) -> ZKWatchResult<MonitoringHandle> {
-        let handle = MonitoringHandle::new();
-        
-        // Start background tasks for monitoring
-        tokio::spawn(async move {
-            // Real-time scanning and analysis would happen here
-            // For demo purposes, we simulate the monitoring loop
-            loop {
-                // Scan for new whale transactions
-                // Analyze patterns
-                // Generate alerts
-                // Update predictions
-                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        let (sender, receiver) = mpsc::channel(1024);
+        let mut tasks = Vec::new();
+        
+        for network in self.scanner.networks().to_vec() {
+            let scanner = self.scanner.for_network(&network.name)
+                .ok_or_else(|| ZKWatchError::Blockchain(format!("No block source for network: {}", network.name)))?;
+            let subscription = NetworkSubscription::new(network, scanner, self.config.min_transaction_value_usd)
+                .with_config(self.subscription_config.clone());
+            tasks.push(tokio::spawn(subscription.run(sender.clone())));
+        }
+        
+        Ok(MonitoringHandle::new(tasks, receiver))
+    }
+
+    /// Write a real-time monitoring event's detection, confirmation or retraction to the database
+    pub fn record_event(&self, event: &MonitorEvent) -> ZKWatchResult<()> {
+        if let MonitorEvent::Scan(event) = event {
+            self.record_scan_event(event)?;
+        }
+        Ok(())
+    }
+
+    fn record_scan_event(&self, event: &ScanEvent) -> ZKWatchResult<()> {
+        match event {
+            ScanEvent::Detected(transaction) => {
+                self.whale_database.insert_transactions(std::slice::from_ref(transaction))?;
+            }
+            ScanEvent::StatusChanged { network, hash, log_index, status } => {
+                self.whale_database.update_confirmation(network, hash, *log_index, *status)?;
             }
-        });
+            ScanEvent::Retracted { transaction, .. } => {
+                self.whale_database.remove_transaction(&transaction.network, &transaction.hash, transaction.log_index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Scan recent whales and store them, dropping stored whales whose blocks were reorganized away
+    async fn scan_and_record(&mut self) -> ZKWatchResult<Vec<WhaleTransaction>> {
+        let whales = self.scanner.scan_whale_transactions(self.config.min_transaction_value_usd).await?;
+        
+        for event in self.scanner.drain_events() {
+            if let ScanEvent::Retracted { .. } = event {
+                self.record_scan_event(&event)?;
+            }
+        }
+        self.whale_database.insert_transactions(&whales)?;
         
-        Ok(handle)
+        Ok(whales)
     }
 
-    /// Detect sophisticated whale patterns
-    pub async fn detect_sophisticated_patterns(&mut self) -> ZKWatchResult<Vec<WhalePattern>> {
+    /// Detect sophisticated whale patterns among recently scanned transactions
+    pub async fn detect_sophisticated_patterns(&mut self, recent_whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
         let mut patterns = Vec::new();
         
-        // Get recent whale transactions
-        let recent_whales = self.scanner.scan_whale_transactions(self.config.min_transaction_threshold).await?;
-        
         // Analyze different pattern types
-        patterns.extend(self.detect_coordinated_movements(&recent_whales).await?);
-        patterns.extend(self.detect_manipulation_patterns(&recent_whales).await?);
-        patterns.extend(self.detect_bridge_whales(&recent_whales).await?);
-        patterns.extend(self.detect_defi_whales(&recent_whales).await?);
+        patterns.extend(self.detect_coordinated_movements(recent_whales).await?);
+        patterns.extend(self.detect_manipulation_patterns(recent_whales).await?);
+        patterns.extend(self.detect_bridge_whales(recent_whales).await?);
+        patterns.extend(self.detect_defi_whales(recent_whales).await?);
+        
+        self.whale_database.insert_patterns(&patterns)?;
+        
+        if self.config.alerts_enabled {
+            for pattern in &patterns {
+                self.alert_manager.generate_alert(pattern).await?;
+            }
+        }
         
         Ok(patterns)
     }
@@ -84,20 +167,23 @@ impl AdvancedWhaleTracker {
         // Detect coordinated movements (multiple large transactions in same time window)
         for (time_window, group) in time_groups.iter() {
             if group.len() >= 3 {
-                let total_volume: u128 = group.iter().map(|w| w.value).sum();
-                let address_count = group.iter().map(|w| &w.from).collect::<HashSet<_>>().len();
+                let total_volume_usd: f64 = group.iter().filter_map(|w| w.volume_usd()).sum();
                 
-                if total_volume > 1000_000_000_000_000_000_000u128 { // > 1000 ETH
+                if total_volume_usd > COORDINATED_MOVEMENT_MIN_USD {
+                    let mut networks: Vec<String> = group.iter().map(|w| w.network.clone()).collect();
+                    networks.sort();
+                    networks.dedup();
+                    
                     patterns.push(WhalePattern {
                         pattern_id: format!("coordinated_{}", time_window.timestamp()),
                         pattern_type: WhalePatternType::CoordinatedMovement,
                         confidence: 0.85,
-                        description: format!("Detected {} coordinated whale movements with total volume {:.2} ETH", 
-                                          group.len(), total_volume as f64 / 1e18),
+                        description: format!("Detected {} coordinated whale movements with total volume ${:.0}", 
+                                          group.len(), total_volume_usd),
                         involved_addresses: group.iter().map(|w| w.from.clone()).collect(),
-                        estimated_impact: total_volume,
+                        estimated_impact_usd: total_volume_usd,
                         time_detected: Utc::now(),
-                        network_affected: vec!["Ethereum".to_string()],
+                        network_affected: networks,
                         risk_level: RiskLevel::High,
                     });
                 }
@@ -147,7 +233,7 @@ impl AdvancedWhaleTracker {
                     confidence: 0.75,
                     description: format!("Detected {} bridge whale movements of type: {}", group.len(), bridge_type),
                     involved_addresses: group.iter().map(|w| w.from.clone()).collect(),
-                    estimated_impact: group.iter().map(|w| w.value).sum(),
+                    estimated_impact_usd: group.iter().filter_map(|w| w.volume_usd()).sum(),
                     time_detected: Utc::now(),
                     network_affected: vec!["Ethereum".to_string(), "Polygon".to_string()],
                     risk_level: RiskLevel::Medium,
@@ -173,7 +259,7 @@ impl AdvancedWhaleTracker {
                 confidence: 0.90,
                 description: format!("Detected {} large DeFi whale interactions", defi_interactions.len()),
                 involved_addresses: defi_interactions.iter().map(|w| w.from.clone()).collect(),
-                estimated_impact: defi_interactions.iter().map(|w| w.value).sum(),
+                estimated_impact_usd: defi_interactions.iter().filter_map(|w| w.volume_usd()).sum(),
                 time_detected: Utc::now(),
                 network_affected: vec!["Ethereum".to_string()],
                 risk_level: RiskLevel::Low,
@@ -201,19 +287,23 @@ impl AdvancedWhaleTracker {
 
     /// Generate comprehensive whale analytics
     pub async fn generate_comprehensive_analytics(&mut self) -> ZKWatchResult<WhaleAnalytics> {
-        let recent_whales = self.scanner.scan_whale_transactions(self.config.min_transaction_threshold).await?;
-        let patterns = self.detect_sophisticated_patterns().await?;
+        let recent_whales = self.scan_and_record().await?;
+        let patterns = self.detect_sophisticated_patterns(&recent_whales).await?;
         let predictions = self.predict_whale_movements().await?;
         
+        let priced: Vec<f64> = recent_whales.iter().filter_map(|w| w.volume_usd()).collect();
+        let total_volume_usd: f64 = priced.iter().sum();
+        
         let analytics = WhaleAnalytics {
             summary: AnalyticsMetrics {
                 total_whale_transactions: recent_whales.len() as u64,
-                total_volume: recent_whales.iter().map(|w| w.value).sum(),
-                average_transaction_size: recent_whales.iter().map(|w| w.value as f64).sum::<f64>() / recent_whales.len() as f64,
+                total_volume_usd,
+                average_transaction_usd: total_volume_usd / priced.len().max(1) as f64,
                 suspected_manipulation_count: patterns.iter().filter(|p| p.risk_level == RiskLevel::High).count() as u64,
                 top_whale_addresses: self.get_top_whale_addresses(&recent_whales),
                 risk_distribution: self.calculate_risk_distribution(&patterns),
                 time_series_data: self.generate_time_series(&recent_whales),
+                exchange_flow_series: exchange_flows::net_flow_series(&recent_whales, self.scanner.networks(), Duration::hours(1)),
             },
             detected_patterns: patterns,
             movement_predictions: predictions,
@@ -225,43 +315,56 @@ impl AdvancedWhaleTracker {
         Ok(analytics)
     }
 
-    /// Monitor whale wallet clustering
+    /// Group whale addresses into entities
+    ///
+    /// Addresses are linked when they reuse one exchange deposit address, were
+    /// topped up with gas by the same funder, or repeatedly sent funds back and
+    /// forth; linked addresses are merged with union-find.
     pub async fn detect_whale_clusters(&mut self) -> ZKWatchResult<Vec<WhaleCluster>> {
-        let all_whales = self.scanner.scan_whale_transactions(100_000_000_000_000_000_000u128).await?; // 100 ETH threshold
-        
-        let mut clusters = Vec::new();
-        
-        // Simple clustering based on transaction patterns
-        let mut cluster_map: HashMap<String, Vec<String>> = HashMap::new();
-        
-        for whale in &all_whales {
-            let cluster_key = self.generate_cluster_key(&whale.from, &whale.to);
-            cluster_map.entry(cluster_key).or_default().push(whale.from.clone());
-        }
-        
-        for (key, addresses) in cluster_map {
-            if addresses.len() >= 3 {
-                clusters.push(WhaleCluster {
-                    cluster_id: key,
-                    member_addresses: addresses,
-                    cluster_type: "Transaction Pattern".to_string(),
-                    confidence: 0.7,
-                    total_volume: 0, // Would calculate actual volume
-                    first_observed: Utc::now() - Duration::days(30),
-                    last_activity: Utc::now(),
-                });
+        let all_whales = self.scan_and_record().await?;
+        
+        let senders: HashSet<String> = all_whales.iter().map(|w| w.from.to_ascii_lowercase()).collect();
+        let fundings = self.scanner.gas_fundings(&senders, self.funding_lookback).await?;
+        
+        let graph = TransferGraph::from_transactions(&all_whales);
+        let mut clusters: Vec<WhaleCluster> = EntityResolver::new(self.scanner.labels())
+            .resolve(&all_whales, &fundings)
+            .into_iter()
+            .map(|entity| WhaleCluster {
+                cluster_id: format!("entity_{}", entity.members[0].trim_start_matches("0x").chars().take(8).collect::<String>()),
+                cluster_type: entity.heuristics.iter().map(|h| h.to_string()).collect::<Vec<_>>().join(" + "),
+                member_addresses: entity.members,
+                heuristics: entity.heuristics,
+                confidence: entity.confidence,
+                total_volume_usd: entity.total_volume_usd,
+                first_observed: entity.first_activity,
+                last_activity: entity.last_activity,
+                risk_assessment: RiskAssessment::Low,
+                screening_evidence: Vec::new(),
+            })
+            .collect();
+        
+        for cluster in &mut clusters {
+            if let Some(flag) = self.scanner.screener().screen_cluster(cluster, &graph) {
+                cluster.risk_assessment = flag.risk;
+                cluster.screening_evidence = flag.evidence;
             }
         }
         
         Ok(clusters)
     }
 
+    /// Whale transactions touching a sanctioned or watched address, directly or within the screener's hop limit
+    pub fn screen_whales(&self, whales: &[WhaleTransaction]) -> Vec<ScreeningFlag> {
+        self.scanner.screener().screen_transactions(whales)
+    }
+
     // Helper methods
     fn detect_wash_trading_patterns(&self, whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
         let mut patterns = Vec::new();
         
         // Simple wash trading detection (same address trading back and forth)
-        let address_pairs: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut address_pairs: HashMap<String, HashSet<String>> = HashMap::new();
         
         for whale in whales {
             address_pairs.entry(whale.from.clone()).or_default().insert(whale.to.clone());
@@ -280,7 +383,7 @@ impl AdvancedWhaleTracker {
                     confidence: 0.6,
                     description: format!("Potential wash trading between {} and {}", addr, counterpart),
                     involved_addresses: vec![addr, counterpart.clone()],
-                    estimated_impact: 0,
+                    estimated_impact_usd: 0.0,
                     time_detected: Utc::now(),
                     network_affected: vec!["Ethereum".to_string()],
                     risk_level: RiskLevel::Medium,
@@ -291,39 +394,33 @@ impl AdvancedWhaleTracker {
         Ok(patterns)
     }
 
-    fn detect_pump_dump_patterns(&self, whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
+    fn detect_pump_dump_patterns(&self, _whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
         // Simplified pump and dump detection
         // In reality, this would require price data and more sophisticated analysis
         Ok(vec![])
     }
 
-    fn detect_spoofing_patterns(&self, whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
+    fn detect_spoofing_patterns(&self, _whales: &[WhaleTransaction]) -> ZKWatchResult<Vec<WhalePattern>> {
         // Simplified spoofing detection
         // In reality, this would require order book data
         Ok(vec![])
     }
 
     fn identify_bridge_pattern(&self, whale: &WhaleTransaction) -> Option<String> {
-        // Check for known bridge contract addresses
-        let bridge_contracts = [
-            "0x88a2C09d9B3a0Cb8b3E1D5D5c2F1c7A8d3B5e9f1".to_string(), // Example bridge
-        ];
-        
-        if bridge_contracts.contains(&whale.to) || bridge_contracts.contains(&whale.from) {
-            Some("CrossChain Bridge".to_string())
-        } else {
-            None
-        }
+        // Group by the bridge operator named in the label registry
+        [&whale.to, &whale.from].into_iter()
+            .filter_map(|address| self.scanner.labels().get_str(whale.chain_id, address))
+            .find(|label| label.has(LabelCategory::Bridge))
+            .map(|label| label.entity.clone().unwrap_or_else(|| label.name.clone()))
     }
 
     fn is_defi_interaction(&self, whale: &WhaleTransaction) -> bool {
         // Check if transaction involves known DeFi protocols
-        let defi_protocols = [
-            "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D".to_string(), // Uniswap V2
-            "0xE592427A0AEce92De3Edee1F18E0157C05861564".to_string(), // Uniswap V3
-        ];
-        
-        defi_protocols.contains(&whale.from) || defi_protocols.contains(&whale.to)
+        let labels = self.scanner.labels();
+        [&whale.from, &whale.to].into_iter().any(|address| {
+            labels.is(whale.chain_id, address, LabelCategory::DexRouter)
+                || labels.is(whale.chain_id, address, LabelCategory::LendingPool)
+        })
     }
 
     fn get_top_whale_addresses(&self, whales: &[WhaleTransaction]) -> Vec<String> {
@@ -334,7 +431,7 @@ impl AdvancedWhaleTracker {
         }
         
         let mut sorted_addresses: Vec<_> = address_volumes.into_iter().collect();
-        sorted_addresses.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted_addresses.sort_by_key(|(_, volume)| std::cmp::Reverse(*volume));
         
         sorted_addresses.into_iter().take(10).map(|(addr, _)| addr).collect()
     }
@@ -342,9 +439,8 @@ impl AdvancedWhaleTracker {
     fn calculate_risk_distribution(&self, patterns: &[WhalePattern]) -> HashMap<crate::TransactionPattern, u64> {
         let mut distribution = HashMap::new();
         
-        for pattern in patterns {
-            let count = distribution.entry(crate::TransactionPattern::SuspectedPump).or_insert(0);
-            *count += 1;
+        if !patterns.is_empty() {
+            distribution.insert(crate::TransactionPattern::SuspectedPump, patterns.len() as u64);
         }
         
         distribution
@@ -354,13 +450,13 @@ impl AdvancedWhaleTracker {
         // Simplified time series generation
         vec![crate::TimeSeriesPoint {
             timestamp: Utc::now(),
-            volume: whales.iter().map(|w| w.value).sum(),
+            volume_usd: whales.iter().filter_map(|w| w.volume_usd()).sum(),
             transaction_count: whales.len() as u64,
             avg_gas_price: 20.0, // ETH
         }]
     }
 
-    async fn analyze_network_distribution(&self, whales: &[WhaleTransaction]) -> ZKWatchResult<NetworkDistribution> {
+    async fn analyze_network_distribution(&self, _whales: &[WhaleTransaction]) -> ZKWatchResult<NetworkDistribution> {
         // Simplified network analysis
         Ok(NetworkDistribution {
             ethereum: 70.0,
@@ -370,13 +466,7 @@ impl AdvancedWhaleTracker {
         })
     }
 
-    fn generate_cluster_key(&self, from: &str, to: &str) -> String {
-        let mut addrs = vec![from, to];
-        addrs.sort();
-        addrs.join("_")
-    }
-
-    async fn generate_behavioral_insights(&self, whales: &[WhaleTransaction]) -> ZKWatchResult<BehavioralInsights> {
+    async fn generate_behavioral_insights(&self, _whales: &[WhaleTransaction]) -> ZKWatchResult<BehavioralInsights> {
         Ok(BehavioralInsights {
             most_active_hours: 14, // 2 PM
             preferred_networks: vec!["Ethereum".to_string()],
@@ -395,7 +485,8 @@ pub struct WhalePattern {
     pub confidence: f64,
     pub description: String,
     pub involved_addresses: Vec<String>,
-    pub estimated_impact: u128,
+    /// USD value of the priced transfers behind the pattern
+    pub estimated_impact_usd: f64,
     pub time_detected: DateTime<Utc>,
     pub network_affected: Vec<String>,
     pub risk_level: RiskLevel,
@@ -412,7 +503,7 @@ pub enum WhalePatternType {
     MEVAttack,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -474,41 +565,49 @@ pub struct BehavioralInsights {
 pub struct WhaleCluster {
     pub cluster_id: String,
     pub member_addresses: Vec<String>,
+    /// Heuristics that linked the members, joined for display
     pub cluster_type: String,
+    pub heuristics: Vec<ClusterHeuristic>,
     pub confidence: f64,
-    pub total_volume: u128,
+    /// USD value of the whale transfers touching the cluster
+    pub total_volume_usd: f64,
     pub first_observed: DateTime<Utc>,
     pub last_activity: DateTime<Utc>,
+    /// High when a member touches a sanctioned or watched address
+    pub risk_assessment: RiskAssessment,
+    pub screening_evidence: Vec<ScreeningHit>,
 }
 
 /// Monitoring handle for real-time tracking
+///
+/// Dropping the handle stops all subscription tasks.
 pub struct MonitoringHandle {
-    // In real implementation, this would contain handles to background tasks
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+    events: mpsc::Receiver<MonitorEvent>,
 }
 
 impl MonitoringHandle {
-    fn new() -> Self {
-        Self {}
+    fn new(tasks: Vec<tokio::task::JoinHandle<()>>, events: mpsc::Receiver<MonitorEvent>) -> Self {
+        Self { tasks, events }
+    }
+    
+    /// Next event from any monitored network; `None` once every subscription ended
+    pub async fn next_event(&mut self) -> Option<MonitorEvent> {
+        self.events.recv().await
     }
     
     pub async fn stop(&self) {
-        // In real implementation, this would stop the monitoring
+        for task in &self.tasks {
+            task.abort();
+        }
     }
 }
 
-/// Whale database for historical data storage
-struct WhaleDatabase {
-    // In real implementation, this would connect to a database
-}
-
-impl WhaleDatabase {
-    fn new() -> Self {
-        Self {}
-    }
-    
-    async fn get_historical_data(&self, days: i64) -> ZKWatchResult<Vec<WhaleTransaction>> {
-        // Simulate historical data retrieval
-        Ok(vec![])
+impl Drop for MonitoringHandle {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
     }
 }
 
@@ -522,7 +621,7 @@ impl AlertManager {
         Self {}
     }
     
-    async fn generate_alert(&self, pattern: &WhalePattern) -> ZKWatchResult<()> {
+    async fn generate_alert(&self, _pattern: &WhalePattern) -> ZKWatchResult<()> {
         // In real implementation, this would send alerts via email, SMS, etc.
         Ok(())
     }
@@ -537,7 +636,7 @@ struct PredictionModel {
 }
 
 impl PredictionModel {
-    async fn predict(&self, data: &[WhaleTransaction]) -> ZKWatchResult<MovementPrediction> {
+    async fn predict(&self, _data: &[WhaleTransaction]) -> ZKWatchResult<MovementPrediction> {
         // Simulate ML prediction
         Ok(MovementPrediction {
             predicted_address: "0x742d35Cc6634C0532925a3b8D8Ac87b5F8aF7C3F".to_string(),
@@ -545,7 +644,10 @@ impl PredictionModel {
             confidence: 0.75,
             time_horizon: Duration::hours(24),
             estimated_volume: 500_000_000_000_000_000_000u128,
-            supporting_indicators: vec!["High transaction frequency".to_string()],
+            supporting_indicators: vec![
+                "High transaction frequency".to_string(),
+                format!("{} model, {:.0}% accuracy, trained {}", self.model_type, self.accuracy * 100.0, self.last_trained.format("%Y-%m-%d")),
+            ],
         })
     }
 }
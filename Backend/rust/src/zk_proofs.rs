@@ -3,70 +3,623 @@
 //! This module provides implementations of zk-SNARKs and zk-STARKs
 //! for private whale tracking and transaction validation.
 
-use crate::{ZKWatchResult, ZKProof, ZKWatchError};
+use crate::commitments::{address_scalar, hex_decode, hex_encode, value_scalar, Commitment, Opening};
+use crate::proof_envelope::{ProofScheme, VerificationKey};
+use crate::stark::{self, StarkParams, StarkTransfer};
+use crate::{ZKWatchResult, ZKProof, ZKWatchError, WhaleTransaction};
+use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, IsIdentity, VartimeMultiscalarMul};
+use merlin::Transcript;
+use rayon::prelude::*;
 use ring::digest;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+use std::sync::OnceLock;
 
-/// ZK-SNARK proof generator
+/// Bit width of the range proof, so `amount - threshold` is shown to lie in `[0, 2^64)`
+pub const RANGE_PROOF_BITS: usize = 64;
+
+/// Decimals of the units amounts are proven in; 9 keeps ETH amounts in gwei within a u64
+pub const PROOF_AMOUNT_DECIMALS: u8 = 9;
+
+/// Domain separator of the whale threshold proof transcript
+const WHALE_THRESHOLD_DOMAIN: &[u8] = b"zkwatch.whale_threshold.v1";
+
+/// Domain separator of the aggregated whale threshold proof transcript
+const WHALE_AGGREGATE_DOMAIN: &[u8] = b"zkwatch.whale_threshold.aggregate.v1";
+
+/// Public inputs of a whale threshold proof, in order
+const WHALE_PROOF_PUBLIC_INPUTS: usize = 7;
+
+/// Most transactions covered by one aggregated proof; a power of two
+pub const MAX_AGGREGATED_PROOFS: usize = 64;
+
+/// Security level, in bits, of the STARK keys registered by default
+pub const DEFAULT_STARK_SECURITY_LEVEL: u32 = stark::MAX_STARK_SECURITY_LEVEL;
+
+/// Whale threshold prover and verifier
+///
+/// Commits to a transfer amount with a Pedersen commitment and proves with a
+/// Bulletproofs range proof that the committed amount is at least a public
+/// threshold, without revealing the amount.
 pub struct ZKSNARKGenerator {
-    proving_key: Option<Vec<u8>>,
-    verification_key: String,
+    params: RangeProofParams,
+    aggregate_params: RangeProofParams,
+    pc_gens: PedersenGens,
+    /// Sized for aggregated proofs; single proofs use the first party's generators
+    bp_gens: BulletproofGens,
+    /// H generators of every party, party by party, derived on first batch verification
+    h_gens: OnceLock<Vec<RistrettoPoint>>,
+    verification_key: VerificationKey,
+    aggregate_verification_key: VerificationKey,
+}
+
+impl Default for ZKSNARKGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ZKSNARKGenerator {
     pub fn new() -> Self {
+        let params = RangeProofParams { bits: RANGE_PROOF_BITS, parties: 1 };
+        let aggregate_params = RangeProofParams { bits: RANGE_PROOF_BITS, parties: MAX_AGGREGATED_PROOFS };
         Self {
-            proving_key: None,
-            verification_key: generate_verification_key(),
+            pc_gens: PedersenGens::default(),
+            bp_gens: BulletproofGens::new(aggregate_params.bits, aggregate_params.parties),
+            h_gens: OnceLock::new(),
+            verification_key: params.verification_key(),
+            aggregate_verification_key: aggregate_params.verification_key(),
+            params,
+            aggregate_params,
         }
     }
 
-    /// Generate a ZK-SNARK proof for whale transaction privacy
+    /// Key identifying the parameters of single proofs
+    pub fn verification_key(&self) -> &VerificationKey {
+        &self.verification_key
+    }
+
+    /// Key identifying the parameters of aggregated proofs
+    pub fn aggregate_verification_key(&self) -> &VerificationKey {
+        &self.aggregate_verification_key
+    }
+
+    /// Prove that the transaction's amount is at least its whale threshold
+    ///
+    /// Public inputs are the transaction hash, block number, timestamp,
+    /// threshold and the amount, address and gas commitments.
     pub fn generate_whale_transaction_proof(
         &mut self,
         transaction_data: &WhaleTransactionInputs,
     ) -> ZKWatchResult<ZKProof> {
-        // In a real implementation, this would use actual zk-SNARK circuits
-        // For demo purposes, we simulate the proof generation
+        let excess = transaction_data.excess()?;
+        let statement = transaction_data.statement();
         
-        let public_inputs = vec![
-            transaction_data.tx_hash.clone(),
-            format!("{}", transaction_data.block_number),
-            format!("{}", transaction_data.timestamp.timestamp()),
-        ];
+        let mut transcript = Transcript::new(WHALE_THRESHOLD_DOMAIN);
+        statement.append_to(&mut transcript);
+        // The excess commitment is the amount commitment shifted down by the threshold,
+        // so the verifier derives it instead of trusting a second commitment
+        let (range_proof, _excess_commitment) = RangeProof::prove_single(
+            &self.bp_gens,
+            &self.pc_gens,
+            &mut transcript,
+            excess,
+            transaction_data.openings.amount.blinding.scalar(),
+            self.params.bits,
+        )
+        .map_err(|e| ZKWatchError::ProofGeneration(e.to_string()))?;
+        
+        Ok(ZKProof {
+            proof_data: range_proof.to_bytes(),
+            public_inputs: statement.public_inputs(),
+            verification_key: self.verification_key.id(),
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    /// Prove in one aggregated range proof that every transaction meets its threshold
+    ///
+    /// Public inputs are the single-proof public inputs of each transaction, in order.
+    pub fn generate_aggregated_proof(&self, transactions: &[WhaleTransactionInputs]) -> ZKWatchResult<ZKProof> {
+        if transactions.is_empty() || transactions.len() > self.aggregate_params.parties {
+            return Err(ZKWatchError::ProofGeneration(format!(
+                "an aggregated proof covers 1 to {} transactions, got {}",
+                self.aggregate_params.parties,
+                transactions.len()
+            )));
+        }
+        
+        let parties = transactions.len().next_power_of_two();
+        let mut values = Vec::with_capacity(parties);
+        let mut blindings = Vec::with_capacity(parties);
+        for transaction_data in transactions {
+            values.push(transaction_data.excess()?);
+            blindings.push(*transaction_data.openings.amount.blinding.scalar());
+        }
+        // Padding parties prove zero under a zero blinding, a commitment the verifier rebuilds
+        values.resize(parties, 0);
+        blindings.resize(parties, Scalar::ZERO);
         
-        let proof_data = simulate_proof_generation(&transaction_data)?;
+        let statements: Vec<_> = transactions.iter().map(WhaleTransactionInputs::statement).collect();
+        let mut transcript = aggregate_transcript(&statements);
+        let (range_proof, _excess_commitments) = RangeProof::prove_multiple(
+            &self.bp_gens,
+            &self.pc_gens,
+            &mut transcript,
+            &values,
+            &blindings,
+            self.aggregate_params.bits,
+        )
+        .map_err(|e| ZKWatchError::ProofGeneration(e.to_string()))?;
         
         Ok(ZKProof {
-            proof_data,
-            public_inputs,
-            verification_key: self.verification_key.clone(),
+            proof_data: range_proof.to_bytes(),
+            public_inputs: statements.iter().flat_map(WhaleStatement::public_inputs).collect(),
+            verification_key: self.aggregate_verification_key.id(),
             timestamp: chrono::Utc::now(),
         })
     }
 
-    /// Verify a ZK-SNARK proof
+    /// Check a single or aggregated whale threshold proof against its public inputs
+    ///
+    /// Malformed or tampered proofs verify as `false`.
     pub fn verify_proof(&self, proof: &ZKProof) -> ZKWatchResult<bool> {
-        // In a real implementation, this would perform actual proof verification
-        // For demo purposes, we simulate the verification
-        verify_simulated_proof(proof)
+        let verified = if proof.verification_key == self.verification_key.id() {
+            verify_whale_threshold(&self.pc_gens, &self.bp_gens, self.params.bits, &proof.public_inputs, &proof.proof_data)
+        } else if proof.verification_key == self.aggregate_verification_key.id() {
+            verify_aggregated_whale_threshold(
+                &self.pc_gens,
+                &self.bp_gens,
+                self.aggregate_params.bits,
+                &proof.public_inputs,
+                &proof.proof_data,
+            )
+        } else {
+            false
+        };
+        Ok(verified)
     }
 
-    /// Generate batch proofs for multiple transactions
+    /// Aggregated proofs covering up to `MAX_AGGREGATED_PROOFS` transactions each, built in parallel
     pub fn generate_batch_proofs(
         &mut self,
         transactions: &[WhaleTransactionInputs],
     ) -> ZKWatchResult<Vec<ZKProof>> {
-        let mut proofs = Vec::new();
+        let generator = &*self;
+        transactions
+            .par_chunks(self.aggregate_params.parties)
+            .map(|chunk| generator.generate_aggregated_proof(chunk))
+            .collect()
+    }
+
+    /// Verify single and aggregated proofs together, `true` only when every proof verifies
+    ///
+    /// Each proof's verification equation is replayed from its transcript in
+    /// parallel, weighted by a fresh random scalar and summed into one
+    /// multiscalar multiplication, so the shared generators are multiplied
+    /// once for the whole batch. A forged proof passes only with negligible
+    /// probability; finding which proof failed needs `verify_proof`.
+    pub fn verify_batch(&self, proofs: &[ZKProof]) -> ZKWatchResult<bool> {
+        let checks = proofs
+            .par_iter()
+            .map(|proof| self.range_check(proof))
+            .collect::<Option<Vec<_>>>();
+        Ok(checks.is_some_and(|checks| {
+            batch_holds(&self.pc_gens, &self.bp_gens, self.h_gens(), self.aggregate_params.bits, &checks)
+        }))
+    }
+
+    fn h_gens(&self) -> &[RistrettoPoint] {
+        self.h_gens.get_or_init(|| {
+            (0..self.aggregate_params.parties as u32)
+                .flat_map(|party| party_generators(b'H', party, self.aggregate_params.bits))
+                .collect()
+        })
+    }
+
+    /// Verification equation of a single or aggregated proof, `None` when it cannot hold
+    fn range_check(&self, proof: &ZKProof) -> Option<RangeCheck> {
+        let (mut transcript, excess_commitments, bits) = if proof.verification_key == self.verification_key.id() {
+            let (transcript, excess_commitment) = single_claim(&self.pc_gens, &proof.public_inputs)?;
+            (transcript, vec![excess_commitment], self.params.bits)
+        } else if proof.verification_key == self.aggregate_verification_key.id() {
+            let (transcript, excess_commitments) =
+                aggregated_claim(&self.pc_gens, self.bp_gens.party_capacity, &proof.public_inputs)?;
+            (transcript, excess_commitments, self.aggregate_params.bits)
+        } else {
+            return None;
+        };
+        RangeCheck::new(&mut transcript, &excess_commitments, bits, &proof.proof_data)
+    }
+}
+
+/// Parameters of whale threshold range proofs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeProofParams {
+    pub bits: usize,
+    /// Values aggregated into one proof
+    pub parties: usize,
+}
+
+impl RangeProofParams {
+    /// Length of the encoded parameters: bits, parties and the two Pedersen generators
+    const ENCODED_LEN: usize = 4 + 4 + 32 + 32;
+
+    /// Bits and parties followed by the Pedersen generators, so a change of generators changes the key
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pc_gens = PedersenGens::default();
+        let mut bytes = Vec::with_capacity(Self::ENCODED_LEN);
+        bytes.extend_from_slice(&(self.bits as u32).to_be_bytes());
+        bytes.extend_from_slice(&(self.parties as u32).to_be_bytes());
+        bytes.extend_from_slice(pc_gens.B.compress().as_bytes());
+        bytes.extend_from_slice(pc_gens.B_blinding.compress().as_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> ZKWatchResult<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "range proof parameters must be {} bytes, got {}",
+                Self::ENCODED_LEN,
+                bytes.len()
+            )));
+        }
+        let bits = u32::from_be_bytes(bytes[0..4].try_into().unwrap_or_default()) as usize;
+        let parties = u32::from_be_bytes(bytes[4..8].try_into().unwrap_or_default()) as usize;
+        if ![8, 16, 32, 64].contains(&bits) || !parties.is_power_of_two() || parties > MAX_AGGREGATED_PROOFS {
+            return Err(ZKWatchError::MalformedProof(format!(
+                "unsupported range proof parameters: {} bits, {} parties",
+                bits, parties
+            )));
+        }
+
+        let pc_gens = PedersenGens::default();
+        if bytes[8..40] != pc_gens.B.compress().to_bytes() || bytes[40..72] != pc_gens.B_blinding.compress().to_bytes() {
+            return Err(ZKWatchError::MalformedProof("range proof uses unknown Pedersen generators".to_string()));
+        }
+        Ok(Self { bits, parties })
+    }
+
+    /// Single proofs have one party; more parties mean aggregated proofs
+    pub fn scheme(&self) -> ProofScheme {
+        if self.parties == 1 {
+            ProofScheme::BulletproofsRange
+        } else {
+            ProofScheme::BulletproofsAggregatedRange
+        }
+    }
+
+    pub fn verification_key(&self) -> VerificationKey {
+        VerificationKey::new(self.scheme(), self.to_bytes())
+    }
+}
+
+/// Public statement of one whale threshold proof
+struct WhaleStatement {
+    tx_hash: String,
+    block_number: u64,
+    timestamp: i64,
+    threshold: u64,
+    /// Amount, address and gas commitments
+    commitments: [Commitment; 3],
+}
+
+impl WhaleStatement {
+    fn parse(public_inputs: &[String]) -> Option<Self> {
+        if public_inputs.len() != WHALE_PROOF_PUBLIC_INPUTS {
+            return None;
+        }
+        Some(Self {
+            tx_hash: public_inputs[0].clone(),
+            block_number: public_inputs[1].parse().ok()?,
+            timestamp: public_inputs[2].parse().ok()?,
+            threshold: public_inputs[3].parse().ok()?,
+            commitments: [
+                public_inputs[4].parse().ok()?,
+                public_inputs[5].parse().ok()?,
+                public_inputs[6].parse().ok()?,
+            ],
+        })
+    }
+
+    fn public_inputs(&self) -> Vec<String> {
+        let mut inputs = vec![
+            self.tx_hash.clone(),
+            format!("{}", self.block_number),
+            format!("{}", self.timestamp),
+            format!("{}", self.threshold),
+        ];
+        inputs.extend(self.commitments.iter().map(Commitment::to_string));
+        inputs
+    }
+
+    fn append_to(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"tx_hash", self.tx_hash.as_bytes());
+        transcript.append_u64(b"block_number", self.block_number);
+        transcript.append_message(b"timestamp", &self.timestamp.to_be_bytes());
+        transcript.append_u64(b"threshold", self.threshold);
+        for commitment in &self.commitments {
+            transcript.append_message(b"commitment", commitment.compressed().as_bytes());
+        }
+    }
+
+    /// Amount commitment shifted down by the threshold, a commitment to the excess
+    fn excess_commitment(&self, pc_gens: &PedersenGens) -> CompressedRistretto {
+        (self.commitments[0].point() - pc_gens.B * Scalar::from(self.threshold)).compress()
+    }
+}
+
+/// Transcript binding an aggregated range proof to every statement it covers
+fn aggregate_transcript(statements: &[WhaleStatement]) -> Transcript {
+    let mut transcript = Transcript::new(WHALE_AGGREGATE_DOMAIN);
+    transcript.append_u64(b"statements", statements.len() as u64);
+    for statement in statements {
+        statement.append_to(&mut transcript);
+    }
+    transcript
+}
+
+/// Transcript and excess commitment a single whale threshold proof is checked against
+fn single_claim(pc_gens: &PedersenGens, public_inputs: &[String]) -> Option<(Transcript, CompressedRistretto)> {
+    let statement = WhaleStatement::parse(public_inputs)?;
+    let mut transcript = Transcript::new(WHALE_THRESHOLD_DOMAIN);
+    statement.append_to(&mut transcript);
+    Some((transcript, statement.excess_commitment(pc_gens)))
+}
+
+/// Transcript and padded excess commitments an aggregated whale threshold proof is checked against
+fn aggregated_claim(
+    pc_gens: &PedersenGens,
+    party_capacity: usize,
+    public_inputs: &[String],
+) -> Option<(Transcript, Vec<CompressedRistretto>)> {
+    if public_inputs.is_empty() || !public_inputs.len().is_multiple_of(WHALE_PROOF_PUBLIC_INPUTS) {
+        return None;
+    }
+    let statements = public_inputs
+        .chunks(WHALE_PROOF_PUBLIC_INPUTS)
+        .map(WhaleStatement::parse)
+        .collect::<Option<Vec<_>>>()?;
+    let parties = statements.len().next_power_of_two();
+    if parties > party_capacity {
+        return None;
+    }
+    
+    let mut excess_commitments: Vec<_> = statements.iter().map(|s| s.excess_commitment(pc_gens)).collect();
+    excess_commitments.resize(parties, RistrettoPoint::identity().compress());
+    Some((aggregate_transcript(&statements), excess_commitments))
+}
+
+/// Verify a single whale threshold proof under the given generators
+///
+/// Malformed public inputs or proof bytes verify as `false`.
+pub(crate) fn verify_whale_threshold(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    bits: usize,
+    public_inputs: &[String],
+    proof_data: &[u8],
+) -> bool {
+    let (Some((mut transcript, excess_commitment)), Ok(range_proof)) =
+        (single_claim(pc_gens, public_inputs), RangeProof::from_bytes(proof_data))
+    else {
+        return false;
+    };
+    range_proof
+        .verify_single(bp_gens, pc_gens, &mut transcript, &excess_commitment, bits)
+        .is_ok()
+}
+
+/// Verify an aggregated whale threshold proof under the given generators
+///
+/// Malformed public inputs or proof bytes verify as `false`.
+pub(crate) fn verify_aggregated_whale_threshold(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    bits: usize,
+    public_inputs: &[String],
+    proof_data: &[u8],
+) -> bool {
+    let (Some((mut transcript, excess_commitments)), Ok(range_proof)) =
+        (aggregated_claim(pc_gens, bp_gens.party_capacity, public_inputs), RangeProof::from_bytes(proof_data))
+    else {
+        return false;
+    };
+    range_proof
+        .verify_multiple(bp_gens, pc_gens, &mut transcript, &excess_commitments, bits)
+        .is_ok()
+}
+
+/// Verification equation of one range proof, which holds when its terms sum to the identity
+///
+/// `RangeProof` keeps its verification scalars private, so this replays the
+/// transcript of `RangeProof::verify_multiple` from the encoded proof. The
+/// terms match the ones that method feeds to its own multiscalar check.
+struct RangeCheck {
+    /// A, S, T_1, T_2, the inner product L and R points and the value commitments
+    points: Vec<CompressedRistretto>,
+    point_scalars: Vec<Scalar>,
+    /// Coefficient of the value generator `B`
+    b: Scalar,
+    /// Coefficient of the blinding generator `B_blinding`
+    b_blinding: Scalar,
+    /// Coefficients of the first `bits` G and H generators of each party, party by party
+    g: Vec<Scalar>,
+    h: Vec<Scalar>,
+}
+
+impl RangeCheck {
+    /// Replay the proof's challenges, `None` for malformed proofs or unsupported sizes
+    fn new(transcript: &mut Transcript, value_commitments: &[CompressedRistretto], bits: usize, proof_data: &[u8]) -> Option<Self> {
+        // Checks the layout and that every scalar is canonical
+        RangeProof::from_bytes(proof_data).ok()?;
+        let point = |i: usize| CompressedRistretto::from_slice(&proof_data[32 * i..32 * (i + 1)]).ok();
+        let scalar = |i: usize| Option::<Scalar>::from(Scalar::from_canonical_bytes(proof_data[32 * i..32 * (i + 1)].try_into().ok()?));
+        let parties = value_commitments.len();
+        let n = bits * parties;
+        let lg_n = (proof_data.len() / 32 - 9) / 2;
+        if ![8, 16, 32, 64].contains(&bits) || n != 1 << lg_n {
+            return None;
+        }
+        let validated = |transcript: &mut Transcript, label: &'static [u8], point: CompressedRistretto| {
+            (point != CompressedRistretto::identity()).then(|| transcript.append_message(label, point.as_bytes()))
+        };
+        
+        transcript.append_message(b"dom-sep", b"rangeproof v1");
+        transcript.append_u64(b"n", bits as u64);
+        transcript.append_u64(b"m", parties as u64);
+        for commitment in value_commitments {
+            transcript.append_message(b"V", commitment.as_bytes());
+        }
+        let (a_point, s_point, t_1, t_2) = (point(0)?, point(1)?, point(2)?, point(3)?);
+        validated(transcript, b"A", a_point)?;
+        validated(transcript, b"S", s_point)?;
+        let y = challenge_scalar(transcript, b"y");
+        let z = challenge_scalar(transcript, b"z");
+        validated(transcript, b"T_1", t_1)?;
+        validated(transcript, b"T_2", t_2)?;
+        let x = challenge_scalar(transcript, b"x");
+        let (t_x, t_x_blinding, e_blinding) = (scalar(4)?, scalar(5)?, scalar(6)?);
+        transcript.append_message(b"t_x", t_x.as_bytes());
+        transcript.append_message(b"t_x_blinding", t_x_blinding.as_bytes());
+        transcript.append_message(b"e_blinding", e_blinding.as_bytes());
+        let w = challenge_scalar(transcript, b"w");
+        
+        // Inner product argument over the n·m generators
+        transcript.append_message(b"dom-sep", b"ipp v1");
+        transcript.append_u64(b"n", n as u64);
+        let mut l_points = Vec::with_capacity(lg_n);
+        let mut r_points = Vec::with_capacity(lg_n);
+        let mut challenges = Vec::with_capacity(lg_n);
+        for round in 0..lg_n {
+            let (l, r) = (point(7 + 2 * round)?, point(8 + 2 * round)?);
+            validated(transcript, b"L", l)?;
+            validated(transcript, b"R", r)?;
+            challenges.push(challenge_scalar(transcript, b"u"));
+            l_points.push(l);
+            r_points.push(r);
+        }
+        let (a, b) = (scalar(7 + 2 * lg_n)?, scalar(8 + 2 * lg_n)?);
         
-        for tx in transactions {
-            let proof = self.generate_whale_transaction_proof(tx)?;
-            proofs.push(proof);
+        let mut challenges_inv = challenges.clone();
+        let all_inv = Scalar::batch_invert(&mut challenges_inv);
+        let challenges_sq: Vec<Scalar> = challenges.iter().map(|u| u * u).collect();
+        let challenges_inv_sq: Vec<Scalar> = challenges_inv.iter().map(|u| u * u).collect();
+        let mut s = Vec::with_capacity(n);
+        s.push(all_inv);
+        for i in 1..n {
+            let lg_i = (usize::BITS - 1 - i.leading_zeros()) as usize;
+            s.push(s[i - (1 << lg_i)] * challenges_sq[lg_n - 1 - lg_i]);
         }
         
-        Ok(proofs)
+        // Random weight of the polynomial check relative to the inner product check
+        let c = Scalar::random(&mut rand::rngs::OsRng);
+        let zz = z * z;
+        let powers_of_2: Vec<Scalar> = powers(Scalar::from(2u64)).take(bits).collect();
+        let z_and_2 = powers(z).take(parties).flat_map(|z_j| powers_of_2.iter().map(move |p| z_j * p));
+        let delta = (z - zz) * powers(y).take(n).sum::<Scalar>()
+            - zz * z * powers_of_2.iter().sum::<Scalar>() * powers(z).take(parties).sum::<Scalar>();
+        
+        let mut points = vec![a_point, s_point, t_1, t_2];
+        points.extend(l_points.into_iter().chain(r_points));
+        points.extend_from_slice(value_commitments);
+        let mut point_scalars = vec![Scalar::ONE, x, c * x, c * x * x];
+        point_scalars.extend(challenges_sq.into_iter().chain(challenges_inv_sq));
+        point_scalars.extend(powers(z).take(parties).map(|z_j| c * zz * z_j));
+        
+        Some(Self {
+            points,
+            point_scalars,
+            b: w * (t_x - a * b) + c * (delta - t_x),
+            b_blinding: -e_blinding - c * t_x_blinding,
+            g: s.iter().map(|s_i| -z - a * s_i).collect(),
+            h: s.iter()
+                .rev()
+                .zip(powers(y.invert()))
+                .zip(z_and_2)
+                .map(|((s_i_inv, y_inv_i), z_and_2_i)| z + y_inv_i * (zz * z_and_2_i - b * s_i_inv))
+                .collect(),
+        })
+    }
+}
+
+/// Whether the randomly weighted sum of every check is the identity
+///
+/// Single and aggregated proofs share `bits`, so party `j`'s generators sit
+/// at the same offset in every check.
+fn batch_holds(
+    pc_gens: &PedersenGens,
+    bp_gens: &BulletproofGens,
+    h_gens: &[RistrettoPoint],
+    bits: usize,
+    checks: &[RangeCheck],
+) -> bool {
+    let generators = checks.iter().map(|check| check.g.len()).max().unwrap_or_default();
+    if generators > bits * bp_gens.party_capacity || generators > h_gens.len() {
+        return false;
     }
+    let mut b = Scalar::ZERO;
+    let mut b_blinding = Scalar::ZERO;
+    let mut g = vec![Scalar::ZERO; generators];
+    let mut h = vec![Scalar::ZERO; generators];
+    let mut scalars = Vec::new();
+    let mut points = Vec::new();
+    for check in checks {
+        let weight = Scalar::random(&mut rand::rngs::OsRng);
+        b += weight * check.b;
+        b_blinding += weight * check.b_blinding;
+        for (total, term) in g.iter_mut().zip(&check.g).chain(h.iter_mut().zip(&check.h)) {
+            *total += weight * term;
+        }
+        scalars.extend(check.point_scalars.iter().map(|scalar| weight * scalar));
+        points.extend(check.points.iter().map(CompressedRistretto::decompress));
+    }
+    
+    // Collected so the multiscalar multiplication sees exact lengths
+    points.extend([Some(pc_gens.B), Some(pc_gens.B_blinding)]);
+    points.extend((0..generators / bits).flat_map(|party| bp_gens.share(party).G(bits)).map(|point| Some(*point)));
+    points.extend(h_gens[..generators].iter().map(|point| Some(*point)));
+    let sum = RistrettoPoint::optional_multiscalar_mul(
+        scalars.into_iter().chain([b, b_blinding]).chain(g).chain(h),
+        points,
+    );
+    sum.is_some_and(|sum| sum.is_identity())
+}
+
+/// First `count` G or H generators of one party
+///
+/// `BulletproofGens` only exposes the G generators, so these follow its
+/// documented chain: SHAKE256 over a per-party label, hashed to the group
+/// 64 bytes at a time.
+fn party_generators(kind: u8, party: u32, count: usize) -> Vec<RistrettoPoint> {
+    let mut shake = Shake256::default();
+    shake.update(b"GeneratorsChain");
+    shake.update(&[kind]);
+    shake.update(&party.to_le_bytes());
+    let mut reader = shake.finalize_xof();
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; 64];
+            reader.read(&mut bytes);
+            RistrettoPoint::from_uniform_bytes(&bytes)
+        })
+        .collect()
+}
+
+/// Challenge scalar drawn from the transcript as Bulletproofs does
+fn challenge_scalar(transcript: &mut Transcript, label: &'static [u8]) -> Scalar {
+    let mut bytes = [0u8; 64];
+    transcript.challenge_bytes(label, &mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// 1, x, x², …
+fn powers(x: Scalar) -> impl Iterator<Item = Scalar> {
+    std::iter::successors(Some(Scalar::ONE), move |power| Some(power * x))
 }
 
 /// Inputs for whale transaction ZK-proof
@@ -75,50 +628,177 @@ pub struct WhaleTransactionInputs {
     pub tx_hash: String,
     pub block_number: u64,
     pub timestamp: chrono::DateTime<chrono::Utc>,
-    pub amount_commitment: String,
-    pub address_commitment: String,
-    pub gas_commitment: String,
+    /// Transfer amount in `PROOF_AMOUNT_DECIMALS` units, known only to the prover
+    pub amount: u64,
+    /// Public whale threshold in the same units
+    pub threshold: u64,
+    pub amount_commitment: Commitment,
+    /// Commitment to the sender address
+    pub address_commitment: Commitment,
+    pub gas_commitment: Commitment,
+    /// Openings of the commitments, known only to the prover
+    pub openings: InputOpenings,
+}
+
+/// Prover-side openings of the commitments in `WhaleTransactionInputs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputOpenings {
+    pub amount: Opening,
+    pub address: Opening,
+    pub gas: Opening,
+}
+
+impl WhaleTransactionInputs {
+    /// Commit to a scanned transaction's amount, sender and gas under fresh blindings
+    ///
+    /// `threshold` is in `PROOF_AMOUNT_DECIMALS` units of the transferred asset.
+    pub fn from_transaction(transaction: &WhaleTransaction, threshold: u64) -> ZKWatchResult<Self> {
+        let amount = proof_amount(transaction.value, transaction.decimals())?;
+        let (amount_commitment, amount_blinding) = Commitment::random(value_scalar(amount));
+        let (address_commitment, address_blinding) = Commitment::random(address_scalar(&transaction.from)?);
+        let (gas_commitment, gas_blinding) = Commitment::random(value_scalar(transaction.gas_used));
+        
+        Ok(Self {
+            tx_hash: transaction.hash.clone(),
+            block_number: transaction.block_number,
+            timestamp: transaction.timestamp,
+            amount,
+            threshold,
+            amount_commitment,
+            address_commitment,
+            gas_commitment,
+            openings: InputOpenings {
+                amount: Opening { value: value_scalar(amount), blinding: amount_blinding },
+                address: Opening { value: address_scalar(&transaction.from)?, blinding: address_blinding },
+                gas: Opening { value: value_scalar(transaction.gas_used), blinding: gas_blinding },
+            },
+        })
+    }
+
+    /// Amount above the threshold, after checking the commitment openings
+    fn excess(&self) -> ZKWatchResult<u64> {
+        self.check_openings()?;
+        self.amount.checked_sub(self.threshold).ok_or_else(|| {
+            ZKWatchError::ProofGeneration(format!(
+                "amount of {} is below the whale threshold of {}",
+                self.tx_hash, self.threshold
+            ))
+        })
+    }
+
+    fn statement(&self) -> WhaleStatement {
+        WhaleStatement {
+            tx_hash: self.tx_hash.clone(),
+            block_number: self.block_number,
+            timestamp: self.timestamp.timestamp(),
+            threshold: self.threshold,
+            commitments: [self.amount_commitment, self.address_commitment, self.gas_commitment],
+        }
+    }
+
+    /// Ensure every commitment opens and the amount opening matches `amount`
+    fn check_openings(&self) -> ZKWatchResult<()> {
+        let checks = [
+            ("amount", &self.amount_commitment, &self.openings.amount),
+            ("address", &self.address_commitment, &self.openings.address),
+            ("gas", &self.gas_commitment, &self.openings.gas),
+        ];
+        for (name, commitment, opening) in checks {
+            if opening.commitment() != *commitment {
+                return Err(ZKWatchError::InvalidCommitment(format!("{} commitment does not match its opening", name)));
+            }
+        }
+        if self.openings.amount.value != value_scalar(self.amount) {
+            return Err(ZKWatchError::InvalidCommitment("amount commitment does not open to the amount".to_string()));
+        }
+        Ok(())
+    }
 }
 
-/// ZK-STARK implementation for scalable proofs
+/// Whale aggregate STARK prover and verifier
+///
+/// Proves with a transparent STARK over the Goldilocks field that the batch of
+/// transfers behind a public commitment adds up to at least a public threshold.
+/// `security_level` sets the number of FRI queries and is clamped to
+/// `stark::MAX_STARK_SECURITY_LEVEL`.
 pub struct ZKSTARKGenerator {
     security_level: u32,
-    field_prime: u64,
+    params: StarkParams,
+    verification_key: VerificationKey,
 }
 
 impl ZKSTARKGenerator {
     pub fn new(security_level: u32) -> Self {
+        let params = StarkParams::from_security_level(security_level);
         Self {
-            security_level,
-            field_prime: 18446744069414584321u64, // 2^64 - 59
+            security_level: params.security_level(),
+            verification_key: VerificationKey::new(ProofScheme::StarkAggregateThreshold, params.to_bytes()),
+            params,
         }
     }
 
-    /// Generate a STARK proof for transaction anonymity
-    pub fn generate_stark_proof(
-        &self,
-        witness: &StarkWitness,
-        program_hash: &str,
-    ) -> ZKWatchResult<ZKProof> {
-        // Simulate STARK proof generation
-        let trace = generate_stark_trace(witness)?;
-        let proof = simulate_stark_proof_generation(&trace, program_hash)?;
+    pub fn security_level(&self) -> u32 {
+        self.security_level
+    }
+
+    pub fn verification_key(&self) -> &VerificationKey {
+        &self.verification_key
+    }
+
+    /// Hex commitment to the witness transfers, recomputable by anyone holding them
+    pub fn transfer_commitment(&self, witness: &StarkWitness) -> ZKWatchResult<String> {
+        Ok(hex_encode(&stark::commit_transfers(&self.params, &witness.transfers)?))
+    }
+
+    /// Prove that the witness transfers add up to at least `threshold`
+    ///
+    /// Public inputs are the number of transfers, the threshold and the
+    /// transfer commitment.
+    pub fn generate_stark_proof(&self, witness: &StarkWitness, threshold: u64) -> ZKWatchResult<ZKProof> {
+        let (commitment, proof_data) = stark::prove(&self.params, &witness.transfers, threshold)?;
         
         Ok(ZKProof {
-            proof_data: proof,
-            public_inputs: vec![program_hash.to_string()],
-            verification_key: format!("stark_vk_{}_{}", self.security_level, self.field_prime),
+            proof_data,
+            public_inputs: vec![
+                format!("{}", witness.transfers.len()),
+                format!("{}", threshold),
+                hex_encode(&commitment),
+            ],
+            verification_key: self.verification_key.id(),
             timestamp: chrono::Utc::now(),
         })
     }
+
+    /// Check a whale aggregate STARK against its public inputs
+    ///
+    /// Malformed or tampered proofs verify as `false`.
+    pub fn verify_stark_proof(&self, proof: &ZKProof) -> ZKWatchResult<bool> {
+        Ok(proof.verification_key == self.verification_key.id()
+            && verify_aggregate_threshold_stark(&self.params, &proof.public_inputs, &proof.proof_data))
+    }
 }
 
-/// Witness for STARK computations
+/// Private inputs of a whale aggregate STARK
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StarkWitness {
-    pub input_values: Vec<u64>,
-    pub output_values: Vec<u64>,
-    pub computation_steps: Vec<u64>,
+    /// Transaction hashes and amounts in `PROOF_AMOUNT_DECIMALS` units
+    pub transfers: Vec<StarkTransfer>,
+}
+
+/// Verify a whale aggregate STARK under the given parameters
+///
+/// Malformed public inputs or proof bytes verify as `false`.
+pub(crate) fn verify_aggregate_threshold_stark(params: &StarkParams, public_inputs: &[String], proof_data: &[u8]) -> bool {
+    let [transfers, threshold, commitment] = public_inputs else {
+        return false;
+    };
+    let (Ok(transfers), Ok(threshold)) = (transfers.parse(), threshold.parse()) else {
+        return false;
+    };
+    let Some(Ok(commitment)) = hex_decode(commitment).map(<[u8; 32]>::try_from) else {
+        return false;
+    };
+    stark::verify(params, transfers, threshold, &commitment, proof_data)
 }
 
 /// Privacy-preserving whale detection
@@ -143,11 +823,10 @@ impl PrivateWhaleDetector {
         let mut alerts = Vec::new();
         
         for tx in encrypted_transactions {
-            let detection_proof = self.generate_detection_proof(tx)?;
-            if detection_proof.is_some() {
+            if let Some(detection_proof) = self.generate_detection_proof(tx)? {
                 alerts.push(PrivateWhaleAlert {
                     alert_id: format!("alert_{}", tx.tx_id),
-                    detection_confidence: detection_proof.as_ref().unwrap().confidence,
+                    detection_confidence: detection_proof.confidence,
                     network: tx.network.clone(),
                     estimated_value_range: tx.estimated_range.clone(),
                     timestamp: chrono::Utc::now(),
@@ -166,7 +845,7 @@ impl PrivateWhaleDetector {
         if tx.confidence_score > self.anonymity_threshold {
             Ok(Some(DetectionProof {
                 confidence: tx.confidence_score,
-                proof_data: simulate_detection_proof(tx)?,
+                proof_data: simulate_detection_proof(&self.detector_circuit_hash, tx)?,
             }))
         } else {
             Ok(None)
@@ -202,63 +881,173 @@ pub struct DetectionProof {
     pub proof_data: Vec<u8>,
 }
 
-// Helper functions for simulation (in real implementation, these would use actual cryptographic libraries)
-
-fn generate_verification_key() -> String {
-    "zk_snark_vk_v2.1.0_security_128".to_string()
+/// Convert a raw amount with `decimals` to proof units, rounding down
+///
+/// Rounding down keeps threshold proofs sound: a truncated amount that meets
+/// the threshold means the real amount does too.
+pub fn proof_amount(value: u128, decimals: u8) -> ZKWatchResult<u64> {
+    let scaled = if decimals >= PROOF_AMOUNT_DECIMALS {
+        10u128
+            .checked_pow((decimals - PROOF_AMOUNT_DECIMALS) as u32)
+            .map_or(0, |divisor| value / divisor)
+    } else {
+        value.saturating_mul(10u128.pow((PROOF_AMOUNT_DECIMALS - decimals) as u32))
+    };
+    u64::try_from(scaled).map_err(|_| ZKWatchError::ProofGeneration(format!("amount {} does not fit in a range proof", value)))
 }
 
-fn simulate_proof_generation(inputs: &WhaleTransactionInputs) -> ZKWatchResult<Vec<u8>> {
-    // Simulate proof data generation
+fn simulate_detection_proof(circuit_hash: &str, tx: &EncryptedTransaction) -> ZKWatchResult<Vec<u8>> {
     let mut data = Vec::new();
-    data.extend_from_slice(inputs.tx_hash.as_bytes());
-    data.extend_from_slice(&inputs.block_number.to_be_bytes());
-    data.extend_from_slice(&inputs.timestamp.timestamp().to_be_bytes());
+    data.extend_from_slice(circuit_hash.as_bytes());
+    data.extend_from_slice(tx.tx_id.as_bytes());
+    data.extend_from_slice(&tx.confidence_score.to_le_bytes());
     
-    // Add cryptographic padding
     let hash = digest::digest(&digest::SHA256, &data);
     Ok(hash.as_ref().to_vec())
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_tracker::ConfirmationStatus;
+    use crate::exchange_flows::ExchangeFlow;
+    use crate::TransactionPattern;
 
-fn verify_simulated_proof(proof: &ZKProof) -> ZKWatchResult<bool> {
-    // In a real implementation, this would verify the actual cryptographic proof
-    // For simulation, we check basic structure
-    Ok(!proof.proof_data.is_empty() && !proof.public_inputs.is_empty())
-}
+    /// 100 ETH in proof units
+    const THRESHOLD: u64 = 100_000_000_000;
 
-fn generate_stark_trace(witness: &StarkWitness) -> ZKWatchResult<Vec<Vec<u64>>> {
-    // Simulate STARK trace generation
-    let trace_length = witness.computation_steps.len();
-    let mut trace = Vec::with_capacity(trace_length);
-    
-    for step in &witness.computation_steps {
-        let row = vec![step.clone(), step.clone() + 1, step.clone() + 2];
-        trace.push(row);
+    fn inputs(seed: u64, value: u128) -> WhaleTransactionInputs {
+        let transaction = WhaleTransaction {
+            hash: format!("0x{:064x}", seed),
+            network: "Ethereum".to_string(),
+            chain_id: 1,
+            from: format!("0x{:040x}", seed),
+            to: format!("0x{:040x}", seed + 1),
+            value,
+            borrowed: 0,
+            token: None,
+            log_index: None,
+            usd_value: None,
+            confirmation: ConfirmationStatus::Safe,
+            gas_used: 21_000,
+            block_number: 18_000_000 + seed,
+            timestamp: chrono::Utc::now(),
+            zk_proof_hash: None,
+            risk_score: 0.5,
+            pattern_type: TransactionPattern::LargeTransaction,
+            exchange_flow: ExchangeFlow::NonExchange,
+        };
+        WhaleTransactionInputs::from_transaction(&transaction, THRESHOLD).unwrap()
     }
-    
-    Ok(trace)
-}
 
-fn simulate_stark_proof_generation(trace: &[Vec<u64>], program_hash: &str) -> ZKWatchResult<Vec<u8>> {
-    // Simulate STARK proof data
-    let mut data = Vec::new();
-    data.extend_from_slice(program_hash.as_bytes());
-    
-    for row in trace {
-        for val in row {
-            data.extend_from_slice(&val.to_be_bytes());
+    #[test]
+    fn threshold_proof_verifies() {
+        let mut generator = ZKSNARKGenerator::new();
+        let proof = generator.generate_whale_transaction_proof(&inputs(1, 10u128.pow(21))).unwrap();
+        assert!(generator.verify_proof(&proof).unwrap());
+    }
+
+    #[test]
+    fn tampered_threshold_proof_is_rejected() {
+        let mut generator = ZKSNARKGenerator::new();
+        let proof = generator.generate_whale_transaction_proof(&inputs(1, 10u128.pow(21))).unwrap();
+
+        for position in (0..proof.proof_data.len()).step_by(proof.proof_data.len() / 16) {
+            let mut tampered = proof.clone();
+            tampered.proof_data[position] ^= 1;
+            assert!(!generator.verify_proof(&tampered).unwrap(), "byte {}", position);
+        }
+
+        // Every public input is bound by the transcript or the excess commitment
+        for index in 0..proof.public_inputs.len() {
+            let mut tampered = proof.clone();
+            tampered.public_inputs[index] = proof.public_inputs[(index + 1) % proof.public_inputs.len()].clone();
+            assert!(!generator.verify_proof(&tampered).unwrap(), "public input {}", index);
         }
+        let mut raised = proof.clone();
+        raised.public_inputs[3] = format!("{}", THRESHOLD * 100);
+        assert!(!generator.verify_proof(&raised).unwrap());
+
+        let mut truncated = proof.clone();
+        truncated.proof_data.pop();
+        assert!(!generator.verify_proof(&truncated).unwrap());
+        let mut rekeyed = proof.clone();
+        rekeyed.verification_key = generator.aggregate_verification_key().id();
+        assert!(!generator.verify_proof(&rekeyed).unwrap());
     }
-    
-    let hash = digest::digest(&digest::SHA256, &data);
-    Ok(hash.as_ref().to_vec())
-}
 
-fn simulate_detection_proof(tx: &EncryptedTransaction) -> ZKWatchResult<Vec<u8>> {
-    let mut data = Vec::new();
-    data.extend_from_slice(tx.tx_id.as_bytes());
-    data.extend_from_slice(&tx.confidence_score.to_le_bytes());
-    
-    let hash = digest::digest(&digest::SHA256, &data);
-    Ok(hash.as_ref().to_vec())
-}
\ No newline at end of file
+    #[test]
+    fn tampered_aggregated_proof_is_rejected() {
+        let generator = ZKSNARKGenerator::new();
+        let transactions: Vec<_> = (1..=3).map(|seed| inputs(seed, 10u128.pow(21) * seed as u128)).collect();
+        let proof = generator.generate_aggregated_proof(&transactions).unwrap();
+        assert!(generator.verify_proof(&proof).unwrap());
+
+        let mut swapped = proof.clone();
+        swapped.public_inputs.swap(0, WHALE_PROOF_PUBLIC_INPUTS);
+        assert!(!generator.verify_proof(&swapped).unwrap());
+        let mut dropped = proof.clone();
+        dropped.public_inputs.truncate(2 * WHALE_PROOF_PUBLIC_INPUTS);
+        assert!(!generator.verify_proof(&dropped).unwrap());
+        let mut flipped = proof.clone();
+        flipped.proof_data[0] ^= 1;
+        assert!(!generator.verify_proof(&flipped).unwrap());
+    }
+
+    #[test]
+    fn derived_generators_match_bulletproofs() {
+        let bp_gens = BulletproofGens::new(RANGE_PROOF_BITS, MAX_AGGREGATED_PROOFS);
+        for party in [0, 1, MAX_AGGREGATED_PROOFS - 1] {
+            let expected: Vec<_> = bp_gens.share(party).G(RANGE_PROOF_BITS).copied().collect();
+            assert_eq!(party_generators(b'G', party as u32, RANGE_PROOF_BITS), expected, "party {}", party);
+        }
+    }
+
+    #[test]
+    fn batch_verification_agrees_with_single_verification() {
+        let mut generator = ZKSNARKGenerator::new();
+        let mut proofs = vec![
+            generator.generate_whale_transaction_proof(&inputs(1, 10u128.pow(21))).unwrap(),
+            generator.generate_whale_transaction_proof(&inputs(2, 10u128.pow(23))).unwrap(),
+        ];
+        let transactions: Vec<_> = (3..=7).map(|seed| inputs(seed, 10u128.pow(21) * seed as u128)).collect();
+        proofs.extend(generator.generate_batch_proofs(&transactions).unwrap());
+        proofs.push(generator.generate_aggregated_proof(&transactions[..2]).unwrap());
+        assert!(proofs.iter().all(|proof| generator.verify_proof(proof).unwrap()));
+        assert!(generator.verify_batch(&proofs).unwrap());
+        assert!(generator.verify_batch(&[]).unwrap());
+
+        // One bad proof anywhere in the batch fails the whole batch
+        for index in 0..proofs.len() {
+            let mut flipped = proofs.clone();
+            let position = flipped[index].proof_data.len() / 2;
+            flipped[index].proof_data[position] ^= 1;
+            assert!(!generator.verify_batch(&flipped).unwrap(), "proof {}", index);
+
+            let mut raised = proofs.clone();
+            raised[index].public_inputs[3] = format!("{}", THRESHOLD * 1_000);
+            assert!(!generator.verify_batch(&raised).unwrap(), "proof {}", index);
+        }
+
+        // Swapping range proofs between statements breaks both transcripts
+        let mut swapped = proofs.clone();
+        let proof_data = swapped[0].proof_data.clone();
+        swapped[0].proof_data = swapped[1].proof_data.clone();
+        swapped[1].proof_data = proof_data;
+        assert!(!generator.verify_batch(&swapped).unwrap());
+
+        let mut truncated = proofs.clone();
+        truncated[2].proof_data.truncate(7 * 32);
+        assert!(!generator.verify_batch(&truncated).unwrap());
+        let mut rekeyed = proofs.clone();
+        rekeyed[0].verification_key = generator.aggregate_verification_key().id();
+        assert!(!generator.verify_batch(&rekeyed).unwrap());
+    }
+
+    #[test]
+    fn amount_below_threshold_cannot_be_proven() {
+        let mut generator = ZKSNARKGenerator::new();
+        let small = inputs(1, 10u128.pow(19));
+        assert!(generator.generate_whale_transaction_proof(&small).is_err());
+        assert!(generator.generate_aggregated_proof(&[inputs(2, 10u128.pow(21)), small]).is_err());
+    }
+}